@@ -1,8 +1,8 @@
 use serde::Serialize;
 
-#[path = "../src/build_common.rs"]
-mod build_common;
-use build_common::*;
+use crate::build_common::*;
+use crate::opcodes::Opcode;
+use crate::semantics::Semantics;
 
 #[derive(Debug, Serialize)]
 pub struct Instruction {
@@ -10,76 +10,105 @@ pub struct Instruction {
   pub expansion: String,
   pub syntax: Vec<String>,
   pub description: String,
+  pub extension: String,
+  pub operands: Vec<(String, String)>, // The name and the kind
   pub implementation: String,
 }
 
 impl Instruction {
-  fn reg_or_imm(arg: &String) -> &'static str {
-    if arg.eq(&"imm".to_string()) || arg.eq(&"offset".to_string()) {
-      "Imm12"
-    } else if arg.eq(&"imm20".to_string()) {
-      "Imm20"
-    } else if arg.eq(&"shamt".to_string()) {
-      "Shamt"
-    } else {
-      "Register"
-    }
+  fn escaped_mnemonic(&self) -> String {
+    self.mnemonic.replace(|c: char| !c.is_alphanumeric(), "_")
   }
 
-  fn get_args(&self) -> Vec<&String> {
+  /// The name of the instruction's variant of `Op`, e.g. `FenceI`.
+  fn variant(&self) -> String {
     self
-      .syntax
-      .iter()
-      .filter(|x| x.chars().all(char::is_alphanumeric))
-      .skip(1) // First one is the mnemonic, not an arg
+      .mnemonic
+      .split(|c: char| !c.is_alphanumeric())
+      .map(|part| part[..1].to_uppercase() + &part[1..])
       .collect()
   }
 
-  fn escaped_mnemonic(&self) -> String {
-    self.mnemonic.replace(|c: char| !c.is_alphanumeric(), "_")
+  /// The Rust type an operand is stored as in `Op`. Registers are stored as
+  /// bytes to keep it small, and immediates are sign extended up front.
+  fn operand_type(kind: &str) -> &'static str {
+    match kind {
+      "Register" => "u8",
+      "Imm12" | "Imm20" | "Imm21" => "Immediate",
+      "Csr" => "u16",
+      _ => "u64",
+    }
+  }
+
+  /// The variant with `fields`, e.g. `Addi { rd, rs1, imm }`, or just the
+  /// variant if there aren't any.
+  fn with_fields(&self, fields: &[String]) -> String {
+    match fields.len() {
+      0 => self.variant(),
+      _ => format!("{} {{ {} }}", self.variant(), fields.join(", ")),
+    }
   }
 
-  pub fn create_implementation_source(&self) -> String {
-    let mut impl_src = format!(
-      "#[allow(unused_variables)]\n\
-       fn {} (args: Vec<ImplementationArg>) -> MachineInstruction {{\n\
-       if let [",
+  /// The instruction's variant of `Op`, e.g. `Addi { rd: u8, ... }`.
+  pub fn variant_source(&self) -> String {
+    let fields: Vec<String> = self
+      .operands
+      .iter()
+      .map(|(name, kind)| format!("{}: {}", name, Self::operand_type(kind)))
+      .collect();
+    self.with_fields(&fields)
+  }
+
+  /// A function to turn the instruction's operands into its `Op`.
+  pub fn create_constructor_source(&self) -> String {
+    let mut pattern = String::new();
+    let mut fields = Vec::new();
+    for (name, kind) in &self.operands {
+      pattern.push_str(&format!("ImplementationArg::{}({}), ", kind, name));
+      fields.push(match Self::operand_type(kind) {
+        "u8" => format!("{}: {} as u8", name, name),
+        "Immediate" => format!("{}: Immediate(sext({}))", name, name),
+        _ => name.clone(),
+      });
+    }
+    format!(
+      "#[allow(clippy::comparison_to_empty)]\n\
+       fn {}(args: &[ImplementationArg]) -> Op {{\n\
+       if let [{}] = *args {{\n\
+       Op::{}\n\
+       }} else {{\n\
+       unreachable!(\"Wrong arg type\")\n\
+       }}\n\
+       }}\n\n",
       self.escaped_mnemonic(),
-    );
-    for arg in self.get_args() {
-      impl_src.push_str(
-        format!(
-          "ImplementationArg::{}({}), ",
-          Instruction::reg_or_imm(arg),
-          arg
-        )
-        .as_str(),
-      );
+      pattern,
+      self.with_fields(&fields)
+    )
+  }
+
+  /// The instruction's arm of the match in `execute`, which runs it.
+  pub fn execute_arm_source(&self) -> String {
+    let names: Vec<String> =
+      self.operands.iter().map(|(name, _)| name.clone()).collect();
+    let mut arm = format!("Op::{} => {{\n", self.with_fields(&names));
+    for (name, kind) in &self.operands {
+      if kind == "Register" {
+        arm.push_str(&format!("let {} = {} as usize;\n", name, name));
+      }
     }
     let mut log_string = String::from("\"");
-    for arg in self.get_args() {
-      log_string.push_str(format!("{}: {{:?}} ", arg).as_str());
+    for name in &names {
+      log_string.push_str(format!("{}: {{:?}} ", name).as_str());
     }
     log_string.push_str("\", ");
-    for arg in self.get_args() {
-      log_string.push_str(format!("{}, ", arg).as_str());
+    for name in &names {
+      log_string.push_str(format!("{}, ", name).as_str());
     }
-    impl_src.push_str(
-      format!(
-        "] = args[..] {{\n\
-         \tBox::new(move |x: &mut [Register; 32], pc: &mut PC, mem: &mut [u8; crate::rv64_i::MEMORY_SIZE]| {{\n\
-         crate::log!({});
-         \t\t{}\n\
-         \t}})\n\
-         }} else {{\n\
-         \tunreachable!(\"Wrong arg type\") }}\n\
-         }}\n\n",
-        log_string,
-        self.implementation
-      )
-      .as_str(),
-    );
-    impl_src
+    arm.push_str(&format!(
+      "crate::log!({});\n{}\n}}\n",
+      log_string, self.implementation
+    ));
+    arm
   }
 
   pub fn as_source(&self) -> String {
@@ -90,8 +119,8 @@ impl Instruction {
              expansion: \"{}\",\n\
              syntax: {},\n\
              description: r#\"{}\"#,\n\
-             implementation_str: \"{}\",\n\
-             implementation: {}\n\
+             implementation_str: {:?},\n\
+             op: {}\n\
              }}",
       self.mnemonic,
       self.expansion,
@@ -102,19 +131,50 @@ impl Instruction {
     )
   }
 
-  pub fn parse(cells: [&str; 5]) -> Option<Instruction> {
-    if let [m, e, s, d, i] = &cells[..] {
-      let i_str = i.trim().replace("BITWISE_OR", "|");
-      Some(Instruction {
-        mnemonic: m.trim().to_string(),
-        expansion: e.trim().to_string(),
-        syntax: tokenise(s),
-        description: d.trim().to_string(),
-        implementation: i_str,
-      })
-    } else {
-      println!("{:?}", cells);
-      None
+  /// Puts together an instruction from its row in the documentation, its
+  /// encoding and its semantics, checking that they agree: the semantics
+  /// take the operands in the syntax, and each is the kind of operand that
+  /// its field in the encoding holds.
+  pub fn new(
+    cells: [&str; 4],
+    opcode: &Opcode,
+    semantics: Semantics,
+  ) -> Result<Instruction, String> {
+    let [mnemonic, expansion, syntax, description] = cells;
+    let syntax = tokenise(syntax);
+    let names: Vec<&str> = semantics
+      .parameters
+      .iter()
+      .map(|(name, _)| name.as_str())
+      .collect();
+    if names != arg_names(&syntax).collect::<Vec<_>>() {
+      return Err(format!(
+        "the parameters should be the operands in `{}`, in order",
+        syntax.join(" ")
+      ));
+    }
+    for (name, kind) in &semantics.parameters {
+      let field = opcode
+        .fields
+        .iter()
+        .filter_map(|name| field(name))
+        .find(|field| field.operands.contains(&name.as_str()))
+        .ok_or_else(|| format!("`{}` isn't in any field", name))?;
+      if *kind != field.kind {
+        return Err(format!(
+          "`{}` should be `{}: {}`, as it's in the {} field",
+          name, name, field.kind, field.name
+        ));
+      }
     }
+    Ok(Instruction {
+      mnemonic: mnemonic.trim().to_string(),
+      expansion: expansion.trim().to_string(),
+      syntax,
+      description: description.trim().to_string(),
+      extension: opcode.extension.clone(),
+      operands: semantics.parameters,
+      implementation: semantics.body,
+    })
   }
 }
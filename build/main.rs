@@ -7,17 +7,21 @@ use std::io::BufWriter;
 use std::path::Path;
 use tera::{Context, Tera};
 
+#[path = "../src/build_common.rs"]
+mod build_common;
 mod instruction;
 use instruction::Instruction;
 mod interpreter_dispatch;
+mod opcodes;
 mod rustfmt;
+mod semantics;
 
 fn create_html(instructions: &Vec<Instruction>, registers: &Vec<Register>) {
   // Use globbing
   let tera = Tera::new("templates/*.html").expect("Parsing error(s):");
 
   let path = Path::new("./www/index.html");
-  let mut file = BufWriter::new(File::create(&path).unwrap());
+  let mut file = BufWriter::new(File::create(path).unwrap());
 
   let mut context = Context::new();
   context.insert("instructions", &instructions);
@@ -38,6 +42,19 @@ struct Register {
 }
 
 impl Register {
+  fn as_source(&self) -> String {
+    let saver = match self.saver.as_str() {
+      "Caller" => "Caller",
+      "Callee" => "Callee",
+      "" => "Neither",
+      other => panic!("Unknown saver \"{}\" for {}", other, self.primary_name),
+    };
+    format!(
+      "RegisterInfo {{ name: \"{}\", abi_name: \"{}\", saver: Saver::{} }}",
+      self.primary_name, self.secondary_names[0], saver
+    )
+  }
+
   fn parse(cells: [&str; 4]) -> Option<Register> {
     if let [primary, secondary, desc, saver] = &cells[..] {
       Some(Register {
@@ -58,7 +75,14 @@ impl Register {
   }
 }
 
-fn parse_org_file(filename: &str) -> (Vec<Instruction>, Vec<Register>) {
+/// Reads the instructions' documentation from `filename`, along with their
+/// encodings from the riscv-opcodes files in `directory` and their semantics
+/// from the Rust functions in `semantics`, and the registers.
+fn parse_isa(
+  filename: &str,
+  directory: &str,
+  semantics: &str,
+) -> (Vec<Instruction>, Vec<Register>, Vec<opcodes::Opcode>) {
   let mut file = File::open(filename).expect("File open error");
   let mut contents = String::new();
   file.read_to_string(&mut contents).expect("File read error");
@@ -80,21 +104,51 @@ fn parse_org_file(filename: &str) -> (Vec<Instruction>, Vec<Register>) {
   }
   sections.insert(current_title, current_section);
 
-  let instruction_strings: Vec<[&str; 5]> =
+  let instruction_strings: Vec<[&str; 4]> =
     parse_org_table(sections.get("Instructions").unwrap());
   let register_strings: Vec<[&str; 4]> =
     parse_org_table(sections.get("Registers").unwrap());
-  (
-    instruction_strings
+
+  let opcodes = opcodes::read(directory);
+  let mut semantics: HashMap<String, semantics::Semantics> =
+    semantics::read(semantics)
       .into_iter()
-      .map(Instruction::parse)
-      .map(Option::unwrap)
-      .collect(),
+      .map(|semantics| (semantics.name.clone(), semantics))
+      .collect();
+  let mut instructions = Vec::new();
+  for cells in instruction_strings {
+    let mnemonic = cells[0].trim();
+    let opcode = opcodes
+      .iter()
+      .find(|opcode| opcode.mnemonic == mnemonic)
+      .unwrap_or_else(|| {
+        panic!("{} has no encoding in {}", mnemonic, directory)
+      });
+    let escaped = mnemonic.replace(|c: char| !c.is_alphanumeric(), "_");
+    let function = semantics
+      .remove(&escaped)
+      .unwrap_or_else(|| panic!("{} has no semantics", mnemonic));
+    let instruction = Instruction::new(cells, opcode, function)
+      .unwrap_or_else(|message| panic!("{}: {}", mnemonic, message));
+    instructions.push(instruction);
+  }
+  for opcode in &opcodes {
+    if !instructions.iter().any(|i| i.mnemonic == opcode.mnemonic) {
+      panic!("{} isn't documented in {}", opcode.mnemonic, filename);
+    }
+  }
+  if let Some(name) = semantics.keys().next() {
+    panic!("{} is in the semantics, but isn't an instruction", name);
+  }
+
+  (
+    instructions,
     register_strings
       .into_iter()
       .map(Register::parse)
       .map(Option::unwrap)
       .collect(),
+    opcodes,
   )
 }
 
@@ -130,27 +184,41 @@ fn parse_org_table<'a, const NUM_COLUMNS: usize>(
 }
 
 fn main() -> std::io::Result<()> {
-  let (instructions, registers) = parse_org_file("rv64_i.org");
+  let (instructions, registers, opcodes) =
+    parse_isa("isa/docs.org", "isa", "isa/semantics.rs");
 
   let path = Path::new("src/codegen.rs");
-  let mut file = BufWriter::new(File::create(&path).unwrap());
+  let mut file = BufWriter::new(File::create(path).unwrap());
 
   rustfmt::write(
-    "use crate::instruction::*;
-use crate::rv64_i::*;
-pub type MachineInstruction = Box<dyn Fn(
-&mut [Register; 32],
-&mut PC,
-&mut [u8; crate::rv64_i::MEMORY_SIZE])>;"
+    "use crate::csr::CsrFile;
+use crate::encoding::{Encoding, Format};
+use crate::instruction::*;
+use crate::memory::Memory;
+use crate::rv64_i::*;"
       .to_string(),
     &mut file,
   )
   .unwrap();
 
+  // Each instruction is decoded into an `Op` once, when it's assembled, and
+  // `execute` runs it by matching on that.
+  let variants: Vec<String> = instructions
+    .iter()
+    .map(Instruction::variant_source)
+    .collect();
+  rustfmt::write(
+    format!(
+      "#[derive(Copy, Clone, Debug, PartialEq)]\npub enum Op {{\n{}\n}}\n",
+      variants.join(",\n")
+    ),
+    &mut file,
+  )
+  .unwrap();
+
   let mut instruction_map = phf_codegen::Map::new();
   for instruction in &instructions {
-    rustfmt::write(instruction.create_implementation_source(), &mut file)
-      .unwrap();
+    rustfmt::write(instruction.create_constructor_source(), &mut file).unwrap();
     instruction_map
       .entry(instruction.mnemonic.clone(), &instruction.as_source());
   }
@@ -161,6 +229,39 @@ pub type MachineInstruction = Box<dyn Fn(
   )
   .unwrap();
 
+  let arms: Vec<String> = instructions
+    .iter()
+    .map(Instruction::execute_arm_source)
+    .collect();
+  rustfmt::write(
+    format!(
+      "#[allow(unused_variables)]\n\
+       pub fn execute(\n\
+       op: Op,\n\
+       x: &mut [Register; 32],\n\
+       pc: &mut PC,\n\
+       mem: &mut dyn Memory,\n\
+       csrs: &mut CsrFile,\n\
+       ) {{\n\
+       match op {{\n{}}}\n\
+       }}\n",
+      arms.join("")
+    ),
+    &mut file,
+  )
+  .unwrap();
+
+  let encodings: Vec<String> = opcodes.iter().map(|o| o.as_source()).collect();
+  rustfmt::write(
+    format!(
+      "pub static ENCODINGS: [Encoding; {}] = [{}];\n",
+      encodings.len(),
+      encodings.join(",\n")
+    ),
+    &mut file,
+  )
+  .unwrap();
+
   let mut register_map = phf_codegen::Map::new();
   for (register_num, register) in registers.iter().enumerate() {
     register_map
@@ -176,6 +277,18 @@ pub type MachineInstruction = Box<dyn Fn(
   )
   .unwrap();
 
+  let register_info: Vec<String> =
+    registers.iter().map(Register::as_source).collect();
+  rustfmt::write(
+    format!(
+      "pub static REGISTER_INFO: [RegisterInfo; {}] = [{}];\n",
+      registers.len(),
+      register_info.join(",\n")
+    ),
+    &mut file,
+  )
+  .unwrap();
+
   create_html(&instructions, &registers);
 
   interpreter_dispatch::create_dispatch_file()?;
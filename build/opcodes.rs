@@ -0,0 +1,177 @@
+//! Reads the instruction encodings from files in the riscv-opcodes format,
+//! one per extension. Each line is a mnemonic followed by the fields holding
+//! its operands and the bits which are fixed, e.g.
+//! `addi rd rs1 imm12 14..12=0 6..2=0x04 1..0=3`.
+
+use crate::build_common::field;
+use std::fs;
+
+#[derive(Debug)]
+pub struct Opcode {
+  pub mnemonic: String,
+  pub extension: String,
+  pub fields: Vec<String>,
+  pub mask: u32,  // The bits which are fixed
+  pub value: u32, // and what they're fixed to
+}
+
+fn number(text: &str) -> Result<u32, String> {
+  let parsed = match text.strip_prefix("0x") {
+    Some(hex) => u32::from_str_radix(hex, 16),
+    None => text.parse(),
+  };
+  parsed.map_err(|_| format!("expected a number, found `{}`", text))
+}
+
+/// The bits from `high` down to `low`.
+fn mask(high: u32, low: u32) -> u32 {
+  (((1u64 << (high - low + 1)) - 1) << low) as u32
+}
+
+impl Opcode {
+  fn parse(extension: &str, line: &str) -> Result<Opcode, String> {
+    let mut tokens = line.split_whitespace();
+    let mut opcode = Opcode {
+      mnemonic: tokens.next().unwrap().to_string(),
+      extension: extension.to_string(),
+      fields: Vec::new(),
+      mask: 0,
+      value: 0,
+    };
+    // Every bit has to be either fixed or in a field, but not both
+    let mut covered = 0;
+    let mut cover = |bits: u32, token: &str| {
+      if covered & bits != 0 {
+        return Err(format!("`{}` overlaps another field", token));
+      }
+      covered |= bits;
+      Ok(())
+    };
+    for token in tokens {
+      match token.split_once('=') {
+        Some((range, value)) => {
+          let (high, low) = match range.split_once("..") {
+            Some((high, low)) => (number(high)?, number(low)?),
+            None => (number(range)?, number(range)?),
+          };
+          if high < low || high > 31 {
+            return Err(format!("`{}` isn't a range of bits", range));
+          }
+          let value = number(value)?;
+          if value > mask(high, low) >> low {
+            return Err(format!("`{}` doesn't fit in its bits", token));
+          }
+          cover(mask(high, low), token)?;
+          opcode.mask |= mask(high, low);
+          opcode.value |= value << low;
+        }
+        None => {
+          let field =
+            field(token).ok_or_else(|| format!("unknown field `{}`", token))?;
+          for &(high, low, shift) in field.pieces {
+            cover(mask(high - low + shift, shift), token)?;
+          }
+          opcode.fields.push(token.to_string());
+        }
+      }
+    }
+    if covered != u32::MAX {
+      return Err(format!("bits {:#010x} aren't in any field", !covered));
+    }
+    Ok(opcode)
+  }
+
+  /// How the operands are laid out, which the assembler needs to know for
+  /// some of them.
+  pub fn format(&self) -> &'static str {
+    let has = |name: &str| self.fields.iter().any(|field| field == name);
+    if has("bimm12hi") {
+      "B"
+    } else if has("jimm20") {
+      "J"
+    } else if has("imm12hi") {
+      "S"
+    } else if has("imm20") {
+      "U"
+    } else if has("shamtd") {
+      "Shift"
+    } else if has("shamtw") {
+      "ShiftW"
+    } else if has("zimm") {
+      "CsrI"
+    } else if has("csr") {
+      "Csr"
+    } else if has("pred") {
+      "Fence"
+    } else if has("imm12") {
+      "I"
+    } else if has("rs2") && has("rd") {
+      "R"
+    } else if has("rs2") {
+      "FenceVma"
+    } else {
+      "Fixed"
+    }
+  }
+
+  pub fn as_source(&self) -> String {
+    format!(
+      "Encoding {{\n\
+             mnemonic: \"{}\",\n\
+             extension: \"{}\",\n\
+             format: Format::{},\n\
+             mask: {:#010x},\n\
+             value: {:#010x},\n\
+             fields: &[\"{}\"],\n\
+             }}",
+      self.mnemonic,
+      self.extension,
+      self.format(),
+      self.mask,
+      self.value,
+      self.fields.join("\", \"")
+    )
+    .replace("&[\"\"]", "&[]")
+  }
+}
+
+/// The encodings of the instructions in every extension in `directory`.
+pub fn read(directory: &str) -> Vec<Opcode> {
+  let mut paths: Vec<_> = fs::read_dir(directory)
+    .expect("Directory read error")
+    .map(|entry| entry.unwrap().path())
+    .filter(|path| path.extension().is_none())
+    .collect();
+  paths.sort();
+
+  let mut opcodes: Vec<Opcode> = Vec::new();
+  for path in paths {
+    let extension = path.file_name().unwrap().to_str().unwrap();
+    let contents = fs::read_to_string(&path).expect("File read error");
+    for (line_num, line) in contents.lines().enumerate() {
+      let line = line.split('#').next().unwrap().trim();
+      // Pseudo-ops are instructions from other extensions under another
+      // name, and imports are for instructions which are read anyway
+      if line.is_empty() || line.starts_with('$') {
+        continue;
+      }
+      let opcode = Opcode::parse(extension, line).unwrap_or_else(|message| {
+        panic!("{}:{}: {}", path.display(), line_num + 1, message)
+      });
+      for other in &opcodes {
+        let fixed = opcode.mask & other.mask;
+        if (opcode.value ^ other.value) & fixed == 0 {
+          panic!(
+            "{}:{}: {} can't be told apart from {}",
+            path.display(),
+            line_num + 1,
+            opcode.mnemonic,
+            other.mnemonic
+          );
+        }
+      }
+      opcodes.push(opcode);
+    }
+  }
+  opcodes
+}
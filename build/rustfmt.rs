@@ -2,7 +2,7 @@ use std::borrow::Cow;
 use std::env;
 use std::io;
 use std::io::*;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
 
 /// Write these bindings as source text to the given `Write`able.
@@ -23,18 +23,18 @@ pub fn write<W: std::io::Write>(
 }
 
 /// Gets the rustfmt path to rustfmt the generated bindings.
-fn rustfmt_path<'a>() -> io::Result<Cow<'a, PathBuf>> {
+fn rustfmt_path<'a>() -> io::Result<Cow<'a, Path>> {
   if let Ok(rustfmt) = env::var("RUSTFMT") {
-    return Ok(Cow::Owned(rustfmt.into()));
+    return Ok(Cow::Owned(PathBuf::from(rustfmt)));
   }
   match which::which("rustfmt") {
     Ok(p) => Ok(Cow::Owned(p)),
-    Err(e) => Err(io::Error::new(io::ErrorKind::Other, format!("{}", e))),
+    Err(e) => Err(io::Error::other(format!("{}", e))),
   }
 }
 
 /// Checks if rustfmt_bindings is set and runs rustfmt on the string
-fn rustfmt_generated_string(source: &str) -> io::Result<Cow<str>> {
+fn rustfmt_generated_string(source: &str) -> io::Result<Cow<'_, str>> {
   let rustfmt = rustfmt_path()?;
   let mut cmd = Command::new(&*rustfmt);
 
@@ -66,18 +66,12 @@ fn rustfmt_generated_string(source: &str) -> io::Result<Cow<str>> {
   match String::from_utf8(output) {
     Ok(bindings) => match status.code() {
       Some(0) => Ok(Cow::Owned(bindings)),
-      Some(2) => Err(io::Error::new(
-        io::ErrorKind::Other,
-        "Rustfmt parsing errors.".to_string(),
-      )),
+      Some(2) => Err(io::Error::other("Rustfmt parsing errors.".to_string())),
       Some(3) => {
         println!("Rustfmt could not format some lines.");
         Ok(Cow::Owned(bindings))
       }
-      _ => Err(io::Error::new(
-        io::ErrorKind::Other,
-        "Internal rustfmt error".to_string(),
-      )),
+      _ => Err(io::Error::other("Internal rustfmt error".to_string())),
     },
     _ => Ok(Cow::Owned(source)),
   }
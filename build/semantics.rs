@@ -0,0 +1,78 @@
+//! Reads what each instruction does from a file of Rust functions, one per
+//! instruction, whose parameters are its operands and whose body is what it
+//! does. The file isn't compiled as it is, but the bodies are, as the arms of
+//! the match which runs the instructions.
+
+use std::fs;
+
+#[derive(Debug)]
+pub struct Semantics {
+  pub name: String,
+  pub parameters: Vec<(String, String)>, // The name and the kind
+  pub body: String,
+}
+
+/// `text` up to the `close` which matches the `open` before it, and the text
+/// after that.
+fn until_matching(text: &str, open: char, close: char) -> (&str, &str) {
+  let mut depth = 0;
+  for (i, c) in text.char_indices() {
+    if c == open {
+      depth += 1;
+    } else if c == close && depth == 0 {
+      return (&text[..i], &text[i + 1..]);
+    } else if c == close {
+      depth -= 1;
+    }
+  }
+  panic!("No `{}` to match `{}`", close, open);
+}
+
+fn parse_function(text: &str) -> (Semantics, &str) {
+  let (name, rest) = text.split_once('(').unwrap();
+  let (parameters, rest) = until_matching(rest, '(', ')');
+  let parameters = parameters
+    .split(',')
+    .map(str::trim)
+    .filter(|parameter| !parameter.is_empty())
+    .map(|parameter| match parameter.split_once(':') {
+      Some((name, kind)) => (name.trim().to_string(), kind.trim().to_string()),
+      None => panic!("`{}` in {} has no type", parameter, name),
+    })
+    .collect();
+  let rest = rest.trim_start().strip_prefix('{').unwrap_or_else(|| {
+    panic!("Expected `{{` after the parameters of {}", name);
+  });
+  let (body, rest) = until_matching(rest, '{', '}');
+  let body: Vec<&str> = body
+    .lines()
+    .map(|line| line.strip_prefix("  ").unwrap_or(line))
+    .collect();
+  let semantics = Semantics {
+    name: name.trim().to_string(),
+    parameters,
+    body: body.join("\n").trim().to_string(),
+  };
+  (semantics, rest)
+}
+
+pub fn read(filename: &str) -> Vec<Semantics> {
+  let contents = fs::read_to_string(filename).expect("File read error");
+  let mut rest = contents.as_str();
+  let mut functions = Vec::new();
+  loop {
+    rest = rest.trim_start();
+    if rest.is_empty() {
+      return functions;
+    } else if rest.starts_with("//") {
+      rest = rest.split_once('\n').map_or("", |(_, rest)| rest);
+    } else if let Some(function) = rest.strip_prefix("fn ") {
+      let (semantics, after) = parse_function(function);
+      functions.push(semantics);
+      rest = after;
+    } else {
+      let line = rest.lines().next().unwrap();
+      panic!("Expected a function in {}, found `{}`", filename, line);
+    }
+  }
+}
@@ -0,0 +1,294 @@
+// What each instruction does. The body of each function becomes the
+// instruction's arm of the match which runs it, so it can use the registers
+// `x`, the program counter `pc`, memory `mem` and the CSRs `csrs`, along with
+// the instruction's operands. The parameters are the operands, in the order
+// they're written in the instruction's syntax, and their types are the kind
+// of operand, which the build checks against the instruction's encoding:
+// `Register` for a register number, `Imm12`, `Imm20` and `Imm21` for
+// immediates (sign extend them with `sext`), `Shamt` and `Uimm` for unsigned
+// immediates and `Csr` for a CSR number.
+//
+// Functions are named after their instruction, with any `.` written as `_`.
+
+fn lui(rd: Register, imm20: Imm20) {
+  x[rd] = sext(imm20) << 12
+}
+
+fn auipc(rd: Register, imm20: Imm20) {
+  x[rd] = pc.get() + (sext(imm20) << 12)
+}
+
+fn addi(rd: Register, rs1: Register, imm: Imm12) {
+  x[rd] = x[rs1] + sext(imm)
+}
+
+fn slti(rd: Register, rs1: Register, imm: Imm12) {
+  x[rd] = signed_lt(x[rs1], sext(imm)).into()
+}
+
+fn sltiu(rd: Register, rs1: Register, imm: Imm12) {
+  x[rd] = (x[rs1] < sext(imm)).into()
+}
+
+fn xori(rd: Register, rs1: Register, imm: Imm12) {
+  x[rd] = x[rs1] ^ sext(imm)
+}
+
+fn ori(rd: Register, rs1: Register, imm: Imm12) {
+  x[rd] = x[rs1] | sext(imm)
+}
+
+fn andi(rd: Register, rs1: Register, imm: Imm12) {
+  x[rd] = x[rs1] & sext(imm)
+}
+
+fn slli(rd: Register, rs1: Register, shamt: Shamt) {
+  x[rd] = x[rs1] << shamt
+}
+
+fn srli(rd: Register, rs1: Register, shamt: Shamt) {
+  x[rd] = x[rs1] >> shamt
+}
+
+fn srai(rd: Register, rs1: Register, shamt: Shamt) {
+  x[rd] = arith_r_shift_i(x[rs1], shamt)
+}
+
+fn add(rd: Register, rs1: Register, rs2: Register) {
+  x[rd] = x[rs1] + x[rs2]
+}
+
+fn sub(rd: Register, rs1: Register, rs2: Register) {
+  x[rd] = x[rs1] - x[rs2]
+}
+
+fn sll(rd: Register, rs1: Register, rs2: Register) {
+  x[rd] = x[rs1] << (x[rs2] & 0x3F)
+}
+
+fn slt(rd: Register, rs1: Register, rs2: Register) {
+  x[rd] = signed_lt(x[rs1], x[rs2]).into()
+}
+
+fn sltu(rd: Register, rs1: Register, rs2: Register) {
+  x[rd] = (x[rs1] < x[rs2]).into()
+}
+
+fn xor(rd: Register, rs1: Register, rs2: Register) {
+  x[rd] = x[rs1] ^ x[rs2]
+}
+
+fn srl(rd: Register, rs1: Register, rs2: Register) {
+  x[rd] = x[rs1] >> (x[rs2] & 0x3F)
+}
+
+fn sra(rd: Register, rs1: Register, rs2: Register) {
+  x[rd] = arith_r_shift(x[rs1], x[rs2] & 0x3F)
+}
+
+fn or(rd: Register, rs1: Register, rs2: Register) {
+  x[rd] = x[rs1] | x[rs2]
+}
+
+fn and(rd: Register, rs1: Register, rs2: Register) {
+  x[rd] = x[rs1] & x[rs2]
+}
+
+fn fence(pred: Register, succ: Register) {
+  /* Fence(pred, succ) */
+}
+
+fn fence_i() {
+  /* Fence(Store, Fetch) */
+}
+
+fn csrrw(rd: Register, csr: Csr, rs1: Register) {
+  // With rd = x0 the CSR isn't read, so its read side effects don't happen
+  let value = x[rs1];
+  if rd != 0 {
+    x[rd] = csrs.read(csr)
+  }
+  csrs.write(csr, value)
+}
+
+fn csrrs(rd: Register, csr: Csr, rs1: Register) {
+  let t = csrs.read(csr);
+  if rs1 != 0 {
+    csrs.write(csr, t | x[rs1])
+  }
+  x[rd] = t
+}
+
+fn csrrc(rd: Register, csr: Csr, rs1: Register) {
+  let t = csrs.read(csr);
+  if rs1 != 0 {
+    csrs.write(csr, t & !x[rs1])
+  }
+  x[rd] = t
+}
+
+fn csrrwi(rd: Register, csr: Csr, uimm: Uimm) {
+  if rd != 0 {
+    x[rd] = csrs.read(csr)
+  }
+  csrs.write(csr, Register { value: uimm })
+}
+
+fn csrrsi(rd: Register, csr: Csr, uimm: Uimm) {
+  let t = csrs.read(csr);
+  if uimm != 0 {
+    csrs.write(csr, t | uimm)
+  }
+  x[rd] = t
+}
+
+fn csrrci(rd: Register, csr: Csr, uimm: Uimm) {
+  let t = csrs.read(csr);
+  if uimm != 0 {
+    csrs.write(csr, t & !uimm)
+  }
+  x[rd] = t
+}
+
+fn ecall() {
+  csrs.ecall()
+}
+
+fn ebreak() {
+  csrs.ebreak()
+}
+
+fn uret() {
+  /* ExceptionReturn(User) */
+}
+
+fn sret() {
+  pc.set(csrs.sret())
+}
+
+fn mret() {
+  pc.set(csrs.mret())
+}
+
+fn wfi() {
+  csrs.wfi()
+}
+
+fn sfence_vma(rs1: Register, rs2: Register) {
+  csrs.sfence_vma()
+}
+
+fn lb(rd: Register, offset: Imm12, rs1: Register) {
+  x[rd] = read_sext(mem, x[rs1] + sext(offset), 8)
+}
+
+fn lh(rd: Register, offset: Imm12, rs1: Register) {
+  x[rd] = read_sext(mem, x[rs1] + sext(offset), 16)
+}
+
+fn lw(rd: Register, offset: Imm12, rs1: Register) {
+  x[rd] = read_sext(mem, x[rs1] + sext(offset), 32)
+}
+
+fn lbu(rd: Register, offset: Imm12, rs1: Register) {
+  x[rd] = read(mem, x[rs1] + sext(offset), 8)
+}
+
+fn lhu(rd: Register, offset: Imm12, rs1: Register) {
+  x[rd] = read(mem, x[rs1] + sext(offset), 16)
+}
+
+fn sb(rs2: Register, offset: Imm12, rs1: Register) {
+  write(mem, x[rs1] + sext(offset),  8, x[rs2])
+}
+
+fn sh(rs2: Register, offset: Imm12, rs1: Register) {
+  write(mem, x[rs1] + sext(offset), 16, x[rs2])
+}
+
+fn sw(rs2: Register, offset: Imm12, rs1: Register) {
+  write(mem, x[rs1] + sext(offset), 32, x[rs2])
+}
+
+fn jal(rd: Register, offset: Imm21) {
+  x[rd] = pc.get() + 4;
+  pc.inc(sext(offset))
+}
+
+fn jalr(rd: Register, offset: Imm12, rs1: Register) {
+  let t = pc.get() + 4;
+  pc.set((x[rs1] + sext(offset)) & !1);
+  x[rd] = t
+}
+
+fn beq(rs1: Register, rs2: Register, offset: Imm20) {
+  if x[rs1] == x[rs2] { pc.inc(sext(offset)) }
+}
+
+fn bne(rs1: Register, rs2: Register, offset: Imm20) {
+  if x[rs1] != x[rs2] { pc.inc(sext(offset)) }
+}
+
+fn blt(rs1: Register, rs2: Register, offset: Imm20) {
+  if signed_lt(x[rs1], x[rs2]) { pc.inc(sext(offset)) }
+}
+
+fn bge(rs1: Register, rs2: Register, offset: Imm20) {
+  if !signed_lt(x[rs1], x[rs2]) { pc.inc(sext(offset)) }
+}
+
+fn bltu(rs1: Register, rs2: Register, offset: Imm20) {
+  if x[rs1] < x[rs2] { pc.inc(sext(offset)) }
+}
+
+fn bgeu(rs1: Register, rs2: Register, offset: Imm20) {
+  if x[rs1] >= x[rs2] { pc.inc(sext(offset)) }
+}
+
+fn addiw(rd: Register, rs1: Register, imm: Imm12) {
+  x[rd] = sext_n(x[rs1] + sext(imm), 32)
+}
+
+fn slliw(rd: Register, rs1: Register, shamt: Shamt) {
+  x[rd] = sext_n(x[rs1] << shamt, 32)
+}
+
+fn srliw(rd: Register, rs1: Register, shamt: Shamt) {
+  x[rd] = sext_n((x[rs1] & 0xFFFFFFFF) >> shamt, 32)
+}
+
+fn sraiw(rd: Register, rs1: Register, shamt: Shamt) {
+  x[rd] = arith_r_shift_i(sext_n(x[rs1], 32), shamt)
+}
+
+fn addw(rd: Register, rs1: Register, rs2: Register) {
+  x[rd] = sext_n(x[rs1] + x[rs2], 32)
+}
+
+fn subw(rd: Register, rs1: Register, rs2: Register) {
+  x[rd] = sext_n(x[rs1] - x[rs2], 32)
+}
+
+fn sllw(rd: Register, rs1: Register, rs2: Register) {
+  x[rd] = sext_n(x[rs1] << (x[rs2] & 0x1F), 32)
+}
+
+fn srlw(rd: Register, rs1: Register, rs2: Register) {
+  x[rd] = sext_n((x[rs1] & 0xFFFFFFFF) >> (x[rs2] & 0x1F), 32)
+}
+
+fn sraw(rd: Register, rs1: Register, rs2: Register) {
+  x[rd] = arith_r_shift(sext_n(x[rs1], 32), x[rs2] & 0x1F)
+}
+
+fn lwu(rd: Register, offset: Imm12, rs1: Register) {
+  x[rd] = read(mem, x[rs1] + sext(offset), 32)
+}
+
+fn ld(rd: Register, offset: Imm12, rs1: Register) {
+  x[rd] = read(mem, x[rs1] + sext(offset), 64)
+}
+
+fn sd(rs2: Register, offset: Imm12, rs1: Register) {
+  write(mem, x[rs1] + sext(offset), 64, x[rs2])
+}
@@ -0,0 +1,224 @@
+//! Turns source code into instructions. After macros and the like have been
+//! expanded, each line has an optional label and then an instruction or a
+//! directive: `.equ name, value` (or `.set`) to define a constant, or
+//! `.globl name, ...` (or `.global`) to let other files use labels.
+
+use crate::codegen::{INSTRUCTIONS, REGISTERS};
+use crate::macros::{expand, Files, Line};
+use crate::operand::{evaluate_str, Symbols};
+use crate::split_label;
+use crate::valid_label;
+use crate::Instruction;
+use crate::InstructionSource;
+use std::collections::HashSet;
+use std::fmt;
+use std::ops::Range;
+
+pub struct Assembly {
+  pub instructions: Vec<Instruction>,
+  pub symbols: Symbols,
+  pub errors: Vec<AssemblyError>,
+}
+
+/// An error in the code, with where it is so that an editor can underline
+/// it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AssemblyError {
+  pub message: String, // Which says where it is, e.g. "Error on line 3: ..."
+  pub line: u32,       // In the file being assembled, 1 indexed
+  // The bytes of the line the error is about: the statement if it's written
+  // there, or the whole line if it comes from a macro or an included file
+  pub columns: Range<usize>,
+}
+
+impl AssemblyError {
+  fn new(code: &str, line: &Line, message: String) -> Self {
+    let line_num = line.line_num();
+    let source = code
+      .lines()
+      .nth(line_num.saturating_sub(1) as usize)
+      .unwrap_or("");
+    let start = match line.locations.len() {
+      1 => source.find(line.text.as_str()),
+      _ => None,
+    };
+    AssemblyError {
+      message,
+      line: line_num,
+      columns: match start {
+        Some(start) => start..start + line.text.len(),
+        None => 0..source.len(),
+      },
+    }
+  }
+}
+
+impl fmt::Display for AssemblyError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.write_str(&self.message)
+  }
+}
+
+enum Directive<'a> {
+  Constant(&'a str, &'a str),
+  Global(&'a str),
+}
+
+/// The directive on a line, or None if it's an instruction.
+fn directive(instruction: &str) -> Option<Result<Directive<'_>, String>> {
+  let (name, rest) = instruction
+    .split_once(char::is_whitespace)
+    .unwrap_or((instruction, ""));
+  let rest = rest.trim();
+  match name {
+    ".equ" | ".set" => Some(match rest.split_once(',') {
+      Some((name, value)) => Ok(Directive::Constant(name.trim(), value.trim())),
+      None => Err(format!(
+        "expected `{} name, value`, found `{}`",
+        name, instruction
+      )),
+    }),
+    ".globl" | ".global" => Some(match rest {
+      "" => Err(format!("expected `{} name`, found `{}`", name, instruction)),
+      _ => Ok(Directive::Global(rest)),
+    }),
+    _ => None,
+  }
+}
+
+/// Assembles the code into a fresh program, returning the instructions,
+/// symbols and any errors rather than modifying an interpreter, so that the
+/// caller can swap them in in one go. The code can include `files`. If it's
+/// `relocatable` it's going to be linked with other files, so the
+/// relocations are in the symbols.
+pub fn assemble(code: &str, files: &Files, relocatable: bool) -> Assembly {
+  let mut instructions: Vec<Instruction> = Vec::new();
+  let mut symbols = Symbols {
+    relocatable,
+    ..Symbols::default()
+  };
+  let (lines, macro_errors) = expand(code, files);
+  let mut errors: Vec<AssemblyError> = macro_errors
+    .into_iter()
+    .map(|(line, message)| AssemblyError::new(code, &line, message))
+    .collect();
+  let error = |line: &Line, message| AssemblyError::new(code, line, message);
+
+  // Labels can be used before they're defined, so find them all first
+  let mut address: u64 = 0;
+  for line in &lines {
+    let (label, instruction) = split_label(&line.text);
+    if let Some(label) = label {
+      if !valid_label(label) {
+        let message =
+          format!("Invalid label on {}: {}", line.location(), label);
+        errors.push(error(line, message));
+      } else if symbols.labels.insert(label.to_string(), address).is_some() {
+        let location = line.location();
+        let message = format!("Duplicate label on {}: {}", location, label);
+        errors.push(error(line, message));
+      }
+    }
+    if !instruction.is_empty() && directive(instruction).is_none() {
+      address += 4; // 4 bytes/instruction
+    }
+  }
+
+  // Constants can refer to labels, and to the constants before them
+  for line in &lines {
+    let (_, instruction) = split_label(&line.text);
+    let (name, value) = match directive(instruction) {
+      Some(Ok(Directive::Constant(name, value))) => (name, value),
+      Some(Ok(Directive::Global(names))) => {
+        let names = names.split(',').map(str::trim);
+        symbols.globals.extend(names.map(str::to_string));
+        continue;
+      }
+      Some(Err(message)) => {
+        let message = format!("Error on {}: {}", line.location(), message);
+        errors.push(error(line, message));
+        continue;
+      }
+      None => continue,
+    };
+    if !valid_label(name) || REGISTERS.contains_key(name) {
+      let location = line.location();
+      let message = format!("Invalid constant on {}: {}", location, name);
+      errors.push(error(line, message));
+      continue;
+    }
+    if symbols.labels.contains_key(name) {
+      let location = line.location();
+      let message = format!("Duplicate label on {}: {}", location, name);
+      errors.push(error(line, message));
+      continue;
+    }
+    match evaluate_str(value, &symbols) {
+      Ok(value) => {
+        symbols.constants.insert(name.to_string(), value);
+      }
+      Err(message) => {
+        let message = format!("Error on {}: {}", line.location(), message);
+        errors.push(error(line, message));
+      }
+    }
+  }
+
+  for line in lines {
+    let (_, instruction) = split_label(&line.text);
+    match directive(instruction) {
+      // .set can change a constant, so the instructions after it see the
+      // new value
+      Some(Ok(Directive::Constant(name, value))) => {
+        if let Ok(value) = evaluate_str(value, &symbols) {
+          symbols.constants.insert(name.to_string(), value);
+        }
+        continue;
+      }
+      Some(_) => continue,
+      None if instruction.is_empty() => continue,
+      None => (),
+    }
+
+    let opt_inst: Option<&InstructionSource> =
+      INSTRUCTIONS.get(instruction.split_whitespace().next().unwrap());
+    if opt_inst.is_none() {
+      let message = format!(
+        "Invalid instruction on {}: {}",
+        line.location(),
+        instruction
+      );
+      errors.push(error(&line, message));
+      continue;
+    }
+    let inst: &InstructionSource = opt_inst.unwrap();
+    let address = instructions.len() as u64 * 4;
+    let args = match inst.parse(instruction, &mut symbols, address) {
+      Ok(args) => args,
+      Err(message) => {
+        let message = format!("Error on {}: {}", line.location(), message);
+        errors.push(error(&line, message));
+        continue;
+      }
+    };
+    let actual_instruction = Instruction {
+      source: inst,
+      line_num: line.line_num(),
+      locations: line.locations,
+      breakpoint: false,
+      op: (inst.op)(&args),
+      args,
+    };
+    instructions.push(actual_instruction);
+  }
+
+  // A bad line in a macro or a .rept is wrong in the same way each time
+  // it's expanded, so only report each error once
+  let mut reported = HashSet::new();
+  errors.retain(|error| reported.insert(error.message.clone()));
+  Assembly {
+    instructions,
+    symbols,
+    errors,
+  }
+}
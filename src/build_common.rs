@@ -1,3 +1,4 @@
+#[allow(dead_code)] // Only the build script tokenises, to split up the syntax
 pub fn tokenise(line: &str) -> Vec<String> {
   let splits: Vec<String> = line
     .trim()
@@ -11,7 +12,7 @@ pub fn tokenise(line: &str) -> Vec<String> {
     if new_split.is_empty() {
       continue;
     }
-    if new_split.ends_with(&[',', '(', ')']) {
+    if new_split.ends_with([',', '(', ')']) {
       let len = new_split.len();
       let (first, last) = new_split.split_at(len - 1);
       new_splits.push(first.to_string());
@@ -22,3 +23,79 @@ pub fn tokenise(line: &str) -> Vec<String> {
   }
   new_splits
 }
+
+/// The operand names in an instruction's syntax, e.g. ["rd", "offset", "rs1"]
+/// for "lw rd,offset(rs1)". This is also the order that the operands are
+/// passed to the implementation in.
+pub fn arg_names<S: AsRef<str>>(
+  syntax: &[S],
+) -> impl Iterator<Item = &str> + '_ {
+  syntax
+    .iter()
+    .skip(1) // First one is the mnemonic, not an arg
+    .map(AsRef::as_ref)
+    .filter(|x| x.chars().all(char::is_alphanumeric))
+}
+
+/// A field of an instruction word, named as in riscv-opcodes.
+pub struct Field {
+  pub name: &'static str,
+  /// The operands in the syntax which it holds (some of the bits of).
+  pub operands: &'static [&'static str],
+  /// The `ImplementationArg` the operand is passed to the implementation as.
+  pub kind: &'static str,
+  /// Where the bits go, as (high, low, shift): `operand[high:low]` is at bit
+  /// `shift` of the word.
+  pub pieces: &'static [(u32, u32, u32)],
+}
+
+const fn f(
+  name: &'static str,
+  operands: &'static [&'static str],
+  kind: &'static str,
+  pieces: &'static [(u32, u32, u32)],
+) -> Field {
+  Field {
+    name,
+    operands,
+    kind,
+    pieces,
+  }
+}
+
+pub const FIELDS: [Field; 17] = [
+  f("rd", &["rd"], "Register", &[(4, 0, 7)]),
+  f("rs1", &["rs1"], "Register", &[(4, 0, 15)]),
+  f("rs2", &["rs2"], "Register", &[(4, 0, 20)]),
+  f("imm12", &["imm", "offset"], "Imm12", &[(11, 0, 20)]),
+  f("imm12hi", &["offset"], "Imm12", &[(11, 5, 25)]),
+  f("imm12lo", &["offset"], "Imm12", &[(4, 0, 7)]),
+  // Branch and jump offsets have no base register, and need more bits: a
+  // branch's 13 fit in an Imm20, but jal's are a full 21
+  f(
+    "bimm12hi",
+    &["offset"],
+    "Imm20",
+    &[(12, 12, 31), (10, 5, 25)],
+  ),
+  f("bimm12lo", &["offset"], "Imm20", &[(4, 1, 8), (11, 11, 7)]),
+  f(
+    "jimm20",
+    &["offset"],
+    "Imm21",
+    &[(20, 20, 31), (10, 1, 21), (11, 11, 20), (19, 12, 12)],
+  ),
+  f("imm20", &["imm20"], "Imm20", &[(19, 0, 12)]),
+  f("shamtd", &["shamt"], "Shamt", &[(5, 0, 20)]),
+  f("shamtw", &["shamt"], "Shamt", &[(4, 0, 20)]),
+  f("csr", &["csr"], "Csr", &[(11, 0, 20)]),
+  f("zimm", &["uimm"], "Uimm", &[(4, 0, 15)]),
+  f("fm", &[], "", &[(3, 0, 28)]),
+  // fence's sets are passed as registers, as the implementation expects
+  f("pred", &["pred"], "Register", &[(3, 0, 24)]),
+  f("succ", &["succ"], "Register", &[(3, 0, 20)]),
+];
+
+pub fn field(name: &str) -> Option<&'static Field> {
+  FIELDS.iter().find(|field| field.name == name)
+}
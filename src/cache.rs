@@ -0,0 +1,434 @@
+//! A model of the caches between the processor and memory, for seeing how a
+//! program's access pattern turns into hits and misses. The data itself stays
+//! in memory; the caches only track which blocks they'd hold, so turning them
+//! on never changes what a program does.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Replacement {
+  Lru,
+  Fifo,
+  Random,
+}
+
+impl Replacement {
+  pub fn parse(name: &str) -> Option<Self> {
+    match name {
+      "lru" => Some(Replacement::Lru),
+      "fifo" => Some(Replacement::Fifo),
+      "random" => Some(Replacement::Random),
+      _ => None,
+    }
+  }
+}
+
+/// Write back caches allocate a block on a write miss and only write it to
+/// memory when it's evicted. Write through caches send every write straight
+/// to memory, and don't allocate on a write miss.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WritePolicy {
+  WriteBack,
+  WriteThrough,
+}
+
+impl WritePolicy {
+  pub fn parse(name: &str) -> Option<Self> {
+    match name {
+      "write-back" => Some(WritePolicy::WriteBack),
+      "write-through" => Some(WritePolicy::WriteThrough),
+      _ => None,
+    }
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CacheConfig {
+  pub size: u64, // In bytes
+  pub associativity: u64,
+  pub block_size: u64, // In bytes
+  pub replacement: Replacement,
+  pub write_policy: WritePolicy,
+}
+
+impl CacheConfig {
+  pub fn validate(&self) -> Result<(), String> {
+    for (name, value) in [
+      ("size", self.size),
+      ("associativity", self.associativity),
+      ("block size", self.block_size),
+    ] {
+      if !value.is_power_of_two() {
+        return Err(format!("Cache {} must be a power of two", name));
+      }
+    }
+    if self.associativity * self.block_size > self.size {
+      return Err(format!(
+        "A {} byte cache can't hold {} blocks of {} bytes in a set",
+        self.size, self.associativity, self.block_size
+      ));
+    }
+    Ok(())
+  }
+
+  pub fn sets(&self) -> u64 {
+    self.size / (self.associativity * self.block_size)
+  }
+}
+
+impl Default for CacheConfig {
+  /// Small enough that every line fits on screen.
+  fn default() -> Self {
+    CacheConfig {
+      size: 256,
+      associativity: 2,
+      block_size: 16,
+      replacement: Replacement::Lru,
+      write_policy: WritePolicy::WriteBack,
+    }
+  }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CacheLine {
+  pub valid: bool,
+  pub dirty: bool,
+  pub tag: u64,
+  pub hits: u64,
+  pub misses: u64, // Misses which filled this line
+  last_used: u64,
+  filled: u64,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CacheStats {
+  pub hits: u64,
+  pub misses: u64,
+  pub writebacks: u64, // Dirty blocks written to memory on eviction
+  pub write_throughs: u64,
+}
+
+impl CacheStats {
+  fn add(&mut self, other: CacheStats) {
+    self.hits += other.hits;
+    self.misses += other.misses;
+    self.writebacks += other.writebacks;
+    self.write_throughs += other.write_throughs;
+  }
+}
+
+impl fmt::Display for CacheStats {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let accesses = self.hits + self.misses;
+    write!(f, "{} hits, {} misses", self.hits, self.misses)?;
+    if accesses > 0 {
+      write!(
+        f,
+        " ({:.1}% hit rate)",
+        self.hits as f64 * 100.0 / accesses as f64
+      )?;
+    }
+    Ok(())
+  }
+}
+
+#[derive(Clone, Debug)]
+pub struct Cache {
+  pub name: &'static str,
+  pub config: CacheConfig,
+  lines: Vec<CacheLine>, // Set by set, each set has associativity lines
+  pub stats: CacheStats,
+  pub last_access: Option<usize>, // Index into lines
+  clock: u64,
+  random: u64,
+}
+
+impl Cache {
+  pub fn new(name: &'static str, config: CacheConfig) -> Self {
+    Cache {
+      name,
+      config,
+      lines: vec![
+        CacheLine::default();
+        (config.sets() * config.associativity) as usize
+      ],
+      stats: CacheStats::default(),
+      last_access: None,
+      clock: 0,
+      // Random replacement is the same on every run, so that results can be
+      // reproduced
+      random: 0x2545_f491_4f6c_dd1d,
+    }
+  }
+
+  /// Each line along with its set and way.
+  pub fn lines(&self) -> impl Iterator<Item = (u64, u64, &CacheLine)> {
+    let ways = self.config.associativity;
+    self
+      .lines
+      .iter()
+      .enumerate()
+      .map(move |(i, line)| (i as u64 / ways, i as u64 % ways, line))
+  }
+
+  /// The address of the first byte of the block a line holds.
+  pub fn line_address(&self, set: u64, line: &CacheLine) -> u64 {
+    (line.tag * self.config.sets() + set) * self.config.block_size
+  }
+
+  fn next_random(&mut self) -> u64 {
+    // xorshift64
+    self.random ^= self.random << 13;
+    self.random ^= self.random >> 7;
+    self.random ^= self.random << 17;
+    self.random
+  }
+
+  fn victim(&mut self, set: usize) -> usize {
+    let ways = self.config.associativity as usize;
+    let start = set * ways;
+    let range = start..start + ways;
+    if let Some(i) = range.clone().find(|&i| !self.lines[i].valid) {
+      return i;
+    }
+    match self.config.replacement {
+      Replacement::Lru => range.min_by_key(|&i| self.lines[i].last_used),
+      Replacement::Fifo => range.min_by_key(|&i| self.lines[i].filled),
+      Replacement::Random => {
+        Some(start + (self.next_random() % ways as u64) as usize)
+      }
+    }
+    .unwrap()
+  }
+
+  fn access_block(&mut self, block: u64, write: bool) -> CacheStats {
+    let mut stats = CacheStats::default();
+    self.clock += 1;
+    let sets = self.config.sets();
+    let set = (block % sets) as usize;
+    let tag = block / sets;
+    let ways = self.config.associativity as usize;
+    let write_through = self.config.write_policy == WritePolicy::WriteThrough;
+    if write && write_through {
+      stats.write_throughs += 1;
+    }
+
+    let hit = (set * ways..(set + 1) * ways)
+      .find(|&i| self.lines[i].valid && self.lines[i].tag == tag);
+    let index = match hit {
+      Some(i) => {
+        stats.hits += 1;
+        self.lines[i].hits += 1;
+        i
+      }
+      None => {
+        stats.misses += 1;
+        if write && write_through {
+          // No write allocate
+          self.last_access = None;
+          return stats;
+        }
+        let i = self.victim(set);
+        let line = &mut self.lines[i];
+        if line.valid && line.dirty {
+          stats.writebacks += 1;
+        }
+        line.valid = true;
+        line.dirty = false;
+        line.tag = tag;
+        line.misses += 1;
+        line.filled = self.clock;
+        i
+      }
+    };
+    let line = &mut self.lines[index];
+    line.last_used = self.clock;
+    if write && !write_through {
+      line.dirty = true;
+    }
+    self.last_access = Some(index);
+    stats
+  }
+
+  /// Accesses each block the bytes touch, and returns what happened.
+  pub fn access(&mut self, address: u64, len: u64, write: bool) -> CacheStats {
+    let mut stats = CacheStats::default();
+    let first = address / self.config.block_size;
+    let last = address.saturating_add(len.max(1) - 1) / self.config.block_size;
+    for block in first..=last {
+      stats.add(self.access_block(block, write));
+    }
+    self.stats.add(stats);
+    stats
+  }
+}
+
+/// Hits and misses caused by one instruction.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct InstructionStats {
+  pub fetch: CacheStats,
+  pub data: CacheStats,
+}
+
+/// Either separate instruction and data caches, or one unified cache which
+/// both go through.
+#[derive(Clone, Debug)]
+pub struct Caches {
+  pub instruction: Cache,
+  pub data: Option<Cache>,
+  pub by_line: BTreeMap<u32, InstructionStats>, // Keyed by 1 indexed line
+}
+
+impl Caches {
+  pub fn new(config: CacheConfig, split: bool) -> Self {
+    if split {
+      Caches {
+        instruction: Cache::new("Instruction cache", config),
+        data: Some(Cache::new("Data cache", config)),
+        by_line: BTreeMap::new(),
+      }
+    } else {
+      Caches {
+        instruction: Cache::new("Unified cache", config),
+        data: None,
+        by_line: BTreeMap::new(),
+      }
+    }
+  }
+
+  /// Empties the caches and forgets the statistics.
+  pub fn reset(&mut self) {
+    *self = Caches::new(self.instruction.config, self.data.is_some());
+  }
+
+  pub fn all(&self) -> Vec<&Cache> {
+    std::iter::once(&self.instruction)
+      .chain(self.data.as_ref())
+      .collect()
+  }
+
+  pub fn fetch(&mut self, line_num: u32, address: u64) {
+    let stats = self.instruction.access(address, 4, false);
+    self.by_line.entry(line_num).or_default().fetch.add(stats);
+  }
+
+  pub fn data(&mut self, line_num: u32, address: u64, len: u64, write: bool) {
+    let cache = self.data.as_mut().unwrap_or(&mut self.instruction);
+    let stats = cache.access(address, len, write);
+    self.by_line.entry(line_num).or_default().data.add(stats);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn direct_mapped(write_policy: WritePolicy) -> Cache {
+    Cache::new(
+      "test",
+      CacheConfig {
+        size: 64,
+        associativity: 1,
+        block_size: 16,
+        replacement: Replacement::Lru,
+        write_policy,
+      },
+    )
+  }
+
+  #[test]
+  fn spatial_locality() {
+    let mut cache = direct_mapped(WritePolicy::WriteBack);
+    for address in (0..32).step_by(4) {
+      cache.access(address, 4, false);
+    }
+    assert_eq!(cache.stats.hits, 6);
+    assert_eq!(cache.stats.misses, 2);
+  }
+
+  #[test]
+  fn conflict_misses_and_writebacks() {
+    let mut cache = direct_mapped(WritePolicy::WriteBack);
+    // 0 and 64 map to the same set of a 64 byte direct mapped cache
+    cache.access(0, 8, true);
+    cache.access(64, 8, false);
+    cache.access(0, 8, false);
+    assert_eq!(cache.stats.misses, 3);
+    assert_eq!(cache.stats.writebacks, 1);
+    let (set, _, line) = cache.lines().next().unwrap();
+    assert_eq!(cache.line_address(set, line), 0);
+    assert_eq!(line.misses, 3);
+  }
+
+  #[test]
+  fn write_through_does_not_allocate() {
+    let mut cache = direct_mapped(WritePolicy::WriteThrough);
+    cache.access(0, 8, true);
+    cache.access(0, 8, false);
+    cache.access(0, 8, true);
+    assert_eq!(cache.stats.misses, 2);
+    assert_eq!(cache.stats.hits, 1);
+    assert_eq!(cache.stats.write_throughs, 2);
+    assert_eq!(cache.stats.writebacks, 0);
+  }
+
+  #[test]
+  fn access_across_blocks() {
+    let mut cache = direct_mapped(WritePolicy::WriteBack);
+    assert_eq!(cache.access(12, 8, false).misses, 2);
+  }
+
+  fn two_way(replacement: Replacement) -> Cache {
+    Cache::new(
+      "test",
+      CacheConfig {
+        size: 32,
+        associativity: 2,
+        block_size: 16,
+        replacement,
+        write_policy: WritePolicy::WriteBack,
+      },
+    )
+  }
+
+  #[test]
+  fn lru_and_fifo_replacement() {
+    // Blocks 0, 1 and 2 all map to the only set. Touching block 0 again makes
+    // block 1 the least recently used, but block 0 is still the oldest.
+    let pattern = [0, 16, 0, 32, 0];
+    let mut lru = two_way(Replacement::Lru);
+    let mut fifo = two_way(Replacement::Fifo);
+    for address in pattern {
+      lru.access(address, 4, false);
+      fifo.access(address, 4, false);
+    }
+    assert_eq!(lru.stats.misses, 3);
+    assert_eq!(fifo.stats.misses, 4);
+  }
+
+  #[test]
+  fn invalid_configs() {
+    let config = CacheConfig {
+      size: 48,
+      ..CacheConfig::default()
+    };
+    assert!(config.validate().is_err());
+    let config = CacheConfig {
+      size: 16,
+      ..CacheConfig::default()
+    };
+    assert!(config.validate().is_err());
+    assert!(CacheConfig::default().validate().is_ok());
+  }
+
+  #[test]
+  fn unified_cache_shares_lines() {
+    let mut caches = Caches::new(CacheConfig::default(), false);
+    caches.fetch(1, 0x100);
+    caches.data(1, 0x104, 4, false);
+    assert_eq!(caches.all().len(), 1);
+    assert_eq!(caches.by_line[&1].fetch.misses, 1);
+    assert_eq!(caches.by_line[&1].data.hits, 1);
+  }
+}
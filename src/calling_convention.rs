@@ -0,0 +1,121 @@
+use crate::codegen::REGISTER_INFO;
+use crate::instruction::{Instruction, Register, Saver};
+
+// a0 and a1 hold the return value, so they're expected to change
+const RETURN_VALUE_REGISTERS: [usize; 2] = [10, 11];
+
+struct Frame {
+  return_address: u64,
+  call_line: u32,
+  callee: String,
+  registers: [Register; 32], // On entry to the callee
+}
+
+/// Watches a running program for breaches of the calling convention described
+/// by the Saver column of the register table:
+///  - Callee saved registers must have the same value on return as on entry.
+///  - Caller saved registers may have been changed by a call, so shouldn't be
+///    read after a call until they've been written again.
+pub struct CallingConventionChecker {
+  frames: Vec<Frame>,
+  // For each register, the line of the call which may have changed it, if it
+  // hasn't been written since.
+  clobbered_by: [Option<u32>; 32],
+}
+
+impl CallingConventionChecker {
+  pub fn new() -> Self {
+    CallingConventionChecker {
+      frames: Vec::new(),
+      clobbered_by: [None; 32],
+    }
+  }
+
+  pub fn call(
+    &mut self,
+    call_line: u32,
+    callee: String,
+    return_address: u64,
+    registers: &[Register; 32],
+  ) {
+    self.frames.push(Frame {
+      return_address,
+      call_line,
+      callee,
+      registers: *registers,
+    });
+  }
+
+  /// Called after a return to `target` has executed on `line_num`.
+  pub fn ret(
+    &mut self,
+    line_num: u32,
+    target: u64,
+    registers: &[Register; 32],
+  ) -> Vec<String> {
+    let mut warnings: Vec<String> = Vec::new();
+    let depth = match self
+      .frames
+      .iter()
+      .rposition(|frame| frame.return_address == target)
+    {
+      Some(depth) => depth,
+      None => return warnings,
+    };
+    let frame = &self.frames[depth];
+
+    for (i, info) in REGISTER_INFO.iter().enumerate() {
+      if info.saver == Saver::Callee && frame.registers[i] != registers[i] {
+        warnings.push(format!(
+          "Calling convention warning on line {}: {} returned with {} \
+           changed from 0x{:x} to 0x{:x}, but {} is callee saved",
+          line_num,
+          frame.callee,
+          info.abi_name,
+          frame.registers[i].value,
+          registers[i].value,
+          info.abi_name
+        ));
+      }
+      if info.saver == Saver::Caller
+        && i != 1 // ra is written by the call itself
+        && !RETURN_VALUE_REGISTERS.contains(&i)
+      {
+        self.clobbered_by[i] = Some(frame.call_line);
+      }
+    }
+
+    self.frames.truncate(depth);
+    warnings
+  }
+
+  /// Called before `instruction` on `line_num` executes.
+  pub fn check_reads(
+    &mut self,
+    instruction: &Instruction,
+    line_num: u32,
+  ) -> Vec<String> {
+    let mut warnings: Vec<String> = Vec::new();
+    for name in ["rs1", "rs2"] {
+      if let Some(reg) = instruction.register_arg(name) {
+        if let Some(call_line) = self.clobbered_by[reg].take() {
+          let abi_name = REGISTER_INFO[reg].abi_name;
+          warnings.push(format!(
+            "Calling convention warning on line {}: {} was read after the \
+             call on line {} without being written since, but {} is caller \
+             saved so the call may have changed it",
+            line_num, abi_name, call_line, abi_name
+          ));
+        }
+      }
+    }
+    warnings
+  }
+
+  /// Called after `instruction` executes.
+  pub fn record_writes(&mut self, instruction: &Instruction) {
+    if let Some(reg) = instruction.register_arg("rd") {
+      self.clobbered_by[reg] = None;
+    }
+  }
+}
@@ -0,0 +1,673 @@
+use crate::instruction::Register;
+use std::fmt;
+
+pub const SSTATUS: u16 = 0x100;
+pub const SIE: u16 = 0x104;
+pub const STVEC: u16 = 0x105;
+pub const SSCRATCH: u16 = 0x140;
+pub const SEPC: u16 = 0x141;
+pub const SCAUSE: u16 = 0x142;
+pub const STVAL: u16 = 0x143;
+pub const SIP: u16 = 0x144;
+pub const SATP: u16 = 0x180;
+pub const MSTATUS: u16 = 0x300;
+pub const MISA: u16 = 0x301;
+pub const MEDELEG: u16 = 0x302;
+pub const MIDELEG: u16 = 0x303;
+pub const MIE: u16 = 0x304;
+pub const MTVEC: u16 = 0x305;
+pub const MSCRATCH: u16 = 0x340;
+pub const MEPC: u16 = 0x341;
+pub const MCAUSE: u16 = 0x342;
+pub const MTVAL: u16 = 0x343;
+pub const MIP: u16 = 0x344;
+pub const MCYCLE: u16 = 0xb00;
+pub const MINSTRET: u16 = 0xb02;
+pub const CYCLE: u16 = 0xc00;
+pub const INSTRET: u16 = 0xc02;
+pub const MHARTID: u16 = 0xf14;
+
+const NAMES: [(&str, u16); 25] = [
+  ("sstatus", SSTATUS),
+  ("sie", SIE),
+  ("stvec", STVEC),
+  ("sscratch", SSCRATCH),
+  ("sepc", SEPC),
+  ("scause", SCAUSE),
+  ("stval", STVAL),
+  ("sip", SIP),
+  ("satp", SATP),
+  ("mstatus", MSTATUS),
+  ("misa", MISA),
+  ("medeleg", MEDELEG),
+  ("mideleg", MIDELEG),
+  ("mie", MIE),
+  ("mtvec", MTVEC),
+  ("mscratch", MSCRATCH),
+  ("mepc", MEPC),
+  ("mcause", MCAUSE),
+  ("mtval", MTVAL),
+  ("mip", MIP),
+  ("mcycle", MCYCLE),
+  ("minstret", MINSTRET),
+  ("cycle", CYCLE),
+  ("instret", INSTRET),
+  ("mhartid", MHARTID),
+];
+
+pub fn csr_name(address: u16) -> Option<&'static str> {
+  NAMES
+    .iter()
+    .find(|(_, a)| *a == address)
+    .map(|(name, _)| *name)
+}
+
+/// The CSR operand of an instruction, either by name or by number.
+pub fn csr_address(token: &str) -> Option<u16> {
+  match NAMES.iter().find(|(name, _)| *name == token) {
+    Some((_, address)) => Some(*address),
+    None => parse_int::parse::<u16>(token).ok().filter(|a| *a <= 0xfff),
+  }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, PartialOrd)]
+pub enum Privilege {
+  User = 0,
+  Supervisor = 1,
+  Machine = 3,
+}
+
+impl Privilege {
+  fn from_bits(bits: u64) -> Privilege {
+    match bits & 3 {
+      0 => Privilege::User,
+      1 => Privilege::Supervisor,
+      _ => Privilege::Machine,
+    }
+  }
+}
+
+impl fmt::Display for Privilege {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Privilege::User => write!(f, "user"),
+      Privilege::Supervisor => write!(f, "supervisor"),
+      Privilege::Machine => write!(f, "machine"),
+    }
+  }
+}
+
+// mstatus fields. sstatus is a view of the supervisor ones.
+const MSTATUS_SIE: u64 = 1 << 1;
+const MSTATUS_MIE: u64 = 1 << 3;
+const MSTATUS_SPIE: u64 = 1 << 5;
+const MSTATUS_MPIE: u64 = 1 << 7;
+const MSTATUS_SPP: u64 = 1 << 8;
+const MSTATUS_MPP_SHIFT: u64 = 11;
+const MSTATUS_MPP: u64 = 3 << MSTATUS_MPP_SHIFT;
+const MSTATUS_SUM: u64 = 1 << 18;
+const MSTATUS_MXR: u64 = 1 << 19;
+const SSTATUS_MASK: u64 =
+  MSTATUS_SIE | MSTATUS_SPIE | MSTATUS_SPP | MSTATUS_SUM | MSTATUS_MXR;
+
+// Interrupt bits in mie and mip, which are also the interrupt cause codes
+pub const SSI: u64 = 1 << 1;
+pub const MSI: u64 = 1 << 3;
+pub const STI: u64 = 1 << 5;
+pub const MTI: u64 = 1 << 7;
+pub const SEI: u64 = 1 << 9;
+pub const MEI: u64 = 1 << 11;
+const SUPERVISOR_INTERRUPTS: u64 = SSI | STI | SEI;
+
+/// In priority order, highest first
+pub const INTERRUPTS: [(&str, u64); 6] = [
+  ("Machine external", MEI),
+  ("Machine software", MSI),
+  ("Machine timer", MTI),
+  ("Supervisor external", SEI),
+  ("Supervisor software", SSI),
+  ("Supervisor timer", STI),
+];
+
+pub const ILLEGAL_INSTRUCTION: u64 = 2;
+pub const BREAKPOINT: u64 = 3;
+pub const ENVIRONMENT_CALL_FROM_U: u64 = 8;
+pub const INSTRUCTION_PAGE_FAULT: u64 = 12;
+pub const LOAD_PAGE_FAULT: u64 = 13;
+pub const STORE_PAGE_FAULT: u64 = 15;
+
+const EXCEPTIONS: [(u64, &str); 8] = [
+  (ILLEGAL_INSTRUCTION, "illegal instruction"),
+  (BREAKPOINT, "breakpoint"),
+  (ENVIRONMENT_CALL_FROM_U, "environment call from user mode"),
+  (9, "environment call from supervisor mode"),
+  (11, "environment call from machine mode"),
+  (INSTRUCTION_PAGE_FAULT, "instruction page fault"),
+  (LOAD_PAGE_FAULT, "load page fault"),
+  (STORE_PAGE_FAULT, "store page fault"),
+];
+// Everything except environment calls from machine mode, which can't be
+// delegated
+const DELEGABLE_EXCEPTIONS: u64 = 0xb3ff & !(1 << 11);
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Trap {
+  Exception { code: u64, tval: u64 },
+  Interrupt(u64),
+}
+
+impl Trap {
+  fn mcause(&self) -> u64 {
+    match self {
+      Trap::Exception { code, .. } => *code,
+      Trap::Interrupt(code) => 1 << 63 | code,
+    }
+  }
+}
+
+impl fmt::Display for Trap {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Trap::Exception { code, tval } => {
+        match EXCEPTIONS.iter().find(|(c, _)| c == code) {
+          Some((_, name)) => write!(f, "{}", name)?,
+          None => write!(f, "exception {}", code)?,
+        }
+        if *tval != 0 {
+          write!(f, " (tval 0x{:x})", tval)?;
+        }
+        Ok(())
+      }
+      Trap::Interrupt(code) => {
+        match INTERRUPTS.iter().find(|(_, bit)| 1 << code == *bit) {
+          Some((name, _)) => write!(f, "{} interrupt", name.to_lowercase()),
+          None => write!(f, "interrupt {}", code),
+        }
+      }
+    }
+  }
+}
+
+/// What the MMU needs to know to translate an address.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Translation {
+  pub satp: u64,
+  pub privilege: Privilege,
+  // Supervisor may access user pages
+  pub sum: bool,
+  // Loads from executable pages are allowed
+  pub mxr: bool,
+}
+
+/// The machine and supervisor mode control and status registers, and the
+/// current privilege level. Instructions read and write them through read()
+/// and write(); an access to a CSR which doesn't exist, or isn't accessible
+/// from the current privilege level, or a write to a read only one, raises an
+/// illegal instruction exception for the interpreter to pick up with
+/// take_exception().
+pub struct CsrFile {
+  pub privilege: Privilege,
+  mstatus: u64,
+  medeleg: u64,
+  mideleg: u64,
+  mie: u64,
+  // Pending bits set by software; the rest come from the devices
+  mip_software: u64,
+  mip: u64,
+  mtvec: u64,
+  mscratch: u64,
+  mepc: u64,
+  mcause: u64,
+  mtval: u64,
+  stvec: u64,
+  sscratch: u64,
+  sepc: u64,
+  scause: u64,
+  stval: u64,
+  satp: u64,
+  mcycle: u64,
+  minstret: u64,
+  exception: Option<Trap>,
+  // Set by sfence.vma until the interpreter flushes the TLB
+  flush_tlb: bool,
+  // Set by wfi until an interrupt is pending
+  pub waiting: bool,
+  // The CSRs read, in order, for tests to check that instructions which
+  // mustn't read a CSR don't
+  #[cfg(test)]
+  pub reads: Vec<u16>,
+}
+
+impl CsrFile {
+  pub fn new() -> Self {
+    CsrFile {
+      privilege: Privilege::Machine,
+      mstatus: 0,
+      medeleg: 0,
+      mideleg: 0,
+      mie: 0,
+      mip_software: 0,
+      mip: 0,
+      mtvec: 0,
+      mscratch: 0,
+      mepc: 0,
+      mcause: 0,
+      mtval: 0,
+      stvec: 0,
+      sscratch: 0,
+      sepc: 0,
+      scause: 0,
+      stval: 0,
+      satp: 0,
+      mcycle: 0,
+      minstret: 0,
+      exception: None,
+      flush_tlb: false,
+      waiting: false,
+      #[cfg(test)]
+      reads: Vec::new(),
+    }
+  }
+
+  /// The value of a CSR, without side effects or privilege checks, or None
+  /// if it doesn't exist.
+  pub fn get(&self, address: u16) -> Option<u64> {
+    Some(match address {
+      SSTATUS => self.mstatus & SSTATUS_MASK,
+      SIE => self.mie & self.mideleg,
+      STVEC => self.stvec,
+      SSCRATCH => self.sscratch,
+      SEPC => self.sepc,
+      SCAUSE => self.scause,
+      STVAL => self.stval,
+      SIP => self.mip & self.mideleg,
+      SATP => self.satp,
+      MSTATUS => self.mstatus,
+      // RV64 (2 in the top bits) with the I extension, and S and U modes
+      MISA => 2 << 62 | 1 << 8 | 1 << 18 | 1 << 20,
+      MEDELEG => self.medeleg,
+      MIDELEG => self.mideleg,
+      MIE => self.mie,
+      MTVEC => self.mtvec,
+      MSCRATCH => self.mscratch,
+      MEPC => self.mepc,
+      MCAUSE => self.mcause,
+      MTVAL => self.mtval,
+      MIP => self.mip,
+      MCYCLE | CYCLE => self.mcycle,
+      MINSTRET | INSTRET => self.minstret,
+      MHARTID => 0,
+      _ => return None,
+    })
+  }
+
+  fn raise(&mut self, code: u64, tval: u64) {
+    self.exception.get_or_insert(Trap::Exception { code, tval });
+  }
+
+  fn illegal(&mut self, address: u16) {
+    self.raise(ILLEGAL_INSTRUCTION, address as u64);
+  }
+
+  /// Bits 8-9 of a CSR's address are the lowest privilege that can use it.
+  fn accessible(&self, address: u16) -> bool {
+    (address >> 8) & 3 <= self.privilege as u16
+  }
+
+  pub fn read(&mut self, address: u16) -> Register {
+    #[cfg(test)]
+    self.reads.push(address);
+    match self.get(address).filter(|_| self.accessible(address)) {
+      Some(value) => Register { value },
+      None => {
+        self.illegal(address);
+        Register { value: 0 }
+      }
+    }
+  }
+
+  pub fn write(&mut self, address: u16, value: Register) {
+    if !self.accessible(address) {
+      self.illegal(address);
+      return;
+    }
+    let value = value.value;
+    let machine_interrupts = MSI | MTI | MEI;
+    match address {
+      SSTATUS => {
+        self.mstatus = (self.mstatus & !SSTATUS_MASK) | (value & SSTATUS_MASK)
+      }
+      SIE => {
+        self.mie = (self.mie & !self.mideleg) | (value & self.mideleg);
+      }
+      STVEC => self.stvec = value & !2,
+      SSCRATCH => self.sscratch = value,
+      SEPC => self.sepc = value & !3,
+      SCAUSE => self.scause = value,
+      STVAL => self.stval = value,
+      // Supervisor software may only clear or set its own software interrupt
+      SIP => {
+        let writable = SSI & self.mideleg;
+        self.mip_software =
+          (self.mip_software & !writable) | (value & writable);
+      }
+      // Only Sv39 (8) and Bare (0) are supported; other modes are ignored
+      SATP if value >> 60 == 0 || value >> 60 == 8 => self.satp = value,
+      SATP => (),
+      MSTATUS => {
+        let writable = SSTATUS_MASK | MSTATUS_MIE | MSTATUS_MPIE | MSTATUS_MPP;
+        let mut value = value;
+        // 2 isn't a valid privilege level
+        if (value & MSTATUS_MPP) >> MSTATUS_MPP_SHIFT == 2 {
+          value &= !MSTATUS_MPP;
+        }
+        self.mstatus = (self.mstatus & !writable) | (value & writable);
+      }
+      MEDELEG => self.medeleg = value & DELEGABLE_EXCEPTIONS,
+      MIDELEG => self.mideleg = value & SUPERVISOR_INTERRUPTS,
+      MIE => self.mie = value & (machine_interrupts | SUPERVISOR_INTERRUPTS),
+      // Mode 2 and 3 are reserved
+      MTVEC => self.mtvec = value & !2,
+      MSCRATCH => self.mscratch = value,
+      MEPC => self.mepc = value & !3,
+      MCAUSE => self.mcause = value,
+      MTVAL => self.mtval = value,
+      // Machine software can raise the supervisor interrupts; the machine
+      // ones are set and cleared by the devices
+      MIP => self.mip_software = value & SUPERVISOR_INTERRUPTS,
+      MCYCLE => self.mcycle = value,
+      MINSTRET => self.minstret = value,
+      // misa is WARL and nothing is optional
+      MISA => (),
+      _ => self.illegal(address),
+    }
+  }
+
+  pub fn take_exception(&mut self) -> Option<Trap> {
+    self.exception.take()
+  }
+
+  /// Called once per step with the interrupt lines raised by the devices.
+  pub fn set_pending(&mut self, mip: u64) {
+    self.mip = mip | self.mip_software;
+  }
+
+  /// Whether interrupts are enabled for the current privilege level.
+  pub fn enabled(&self) -> bool {
+    match self.privilege {
+      Privilege::Machine => self.mstatus & MSTATUS_MIE != 0,
+      Privilege::Supervisor => self.mstatus & MSTATUS_SIE != 0,
+      Privilege::User => true,
+    }
+  }
+
+  pub fn mie(&self) -> u64 {
+    self.mie
+  }
+
+  pub fn mip(&self) -> u64 {
+    self.mip
+  }
+
+  /// The highest priority interrupt which should be taken now, if any.
+  /// Interrupts handled at a higher privilege level than the current one are
+  /// always enabled, and those at a lower one never are.
+  pub fn pending_interrupt(&self) -> Option<Trap> {
+    let machine_enabled =
+      self.privilege < Privilege::Machine || self.mstatus & MSTATUS_MIE != 0;
+    let supervisor_enabled = self.privilege < Privilege::Supervisor
+      || (self.privilege == Privilege::Supervisor
+        && self.mstatus & MSTATUS_SIE != 0);
+    INTERRUPTS
+      .iter()
+      .find(|(_, bit)| {
+        let enabled = if self.mideleg & bit != 0 {
+          supervisor_enabled
+        } else {
+          machine_enabled
+        };
+        enabled && self.mip & self.mie & bit != 0
+      })
+      .map(|(_, bit)| Trap::Interrupt(bit.trailing_zeros() as u64))
+  }
+
+  /// The privilege level a trap will be handled in. Delegated traps go to
+  /// supervisor mode, unless they happen in machine mode.
+  fn target(&self, trap: Trap) -> Privilege {
+    let delegated = match trap {
+      Trap::Exception { code, .. } => self.medeleg >> code & 1 == 1,
+      Trap::Interrupt(code) => self.mideleg >> code & 1 == 1,
+    };
+    if delegated && self.privilege < Privilege::Machine {
+      Privilege::Supervisor
+    } else {
+      Privilege::Machine
+    }
+  }
+
+  pub fn handler_installed(&self, trap: Trap) -> bool {
+    match self.target(trap) {
+      Privilege::Supervisor => self.stvec != 0,
+      _ => self.mtvec != 0,
+    }
+  }
+
+  /// Enters the trap handler, returning its address.
+  pub fn trap(&mut self, trap: Trap, pc: u64) -> Register {
+    let tval = match trap {
+      Trap::Exception { tval, .. } => tval,
+      Trap::Interrupt(_) => 0,
+    };
+    let tvec = if self.target(trap) == Privilege::Supervisor {
+      self.sepc = pc;
+      self.scause = trap.mcause();
+      self.stval = tval;
+      let spie = if self.mstatus & MSTATUS_SIE != 0 {
+        MSTATUS_SPIE
+      } else {
+        0
+      };
+      let spp = if self.privilege == Privilege::User {
+        0
+      } else {
+        MSTATUS_SPP
+      };
+      self.mstatus = (self.mstatus
+        & !(MSTATUS_SIE | MSTATUS_SPIE | MSTATUS_SPP))
+        | spie
+        | spp;
+      self.privilege = Privilege::Supervisor;
+      self.stvec
+    } else {
+      self.mepc = pc;
+      self.mcause = trap.mcause();
+      self.mtval = tval;
+      let mpie = if self.mstatus & MSTATUS_MIE != 0 {
+        MSTATUS_MPIE
+      } else {
+        0
+      };
+      let mpp = (self.privilege as u64) << MSTATUS_MPP_SHIFT;
+      self.mstatus = (self.mstatus
+        & !(MSTATUS_MIE | MSTATUS_MPIE | MSTATUS_MPP))
+        | mpie
+        | mpp;
+      self.privilege = Privilege::Machine;
+      self.mtvec
+    };
+    self.waiting = false;
+    let base = tvec & !3;
+    match trap {
+      // Vectored mode
+      Trap::Interrupt(code) if tvec & 1 == 1 => Register {
+        value: base + 4 * code,
+      },
+      _ => Register { value: base },
+    }
+  }
+
+  pub fn mret(&mut self) -> Register {
+    if self.privilege < Privilege::Machine {
+      self.illegal(0);
+      return Register { value: 0 };
+    }
+    let mie = if self.mstatus & MSTATUS_MPIE != 0 {
+      MSTATUS_MIE
+    } else {
+      0
+    };
+    self.privilege = Privilege::from_bits(self.mstatus >> MSTATUS_MPP_SHIFT);
+    self.mstatus =
+      (self.mstatus & !(MSTATUS_MIE | MSTATUS_MPP)) | mie | MSTATUS_MPIE;
+    Register { value: self.mepc }
+  }
+
+  pub fn sret(&mut self) -> Register {
+    if self.privilege < Privilege::Supervisor {
+      self.illegal(0);
+      return Register { value: 0 };
+    }
+    let sie = if self.mstatus & MSTATUS_SPIE != 0 {
+      MSTATUS_SIE
+    } else {
+      0
+    };
+    self.privilege = if self.mstatus & MSTATUS_SPP != 0 {
+      Privilege::Supervisor
+    } else {
+      Privilege::User
+    };
+    self.mstatus =
+      (self.mstatus & !(MSTATUS_SIE | MSTATUS_SPP)) | sie | MSTATUS_SPIE;
+    Register { value: self.sepc }
+  }
+
+  pub fn ecall(&mut self) {
+    let code = match self.privilege {
+      Privilege::User => ENVIRONMENT_CALL_FROM_U,
+      Privilege::Supervisor => ENVIRONMENT_CALL_FROM_U + 1,
+      Privilege::Machine => ENVIRONMENT_CALL_FROM_U + 3,
+    };
+    self.raise(code, 0);
+  }
+
+  pub fn ebreak(&mut self) {
+    self.raise(BREAKPOINT, 0);
+  }
+
+  /// With no interrupts enabled nothing could ever wake the hart, so it
+  /// carries on as if wfi were a nop.
+  pub fn wfi(&mut self) {
+    if self.privilege == Privilege::User {
+      self.illegal(0);
+      return;
+    }
+    self.waiting = self.mie != 0;
+  }
+
+  /// Whether an enabled interrupt is pending, which wakes the hart from wfi
+  /// even if interrupts are globally disabled.
+  pub fn wake(&self) -> bool {
+    self.mip & self.mie != 0
+  }
+
+  pub fn sfence_vma(&mut self) {
+    if self.privilege == Privilege::User {
+      self.illegal(0);
+      return;
+    }
+    self.flush_tlb = true;
+  }
+
+  pub fn take_tlb_flush(&mut self) -> bool {
+    std::mem::take(&mut self.flush_tlb)
+  }
+
+  pub fn translation(&self) -> Translation {
+    Translation {
+      satp: self.satp,
+      privilege: self.privilege,
+      sum: self.mstatus & MSTATUS_SUM != 0,
+      mxr: self.mstatus & MSTATUS_MXR != 0,
+    }
+  }
+
+  pub fn tick(&mut self, retired: bool) {
+    self.mcycle = self.mcycle.wrapping_add(1);
+    if retired {
+      self.minstret = self.minstret.wrapping_add(1);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn names_and_numbers() {
+    assert_eq!(csr_address("mstatus"), Some(MSTATUS));
+    assert_eq!(csr_address("0x344"), Some(MIP));
+    assert_eq!(csr_address("0x1000"), None);
+    assert_eq!(csr_address("bogus"), None);
+  }
+
+  #[test]
+  fn unknown_and_read_only_csrs_are_illegal() {
+    let mut csrs = CsrFile::new();
+    csrs.read(0x7c0);
+    assert!(csrs.take_exception().is_some());
+    csrs.write(CYCLE, Register { value: 1 });
+    assert!(csrs.take_exception().is_some());
+    csrs.write(MSCRATCH, Register { value: 1 });
+    assert_eq!(csrs.take_exception(), None);
+  }
+
+  #[test]
+  fn trap_and_return() {
+    let mut csrs = CsrFile::new();
+    csrs.write(MTVEC, Register { value: 0x101 });
+    csrs.write(MIE, Register { value: MTI });
+    csrs.write(MSTATUS, Register { value: MSTATUS_MIE });
+    assert_eq!(csrs.pending_interrupt(), None);
+    csrs.set_pending(MTI | MSI);
+    let trap = csrs.pending_interrupt().unwrap();
+    assert_eq!(trap, Trap::Interrupt(7));
+    assert_eq!(csrs.trap(trap, 0x40).value, 0x100 + 4 * 7);
+    assert!(!csrs.enabled());
+    assert_eq!(csrs.get(MCAUSE), Some(1 << 63 | 7));
+    assert_eq!(csrs.mret().value, 0x40);
+    assert!(csrs.enabled());
+  }
+
+  #[test]
+  fn delegation_to_supervisor() {
+    let mut csrs = CsrFile::new();
+    csrs.write(STVEC, Register { value: 0x200 });
+    csrs.write(MTVEC, Register { value: 0x100 });
+    csrs.write(
+      MEDELEG,
+      Register {
+        value: 1 << ENVIRONMENT_CALL_FROM_U,
+      },
+    );
+    // mret to user mode (MPP is 0)
+    csrs.write(MEPC, Register { value: 0x40 });
+    assert_eq!(csrs.mret().value, 0x40);
+    assert_eq!(csrs.privilege, Privilege::User);
+    // User mode can't touch supervisor CSRs
+    csrs.read(SSTATUS);
+    assert!(csrs.take_exception().is_some());
+    csrs.ecall();
+    let trap = csrs.take_exception().unwrap();
+    assert_eq!(csrs.trap(trap, 0x44).value, 0x200);
+    assert_eq!(csrs.privilege, Privilege::Supervisor);
+    assert_eq!(csrs.get(SCAUSE), Some(ENVIRONMENT_CALL_FROM_U));
+    assert_eq!(csrs.get(MCAUSE), Some(0));
+    // An ecall from supervisor mode isn't delegated
+    csrs.ecall();
+    let trap = csrs.take_exception().unwrap();
+    assert_eq!(csrs.trap(trap, 0x200).value, 0x100);
+    assert_eq!(csrs.privilege, Privilege::Machine);
+  }
+}
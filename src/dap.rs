@@ -0,0 +1,874 @@
+//! A Debug Adapter Protocol server, so that programs can be debugged from an
+//! editor such as VS Code. The client launches `riscv-interpreter --dap` and
+//! talks to it over stdin and stdout, then sends a launch request with the
+//! path of the program:
+//!
+//! ```json
+//! { "type": "riscv-interpreter", "request": "launch",
+//!   "program": "${file}", "stopOnEntry": true }
+//! ```
+//!
+//! Breakpoints are on source lines. Stepping goes a line at a time, so a
+//! pseudo instruction which expands to several instructions is one step.
+
+use crate::codegen::{REGISTERS, REGISTER_INFO};
+use crate::macros::read_includes;
+use crate::Interpreter;
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::convert::TryFrom;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+/// How many instructions to run between checks for a pause request.
+const PAUSE_POLL_STEPS: u64 = 4096;
+const THREAD_ID: u64 = 1;
+
+// variablesReference values. Each memory region gets MEMORY_REGION plus its
+// index.
+const REGISTERS_SCOPE: u64 = 1;
+const MEMORY_SCOPE: u64 = 2;
+const MEMORY_REGION: u64 = 100;
+
+/// How much of each memory region the Memory scope shows.
+const MEMORY_ROWS: u64 = 32;
+const MEMORY_ROW_BYTES: usize = 16;
+
+/// Reads one message, which is JSON with a Content-Length header. Returns
+/// None at the end of the input.
+pub fn read_message(input: &mut impl BufRead) -> io::Result<Option<Value>> {
+  let mut length = None;
+  loop {
+    let mut header = String::new();
+    if input.read_line(&mut header)? == 0 {
+      return Ok(None);
+    }
+    let header = header.trim_end();
+    if header.is_empty() {
+      break;
+    }
+    if let Some(value) = header.strip_prefix("Content-Length:") {
+      length = value.trim().parse::<usize>().ok();
+    }
+  }
+  let length = length.ok_or_else(|| {
+    io::Error::new(io::ErrorKind::InvalidData, "No Content-Length header")
+  })?;
+  let mut body = vec![0; length];
+  input.read_exact(&mut body)?;
+  serde_json::from_slice(&body)
+    .map(Some)
+    .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+}
+
+pub fn write_message(
+  output: &mut impl Write,
+  message: &Value,
+) -> io::Result<()> {
+  let body = message.to_string();
+  write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+  output.flush()
+}
+
+/// Evaluates a watch expression. Registers are named either way (`x10` or
+/// `a0`), `pc` is the program counter, numbers are as in the assembler, and
+/// `*` before an expression reads the doubleword at that address. `+`, `-`
+/// and `*` do wrapping 64 bit arithmetic, and brackets group.
+pub fn evaluate(
+  interpreter: &Interpreter,
+  expression: &str,
+) -> Result<u64, String> {
+  let mut parser = ExpressionParser {
+    interpreter,
+    tokens: tokenise(expression)?,
+    position: 0,
+  };
+  let value = parser.sum()?;
+  match parser.tokens.get(parser.position) {
+    None => Ok(value),
+    Some(token) => Err(format!("Unexpected \"{}\"", token)),
+  }
+}
+
+fn tokenise(expression: &str) -> Result<Vec<String>, String> {
+  let mut tokens = Vec::new();
+  let mut chars = expression.chars().peekable();
+  while let Some(&c) = chars.peek() {
+    if c.is_whitespace() {
+      chars.next();
+    } else if "+-*()".contains(c) {
+      tokens.push(c.to_string());
+      chars.next();
+    } else if c.is_ascii_alphanumeric() || c == '_' {
+      let mut token = String::new();
+      while let Some(&c) = chars.peek() {
+        if !(c.is_ascii_alphanumeric() || c == '_') {
+          break;
+        }
+        token.push(c);
+        chars.next();
+      }
+      tokens.push(token);
+    } else {
+      return Err(format!("Unexpected \"{}\"", c));
+    }
+  }
+  Ok(tokens)
+}
+
+struct ExpressionParser<'a> {
+  interpreter: &'a Interpreter,
+  tokens: Vec<String>,
+  position: usize,
+}
+
+impl ExpressionParser<'_> {
+  fn next_is(&mut self, token: &str) -> bool {
+    let matches = self.tokens.get(self.position).is_some_and(|t| t == token);
+    if matches {
+      self.position += 1;
+    }
+    matches
+  }
+
+  fn sum(&mut self) -> Result<u64, String> {
+    let mut value = self.product()?;
+    loop {
+      if self.next_is("+") {
+        value = value.wrapping_add(self.product()?);
+      } else if self.next_is("-") {
+        value = value.wrapping_sub(self.product()?);
+      } else {
+        return Ok(value);
+      }
+    }
+  }
+
+  fn product(&mut self) -> Result<u64, String> {
+    let mut value = self.unary()?;
+    while self.next_is("*") {
+      value = value.wrapping_mul(self.unary()?);
+    }
+    Ok(value)
+  }
+
+  fn unary(&mut self) -> Result<u64, String> {
+    if self.next_is("-") {
+      return Ok(self.unary()?.wrapping_neg());
+    }
+    if self.next_is("*") {
+      let address = self.unary()?;
+      let bytes: Option<Vec<u8>> = self
+        .interpreter
+        .debug_read(address, 8)
+        .into_iter()
+        .collect();
+      let bytes =
+        bytes.ok_or_else(|| format!("Can't read memory at 0x{:x}", address))?;
+      let mut doubleword = [0; 8];
+      doubleword.copy_from_slice(&bytes);
+      return Ok(u64::from_le_bytes(doubleword));
+    }
+    self.primary()
+  }
+
+  fn primary(&mut self) -> Result<u64, String> {
+    if self.next_is("(") {
+      let value = self.sum()?;
+      if !self.next_is(")") {
+        return Err("Missing \")\"".to_string());
+      }
+      return Ok(value);
+    }
+    let token = self
+      .tokens
+      .get(self.position)
+      .ok_or("Expected a value")?
+      .clone();
+    self.position += 1;
+    if token == "pc" {
+      return Ok(self.interpreter.pc());
+    }
+    if let Some(register) = REGISTERS.get(token.as_str()) {
+      return Ok(self.interpreter.register(*register as usize));
+    }
+    parse_int::parse::<u64>(&token)
+      .map_err(|_| format!("Unknown value \"{}\"", token))
+  }
+}
+
+pub struct DapServer<W: Write> {
+  requests: Receiver<Value>,
+  // Requests which arrived while the program was running
+  pending: VecDeque<Value>,
+  output: W,
+  seq: u64,
+  interpreter: Option<Interpreter>,
+  program: String,
+  stop_on_entry: bool,
+  // Whether the client numbers lines from 1, rather than 0
+  lines_start_at_1: bool,
+  // How much of the console output has been sent to the client
+  console_sent: usize,
+}
+
+impl<W: Write> DapServer<W> {
+  /// Reads requests on a thread of their own, so that a pause request can
+  /// arrive while the program is running.
+  pub fn new(input: impl BufRead + Send + 'static, output: W) -> Self {
+    let (sender, requests) = mpsc::channel();
+    thread::spawn(move || {
+      let mut input = input;
+      while let Ok(Some(message)) = read_message(&mut input) {
+        if sender.send(message).is_err() {
+          break;
+        }
+      }
+    });
+    DapServer {
+      requests,
+      pending: VecDeque::new(),
+      output,
+      seq: 0,
+      interpreter: None,
+      program: String::new(),
+      stop_on_entry: false,
+      lines_start_at_1: true,
+      console_sent: 0,
+    }
+  }
+
+  /// Handles requests until the client disconnects.
+  pub fn serve(&mut self) -> io::Result<()> {
+    loop {
+      let request = match self.pending.pop_front() {
+        Some(request) => request,
+        None => match self.requests.recv() {
+          Ok(request) => request,
+          Err(_) => return Ok(()),
+        },
+      };
+      if !self.handle(&request)? {
+        return Ok(());
+      }
+    }
+  }
+
+  fn send(&mut self, mut message: Value) -> io::Result<()> {
+    self.seq += 1;
+    message["seq"] = json!(self.seq);
+    write_message(&mut self.output, &message)
+  }
+
+  fn respond(
+    &mut self,
+    request: &Value,
+    body: Result<Value, String>,
+  ) -> io::Result<()> {
+    let mut response = json!({
+      "type": "response",
+      "request_seq": request["seq"],
+      "command": request["command"],
+      "success": body.is_ok(),
+    });
+    match body {
+      Ok(body) => response["body"] = body,
+      Err(message) => response["message"] = json!(message),
+    }
+    self.send(response)
+  }
+
+  fn event(&mut self, event: &str, body: Value) -> io::Result<()> {
+    self.send(json!({ "type": "event", "event": event, "body": body }))
+  }
+
+  /// Handles one request. Returns false once the session is over.
+  fn handle(&mut self, request: &Value) -> io::Result<bool> {
+    let arguments = &request["arguments"];
+    let command = request["command"].as_str().unwrap_or("");
+    if command == "initialize" {
+      self.lines_start_at_1 =
+        arguments["linesStartAt1"].as_bool().unwrap_or(true);
+      let capabilities = json!({
+        "supportsConfigurationDoneRequest": true,
+        "supportsEvaluateForHovers": true,
+        "supportsTerminateRequest": true,
+      });
+      self.respond(request, Ok(capabilities))?;
+      return Ok(true);
+    }
+    if command == "launch" {
+      let launched = self.launch(arguments);
+      let failed = launched.is_err();
+      self.respond(request, launched.map(|_| json!({})))?;
+      if !failed {
+        self.event("initialized", json!({}))?;
+      }
+      return Ok(true);
+    }
+    if command == "disconnect" || command == "terminate" {
+      self.respond(request, Ok(json!({})))?;
+      if command == "terminate" {
+        self.event("terminated", json!({}))?;
+      }
+      return Ok(false);
+    }
+    if self.interpreter.is_none() {
+      let error = format!("No program has been launched for \"{}\"", command);
+      self.respond(request, Err(error))?;
+      return Ok(true);
+    }
+
+    let running = self.interpreter.as_ref().unwrap().running();
+    match command {
+      "setBreakpoints" => {
+        let body = self.set_breakpoints(arguments);
+        self.respond(request, Ok(body))?;
+      }
+      "continue" | "next" | "stepIn" | "stepOut" if !running => {
+        let error = "The program has finished".to_string();
+        self.respond(request, Err(error))?;
+      }
+      "configurationDone" => {
+        self.respond(request, Ok(json!({})))?;
+        if self.stop_on_entry {
+          self.stopped("entry")?;
+        } else {
+          self.resume()?;
+        }
+      }
+      "continue" => {
+        self.respond(request, Ok(json!({ "allThreadsContinued": true })))?;
+        self.resume()?;
+      }
+      "next" | "stepIn" | "stepOut" => {
+        self.respond(request, Ok(json!({})))?;
+        let interpreter = self.interpreter.as_mut().unwrap();
+        match command {
+          "next" => step_line(interpreter, Interpreter::step_over),
+          "stepIn" => step_line(interpreter, Interpreter::step),
+          _ => interpreter.step_out(),
+        }
+        self.stopped("step")?;
+      }
+      "pause" => {
+        // The program is only paused while a request's being handled
+        self.respond(request, Ok(json!({})))?;
+        self.stopped("pause")?;
+      }
+      _ => {
+        let body = self.inspect(command, arguments);
+        self.respond(request, body)?;
+      }
+    }
+    Ok(true)
+  }
+
+  fn launch(&mut self, arguments: &Value) -> Result<(), String> {
+    let program = arguments["program"]
+      .as_str()
+      .ok_or("The launch configuration has no \"program\"")?;
+    let code = std::fs::read_to_string(program)
+      .map_err(|error| format!("Couldn't read {}: {}", program, error))?;
+    let directory = Path::new(program).parent().unwrap_or(Path::new(""));
+    let mut interpreter = Interpreter::create_RiscV64_i(Vec::new());
+    for (name, code) in read_includes(directory, &code)? {
+      interpreter.add_file(name, code);
+    }
+    interpreter.set_code(code);
+    if !interpreter.errors().is_empty() {
+      return Err(interpreter.errors().join("\n"));
+    }
+    interpreter.set_running(true);
+    self.interpreter = Some(interpreter);
+    self.program = program.to_string();
+    self.stop_on_entry = arguments["stopOnEntry"].as_bool().unwrap_or(false);
+    self.console_sent = 0;
+    Ok(())
+  }
+
+  /// The requests which only look at the state of the program.
+  fn inspect(&self, command: &str, arguments: &Value) -> Result<Value, String> {
+    let interpreter = self.interpreter.as_ref().unwrap();
+    match command {
+      "threads" => Ok(json!({
+        "threads": [{ "id": THREAD_ID, "name": "hart 0" }]
+      })),
+      "stackTrace" => Ok(self.stack_trace()),
+      "scopes" => Ok(json!({ "scopes": [
+        { "name": "Registers", "variablesReference": REGISTERS_SCOPE,
+          "expensive": false },
+        { "name": "Memory", "variablesReference": MEMORY_SCOPE,
+          "expensive": false },
+      ]})),
+      "variables" => {
+        let reference = arguments["variablesReference"].as_u64().unwrap_or(0);
+        Ok(json!({ "variables": variables(interpreter, reference) }))
+      }
+      "evaluate" => {
+        let expression = arguments["expression"].as_str().unwrap_or("");
+        let value = evaluate(interpreter, expression)?;
+        Ok(json!({
+          "result": format!("{} (0x{:x})", value as i64, value),
+          "variablesReference": 0,
+        }))
+      }
+      _ => Err(format!("Unsupported request \"{}\"", command)),
+    }
+  }
+
+  fn source(&self) -> Value {
+    let name = Path::new(&self.program)
+      .file_name()
+      .map_or(self.program.clone(), |name| name.to_string_lossy().into());
+    json!({ "name": name, "path": self.program })
+  }
+
+  /// A (1 indexed) line number as the client numbers lines.
+  fn client_line(&self, line_num: u32) -> u32 {
+    line_num - !self.lines_start_at_1 as u32
+  }
+
+  /// A line number from the client as a 1 indexed one, if it's in range.
+  fn line_num(&self, line: u64) -> Option<u32> {
+    let line = line.checked_add(!self.lines_start_at_1 as u64)?;
+    u32::try_from(line).ok().filter(|&line_num| line_num > 0)
+  }
+
+  /// The frames come from the shadow call stack, innermost first. Each is
+  /// named after the label which was called.
+  fn stack_trace(&self) -> Value {
+    let interpreter = self.interpreter.as_ref().unwrap();
+    let call_stack = interpreter.call_stack();
+    let name = |depth: usize| match depth {
+      0 => "main".to_string(),
+      _ => call_stack[depth - 1].callee.clone(),
+    };
+    let mut frames = vec![json!({
+      "id": 0,
+      "name": name(call_stack.len()),
+      "source": self.source(),
+      "line": self.client_line(interpreter.next_inst_line_num() + 1),
+      "column": 1,
+      "instructionPointerReference": format!("0x{:x}", interpreter.pc()),
+    })];
+    for (depth, frame) in call_stack.iter().enumerate().rev() {
+      frames.push(json!({
+        "id": frames.len(),
+        "name": name(depth),
+        "source": self.source(),
+        "line": self.client_line(frame.caller_line),
+        "column": 1,
+      }));
+    }
+    json!({ "stackFrames": frames, "totalFrames": frames.len() })
+  }
+
+  fn set_breakpoints(&mut self, arguments: &Value) -> Value {
+    let lines: Vec<Value> = arguments["breakpoints"]
+      .as_array()
+      .map(|breakpoints| {
+        breakpoints
+          .iter()
+          .map(|breakpoint| breakpoint["line"].clone())
+          .collect()
+      })
+      .unwrap_or_default();
+    // Lines which aren't in the file are None
+    let line_nums: Vec<Option<u32>> = lines
+      .iter()
+      .map(|line| line.as_u64().and_then(|line| self.line_num(line)))
+      .collect();
+    let interpreter = self.interpreter.as_mut().unwrap();
+    // The request replaces all the breakpoints in the file
+    for (i, set) in interpreter.breakpoints().into_iter().enumerate() {
+      let line_num = i as u32 + 1; /* 1 indexed */
+      if set != line_nums.contains(&Some(line_num)) {
+        interpreter.toggle_breakpoint(line_num);
+      }
+    }
+    // Lines without an instruction can't have a breakpoint
+    let breakpoints = interpreter.breakpoints();
+    let verified: Vec<Value> = lines
+      .iter()
+      .zip(line_nums)
+      .map(|(line, line_num)| {
+        let set = line_num
+          .and_then(|line_num| breakpoints.get(line_num as usize - 1))
+          .copied()
+          .unwrap_or(false);
+        json!({ "verified": set, "line": line })
+      })
+      .collect();
+    json!({ "breakpoints": verified })
+  }
+
+  /// Sends anything the program has written to the console since last time.
+  fn send_console(&mut self) -> io::Result<()> {
+    let console = self.interpreter.as_ref().unwrap().console_output();
+    if console.len() > self.console_sent {
+      let output = console[self.console_sent..].to_string();
+      self.console_sent = console.len();
+      self
+        .event("output", json!({ "category": "stdout", "output": output }))?;
+    }
+    Ok(())
+  }
+
+  /// Tells the client the program has stopped, or exited if it's finished.
+  fn stopped(&mut self, reason: &str) -> io::Result<()> {
+    self.send_console()?;
+    let interpreter = self.interpreter.as_ref().unwrap();
+    if interpreter.running() {
+      return self.event(
+        "stopped",
+        json!({ "reason": reason, "threadId": THREAD_ID,
+                "allThreadsStopped": true }),
+      );
+    }
+    let mut messages: Vec<String> = interpreter
+      .warnings()
+      .iter()
+      .map(|warning| format!("warning: {}\n", warning))
+      .collect();
+    messages.extend(
+      interpreter
+        .errors()
+        .iter()
+        .map(|error| format!("error: {}\n", error)),
+    );
+    let exit_code = if interpreter.errors().is_empty() {
+      0
+    } else {
+      1
+    };
+    for output in messages {
+      self
+        .event("output", json!({ "category": "stderr", "output": output }))?;
+    }
+    self.event("exited", json!({ "exitCode": exit_code }))?;
+    self.event("terminated", json!({}))
+  }
+
+  /// Runs until a breakpoint, the end of the program, or a pause request.
+  /// Other requests wait until it's stopped.
+  fn resume(&mut self) -> io::Result<()> {
+    let mut steps: u64 = 0;
+    loop {
+      let interpreter = self.interpreter.as_mut().unwrap();
+      interpreter.step();
+      steps += 1;
+      if !interpreter.running() {
+        return self.stopped("exit");
+      }
+      if interpreter.at_breakpoint() {
+        return self.stopped("breakpoint");
+      }
+      if steps.is_multiple_of(PAUSE_POLL_STEPS) {
+        while let Ok(request) = self.requests.try_recv() {
+          match request["command"].as_str() {
+            Some("pause") => {
+              self.respond(&request, Ok(json!({})))?;
+              return self.stopped("pause");
+            }
+            Some("disconnect") | Some("terminate") => {
+              self.pending.push_back(request);
+              return Ok(());
+            }
+            _ => self.pending.push_back(request),
+          }
+        }
+      }
+    }
+  }
+}
+
+/// Steps until the next line, or until the program stops or jumps. Stepping
+/// is by instruction, so a line with several instructions takes a few.
+fn step_line(interpreter: &mut Interpreter, step: fn(&mut Interpreter)) {
+  let line_num = interpreter.next_inst_line_num();
+  loop {
+    let pc = interpreter.pc();
+    step(interpreter);
+    if !interpreter.running()
+      || interpreter.at_breakpoint()
+      || interpreter.next_inst_line_num() != line_num
+      || interpreter.pc() != pc.wrapping_add(4)
+    {
+      return;
+    }
+  }
+}
+
+fn variables(interpreter: &Interpreter, reference: u64) -> Vec<Value> {
+  let hex = |value: u64| format!("0x{:016x}", value);
+  match reference {
+    REGISTERS_SCOPE => {
+      let mut variables = vec![json!({
+        "name": "pc",
+        "value": hex(interpreter.pc()),
+        "evaluateName": "pc",
+        "variablesReference": 0,
+      })];
+      for (i, info) in REGISTER_INFO.iter().enumerate() {
+        variables.push(json!({
+          "name": format!("{} ({})", info.abi_name, info.name),
+          "value": hex(interpreter.register(i)),
+          "evaluateName": info.abi_name,
+          "variablesReference": 0,
+        }));
+      }
+      variables
+    }
+    MEMORY_SCOPE => interpreter
+      .memory_regions()
+      .iter()
+      .enumerate()
+      .map(|(i, region)| {
+        json!({
+          "name": region.name,
+          "value": format!("0x{:08x} ({} bytes)", region.start, region.size),
+          "variablesReference": MEMORY_REGION + i as u64,
+        })
+      })
+      .collect(),
+    _ => {
+      let regions = interpreter.memory_regions();
+      let region = match reference
+        .checked_sub(MEMORY_REGION)
+        .and_then(|i| regions.get(i as usize))
+      {
+        Some(region) => region,
+        None => return Vec::new(),
+      };
+      // The interesting part of the stack is the top
+      let sp = interpreter.register(2);
+      let start = if region.name == "stack"
+        && sp >= region.start
+        && sp < region.start + region.size
+      {
+        sp & !(MEMORY_ROW_BYTES as u64 - 1)
+      } else {
+        region.start
+      };
+      let end = region.start + region.size;
+      (0..MEMORY_ROWS)
+        .map(|row| start + row * MEMORY_ROW_BYTES as u64)
+        .take_while(|&address| address < end)
+        .map(|address| {
+          let bytes: Vec<String> = interpreter
+            .debug_read(address, MEMORY_ROW_BYTES)
+            .iter()
+            .map(|byte| byte.map_or("??".to_string(), |b| format!("{:02x}", b)))
+            .collect();
+          json!({
+            "name": format!("0x{:08x}", address),
+            "value": bytes.join(" "),
+            "variablesReference": 0,
+          })
+        })
+        .collect()
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const PROGRAM: &str = "addi a0, zero, 5
+jal ra, double
+addi a1, a0, 0
+jal zero, end
+double: add a0, a0, a0
+jalr zero, 0(ra)
+end: addi a2, zero, 1";
+
+  /// Runs a session against PROGRAM, returning everything the server sent.
+  fn session(requests: &[Value]) -> Vec<Value> {
+    let path = std::env::temp_dir()
+      .join(format!("dap-test-{:?}.s", thread::current().id()));
+    std::fs::write(&path, PROGRAM).unwrap();
+    let mut input = Vec::new();
+    let launch = json!({ "program": path.to_str().unwrap(),
+                         "stopOnEntry": true });
+    let mut requests = requests.to_vec();
+    requests.insert(0, json!(["launch", launch]));
+    for (seq, request) in requests.iter().enumerate() {
+      let message = json!({
+        "seq": seq + 1,
+        "type": "request",
+        "command": request[0],
+        "arguments": request[1],
+      });
+      write_message(&mut input, &message).unwrap();
+    }
+    let mut server = DapServer::new(io::Cursor::new(input), Vec::new());
+    server.serve().unwrap();
+    std::fs::remove_file(&path).ok();
+    let mut output = io::Cursor::new(server.output);
+    std::iter::from_fn(|| read_message(&mut output).unwrap()).collect()
+  }
+
+  fn response<'a>(messages: &'a [Value], command: &str) -> &'a Value {
+    messages
+      .iter()
+      .rev()
+      .find(|m| m["type"] == "response" && m["command"] == command)
+      .unwrap_or_else(|| panic!("No response to {}", command))
+  }
+
+  fn events<'a>(messages: &'a [Value], event: &str) -> Vec<&'a Value> {
+    messages.iter().filter(|m| m["event"] == event).collect()
+  }
+
+  #[test]
+  fn framing() {
+    let mut buffer = Vec::new();
+    write_message(&mut buffer, &json!({ "a": 1 })).unwrap();
+    assert_eq!(buffer, b"Content-Length: 7\r\n\r\n{\"a\":1}");
+    let message = read_message(&mut io::Cursor::new(buffer)).unwrap();
+    assert_eq!(message, Some(json!({ "a": 1 })));
+  }
+
+  #[test]
+  fn breakpoints() {
+    let messages = session(&[
+      json!(["setBreakpoints", { "breakpoints": [{ "line": 5 }, { "line": 8 }] }]),
+      json!(["configurationDone", {}]),
+      json!(["continue", {}]),
+      json!(["stackTrace", {}]),
+      json!(["continue", {}]),
+    ]);
+    let verified =
+      &response(&messages, "setBreakpoints")["body"]["breakpoints"];
+    assert_eq!(verified[0]["verified"], true);
+    assert_eq!(verified[1]["verified"], false);
+    let stops = events(&messages, "stopped");
+    assert_eq!(stops[0]["body"]["reason"], "entry");
+    assert_eq!(stops[1]["body"]["reason"], "breakpoint");
+    let frames = &response(&messages, "stackTrace")["body"]["stackFrames"];
+    assert_eq!(frames[0]["name"], "double");
+    assert_eq!(frames[0]["line"], 5);
+    assert_eq!(frames[1]["name"], "main");
+    assert_eq!(frames[1]["line"], 2);
+    assert_eq!(events(&messages, "exited")[0]["body"]["exitCode"], 0);
+  }
+
+  #[test]
+  fn zero_indexed_lines() {
+    let messages = session(&[
+      json!(["initialize", { "linesStartAt1": false }]),
+      json!(["setBreakpoints", { "breakpoints": [{ "line": 4 }, { "line": 7 }] }]),
+      json!(["configurationDone", {}]),
+      json!(["continue", {}]),
+      json!(["stackTrace", {}]),
+    ]);
+    let verified =
+      &response(&messages, "setBreakpoints")["body"]["breakpoints"];
+    assert_eq!(verified[0], json!({ "verified": true, "line": 4 }));
+    assert_eq!(verified[1], json!({ "verified": false, "line": 7 }));
+    let frames = &response(&messages, "stackTrace")["body"]["stackFrames"];
+    assert_eq!(frames[0]["line"], 4);
+    assert_eq!(frames[1]["line"], 1);
+
+    // Line 0 doesn't exist when lines start at 1
+    let messages = session(&[json!(["setBreakpoints", {
+      "breakpoints": [{ "line": 0 }, { "line": 1 }, { "line": u64::MAX }]
+    }])]);
+    let verified =
+      &response(&messages, "setBreakpoints")["body"]["breakpoints"];
+    assert_eq!(verified[0]["verified"], false);
+    assert_eq!(verified[1]["verified"], true);
+    assert_eq!(verified[2]["verified"], false);
+  }
+
+  #[test]
+  fn stepping() {
+    let messages = session(&[
+      json!(["configurationDone", {}]),
+      json!(["next", {}]),
+      json!(["next", {}]),
+      json!(["stackTrace", {}]),
+      json!(["evaluate", { "expression": "a0 * 2 - x11" }]),
+    ]);
+    // Stepping over the call lands after it, with it done
+    let frames = &response(&messages, "stackTrace")["body"]["stackFrames"];
+    assert_eq!(frames[0]["line"], 3);
+    let result = &response(&messages, "evaluate")["body"]["result"];
+    assert_eq!(result, "20 (0x14)");
+
+    let messages = session(&[
+      json!(["configurationDone", {}]),
+      json!(["stepIn", {}]),
+      json!(["stepIn", {}]),
+      json!(["stackTrace", {}]),
+      json!(["stepOut", {}]),
+      json!(["evaluate", { "expression": "pc" }]),
+      json!(["continue", {}]),
+      json!(["next", {}]),
+    ]);
+    let frames = &response(&messages, "stackTrace")["body"]["stackFrames"];
+    assert_eq!(frames[0]["name"], "double");
+    let result = &response(&messages, "evaluate")["body"]["result"];
+    assert_eq!(result, "8 (0x8)");
+    assert_eq!(events(&messages, "stopped").len(), 4);
+    assert_eq!(events(&messages, "terminated").len(), 1);
+    assert_eq!(response(&messages, "next")["success"], false);
+  }
+
+  #[test]
+  fn scopes() {
+    let messages = session(&[
+      json!(["configurationDone", {}]),
+      json!(["next", {}]),
+      json!(["variables", { "variablesReference": REGISTERS_SCOPE }]),
+      json!(["variables", { "variablesReference": MEMORY_REGION }]),
+      json!(["variables", { "variablesReference": 5 }]),
+      json!(["variables", { "variablesReference": MEMORY_REGION + 99 }]),
+    ]);
+    let variables: Vec<&Value> = messages
+      .iter()
+      .filter(|m| m["command"] == "variables")
+      .collect();
+    let registers = &variables[0]["body"]["variables"];
+    assert_eq!(registers[0]["value"], "0x0000000000000004");
+    assert_eq!(registers[11]["name"], "a0 (x10)");
+    assert_eq!(registers[11]["value"], "0x0000000000000005");
+    // The text shows the machine code
+    let text = &variables[1]["body"]["variables"];
+    assert_eq!(text[0]["name"], "0x00000000");
+    assert!(text[0]["value"]
+      .as_str()
+      .unwrap()
+      .starts_with("13 05 50 00"));
+    // References which weren't handed out have no variables
+    assert_eq!(variables[2]["body"]["variables"], json!([]));
+    assert_eq!(variables[3]["body"]["variables"], json!([]));
+  }
+
+  #[test]
+  fn expressions() {
+    let interpreter = Interpreter::create_RiscV64_i(Vec::new());
+    assert_eq!(evaluate(&interpreter, "(1 + 2) * 0x10"), Ok(48));
+    assert_eq!(evaluate(&interpreter, "-1"), Ok(u64::MAX));
+    assert_eq!(evaluate(&interpreter, "x2 - zero"), Ok(0));
+    assert!(evaluate(&interpreter, "q7").is_err());
+    assert!(evaluate(&interpreter, "(1").is_err());
+    assert!(evaluate(&interpreter, "1 2").is_err());
+    assert!(evaluate(&interpreter, "*0x8000000").is_err());
+  }
+
+  #[test]
+  fn launch_errors() {
+    let messages = session(&[json!(["threads", {}])]);
+    assert_eq!(response(&messages, "launch")["success"], true);
+    let mut server = DapServer::new(io::Cursor::new(Vec::new()), Vec::new());
+    assert!(server
+      .launch(&json!({ "program": "/no/such/file.s" }))
+      .is_err());
+    assert!(server.launch(&json!({})).is_err());
+  }
+}
@@ -0,0 +1,589 @@
+//! Memory mapped peripherals, at the same addresses and with the same register
+//! layouts as on QEMU's `virt` machine, so that code written for that runs
+//! here unchanged. `virt` has no GPIO, so the LED/switch panel uses the
+//! SiFive GPIO layout instead, which is what most RISC-V boards have.
+
+use crate::csr::{MEI, MSI, MTI};
+use crate::memory_map::Device;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+/// The interpreter keeps a handle to each device so that it can show their
+/// state, while the memory map owns another to route accesses to them.
+impl<T: Device> Device for Rc<RefCell<T>> {
+  fn name(&self) -> &'static str {
+    self.borrow().name()
+  }
+  fn base(&self) -> u64 {
+    self.borrow().base()
+  }
+  fn size(&self) -> u64 {
+    self.borrow().size()
+  }
+  fn read(&mut self, offset: u64, buf: &mut [u8]) {
+    self.borrow_mut().read(offset, buf)
+  }
+  fn write(&mut self, offset: u64, data: &[u8]) {
+    self.borrow_mut().write(offset, data)
+  }
+  fn peek(&self, offset: u64, buf: &mut [u8]) {
+    self.borrow().peek(offset, buf)
+  }
+  fn tick(&mut self) {
+    self.borrow_mut().tick()
+  }
+  fn interrupts(&self) -> u64 {
+    self.borrow().interrupts()
+  }
+}
+
+/// Registers are accessed a byte at a time, so that any access size works and
+/// unaligned accesses behave like they would on the bus.
+fn register_byte(value: u64, offset: u64) -> u8 {
+  (value >> (8 * offset)) as u8
+}
+
+fn set_register_byte(value: &mut u64, offset: u64, byte: u8) {
+  *value &= !(0xff << (8 * offset));
+  *value |= (byte as u64) << (8 * offset);
+}
+
+const RBR_THR: u64 = 0; // Receive buffer (read) / transmit holding (write)
+const IER: u64 = 1;
+const IIR_FCR: u64 = 2; // Interrupt identification (read) / FIFO control
+const LCR: u64 = 3;
+const MCR: u64 = 4;
+const LSR: u64 = 5;
+const MSR: u64 = 6;
+const SCR: u64 = 7;
+
+const LCR_DLAB: u8 = 0x80; // Registers 0 and 1 are the baud rate divisor
+const LSR_DATA_READY: u8 = 0x01;
+const LSR_THR_EMPTY: u8 = 0x20;
+const LSR_TRANSMITTER_EMPTY: u8 = 0x40;
+const IER_RX_AVAILABLE: u8 = 0x01;
+const IER_THR_EMPTY: u8 = 0x02;
+const IIR_NO_INTERRUPT: u8 = 0x01;
+const IIR_THR_EMPTY: u8 = 0x02;
+const IIR_RX_AVAILABLE: u8 = 0x04;
+
+/// A 16550 UART. A transmitted byte appears on the console at the end of the
+/// instruction which wrote it, and bytes typed into the console wait in the
+/// receive FIFO until they're read.
+pub struct Uart {
+  ier: u8,
+  thr: Option<u8>, // The byte waiting to be transmitted
+  // Set when the transmit holding register empties, and cleared by writing
+  // to it or by reading IIR while IIR reports it
+  thr_empty_interrupt: bool,
+  lcr: u8,
+  mcr: u8,
+  scr: u8,
+  divisor: [u8; 2],
+  rx: VecDeque<u8>,
+  output: Vec<u8>,
+}
+
+impl Uart {
+  pub const BASE: u64 = 0x1000_0000;
+
+  pub fn new() -> Self {
+    Uart {
+      ier: 0,
+      thr: None,
+      thr_empty_interrupt: false,
+      lcr: 0,
+      mcr: 0,
+      scr: 0,
+      divisor: [0; 2],
+      rx: VecDeque::new(),
+      output: Vec::new(),
+    }
+  }
+
+  /// Clears everything the program has set up and written, but keeps any
+  /// input which hasn't been read yet, as that was typed by the user.
+  pub fn reset(&mut self) {
+    let rx = std::mem::take(&mut self.rx);
+    *self = Uart { rx, ..Uart::new() };
+  }
+
+  pub fn receive(&mut self, input: &str) {
+    self.rx.extend(input.bytes());
+  }
+
+  pub fn output(&self) -> String {
+    String::from_utf8_lossy(&self.output).to_string()
+  }
+
+  pub fn interrupt_pending(&self) -> bool {
+    self.interrupt_id() != IIR_NO_INTERRUPT
+  }
+
+  /// The low bits of IIR, which say why the interrupt is pending.
+  fn interrupt_id(&self) -> u8 {
+    if self.ier & IER_RX_AVAILABLE != 0 && !self.rx.is_empty() {
+      IIR_RX_AVAILABLE
+    } else if self.ier & IER_THR_EMPTY != 0 && self.thr_empty_interrupt {
+      IIR_THR_EMPTY
+    } else {
+      IIR_NO_INTERRUPT
+    }
+  }
+
+  fn register(&self, offset: u64) -> u8 {
+    let dlab = self.lcr & LCR_DLAB != 0;
+    match offset {
+      RBR_THR if dlab => self.divisor[0],
+      RBR_THR => self.rx.front().copied().unwrap_or(0),
+      IER if dlab => self.divisor[1],
+      IER => self.ier,
+      // FIFOs are always enabled
+      IIR_FCR => 0xc0 | self.interrupt_id(),
+      LCR => self.lcr,
+      MCR => self.mcr,
+      LSR => {
+        let ready = if self.rx.is_empty() {
+          0
+        } else {
+          LSR_DATA_READY
+        };
+        let empty = match self.thr {
+          Some(_) => 0,
+          None => LSR_THR_EMPTY | LSR_TRANSMITTER_EMPTY,
+        };
+        ready | empty
+      }
+      MSR => 0,
+      SCR => self.scr,
+      _ => 0,
+    }
+  }
+}
+
+impl Device for Uart {
+  fn name(&self) -> &'static str {
+    "uart"
+  }
+  fn base(&self) -> u64 {
+    Uart::BASE
+  }
+  fn size(&self) -> u64 {
+    0x100
+  }
+
+  fn read(&mut self, offset: u64, buf: &mut [u8]) {
+    self.peek(offset, buf);
+    let dlab = self.lcr & LCR_DLAB != 0;
+    let registers = offset..offset + buf.len() as u64;
+    if !dlab && registers.contains(&RBR_THR) {
+      self.rx.pop_front();
+    }
+    // Reading IIR acknowledges the THR empty interrupt, if that's what it
+    // said was pending
+    if registers.contains(&IIR_FCR)
+      && buf[(IIR_FCR - offset) as usize] & 0x0f == IIR_THR_EMPTY
+    {
+      self.thr_empty_interrupt = false;
+    }
+  }
+
+  fn write(&mut self, offset: u64, data: &[u8]) {
+    let dlab = self.lcr & LCR_DLAB != 0;
+    for (i, byte) in data.iter().enumerate() {
+      match offset + i as u64 {
+        RBR_THR if dlab => self.divisor[0] = *byte,
+        RBR_THR => {
+          if let Some(waiting) = self.thr.replace(*byte) {
+            self.output.push(waiting);
+          }
+          self.thr_empty_interrupt = false;
+        }
+        IER if dlab => self.divisor[1] = *byte,
+        IER => {
+          // Enabling the THR empty interrupt while it's empty raises it
+          let enabled = !self.ier & *byte & IER_THR_EMPTY != 0;
+          if enabled && self.thr.is_none() {
+            self.thr_empty_interrupt = true;
+          }
+          self.ier = *byte & 0x0f;
+        }
+        LCR => self.lcr = *byte,
+        MCR => self.mcr = *byte,
+        SCR => self.scr = *byte,
+        _ => (), // FCR, and the read only registers
+      }
+    }
+  }
+
+  fn peek(&self, offset: u64, buf: &mut [u8]) {
+    for (i, byte) in buf.iter_mut().enumerate() {
+      *byte = self.register(offset + i as u64);
+    }
+  }
+
+  fn tick(&mut self) {
+    if let Some(byte) = self.thr.take() {
+      self.output.push(byte);
+      self.thr_empty_interrupt = true;
+    }
+  }
+
+  // There's no interrupt controller, so this goes straight to the hart
+  fn interrupts(&self) -> u64 {
+    if self.interrupt_pending() {
+      MEI
+    } else {
+      0
+    }
+  }
+}
+
+const MSIP: u64 = 0x0000;
+const MTIMECMP: u64 = 0x4000;
+const MTIME: u64 = 0xbff8;
+
+/// The core local interruptor: a software interrupt bit and a timer which
+/// counts instructions, with a compare register to raise the machine timer
+/// interrupt.
+pub struct Clint {
+  msip: u64,
+  mtimecmp: u64,
+  mtime: u64,
+}
+
+impl Clint {
+  pub const BASE: u64 = 0x0200_0000;
+
+  pub fn new() -> Self {
+    Clint {
+      msip: 0,
+      // So that the timer doesn't fire until it's been set up
+      mtimecmp: u64::MAX,
+      mtime: 0,
+    }
+  }
+
+  pub fn software_interrupt_pending(&self) -> bool {
+    self.msip & 1 != 0
+  }
+
+  pub fn timer_interrupt_pending(&self) -> bool {
+    self.mtime >= self.mtimecmp
+  }
+
+  fn register(&mut self, offset: u64) -> Option<(&mut u64, u64)> {
+    match offset {
+      MSIP..=0x0003 => Some((&mut self.msip, offset - MSIP)),
+      MTIMECMP..=0x4007 => Some((&mut self.mtimecmp, offset - MTIMECMP)),
+      MTIME..=0xbfff => Some((&mut self.mtime, offset - MTIME)),
+      _ => None,
+    }
+  }
+}
+
+impl Device for Clint {
+  fn name(&self) -> &'static str {
+    "clint"
+  }
+  fn base(&self) -> u64 {
+    Clint::BASE
+  }
+  fn size(&self) -> u64 {
+    0x10000
+  }
+
+  fn read(&mut self, offset: u64, buf: &mut [u8]) {
+    self.peek(offset, buf);
+  }
+
+  fn write(&mut self, offset: u64, data: &[u8]) {
+    for (i, byte) in data.iter().enumerate() {
+      if let Some((register, offset)) = self.register(offset + i as u64) {
+        set_register_byte(register, offset, *byte);
+      }
+    }
+    self.msip &= 1; // Only bit 0 is implemented
+  }
+
+  fn peek(&self, offset: u64, buf: &mut [u8]) {
+    for (i, byte) in buf.iter_mut().enumerate() {
+      let offset = offset + i as u64;
+      *byte = match offset {
+        MSIP..=0x0003 => register_byte(self.msip, offset - MSIP),
+        MTIMECMP..=0x4007 => register_byte(self.mtimecmp, offset - MTIMECMP),
+        MTIME..=0xbfff => register_byte(self.mtime, offset - MTIME),
+        _ => 0,
+      };
+    }
+  }
+
+  fn tick(&mut self) {
+    self.mtime = self.mtime.wrapping_add(1);
+  }
+
+  fn interrupts(&self) -> u64 {
+    let software = if self.software_interrupt_pending() {
+      MSI
+    } else {
+      0
+    };
+    let timer = if self.timer_interrupt_pending() {
+      MTI
+    } else {
+      0
+    };
+    software | timer
+  }
+}
+
+pub const LED_COUNT: usize = 8;
+pub const SWITCH_COUNT: usize = 8;
+// Pins 0-7 drive the LEDs, and pins 8-15 read the switches
+const SWITCH_SHIFT: u32 = 8;
+
+const INPUT_VAL: usize = 0x00;
+const INPUT_EN: usize = 0x04;
+const OUTPUT_EN: usize = 0x08;
+const OUTPUT_VAL: usize = 0x0c;
+const RISE_IE: usize = 0x18;
+const RISE_IP: usize = 0x1c;
+const FALL_IE: usize = 0x20;
+const FALL_IP: usize = 0x24;
+const HIGH_IE: usize = 0x28;
+const HIGH_IP: usize = 0x2c;
+const LOW_IE: usize = 0x30;
+const LOW_IP: usize = 0x34;
+const OUT_XOR: usize = 0x40;
+
+/// A SiFive GPIO block with LEDs on its low pins and switches on the ones
+/// above. Each register is 32 bits, one bit per pin.
+pub struct Gpio {
+  registers: [u32; 17],
+  switches: u32,
+}
+
+impl Gpio {
+  pub const BASE: u64 = 0x1000_1000;
+
+  pub fn new() -> Self {
+    Gpio {
+      registers: [0; 17],
+      switches: 0,
+    }
+  }
+
+  /// Clears everything the program has set up, but leaves the switches as
+  /// they are since they're part of the outside world.
+  pub fn reset(&mut self) {
+    self.registers = [0; 17];
+  }
+
+  fn get(&self, register: usize) -> u32 {
+    self.registers[register / 4]
+  }
+
+  fn set(&mut self, register: usize, value: u32) {
+    self.registers[register / 4] = value;
+  }
+
+  pub fn leds(&self) -> Vec<bool> {
+    let pins = (self.get(OUTPUT_VAL) ^ self.get(OUT_XOR)) & self.get(OUTPUT_EN);
+    (0..LED_COUNT).map(|i| pins & (1 << i) != 0).collect()
+  }
+
+  pub fn switches(&self) -> Vec<bool> {
+    (0..SWITCH_COUNT)
+      .map(|i| self.switches & (1 << (i as u32 + SWITCH_SHIFT)) != 0)
+      .collect()
+  }
+
+  pub fn set_switch(&mut self, switch: usize, on: bool) {
+    let bit = 1 << (switch as u32 + SWITCH_SHIFT);
+    let was_on = self.switches & bit != 0;
+    if on {
+      self.switches |= bit;
+    } else {
+      self.switches &= !bit;
+    }
+    if on && !was_on {
+      self.set(RISE_IP, self.get(RISE_IP) | bit);
+    } else if !on && was_on {
+      self.set(FALL_IP, self.get(FALL_IP) | bit);
+    }
+  }
+
+  fn input_val(&self) -> u32 {
+    self.switches & self.get(INPUT_EN)
+  }
+
+  /// The value the program sees, for registers which reflect the pins.
+  fn read_register(&self, register: usize) -> u32 {
+    match register {
+      INPUT_VAL => self.input_val(),
+      HIGH_IP => self.get(HIGH_IP) | self.input_val(),
+      LOW_IP => self.get(LOW_IP) | (!self.input_val() & self.get(INPUT_EN)),
+      _ => self.get(register),
+    }
+  }
+}
+
+impl Device for Gpio {
+  fn name(&self) -> &'static str {
+    "gpio"
+  }
+  fn base(&self) -> u64 {
+    Gpio::BASE
+  }
+  fn size(&self) -> u64 {
+    0x1000
+  }
+
+  fn read(&mut self, offset: u64, buf: &mut [u8]) {
+    self.peek(offset, buf);
+  }
+
+  fn write(&mut self, offset: u64, data: &[u8]) {
+    for (i, byte) in data.iter().enumerate() {
+      let offset = offset as usize + i;
+      if offset >= 4 * self.registers.len() || offset / 4 == INPUT_VAL / 4 {
+        continue; // input_val is read only
+      }
+      let register = offset / 4 * 4;
+      let shift = 8 * (offset % 4);
+      let written = (*byte as u32) << shift;
+      let mask = 0xff << shift;
+      let value = match register {
+        // Interrupt pending bits are cleared by writing 1 to them
+        RISE_IP | FALL_IP | HIGH_IP | LOW_IP => self.get(register) & !written,
+        _ => (self.get(register) & !mask) | written,
+      };
+      self.set(register, value);
+    }
+  }
+
+  fn peek(&self, offset: u64, buf: &mut [u8]) {
+    for (i, byte) in buf.iter_mut().enumerate() {
+      let offset = offset as usize + i;
+      let value = if offset < 4 * self.registers.len() {
+        self.read_register(offset / 4 * 4)
+      } else {
+        0
+      };
+      *byte = register_byte(value as u64, (offset % 4) as u64);
+    }
+  }
+
+  fn interrupts(&self) -> u64 {
+    let pending = [
+      (RISE_IE, RISE_IP),
+      (FALL_IE, FALL_IP),
+      (HIGH_IE, HIGH_IP),
+      (LOW_IE, LOW_IP),
+    ]
+    .iter()
+    .any(|(ie, ip)| self.get(*ie) & self.read_register(*ip) != 0);
+    if pending {
+      MEI
+    } else {
+      0
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn read_u64(device: &mut dyn Device, offset: u64, len: usize) -> u64 {
+    let mut buf = [0; 8];
+    device.read(offset, &mut buf[..len]);
+    u64::from_le_bytes(buf)
+  }
+
+  #[test]
+  fn uart_transmits_and_receives() {
+    let mut uart = Uart::new();
+    uart.write(RBR_THR, b"h");
+    assert_eq!(read_u64(&mut uart, LSR, 1) as u8 & LSR_THR_EMPTY, 0);
+    uart.tick();
+    assert_eq!(read_u64(&mut uart, LSR, 1) as u8 & LSR_THR_EMPTY, 0x20);
+    uart.write(RBR_THR, b"i");
+    uart.tick();
+    assert_eq!(uart.output(), "hi");
+    assert_eq!(read_u64(&mut uart, LSR, 1) as u8 & LSR_DATA_READY, 0);
+    uart.receive("ok");
+    assert_eq!(read_u64(&mut uart, LSR, 1) as u8 & LSR_DATA_READY, 1);
+    assert_eq!(read_u64(&mut uart, RBR_THR, 1), b'o' as u64);
+    assert_eq!(read_u64(&mut uart, RBR_THR, 1), b'k' as u64);
+    assert_eq!(read_u64(&mut uart, LSR, 1) as u8 & LSR_DATA_READY, 0);
+  }
+
+  #[test]
+  fn uart_thr_empty_interrupt() {
+    let mut uart = Uart::new();
+    // Enabling it while the THR is empty raises it
+    uart.write(IER, &[IER_THR_EMPTY]);
+    assert!(uart.interrupt_pending());
+    assert_eq!(read_u64(&mut uart, IIR_FCR, 1), 0xc2);
+    // Reading IIR acknowledges it
+    assert!(!uart.interrupt_pending());
+    assert_eq!(read_u64(&mut uart, IIR_FCR, 1), 0xc1);
+    // Until the next byte has been sent
+    uart.write(RBR_THR, b"a");
+    assert!(!uart.interrupt_pending());
+    uart.tick();
+    assert!(uart.interrupt_pending());
+    // Writing THR acknowledges it too
+    uart.write(RBR_THR, b"b");
+    assert!(!uart.interrupt_pending());
+    // Received data takes priority
+    uart.tick();
+    uart.write(IER, &[IER_THR_EMPTY | IER_RX_AVAILABLE]);
+    uart.receive("c");
+    assert_eq!(read_u64(&mut uart, IIR_FCR, 1), 0xc4);
+    assert!(uart.interrupt_pending());
+    assert_eq!(read_u64(&mut uart, RBR_THR, 1), b'c' as u64);
+    assert_eq!(read_u64(&mut uart, IIR_FCR, 1), 0xc2);
+    assert!(!uart.interrupt_pending());
+  }
+
+  #[test]
+  fn uart_divisor_latch() {
+    let mut uart = Uart::new();
+    uart.write(LCR, &[LCR_DLAB]);
+    uart.write(RBR_THR, &[3]);
+    assert_eq!(uart.output(), "");
+    assert_eq!(read_u64(&mut uart, RBR_THR, 1), 3);
+  }
+
+  #[test]
+  fn clint_timer() {
+    let mut clint = Clint::new();
+    clint.write(MTIMECMP, &2u64.to_le_bytes());
+    clint.tick();
+    assert!(!clint.timer_interrupt_pending());
+    clint.tick();
+    assert!(clint.timer_interrupt_pending());
+    assert_eq!(read_u64(&mut clint, MTIME, 8), 2);
+    clint.write(MSIP, &[1, 0, 0, 0]);
+    assert!(clint.software_interrupt_pending());
+  }
+
+  #[test]
+  fn gpio_leds_and_switches() {
+    let mut gpio = Gpio::new();
+    gpio.write(OUTPUT_EN as u64, &[0xff]);
+    gpio.write(OUTPUT_VAL as u64, &[0b101]);
+    assert_eq!(gpio.leds()[..3], [true, false, true]);
+    gpio.set_switch(1, true);
+    // Inputs read as 0 until they're enabled
+    assert_eq!(read_u64(&mut gpio, INPUT_VAL as u64, 4), 0);
+    gpio.write(INPUT_EN as u64, &[0, 0xff]);
+    assert_eq!(read_u64(&mut gpio, INPUT_VAL as u64, 4), 0x200);
+    assert_eq!(read_u64(&mut gpio, RISE_IP as u64, 4), 0x200);
+    gpio.write(RISE_IP as u64, &[0, 0x02]);
+    assert_eq!(read_u64(&mut gpio, RISE_IP as u64, 4), 0);
+  }
+}
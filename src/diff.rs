@@ -0,0 +1,142 @@
+/// Works out where each line of `old` ended up in `new`.
+///
+/// The result has one entry per line of `old` (so index 0 is line 1), holding
+/// the 1 indexed line number in `new` that it corresponds to, or None if the
+/// line was deleted. Unchanged lines are matched using the longest common
+/// subsequence of the two files. Lines which were edited in place (i.e. a
+/// block of N old lines replaced by N new lines) are matched up by position,
+/// so that a breakpoint on a line survives that line being edited.
+pub fn line_map(old: &str, new: &str) -> Vec<Option<u32>> {
+  let old_lines: Vec<&str> = old.lines().collect();
+  let new_lines: Vec<&str> = new.lines().collect();
+  let mut map: Vec<Option<u32>> = vec![None; old_lines.len()];
+
+  // Most edits only touch a small part of the file, so strip the common
+  // prefix and suffix before doing the quadratic part.
+  let prefix = old_lines
+    .iter()
+    .zip(new_lines.iter())
+    .take_while(|(o, n)| o == n)
+    .count();
+  let suffix = old_lines[prefix..]
+    .iter()
+    .rev()
+    .zip(new_lines[prefix..].iter().rev())
+    .take_while(|(o, n)| o == n)
+    .count();
+  for (i, entry) in map.iter_mut().enumerate().take(prefix) {
+    *entry = Some((i + 1) as u32);
+  }
+  for i in 0..suffix {
+    let old_index = old_lines.len() - suffix + i;
+    let new_index = new_lines.len() - suffix + i;
+    map[old_index] = Some((new_index + 1) as u32);
+  }
+
+  let old_middle = &old_lines[prefix..old_lines.len() - suffix];
+  let new_middle = &new_lines[prefix..new_lines.len() - suffix];
+
+  // lcs[i][j] is the length of the LCS of old_middle[i..] and new_middle[j..]
+  let mut lcs = vec![vec![0u32; new_middle.len() + 1]; old_middle.len() + 1];
+  for i in (0..old_middle.len()).rev() {
+    for j in (0..new_middle.len()).rev() {
+      lcs[i][j] = if old_middle[i] == new_middle[j] {
+        lcs[i + 1][j + 1] + 1
+      } else {
+        std::cmp::max(lcs[i + 1][j], lcs[i][j + 1])
+      };
+    }
+  }
+
+  // Walk the table, recording matches. Between two matches there's a hunk of
+  // removed and added lines; if they're the same size treat it as an edit.
+  let (mut i, mut j) = (0, 0);
+  let (mut hunk_i, mut hunk_j) = (0, 0);
+  loop {
+    let at_end = i == old_middle.len() || j == new_middle.len();
+    if at_end || old_middle[i] == new_middle[j] {
+      let (hunk_end_i, hunk_end_j) = if at_end {
+        (old_middle.len(), new_middle.len())
+      } else {
+        (i, j)
+      };
+      if hunk_end_i - hunk_i == hunk_end_j - hunk_j {
+        for k in 0..(hunk_end_i - hunk_i) {
+          map[prefix + hunk_i + k] = Some((prefix + hunk_j + k + 1) as u32);
+        }
+      }
+      if at_end {
+        break;
+      }
+      map[prefix + i] = Some((prefix + j + 1) as u32);
+      i += 1;
+      j += 1;
+      hunk_i = i;
+      hunk_j = j;
+    } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+      i += 1;
+    } else {
+      j += 1;
+    }
+  }
+
+  map
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn identical() {
+    let code = "a\nb\nc";
+    assert_eq!(line_map(code, code), vec![Some(1), Some(2), Some(3)]);
+  }
+
+  #[test]
+  fn insert_line_before() {
+    assert_eq!(
+      line_map("a\nb\nc", "new\na\nb\nc"),
+      vec![Some(2), Some(3), Some(4)]
+    );
+  }
+
+  #[test]
+  fn delete_line() {
+    assert_eq!(line_map("a\nb\nc", "a\nc"), vec![Some(1), None, Some(2)]);
+  }
+
+  #[test]
+  fn edit_line_in_place() {
+    assert_eq!(
+      line_map("a\nb\nc", "a\nB\nc"),
+      vec![Some(1), Some(2), Some(3)]
+    );
+  }
+
+  #[test]
+  fn edit_and_insert() {
+    assert_eq!(
+      line_map("a\nb\nc\nd", "a\nx\ny\nc\nd\ne"),
+      vec![Some(1), None, Some(4), Some(5)]
+    );
+  }
+
+  #[test]
+  fn duplicate_lines() {
+    assert_eq!(
+      line_map("nop\nnop\nadd", "nop\nadd"),
+      vec![Some(1), None, Some(2)]
+    );
+  }
+
+  #[test]
+  fn from_empty() {
+    assert_eq!(line_map("", "a\nb"), vec![]);
+  }
+
+  #[test]
+  fn to_empty() {
+    assert_eq!(line_map("a\nb", ""), vec![None, None]);
+  }
+}
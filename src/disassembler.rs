@@ -0,0 +1,354 @@
+//! Turns machine code back into assembly, in the style of `objdump -d`:
+//! registers by their ABI names, branch targets as addresses, and common
+//! idioms shown as the pseudo instructions they implement (`addi x0, x0, 0`
+//! is `nop`). The operands are laid out by the instruction's syntax in
+//! rv64_i.org, so there's nothing to keep in step when instructions change.
+
+use crate::codegen::{INSTRUCTIONS, REGISTER_INFO};
+use crate::csr::csr_name;
+use crate::elf::{self, SHT_SYMTAB, STT_FUNC, STT_NOTYPE};
+use crate::encoding::{decode, Decoded};
+use std::collections::BTreeMap;
+use std::convert::TryInto;
+use std::fmt::Write as _;
+
+fn register(number: i64) -> String {
+  REGISTER_INFO[number as usize].abi_name.to_string()
+}
+
+/// A fence's predecessor or successor set, e.g. "rw".
+fn fence_set(set: i64) -> String {
+  let names: String = "iorw"
+    .chars()
+    .enumerate()
+    .filter(|(i, _)| set & (8 >> i) != 0)
+    .map(|(_, name)| name)
+    .collect();
+  if names.is_empty() {
+    "0".to_string()
+  } else {
+    names
+  }
+}
+
+/// The operand as it's shown. `address` is where the instruction is, for
+/// branch and jump targets.
+fn format_operand(decoded: &Decoded, name: &str, address: u64) -> String {
+  let value = decoded.operand(name);
+  match name {
+    "rd" | "rs1" | "rs2" => register(value),
+    "imm20" => format!("0x{:x}", value),
+    "csr" => csr_name(value as u16)
+      .map_or(format!("0x{:x}", value), |name| name.to_string()),
+    "pred" | "succ" => fence_set(value),
+    "offset" => match decoded.target(address) {
+      Some(target) => format!("0x{:x}", target),
+      None => value.to_string(),
+    },
+    _ => value.to_string(),
+  }
+}
+
+/// The pseudo instruction an instruction is an idiom for, with its operands.
+fn pseudo_instruction(
+  decoded: &Decoded,
+  address: u64,
+) -> Option<(&'static str, Vec<String>)> {
+  let value = |name| decoded.operand(name);
+  let operand = |name| format_operand(decoded, name, address);
+  let (rd, rs1, rs2) = (value("rd"), value("rs1"), value("rs2"));
+  let imm = value("imm");
+  let pseudo = match decoded.encoding.mnemonic {
+    "addi" if rd == 0 && rs1 == 0 && imm == 0 => ("nop", vec![]),
+    "addi" if rs1 == 0 => ("li", vec![operand("rd"), operand("imm")]),
+    "addi" if imm == 0 => ("mv", vec![operand("rd"), operand("rs1")]),
+    "addiw" if imm == 0 => ("sext.w", vec![operand("rd"), operand("rs1")]),
+    "xori" if imm == -1 => ("not", vec![operand("rd"), operand("rs1")]),
+    "sub" if rs1 == 0 => ("neg", vec![operand("rd"), operand("rs2")]),
+    "subw" if rs1 == 0 => ("negw", vec![operand("rd"), operand("rs2")]),
+    "sltiu" if imm == 1 => ("seqz", vec![operand("rd"), operand("rs1")]),
+    "sltu" if rs1 == 0 => ("snez", vec![operand("rd"), operand("rs2")]),
+    "slt" if rs2 == 0 => ("sltz", vec![operand("rd"), operand("rs1")]),
+    "slt" if rs1 == 0 => ("sgtz", vec![operand("rd"), operand("rs2")]),
+    "beq" if rs2 == 0 => ("beqz", vec![operand("rs1"), operand("offset")]),
+    "bne" if rs2 == 0 => ("bnez", vec![operand("rs1"), operand("offset")]),
+    "blt" if rs2 == 0 => ("bltz", vec![operand("rs1"), operand("offset")]),
+    "bge" if rs2 == 0 => ("bgez", vec![operand("rs1"), operand("offset")]),
+    "blt" if rs1 == 0 => ("bgtz", vec![operand("rs2"), operand("offset")]),
+    "bge" if rs1 == 0 => ("blez", vec![operand("rs2"), operand("offset")]),
+    "jal" if rd == 0 => ("j", vec![operand("offset")]),
+    "jal" if rd == 1 => ("jal", vec![operand("offset")]),
+    "jalr" if rd == 0 && rs1 == 1 && imm == 0 => ("ret", vec![]),
+    "jalr" if rd == 0 && imm == 0 => ("jr", vec![operand("rs1")]),
+    "jalr" if rd == 1 && imm == 0 => ("jalr", vec![operand("rs1")]),
+    "csrrs" if rs1 == 0 => ("csrr", vec![operand("rd"), operand("csr")]),
+    "csrrs" if rd == 0 => ("csrs", vec![operand("csr"), operand("rs1")]),
+    "csrrw" if rd == 0 => ("csrw", vec![operand("csr"), operand("rs1")]),
+    "csrrc" if rd == 0 => ("csrc", vec![operand("csr"), operand("rs1")]),
+    "csrrsi" if rd == 0 => ("csrsi", vec![operand("csr"), operand("uimm")]),
+    "csrrwi" if rd == 0 => ("csrwi", vec![operand("csr"), operand("uimm")]),
+    "csrrci" if rd == 0 => ("csrci", vec![operand("csr"), operand("uimm")]),
+    "fence" if value("pred") == 0xf && value("succ") == 0xf => {
+      ("fence", vec![])
+    }
+    _ => return None,
+  };
+  Some(pseudo)
+}
+
+/// The assembly for an instruction word at `address`, or None if it isn't
+/// an instruction the interpreter has.
+pub fn disassemble(word: u32, address: u64) -> Option<String> {
+  let decoded = decode(word)?;
+  if let Some((mnemonic, operands)) = pseudo_instruction(&decoded, address) {
+    return Some(
+      format!("{} {}", mnemonic, operands.join(","))
+        .trim_end()
+        .to_string(),
+    );
+  }
+  // Lay the operands out as the syntax does, without the spaces
+  let syntax = INSTRUCTIONS.get(decoded.encoding.mnemonic)?.syntax;
+  let mut text = format!("{} ", syntax[0]);
+  for part in &syntax[1..] {
+    match *part {
+      "," | "(" | ")" => text.push_str(part),
+      name => text.push_str(&format_operand(&decoded, name, address)),
+    }
+  }
+  Some(text.trim_end().to_string())
+}
+
+/// One instruction in a listing. `source` is the line it was assembled
+/// from, if there is one.
+pub struct ListingLine {
+  pub address: u64,
+  pub bytes: Vec<u8>,
+  pub source: Option<String>,
+}
+
+/// Lists the instructions as `objdump -d` does, with each label (or symbol)
+/// as a heading before the instruction it's on, and as a comment on branches
+/// to it.
+pub fn listing(
+  lines: &[ListingLine],
+  labels: &BTreeMap<u64, Vec<String>>,
+) -> String {
+  let label =
+    |address: u64| labels.get(&address).and_then(|names| names.first());
+  let mut listing = String::new();
+  for line in lines {
+    for name in labels.get(&line.address).into_iter().flatten() {
+      writeln!(listing, "\n{:016x} <{}>:", line.address, name).ok();
+    }
+    let (hex, mut text) = match line.bytes.len() {
+      4 => {
+        let word = u32::from_le_bytes(line.bytes[..].try_into().unwrap());
+        let text = disassemble(word, line.address)
+          .unwrap_or_else(|| format!(".4byte 0x{:08x}", word));
+        let target = decode(word).and_then(|d| d.target(line.address));
+        let text = match target.and_then(label) {
+          Some(name) => format!("{} <{}>", text, name),
+          None => text,
+        };
+        (format!("{:08x}", word), text)
+      }
+      2 => {
+        // Compressed instructions aren't supported
+        let half = u16::from_le_bytes(line.bytes[..].try_into().unwrap());
+        (format!("{:04x}", half), format!(".2byte 0x{:04x}", half))
+      }
+      _ => {
+        let hex: String = line
+          .bytes
+          .iter()
+          .map(|byte| format!("{:02x}", byte))
+          .collect();
+        let bytes: Vec<String> = line
+          .bytes
+          .iter()
+          .map(|byte| format!("0x{:02x}", byte))
+          .collect();
+        (hex, format!(".byte {}", bytes.join(",")))
+      }
+    };
+    if let Some(source) = &line.source {
+      text = format!("{:<32}// {}", text, source);
+    }
+    writeln!(
+      listing,
+      "{:8x}:\t{:<8}\t{}",
+      line.address,
+      hex,
+      text.trim_end()
+    )
+    .ok();
+  }
+  listing
+}
+
+/// Splits raw machine code into instructions by their length: 16 bits if the
+/// low two bits aren't both set (the C extension), and 32 otherwise.
+fn split_instructions(bytes: &[u8], base: u64) -> Vec<ListingLine> {
+  let mut lines = Vec::new();
+  let mut offset = 0;
+  while offset < bytes.len() {
+    let length = if bytes[offset] & 0b11 == 0b11 { 4 } else { 2 };
+    let end = (offset + length).min(bytes.len());
+    lines.push(ListingLine {
+      address: base.wrapping_add(offset as u64),
+      bytes: bytes[offset..end].to_vec(),
+      source: None,
+    });
+    offset = end;
+  }
+  lines
+}
+
+/// Lists a raw binary, which is loaded at `base`.
+pub fn disassemble_raw(bytes: &[u8], base: u64) -> String {
+  listing(&split_instructions(bytes, base), &BTreeMap::new())
+}
+
+/// Lists every executable section of a 64 bit little endian RISC-V ELF
+/// file, with its symbols as labels.
+pub fn disassemble_elf(bytes: &[u8]) -> Result<String, String> {
+  let sections = elf::sections(bytes)?;
+
+  // Functions and labels, leaving out mapping symbols such as $x
+  let mut labels: BTreeMap<u64, Vec<String>> = BTreeMap::new();
+  for symbols in sections.iter().filter(|s| s.kind == SHT_SYMTAB) {
+    for symbol in elf::symbols(bytes, &sections, symbols)? {
+      if (symbol.kind == STT_FUNC || symbol.kind == STT_NOTYPE)
+        && !symbol.name.is_empty()
+        && !symbol.name.starts_with('$')
+      {
+        labels.entry(symbol.value).or_default().push(symbol.name);
+      }
+    }
+  }
+
+  let mut output = String::new();
+  for section in sections.iter().filter(|s| s.is_code()) {
+    let code = section.contents(bytes)?;
+    writeln!(output, "\nDisassembly of section {}:", section.name).ok();
+    output.push_str(&listing(
+      &split_instructions(code, section.address),
+      &labels,
+    ));
+  }
+  Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::elf::*;
+
+  fn words(words: &[u32]) -> Vec<u8> {
+    words.iter().flat_map(|word| word.to_le_bytes()).collect()
+  }
+
+  #[test]
+  fn instructions() {
+    assert_eq!(disassemble(0xfff5_0513, 0).unwrap(), "addi a0,a0,-1");
+    assert_eq!(disassemble(0x0011_3423, 0).unwrap(), "sd ra,8(sp)");
+    assert_eq!(disassemble(0x1001_02b7, 0).unwrap(), "lui t0,0x10010");
+    assert_eq!(disassemble(0x43f5_d513, 0).unwrap(), "srai a0,a1,63");
+    assert_eq!(disassemble(0x3053_12f3, 0).unwrap(), "csrrw t0,mtvec,t1");
+    assert_eq!(disassemble(0x0330_000f, 0).unwrap(), "fence rw,rw");
+    assert_eq!(disassemble(0x3020_0073, 0).unwrap(), "mret");
+    assert_eq!(disassemble(0x0000_0000, 0), None);
+  }
+
+  #[test]
+  fn pseudo_instructions() {
+    assert_eq!(disassemble(0x0000_0013, 0).unwrap(), "nop");
+    assert_eq!(disassemble(0x0050_0513, 0).unwrap(), "li a0,5");
+    assert_eq!(disassemble(0x0005_8513, 0).unwrap(), "mv a0,a1");
+    assert_eq!(disassemble(0x0000_8067, 0).unwrap(), "ret");
+    assert_eq!(disassemble(0x0005_1463, 0x10).unwrap(), "bnez a0,0x18");
+    assert_eq!(disassemble(0xfe73_0ee3, 0x10).unwrap(), "beq t1,t2,0xc");
+    assert_eq!(disassemble(0x0100_00ef, 0).unwrap(), "jal 0x10");
+    assert_eq!(disassemble(0x0ff0_000f, 0).unwrap(), "fence");
+    assert_eq!(disassemble(0x3000_2573, 0).unwrap(), "csrr a0,mstatus");
+  }
+
+  #[test]
+  fn raw_binary() {
+    let mut bytes = words(&[0x0050_0513, 0xfff5_0513, 0xfe05_1ee3]);
+    bytes.extend([0x01, 0x45, 0xff]); // c.li a0, 0 and a stray byte
+    let expected = [
+      "     100:\t00500513\tli a0,5",
+      "     104:\tfff50513\taddi a0,a0,-1",
+      "     108:\tfe051ee3\tbnez a0,0x104",
+      "     10c:\t4501    \t.2byte 0x4501",
+      "     10e:\tff      \t.byte 0xff",
+    ];
+    assert_eq!(disassemble_raw(&bytes, 0x100), expected.join("\n") + "\n");
+  }
+
+  /// A minimal ELF file with the code in .text at 0x10000, and a symbol for
+  /// each (name, address).
+  fn elf(code: &[u8], symbols: &[(&str, u64)]) -> Vec<u8> {
+    let mut strings = vec![0u8];
+    let mut symbol_table = vec![0u8; 24];
+    for (name, address) in symbols {
+      symbol_table.extend((strings.len() as u32).to_le_bytes());
+      symbol_table.extend([STT_FUNC, 0, 1, 0]);
+      symbol_table.extend(address.to_le_bytes());
+      symbol_table.extend(0u64.to_le_bytes());
+      strings.extend(name.as_bytes());
+      strings.push(0);
+    }
+    let section_names = b"\0.text\0.symtab\0.strtab\0.shstrtab\0".to_vec();
+    let mut bytes = vec![0u8; 64];
+    bytes[..4].copy_from_slice(ELF_MAGIC);
+    bytes[4] = 2;
+    bytes[5] = 1;
+    bytes[0x12] = EM_RISCV as u8;
+    // (name, type, flags, address, contents, link)
+    type Section<'a> = (u32, u32, u64, u64, &'a [u8], u32);
+    let sections: [Section; 5] = [
+      (0, 0, 0, 0, &[], 0),
+      (1, SHT_PROGBITS as u32, SHF_EXECINSTR, 0x10000, code, 0),
+      (7, SHT_SYMTAB as u32, 0, 0, &symbol_table, 3),
+      (15, 3, 0, 0, &strings, 0),
+      (23, 3, 0, 0, &section_names, 0),
+    ];
+    let mut headers = Vec::new();
+    for (name, kind, flags, address, contents, link) in sections {
+      let mut header = vec![0u8; 64];
+      header[..4].copy_from_slice(&name.to_le_bytes());
+      header[4..8].copy_from_slice(&kind.to_le_bytes());
+      header[8..16].copy_from_slice(&flags.to_le_bytes());
+      header[0x10..0x18].copy_from_slice(&address.to_le_bytes());
+      header[0x18..0x20].copy_from_slice(&(bytes.len() as u64).to_le_bytes());
+      header[0x20..0x28]
+        .copy_from_slice(&(contents.len() as u64).to_le_bytes());
+      header[0x28..0x2c].copy_from_slice(&link.to_le_bytes());
+      headers.extend(header);
+      bytes.extend(contents);
+    }
+    let headers_offset = bytes.len() as u64;
+    bytes[0x28..0x30].copy_from_slice(&headers_offset.to_le_bytes());
+    bytes[0x3a] = 64;
+    bytes[0x3c] = 5;
+    bytes[0x3e] = 4;
+    bytes.extend(headers);
+    bytes
+  }
+
+  #[test]
+  fn elf_text_section() {
+    let code = words(&[0x0050_0513, 0xfff5_0513, 0xfe05_1ee3, 0x0000_8067]);
+    let bytes = elf(&code, &[("_start", 0x10000), ("loop", 0x10004)]);
+    let listing = disassemble_elf(&bytes).unwrap();
+    assert!(listing.starts_with("\nDisassembly of section .text:\n"));
+    assert!(listing.contains("\n0000000000010004 <loop>:\n"));
+    assert!(listing.contains("\tbnez a0,0x10004 <loop>\n"));
+    assert!(listing.ends_with("   1000c:\t00008067\tret\n"));
+    assert!(disassemble_elf(&bytes[..100]).is_err());
+    assert!(disassemble_elf(b"#!/bin/sh").is_err());
+  }
+}
@@ -0,0 +1,303 @@
+//! Reading and writing the parts of 64 bit little endian RISC-V ELF files
+//! that the disassembler and linker need: section headers, symbol tables and
+//! relocations.
+
+use std::convert::{TryFrom, TryInto};
+
+pub const ELF_MAGIC: &[u8] = b"\x7fELF";
+pub const ET_REL: u64 = 1;
+pub const EM_RISCV: u64 = 243;
+pub const SHT_PROGBITS: u64 = 1;
+pub const SHT_SYMTAB: u64 = 2;
+pub const SHT_STRTAB: u64 = 3;
+pub const SHT_RELA: u64 = 4;
+pub const SHF_ALLOC: u64 = 2;
+pub const SHF_EXECINSTR: u64 = 4;
+pub const SHF_INFO_LINK: u64 = 0x40;
+pub const SHN_UNDEF: u64 = 0;
+pub const STB_LOCAL: u8 = 0;
+pub const STB_GLOBAL: u8 = 1;
+pub const STT_NOTYPE: u8 = 0;
+pub const STT_FUNC: u8 = 2;
+pub const STT_SECTION: u8 = 3;
+
+const SYMBOL_SIZE: usize = 24;
+const RELA_SIZE: usize = 24;
+
+pub fn is_elf(bytes: &[u8]) -> bool {
+  bytes.starts_with(ELF_MAGIC)
+}
+
+fn read<const N: usize>(
+  bytes: &[u8],
+  offset: usize,
+) -> Result<[u8; N], String> {
+  offset
+    .checked_add(N)
+    .and_then(|end| bytes.get(offset..end))
+    .and_then(|slice| slice.try_into().ok())
+    .ok_or_else(|| format!("The ELF file is truncated at 0x{:x}", offset))
+}
+
+fn u16_at(bytes: &[u8], offset: usize) -> Result<u64, String> {
+  Ok(u16::from_le_bytes(read(bytes, offset)?) as u64)
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> Result<u64, String> {
+  Ok(u32::from_le_bytes(read(bytes, offset)?) as u64)
+}
+
+fn u64_at(bytes: &[u8], offset: usize) -> Result<u64, String> {
+  Ok(u64::from_le_bytes(read(bytes, offset)?))
+}
+
+/// The `len` bytes at `start` + `offset`. These come from the file, so they
+/// can be anything, including past the end of the address space.
+fn slice_at(
+  bytes: &[u8],
+  start: u64,
+  offset: u64,
+  len: u64,
+) -> Result<&[u8], String> {
+  let range = start.checked_add(offset).and_then(|start| {
+    let end = usize::try_from(start.checked_add(len)?).ok()?;
+    Some(usize::try_from(start).ok()?..end)
+  });
+  range.and_then(|range| bytes.get(range)).ok_or_else(|| {
+    format!(
+      "The ELF file is truncated at 0x{:x}",
+      start.saturating_add(offset)
+    )
+  })
+}
+
+/// A null terminated string in the string table at `table`.
+fn string_at(bytes: &[u8], table: u64, offset: u64) -> String {
+  let name = table
+    .checked_add(offset)
+    .and_then(|start| usize::try_from(start).ok())
+    .and_then(|start| bytes.get(start..))
+    .unwrap_or(&[]);
+  let end = name.iter().position(|b| *b == 0).unwrap_or(name.len());
+  String::from_utf8_lossy(&name[..end]).into_owned()
+}
+
+pub struct Section {
+  pub name: String,
+  pub kind: u64,
+  pub flags: u64,
+  pub address: u64,
+  pub offset: u64,
+  pub size: u64,
+  pub link: u64,
+  pub info: u64,
+}
+
+impl Section {
+  pub fn contents<'a>(&self, bytes: &'a [u8]) -> Result<&'a [u8], String> {
+    slice_at(bytes, self.offset, 0, self.size)
+      .map_err(|_| format!("Section {} is truncated", self.name))
+  }
+
+  pub fn is_code(&self) -> bool {
+    self.kind == SHT_PROGBITS && self.flags & SHF_EXECINSTR != 0
+  }
+}
+
+/// The type of the ELF file (e.g. ET_REL), if it's one we can read.
+pub fn file_type(bytes: &[u8]) -> Result<u64, String> {
+  if !is_elf(bytes) {
+    return Err("Not an ELF file".to_string());
+  }
+  if read::<2>(bytes, 4)? != [2, 1] {
+    return Err(
+      "Only 64 bit little endian ELF files are supported".to_string(),
+    );
+  }
+  if u16_at(bytes, 0x12)? != EM_RISCV {
+    return Err("The ELF file isn't for RISC-V".to_string());
+  }
+  u16_at(bytes, 0x10)
+}
+
+pub fn sections(bytes: &[u8]) -> Result<Vec<Section>, String> {
+  file_type(bytes)?;
+  let header_offset = u64_at(bytes, 0x28)?;
+  let header_size = u16_at(bytes, 0x3a)?;
+  let count = u16_at(bytes, 0x3c)?;
+  let mut sections = Vec::new();
+  let mut name_offsets = Vec::new();
+  for i in 0..count {
+    let header = slice_at(bytes, header_offset, i * header_size, 0x30)?;
+    name_offsets.push(u32_at(header, 0)?);
+    sections.push(Section {
+      name: String::new(),
+      kind: u32_at(header, 0x4)?,
+      flags: u64_at(header, 0x8)?,
+      address: u64_at(header, 0x10)?,
+      offset: u64_at(header, 0x18)?,
+      size: u64_at(header, 0x20)?,
+      link: u32_at(header, 0x28)?,
+      info: u32_at(header, 0x2c)?,
+    });
+  }
+  let names = u16_at(bytes, 0x3e)? as usize;
+  if let Some(table) = sections.get(names).map(|section| section.offset) {
+    for (section, name) in sections.iter_mut().zip(name_offsets) {
+      section.name = string_at(bytes, table, name);
+    }
+  }
+  Ok(sections)
+}
+
+pub struct Symbol {
+  pub name: String,
+  pub binding: u8,
+  pub kind: u8,
+  pub section: u64,
+  pub value: u64,
+}
+
+/// The entries of the symbol table `symbols`, which is one of `sections`.
+pub fn symbols(
+  bytes: &[u8],
+  sections: &[Section],
+  symbols: &Section,
+) -> Result<Vec<Symbol>, String> {
+  let strings = sections.get(symbols.link as usize).map_or(0, |s| s.offset);
+  let mut entries = Vec::new();
+  let size = SYMBOL_SIZE as u64;
+  for i in 0..symbols.size / size {
+    let entry = slice_at(bytes, symbols.offset, i * size, size)?;
+    let info = entry[4];
+    entries.push(Symbol {
+      name: string_at(bytes, strings, u32_at(entry, 0)?),
+      binding: info >> 4,
+      kind: info & 0xf,
+      section: u16_at(entry, 6)?,
+      value: u64_at(entry, 8)?,
+    });
+  }
+  Ok(entries)
+}
+
+pub struct Rela {
+  pub offset: u64,
+  pub symbol: usize, // Index into the symbol table
+  pub kind: u32,
+  pub addend: i64,
+}
+
+pub fn relocations(
+  bytes: &[u8],
+  section: &Section,
+) -> Result<Vec<Rela>, String> {
+  let mut relocations = Vec::new();
+  let size = RELA_SIZE as u64;
+  for i in 0..section.size / size {
+    let entry = slice_at(bytes, section.offset, i * size, size)?;
+    let info = u64_at(entry, 8)?;
+    relocations.push(Rela {
+      offset: u64_at(entry, 0)?,
+      symbol: (info >> 32) as usize,
+      kind: info as u32,
+      addend: u64_at(entry, 16)? as i64,
+    });
+  }
+  Ok(relocations)
+}
+
+/// A section to be written. The section header string table is added after
+/// these, so the indices in `link` and `info` count the null section as 0.
+pub struct NewSection {
+  pub name: &'static str,
+  pub kind: u64,
+  pub flags: u64,
+  pub contents: Vec<u8>,
+  pub link: u32,
+  pub info: u32,
+  pub alignment: u64,
+  pub entry_size: u64,
+}
+
+pub fn symbol_entry(name: u32, info: u8, section: u16, value: u64) -> Vec<u8> {
+  let mut entry = Vec::with_capacity(SYMBOL_SIZE);
+  entry.extend(name.to_le_bytes());
+  entry.extend([info, 0]);
+  entry.extend(section.to_le_bytes());
+  entry.extend(value.to_le_bytes());
+  entry.extend(0u64.to_le_bytes()); // Size
+  entry
+}
+
+pub fn rela_entry(offset: u64, symbol: u32, kind: u32, addend: i64) -> Vec<u8> {
+  let mut entry = Vec::with_capacity(RELA_SIZE);
+  entry.extend(offset.to_le_bytes());
+  entry.extend((((symbol as u64) << 32) | kind as u64).to_le_bytes());
+  entry.extend(addend.to_le_bytes());
+  entry
+}
+
+/// An ELF file of type `file_type` with `sections`.
+pub fn write(file_type: u16, sections: &[NewSection]) -> Vec<u8> {
+  let mut names = vec![0u8];
+  let mut name_offsets = Vec::new();
+  for name in sections.iter().map(|s| s.name).chain([".shstrtab"]) {
+    name_offsets.push(names.len() as u32);
+    names.extend(name.as_bytes());
+    names.push(0);
+  }
+  let string_table = NewSection {
+    name: ".shstrtab",
+    kind: SHT_STRTAB,
+    flags: 0,
+    contents: names,
+    link: 0,
+    info: 0,
+    alignment: 1,
+    entry_size: 0,
+  };
+
+  let mut bytes = vec![0u8; 64];
+  bytes[..4].copy_from_slice(ELF_MAGIC);
+  bytes[4] = 2; // 64 bit
+  bytes[5] = 1; // Little endian
+  bytes[6] = 1; // Version
+  bytes[0x10..0x12].copy_from_slice(&file_type.to_le_bytes());
+  bytes[0x12..0x14].copy_from_slice(&(EM_RISCV as u16).to_le_bytes());
+  bytes[0x14..0x18].copy_from_slice(&1u32.to_le_bytes());
+  bytes[0x34] = 64; // Header size
+
+  let mut headers = vec![0u8; 64]; // The null section
+  for (section, name) in
+    sections.iter().chain([&string_table]).zip(name_offsets)
+  {
+    while !(bytes.len() as u64).is_multiple_of(section.alignment.max(1)) {
+      bytes.push(0);
+    }
+    let mut header = vec![0u8; 64];
+    header[..4].copy_from_slice(&name.to_le_bytes());
+    header[4..8].copy_from_slice(&(section.kind as u32).to_le_bytes());
+    header[8..0x10].copy_from_slice(&section.flags.to_le_bytes());
+    header[0x18..0x20].copy_from_slice(&(bytes.len() as u64).to_le_bytes());
+    let size = section.contents.len() as u64;
+    header[0x20..0x28].copy_from_slice(&size.to_le_bytes());
+    header[0x28..0x2c].copy_from_slice(&section.link.to_le_bytes());
+    header[0x2c..0x30].copy_from_slice(&section.info.to_le_bytes());
+    header[0x30..0x38].copy_from_slice(&section.alignment.to_le_bytes());
+    header[0x38..0x40].copy_from_slice(&section.entry_size.to_le_bytes());
+    headers.extend(header);
+    bytes.extend(&section.contents);
+  }
+  while !bytes.len().is_multiple_of(8) {
+    bytes.push(0);
+  }
+  let header_offset = bytes.len() as u64;
+  let count = sections.len() as u16 + 2;
+  bytes[0x28..0x30].copy_from_slice(&header_offset.to_le_bytes());
+  bytes[0x3a..0x3c].copy_from_slice(&64u16.to_le_bytes());
+  bytes[0x3c..0x3e].copy_from_slice(&count.to_le_bytes());
+  bytes[0x3e..0x40].copy_from_slice(&(count - 1).to_le_bytes());
+  bytes.extend(headers);
+  bytes
+}
@@ -0,0 +1,291 @@
+//! The 32 bit machine code for each instruction. The encodings come from the
+//! riscv-opcodes files in isa/, which the build turns into `ENCODINGS`.
+
+use crate::build_common::{field, Field};
+use crate::codegen::{ENCODINGS, INSTRUCTIONS};
+use crate::imm_bits;
+use crate::macros::Location;
+use crate::ImplementationArg;
+use crate::Instruction;
+
+/// How an instruction's operands are laid out, which the build works out
+/// from the fields in its encoding.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+  R,
+  I,
+  Shift,  // I type with a 6 bit shamt, for RV64 slli/srli/srai
+  ShiftW, // I type with a 5 bit shamt, for slliw/srliw/sraiw
+  S,
+  B,
+  U,
+  J,
+  Csr,      // I type with the CSR number as the immediate
+  CsrI,     // Csr, with a 5 bit immediate in place of rs1
+  Fence,    // The predecessor and successor sets in the immediate
+  Fixed,    // No operands
+  FenceVma, // R type with rd = 0
+}
+
+/// How to encode an instruction, as given by riscv-opcodes: the bits which
+/// are the same for every use of it, and the fields which hold its operands.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Encoding {
+  pub mnemonic: &'static str,
+  pub extension: &'static str, // e.g. "rv64_i"
+  pub format: Format,
+  pub mask: u32,  // The bits which are fixed
+  pub value: u32, // and what they're fixed to
+  pub fields: &'static [&'static str],
+}
+
+impl Encoding {
+  fn fields(&self) -> impl Iterator<Item = &'static Field> {
+    self.fields.iter().map(|name| field(name).unwrap())
+  }
+}
+
+pub fn encoding(mnemonic: &str) -> Option<&'static Encoding> {
+  ENCODINGS.iter().find(|e| e.mnemonic == mnemonic)
+}
+
+/// The value of the named operand as it goes into the instruction word.
+fn operand(instruction: &Instruction, name: &str) -> Option<u32> {
+  let value =
+    core::iter::zip(instruction.source.arg_names(), &instruction.args)
+      .find(|(arg_name, _)| *arg_name == name)
+      .map(|(_, arg)| match arg {
+        ImplementationArg::Register(r) => *r as u64,
+        ImplementationArg::Imm12(_)
+        | ImplementationArg::Imm20(_)
+        | ImplementationArg::Imm21(_) => {
+          instruction.imm_arg(name).unwrap().value
+        }
+        ImplementationArg::Shamt(value) | ImplementationArg::Uimm(value) => {
+          *value
+        }
+        ImplementationArg::Csr(csr) => *csr as u64,
+      });
+  value.map(|value| value as u32)
+}
+
+/// `value[high:low]`, shifted down to bit 0.
+fn bits(value: u32, high: u32, low: u32) -> u32 {
+  (value >> low) & ((1 << (high - low + 1)) - 1)
+}
+
+pub fn encode(instruction: &Instruction) -> Option<u32> {
+  let e = encoding(instruction.source.mnemonic)?;
+  let mut word = e.value;
+  for field in e.fields() {
+    let value = field
+      .operands
+      .iter()
+      .find_map(|name| operand(instruction, name))
+      // fence's sets are written as `pred, succ`, which orders everything
+      .unwrap_or(match field.name {
+        "pred" | "succ" => 0xf,
+        _ => 0,
+      });
+    for &(high, low, shift) in field.pieces {
+      word |= bits(value, high, low) << shift;
+    }
+  }
+  Some(word)
+}
+
+/// An instruction word, with its fields picked out by name.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Decoded {
+  pub encoding: &'static Encoding,
+  pub word: u32,
+}
+
+/// Sign extends the low `width` bits of `value`.
+fn sext(value: u32, width: u32) -> i64 {
+  let shift = 64 - width;
+  ((value as i64) << shift) >> shift
+}
+
+impl Decoded {
+  /// The value of the operand with the given name in the instruction's
+  /// syntax. Immediates are sign extended, and offsets are in bytes.
+  pub fn operand(&self, name: &str) -> i64 {
+    let mut value = 0;
+    let mut width = 0;
+    for field in self.encoding.fields() {
+      if field.operands.contains(&name) {
+        for &(high, low, shift) in field.pieces {
+          value |= bits(self.word, shift + high - low, shift) << low;
+          width = width.max(high + 1);
+        }
+      }
+    }
+    match name {
+      "imm" | "offset" if width > 0 => sext(value, width),
+      _ => value as i64,
+    }
+  }
+
+  /// Where a branch or jal goes, if it's at `address`.
+  pub fn target(&self, address: u64) -> Option<u64> {
+    match self.encoding.format {
+      Format::B | Format::J => {
+        Some(address.wrapping_add(self.operand("offset") as u64))
+      }
+      _ => None,
+    }
+  }
+}
+
+/// The instruction a 32 bit word encodes, if it's one the interpreter has.
+pub fn decode(word: u32) -> Option<Decoded> {
+  let encoding = ENCODINGS.iter().find(|e| word & e.mask == e.value)?;
+  Some(Decoded { encoding, word })
+}
+
+/// The instruction a word encodes, ready to run, as if it was written on
+/// `line_num` of the source.
+pub fn instruction(word: u32, line_num: u32) -> Option<Instruction> {
+  let decoded = decode(word)?;
+  let source = INSTRUCTIONS.get(decoded.encoding.mnemonic)?;
+  let mut args = Vec::new();
+  for name in source.arg_names() {
+    let value = decoded.operand(name);
+    let kind = decoded
+      .encoding
+      .fields()
+      .find(|field| field.operands.contains(&name))?
+      .kind;
+    args.push(match kind {
+      "Imm12" => ImplementationArg::Imm12(imm_bits(value)?),
+      "Imm20" => ImplementationArg::Imm20(imm_bits(value)?),
+      "Imm21" => ImplementationArg::Imm21(imm_bits(value)?),
+      "Shamt" => ImplementationArg::Shamt(value as u64),
+      "Csr" => ImplementationArg::Csr(value as u16),
+      "Uimm" => ImplementationArg::Uimm(value as u64),
+      _ => ImplementationArg::Register(value as usize),
+    });
+  }
+  Some(Instruction {
+    source,
+    line_num,
+    locations: vec![Location {
+      file: None,
+      line: line_num,
+    }],
+    breakpoint: false,
+    op: (source.op)(&args),
+    args,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::operand::Symbols;
+
+  fn encode_line(line: &str) -> u32 {
+    let mnemonic = line.split_whitespace().next().unwrap();
+    let source = INSTRUCTIONS.get(mnemonic).unwrap();
+    let args = source.parse(line, &mut Symbols::default(), 0).unwrap();
+    let instruction = Instruction {
+      source,
+      line_num: 1,
+      locations: Vec::new(),
+      breakpoint: false,
+      op: (source.op)(&args),
+      args,
+    };
+    encode(&instruction).unwrap()
+  }
+
+  #[test]
+  fn every_instruction_has_an_encoding() {
+    for mnemonic in INSTRUCTIONS.keys() {
+      assert!(encoding(mnemonic).is_some(), "{} has no encoding", mnemonic);
+    }
+  }
+
+  #[test]
+  fn encodings_come_from_the_spec() {
+    assert_eq!(encoding("addi").unwrap().extension, "rv_i");
+    assert_eq!(encoding("ld").unwrap().extension, "rv64_i");
+    assert_eq!(encoding("csrrw").unwrap().extension, "rv_zicsr");
+    for e in &ENCODINGS {
+      assert_eq!(e.value & !e.mask, 0, "{}", e.mnemonic);
+      assert_eq!(decode(e.value).unwrap().encoding, e);
+    }
+  }
+
+  #[test]
+  fn encodings_match_gnu_as() {
+    // The same words GNU as produces
+    assert_eq!(encode_line("addi a0, a0, -1"), 0xfff5_0513);
+    assert_eq!(encode_line("lui t0, 0x10010"), 0x1001_02b7);
+    assert_eq!(encode_line("sd ra, 8(sp)"), 0x0011_3423);
+    assert_eq!(encode_line("ld t0, 0(sp)"), 0x0001_3283);
+    assert_eq!(encode_line("bne a0, x0, 8"), 0x0005_1463);
+    assert_eq!(encode_line("beq t1, t2, -4"), 0xfe73_0ee3);
+    assert_eq!(encode_line("jal ra, 16"), 0x0100_00ef);
+    assert_eq!(encode_line("jal x0, 600000"), 0x7c09_206f);
+    assert_eq!(encode_line("jal ra, -1048576"), 0x8000_00ef);
+    assert_eq!(encode_line("jalr x0, 0(ra)"), 0x0000_8067);
+    assert_eq!(encode_line("srai a0, a1, 63"), 0x43f5_d513);
+    assert_eq!(encode_line("sraiw a0, a1, 3"), 0x4035_d51b);
+    assert_eq!(encode_line("sub a0, a1, a2"), 0x40c5_8533);
+    assert_eq!(encode_line("csrrw t0, mtvec, t1"), 0x3053_12f3);
+    assert_eq!(encode_line("csrrsi x0, mstatus, 8"), 0x3004_6073);
+    assert_eq!(encode_line("mret"), 0x3020_0073);
+    assert_eq!(encode_line("sfence.vma x0, x0"), 0x1200_0073);
+    assert_eq!(encode_line("fence.i"), 0x0000_100f);
+  }
+
+  #[test]
+  fn decoding_reverses_encoding() {
+    let lines = [
+      "addi a0, a0, -1",
+      "lui t0, 0x10010",
+      "sd ra, -8(sp)",
+      "lwu t0, 2047(sp)",
+      "beq t1, t2, -4",
+      "bgeu t1, t2, 4094",
+      "jal ra, -16",
+      "jal ra, 1048574",
+      "jal x0, -1048576",
+      "srai a0, a1, 63",
+      "srliw a0, a1, 31",
+      "sub a0, a1, a2",
+      "csrrsi x0, mstatus, 8",
+      "sfence.vma x0, x0",
+      "ebreak",
+    ];
+    for line in lines {
+      let word = encode_line(line);
+      let decoded = decode(word).unwrap_or_else(|| panic!("{}", line));
+      assert_eq!(decoded.encoding.mnemonic, line.split(' ').next().unwrap());
+      let source = INSTRUCTIONS.get(decoded.encoding.mnemonic).unwrap();
+      let args = source.parse(line, &mut Symbols::default(), 0).unwrap();
+      let instruction = Instruction {
+        source,
+        line_num: 1,
+        locations: Vec::new(),
+        breakpoint: false,
+        op: (source.op)(&args),
+        args,
+      };
+      for name in source.arg_names() {
+        let expected = match name {
+          "rd" | "rs1" | "rs2" | "csr" | "shamt" | "uimm" => {
+            operand(&instruction, name).unwrap() as i64
+          }
+          _ => instruction.imm_arg(name).unwrap().value as i64,
+        };
+        assert_eq!(decoded.operand(name), expected, "{} in {}", name, line);
+      }
+    }
+    assert_eq!(decode(0x0330_000f).unwrap().encoding.mnemonic, "fence");
+    assert_eq!(decode(0x0000_0000), None);
+    assert_eq!(decode(0xffff_ffff), None);
+  }
+}
@@ -0,0 +1,650 @@
+//! A stub for GDB's remote serial protocol, so that a program run by the
+//! native runner can be debugged with `riscv64-unknown-elf-gdb`:
+//!
+//! ```text
+//! (gdb) target remote localhost:1234
+//! ```
+//!
+//! There's a single hart, so thread packets all refer to thread 1. Packets
+//! which aren't handled get the empty reply, which tells GDB they aren't
+//! supported.
+
+use crate::AccessKind;
+use crate::Interpreter;
+use std::collections::BTreeSet;
+use std::convert::TryInto;
+use std::fmt::Write as _;
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+
+/// How many instructions to run between checks for an interrupt from GDB.
+const INTERRUPT_POLL_STEPS: u64 = 4096;
+
+/// The longest packet GDB can send or be sent, in bytes. Memory is sent as
+/// two hex digits a byte, so a read gets at most half this.
+const PACKET_SIZE: u64 = 0x4000;
+
+const REGISTER_NAMES: [&str; 32] = [
+  "zero", "ra", "sp", "gp", "tp", "t0", "t1", "t2", "fp", "s1", "a0", "a1",
+  "a2", "a3", "a4", "a5", "a6", "a7", "s2", "s3", "s4", "s5", "s6", "s7", "s8",
+  "s9", "s10", "s11", "t3", "t4", "t5", "t6",
+];
+// GDB's number for the pc, after x0-x31
+const PC: usize = 32;
+
+pub trait Connection: Read + Write {
+  /// Whether GDB has sent an interrupt (^C) while the program is running.
+  /// This mustn't block.
+  fn interrupted(&mut self) -> bool {
+    false
+  }
+}
+
+impl Connection for TcpStream {
+  fn interrupted(&mut self) -> bool {
+    let mut byte = [0];
+    if self.set_nonblocking(true).is_err() {
+      return false;
+    }
+    let interrupted = matches!(self.peek(&mut byte), Ok(1) if byte[0] == 0x03);
+    self.set_nonblocking(false).ok();
+    if interrupted {
+      self.read_exact(&mut byte).ok();
+    }
+    interrupted
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum WatchKind {
+  Write,  // Z2
+  Read,   // Z3
+  Access, // Z4
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Watchpoint {
+  kind: WatchKind,
+  address: u64,
+  length: u64,
+}
+
+pub struct GdbStub<'a, C: Connection> {
+  interpreter: &'a mut Interpreter,
+  connection: C,
+  breakpoints: BTreeSet<u64>,
+  watchpoints: Vec<Watchpoint>,
+  // Cleared by QStartNoAckMode
+  acknowledge: bool,
+}
+
+/// The registers' description, which GDB reads with qXfer:features:read.
+pub fn target_xml() -> String {
+  let mut xml = String::from(
+    "<?xml version=\"1.0\"?>\n\
+     <!DOCTYPE target SYSTEM \"gdb-target.dtd\">\n\
+     <target version=\"1.0\">\n\
+     <architecture>riscv:rv64</architecture>\n\
+     <feature name=\"org.gnu.gdb.riscv.cpu\">\n",
+  );
+  for (i, name) in REGISTER_NAMES.iter().enumerate() {
+    let kind = match *name {
+      "ra" => "code_ptr",
+      "sp" | "fp" => "data_ptr",
+      _ => "int",
+    };
+    writeln!(
+      xml,
+      "<reg name=\"{}\" bitsize=\"64\" type=\"{}\" regnum=\"{}\"/>",
+      name, kind, i
+    )
+    .ok();
+  }
+  writeln!(
+    xml,
+    "<reg name=\"pc\" bitsize=\"64\" type=\"code_ptr\" regnum=\"{}\"/>",
+    PC
+  )
+  .ok();
+  xml.push_str("</feature>\n</target>\n");
+  xml
+}
+
+fn checksum(data: &[u8]) -> u8 {
+  data.iter().fold(0, |sum, byte| sum.wrapping_add(*byte))
+}
+
+fn hex_bytes(bytes: &[u8]) -> String {
+  bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn parse_hex_bytes(hex: &str) -> Option<Vec<u8>> {
+  if !hex.len().is_multiple_of(2) {
+    return None;
+  }
+  (0..hex.len())
+    .step_by(2)
+    .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+    .collect()
+}
+
+fn parse_hex(hex: &str) -> Option<u64> {
+  u64::from_str_radix(hex, 16).ok()
+}
+
+/// A register's value as GDB sends it: 8 bytes, little endian.
+fn parse_register(hex: &str) -> Option<u64> {
+  let bytes: [u8; 8] = parse_hex_bytes(hex)?.try_into().ok()?;
+  Some(u64::from_le_bytes(bytes))
+}
+
+/// Parses `addr,length`, as used by m, M and qXfer.
+fn parse_range(range: &str) -> Option<(u64, u64)> {
+  let (address, length) = range.split_once(',')?;
+  Some((parse_hex(address)?, parse_hex(length)?))
+}
+
+impl<'a, C: Connection> GdbStub<'a, C> {
+  pub fn new(interpreter: &'a mut Interpreter, connection: C) -> Self {
+    interpreter.set_running(true);
+    GdbStub {
+      interpreter,
+      connection,
+      breakpoints: BTreeSet::new(),
+      watchpoints: Vec::new(),
+      acknowledge: true,
+    }
+  }
+
+  /// Handles packets until GDB kills the program, detaches, or disconnects.
+  pub fn serve(&mut self) -> io::Result<()> {
+    while let Some(packet) = self.receive()? {
+      let reply = match packet.as_str() {
+        "k" => return Ok(()),
+        "D" => {
+          self.send("OK")?;
+          return Ok(());
+        }
+        _ => self.handle(&packet),
+      };
+      self.send(&reply)?;
+    }
+    Ok(())
+  }
+
+  fn read_byte(&mut self) -> io::Result<Option<u8>> {
+    let mut byte = [0];
+    match self.connection.read(&mut byte)? {
+      0 => Ok(None),
+      _ => Ok(Some(byte[0])),
+    }
+  }
+
+  /// Reads the next packet, without its framing. Returns None once the
+  /// connection is closed.
+  fn receive(&mut self) -> io::Result<Option<String>> {
+    loop {
+      // Skip acknowledgements, and interrupts sent while already stopped
+      loop {
+        match self.read_byte()? {
+          None => return Ok(None),
+          Some(b'$') => break,
+          Some(_) => {}
+        }
+      }
+      let mut data = Vec::new();
+      loop {
+        match self.read_byte()? {
+          None => return Ok(None),
+          Some(b'#') => break,
+          Some(byte) => data.push(byte),
+        }
+      }
+      let mut sum = [0; 2];
+      self.connection.read_exact(&mut sum)?;
+      let valid = std::str::from_utf8(&sum)
+        .ok()
+        .and_then(|sum| u8::from_str_radix(sum, 16).ok())
+        == Some(checksum(&data));
+      let data = String::from_utf8_lossy(&data).into_owned();
+      if !self.acknowledge {
+        return Ok(Some(data));
+      }
+      if valid {
+        self.connection.write_all(b"+")?;
+        return Ok(Some(data));
+      }
+      self.connection.write_all(b"-")?;
+    }
+  }
+
+  fn send(&mut self, data: &str) -> io::Result<()> {
+    let packet = format!("${}#{:02x}", data, checksum(data.as_bytes()));
+    loop {
+      self.connection.write_all(packet.as_bytes())?;
+      self.connection.flush()?;
+      if !self.acknowledge {
+        return Ok(());
+      }
+      // Anything other than a request to resend counts as an acknowledgement
+      if self.read_byte()? != Some(b'-') {
+        return Ok(());
+      }
+    }
+  }
+
+  /// The reply to a packet, which is empty if it isn't supported.
+  fn handle(&mut self, packet: &str) -> String {
+    if packet.is_empty() {
+      return String::new();
+    }
+    // Commands are ASCII, and anything else can't be a valid packet
+    let (command, arguments) = match (packet.get(..1), packet.get(1..)) {
+      (Some(command), Some(arguments)) => (command, arguments),
+      _ => return "E01".to_string(),
+    };
+    let reply = match command {
+      "?" => Some(self.stop_reason()),
+      "g" => Some(self.read_registers()),
+      "G" => self.write_registers(arguments),
+      "p" => self.read_register(arguments),
+      "P" => self.write_register(arguments),
+      "m" => self.read_memory(arguments),
+      "M" => self.write_memory(arguments),
+      "s" | "c" if !arguments.is_empty() => parse_hex(arguments).map(|pc| {
+        self.interpreter.set_pc(pc);
+        self.resume(command == "s")
+      }),
+      "s" | "c" => Some(self.resume(command == "s")),
+      "Z" => self.set_point(arguments, true),
+      "z" => self.set_point(arguments, false),
+      "H" | "T" => Some("OK".to_string()),
+      "q" | "Q" | "v" => return self.query(packet),
+      _ => return String::new(),
+    };
+    reply.unwrap_or_else(|| "E01".to_string())
+  }
+
+  fn query(&mut self, packet: &str) -> String {
+    if packet.starts_with("qSupported") {
+      return format!(
+        "PacketSize={:x};qXfer:features:read+;swbreak+;\
+         QStartNoAckMode+;vContSupported+",
+        PACKET_SIZE
+      );
+    }
+    if let Some(annex) = packet.strip_prefix("qXfer:features:read:") {
+      return self
+        .read_feature(annex)
+        .unwrap_or_else(|| "E00".to_string());
+    }
+    if let Some(action) = packet.strip_prefix("vCont;") {
+      // There's only one thread, so the first action is the one for it
+      let action = action.split([';', ':']).next().unwrap_or("");
+      return match action {
+        "s" => self.resume(true),
+        "c" => self.resume(false),
+        _ => String::new(),
+      };
+    }
+    match packet {
+      "QStartNoAckMode" => {
+        // This reply is still acknowledged
+        self.acknowledge = false;
+        "OK".to_string()
+      }
+      "qAttached" => "1".to_string(),
+      "qC" => "QC1".to_string(),
+      "qfThreadInfo" => "m1".to_string(),
+      "qsThreadInfo" => "l".to_string(),
+      "vCont?" => "vCont;c;s".to_string(),
+      _ => String::new(),
+    }
+  }
+
+  /// qXfer:features:read:target.xml:offset,length
+  fn read_feature(&self, annex: &str) -> Option<String> {
+    let range = annex.strip_prefix("target.xml:")?;
+    let (offset, length) = parse_range(range)?;
+    let xml = target_xml();
+    let start = (offset as usize).min(xml.len());
+    let end = start.saturating_add(length as usize).min(xml.len());
+    let more = if end < xml.len() { 'm' } else { 'l' };
+    Some(format!("{}{}", more, &xml[start..end]))
+  }
+
+  fn stop_reason(&self) -> String {
+    if self.interpreter.running() {
+      "S05".to_string()
+    } else {
+      self.exit_status()
+    }
+  }
+
+  fn exit_status(&self) -> String {
+    let status = if self.interpreter.errors().is_empty() {
+      0
+    } else {
+      1
+    };
+    format!("W{:02x}", status)
+  }
+
+  fn register_value(&self, register: usize) -> u64 {
+    match register {
+      PC => self.interpreter.pc(),
+      _ => self.interpreter.register(register),
+    }
+  }
+
+  fn set_register_value(&mut self, register: usize, value: u64) {
+    match register {
+      PC => self.interpreter.set_pc(value),
+      _ => self.interpreter.set_register(register, value),
+    }
+  }
+
+  fn read_registers(&self) -> String {
+    (0..=PC)
+      .map(|register| hex_bytes(&self.register_value(register).to_le_bytes()))
+      .collect()
+  }
+
+  fn write_registers(&mut self, hex: &str) -> Option<String> {
+    if hex.len() != (PC + 1) * 16 {
+      return None;
+    }
+    let values = (0..=PC)
+      .map(|register| parse_register(hex.get(register * 16..)?.get(..16)?))
+      .collect::<Option<Vec<u64>>>()?;
+    for (register, value) in values.into_iter().enumerate() {
+      self.set_register_value(register, value);
+    }
+    Some("OK".to_string())
+  }
+
+  fn read_register(&self, register: &str) -> Option<String> {
+    let register = parse_hex(register)? as usize;
+    (register <= PC)
+      .then(|| hex_bytes(&self.register_value(register).to_le_bytes()))
+  }
+
+  fn write_register(&mut self, arguments: &str) -> Option<String> {
+    let (register, value) = arguments.split_once('=')?;
+    let register = parse_hex(register)? as usize;
+    if register > PC {
+      return None;
+    }
+    self.set_register_value(register, parse_register(value)?);
+    Some("OK".to_string())
+  }
+
+  /// Reads as much of the range as can be read, which is an error only if
+  /// that's none of it.
+  fn read_memory(&self, range: &str) -> Option<String> {
+    let (address, length) = parse_range(range)?;
+    // The reply can be shorter than the length asked for, which mustn't be
+    // able to make it allocate all the memory
+    let length = length.min(PACKET_SIZE / 2);
+    let bytes: Vec<u8> = self
+      .interpreter
+      .debug_read(address, length as usize)
+      .into_iter()
+      .map_while(|byte| byte)
+      .collect();
+    if bytes.is_empty() && length > 0 {
+      return Some("E14".to_string());
+    }
+    Some(hex_bytes(&bytes))
+  }
+
+  fn write_memory(&mut self, arguments: &str) -> Option<String> {
+    let (range, data) = arguments.split_once(':')?;
+    let (address, length) = parse_range(range)?;
+    let data = parse_hex_bytes(data)?;
+    if data.len() as u64 != length {
+      return None;
+    }
+    if self.interpreter.debug_write(address, &data) {
+      Some("OK".to_string())
+    } else {
+      Some("E14".to_string())
+    }
+  }
+
+  /// Z/z type,addr,kind
+  fn set_point(&mut self, arguments: &str, insert: bool) -> Option<String> {
+    let mut fields = arguments.split(',');
+    let kind = fields.next()?;
+    let address = parse_hex(fields.next()?)?;
+    let length = parse_hex(fields.next()?)?;
+    let kind = match kind {
+      "0" => {
+        if insert {
+          self.breakpoints.insert(address);
+        } else {
+          self.breakpoints.remove(&address);
+        }
+        return Some("OK".to_string());
+      }
+      "2" => WatchKind::Write,
+      "3" => WatchKind::Read,
+      "4" => WatchKind::Access,
+      _ => return Some(String::new()),
+    };
+    let watchpoint = Watchpoint {
+      kind,
+      address,
+      length,
+    };
+    if insert {
+      self.watchpoints.push(watchpoint);
+    } else if let Some(i) =
+      self.watchpoints.iter().position(|w| *w == watchpoint)
+    {
+      self.watchpoints.remove(i);
+    }
+    Some("OK".to_string())
+  }
+
+  /// The stop reply for a watchpoint hit by the last instruction, if any.
+  fn watchpoint_hit(&self) -> Option<String> {
+    let access = self.interpreter.last_access()?;
+    let end = access.address.saturating_add(access.size as u64);
+    self.watchpoints.iter().find_map(|watchpoint| {
+      let name = match (watchpoint.kind, access.kind) {
+        (WatchKind::Write, AccessKind::Store) => "watch",
+        (WatchKind::Read, AccessKind::Load) => "rwatch",
+        (WatchKind::Access, _) => "awatch",
+        _ => return None,
+      };
+      let watch_end = watchpoint.address.saturating_add(watchpoint.length);
+      (access.address < watch_end && watchpoint.address < end).then(|| {
+        let address = access.address.max(watchpoint.address);
+        format!("T05{}:{:x};", name, address)
+      })
+    })
+  }
+
+  /// Runs one instruction, or until something stops the program, and
+  /// returns the stop reply. At least one instruction is run, so continuing
+  /// from a breakpoint doesn't stop at it again.
+  fn resume(&mut self, single_step: bool) -> String {
+    let mut steps: u64 = 0;
+    loop {
+      self.interpreter.step();
+      steps += 1;
+      if !self.interpreter.running() {
+        return self.exit_status();
+      }
+      if let Some(reply) = self.watchpoint_hit() {
+        return reply;
+      }
+      if single_step {
+        return "T05".to_string();
+      }
+      if self.breakpoints.contains(&self.interpreter.pc()) {
+        return "T05swbreak:;".to_string();
+      }
+      if steps.is_multiple_of(INTERRUPT_POLL_STEPS)
+        && self.connection.interrupted()
+      {
+        return "T02".to_string();
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// Plays back GDB's side of a session, recording the stub's.
+  struct MockConnection {
+    input: io::Cursor<Vec<u8>>,
+    output: Vec<u8>,
+  }
+
+  impl Read for MockConnection {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+      self.input.read(buf)
+    }
+  }
+
+  impl Write for MockConnection {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+      self.output.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+      Ok(())
+    }
+  }
+
+  impl Connection for MockConnection {}
+
+  fn packet(data: &str) -> String {
+    format!("${}#{:02x}", data, checksum(data.as_bytes()))
+  }
+
+  /// Sends the packets without acknowledgements, returning the replies.
+  fn session(code: &str, packets: &[&str]) -> (Vec<String>, Interpreter) {
+    let mut interpreter = Interpreter::create_RiscV64_i(Vec::new());
+    interpreter.set_code(code.to_string());
+    let mut input = packet("QStartNoAckMode");
+    for data in packets {
+      input.push_str(&packet(data));
+    }
+    let connection = MockConnection {
+      input: io::Cursor::new(input.into_bytes()),
+      output: Vec::new(),
+    };
+    let mut stub = GdbStub::new(&mut interpreter, connection);
+    stub.serve().unwrap();
+    let output = String::from_utf8(stub.connection.output.clone()).unwrap();
+    // The first reply is acknowledged, and the rest aren't
+    let replies = output
+      .trim_start_matches('+')
+      .split('$')
+      .skip(2)
+      .map(|reply| reply.split('#').next().unwrap().to_string())
+      .collect();
+    (replies, interpreter)
+  }
+
+  const PROGRAM: &str = "addi a0, zero, 5
+lui s0, 0x10010
+sw a0, 4(s0)
+lw a1, 4(s0)
+addi a0, a0, 1";
+
+  #[test]
+  fn framing() {
+    assert_eq!(packet("OK"), "$OK#9a");
+    assert_eq!(parse_register("0500000000000000"), Some(5));
+    assert_eq!(parse_register("05"), None);
+    assert_eq!(parse_range("10010000,4"), Some((0x1001_0000, 4)));
+  }
+
+  #[test]
+  fn acknowledges_packets() {
+    let mut interpreter = Interpreter::create_RiscV64_i(Vec::new());
+    interpreter.set_code(PROGRAM.to_string());
+    // A corrupt packet is asked for again, and the reply is acknowledged
+    let input = format!("$qAttached#00{}+", packet("qAttached"));
+    let connection = MockConnection {
+      input: io::Cursor::new(input.into_bytes()),
+      output: Vec::new(),
+    };
+    let mut stub = GdbStub::new(&mut interpreter, connection);
+    stub.serve().unwrap();
+    assert_eq!(stub.connection.output, b"-+$1#31");
+  }
+
+  #[test]
+  fn registers_and_memory() {
+    let (replies, interpreter) = session(
+      PROGRAM,
+      &[
+        "s",
+        "s",
+        "s",
+        "p20",
+        "pa",
+        "P2=0800000000000000",
+        "m10010004,4",
+      ],
+    );
+    assert_eq!(replies[..3], ["T05", "T05", "T05"]);
+    assert_eq!(replies[3], "0c00000000000000"); // The pc
+    assert_eq!(replies[4], "0500000000000000"); // a0
+    assert_eq!(replies[6], "05000000");
+    assert_eq!(interpreter.register(2), 8);
+
+    let (replies, _) = session(PROGRAM, &["m0,4", "M10010000,2:abcd", "m0,0"]);
+    // The text reads as the machine code
+    assert_eq!(replies[0], "13055000");
+    assert_eq!(replies[1], "OK");
+    // Reads are cut short at half the packet size
+    let read = ["qSupported", "m10010000,ffffffffffffffff"];
+    let (replies, _) = session(PROGRAM, &read);
+    assert!(replies[0].starts_with("PacketSize=4000;"));
+    assert_eq!(replies[1].len(), 0x4000);
+    let (replies, _) = session(PROGRAM, &["m8000000,4", "g"]);
+    assert_eq!(replies[0], "E14");
+    assert_eq!(replies[1].len(), 33 * 16);
+
+    // Malformed packets are errors rather than panics
+    let registers = format!("G{}\u{e9}{}", "0".repeat(15), "0".repeat(511));
+    let (replies, _) = session(PROGRAM, &["\u{e9}", &registers, "G00", "pa"]);
+    assert_eq!(replies[..3], ["E01", "E01", "E01"]);
+    assert_eq!(replies[3], "0000000000000000");
+  }
+
+  #[test]
+  fn breakpoints_and_watchpoints() {
+    let (replies, _) = session(
+      PROGRAM,
+      &["Z0,8,4", "c", "z0,8,4", "Z3,10010004,4", "c", "c"],
+    );
+    assert_eq!(replies[1], "T05swbreak:;");
+    assert_eq!(replies[4], "T05rwatch:10010004;");
+    assert_eq!(replies[5], "W00");
+
+    let (replies, _) = session(PROGRAM, &["Z2,10010006,2", "vCont;c:1"]);
+    assert_eq!(replies[1], "T05watch:10010006;");
+  }
+
+  #[test]
+  fn target_description() {
+    let (replies, _) = session(
+      PROGRAM,
+      &["qSupported:swbreak+", "qXfer:features:read:target.xml:0,a"],
+    );
+    assert!(replies[0].contains("qXfer:features:read+"));
+    assert_eq!(replies[1], "m<?xml vers");
+    let xml = target_xml();
+    assert_eq!(xml.matches("<reg ").count(), 33);
+    assert!(xml.contains("name=\"pc\""));
+  }
+}
@@ -1,19 +1,28 @@
-use std::convert::{From, TryInto};
+use std::convert::{From, TryFrom, TryInto};
 use std::fmt;
 use std::ops;
 
 use crate::build_common::*;
-use crate::codegen::MachineInstruction;
+use crate::codegen::Op;
 use crate::codegen::REGISTERS;
-use crate::interface;
+use crate::csr::csr_address;
+use crate::encoding::{encoding, Encoding, Format};
+use crate::linker::{Relocation, RelocationKind};
 use crate::log;
+use crate::macros::{strip_comment, Location};
+use crate::operand::*;
+use crate::rv64_i::{sext, Immediate};
 
 #[allow(dead_code)] // Dead code analysis doesn't check in generated code.
+#[derive(Copy, Clone, Debug)]
 pub enum ImplementationArg {
   Register(usize),
   Imm12([bool; 12]),
   Imm20([bool; 20]),
+  Imm21([bool; 21]),
   Shamt(u64),
+  Csr(u16),
+  Uimm(u64),
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, PartialOrd)]
@@ -181,6 +190,14 @@ impl ops::BitAnd<u64> for Register {
   }
 }
 
+impl ops::Not for Register {
+  type Output = Register;
+
+  fn not(self) -> Register {
+    Register { value: !self.value }
+  }
+}
+
 impl From<bool> for Register {
   fn from(item: bool) -> Self {
     Register { value: item as u64 }
@@ -205,6 +222,21 @@ impl std::fmt::Binary for Register {
   }
 }
 
+/// Who is responsible for preserving a register across a call
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Saver {
+  Caller,
+  Callee,
+  Neither,
+}
+
+#[allow(dead_code)] // Dead code analysis doesn't check in generated code.
+pub struct RegisterInfo {
+  pub name: &'static str,
+  pub abi_name: &'static str,
+  pub saver: Saver,
+}
+
 pub struct PC {
   value: u64,
   pub changed: bool,
@@ -233,6 +265,24 @@ impl PC {
   }
 }
 
+/// Splits a line into an optional label definition and the instruction after
+/// it, with any comment removed.
+pub fn split_label(line: &str) -> (Option<&str>, &str) {
+  let code: &str = strip_comment(line).trim();
+  match code.split_once(':') {
+    Some((label, rest)) => (Some(label.trim()), rest.trim()),
+    None => (None, code),
+  }
+}
+
+pub fn valid_label(label: &str) -> bool {
+  !label.is_empty()
+    && !label.starts_with(|c: char| c.is_ascii_digit())
+    && label
+      .chars()
+      .all(|c| c.is_alphanumeric() || c == '_' || c == '.')
+}
+
 #[allow(dead_code)] // Dead code analysis doesn't check in generated code.
 pub struct InstructionSource {
   pub mnemonic: &'static str,
@@ -240,7 +290,7 @@ pub struct InstructionSource {
   pub syntax: &'static [&'static str],
   pub description: &'static str,
   pub implementation_str: &'static str,
-  pub implementation: fn(Vec<ImplementationArg>) -> MachineInstruction,
+  pub op: fn(&[ImplementationArg]) -> Op,
 }
 
 impl fmt::Debug for InstructionSource {
@@ -254,68 +304,510 @@ impl fmt::Debug for InstructionSource {
 }
 
 impl InstructionSource {
-  pub fn format_error(&self, tokens: Vec<String>) {
-    interface::alert(format!("Invalid instruction format. Instruction \"{}\" should have format \"{}\" but instead had \"{}\"", self.mnemonic, self.syntax.join(" "), tokens.join(" ")).as_str());
-  }
-
-  pub fn parse(&self, code: &str) -> Option<Vec<ImplementationArg>> {
-    let tokens: Vec<String> = tokenise(code);
-    if tokens.len() != self.syntax.len() {
-      log!("Wrong number of tokens");
-      self.format_error(tokens);
-      return None;
+  /// The instruction's syntax as it'd be written, e.g. "addi rd, rs1, imm".
+  pub fn signature(&self) -> String {
+    let mut signature = self.syntax[0].to_string();
+    for (i, part) in self.syntax.iter().enumerate().skip(1) {
+      match *part {
+        "," => signature.push(','),
+        "(" | ")" => signature.push_str(part),
+        _ if self.syntax[i - 1] == "(" => signature.push_str(part),
+        _ => {
+          signature.push(' ');
+          signature.push_str(part);
+        }
+      }
+    }
+    signature
+  }
+
+  /// Parses the operands of `code`, which is at `address`. Branch and jump
+  /// targets which refer to a label are converted to the pc relative offset
+  /// the implementation expects, and a %pcrel_hi is recorded in `symbols`
+  /// for the %pcrel_lo which goes with it.
+  pub fn parse(
+    &self,
+    code: &str,
+    symbols: &mut Symbols,
+    address: u64,
+  ) -> Result<Vec<ImplementationArg>, String> {
+    let text = &code.trim()[self.mnemonic.len()..];
+    let lexemes = lex(text)?;
+    let operands = split_operands(&lexemes);
+    let expected: Vec<&[&str]> = match self.syntax.len() {
+      1 => Vec::new(),
+      _ => self.syntax[1..].split(|part| *part == ",").collect(),
+    };
+    if operands.len() != expected.len() {
+      log!("Wrong number of operands");
+      return Err(format!(
+        "expected {} operand{} for `{}`, found {}",
+        expected.len(),
+        if expected.len() == 1 { "" } else { "s" },
+        self.signature(),
+        operands.len()
+      ));
     }
     let mut arguments: Vec<ImplementationArg> = Vec::new();
-    for (actual, expected) in core::iter::zip(tokens.iter(), self.syntax.iter())
-    {
-      if expected.eq(&"rd") || expected.eq(&"rs1") || expected.eq(&"rs2") {
-        let reg_num = REGISTERS.get(actual);
-        if reg_num.is_none() {
-          log!("Failed to get reg num");
-          self.format_error(tokens);
-          return None;
+    for (operand, syntax) in core::iter::zip(operands, expected) {
+      let mut parser = OperandParser {
+        instruction: self,
+        text,
+        symbols,
+        address,
+      };
+      match *syntax {
+        ["offset", "(", "rs1", ")"] => {
+          let (offset, base) = parser.memory(operand)?;
+          arguments.push(offset);
+          arguments.push(base);
         }
-        arguments.push(ImplementationArg::Register(
-          (*reg_num.unwrap()).try_into().unwrap(),
-        ));
-      } else if expected.eq(&"imm") || expected.eq(&"offset") {
-        let val = parse_imm::<12>(actual.to_string());
-        if val.is_none() {
-          log!("Failed to get val");
-          self.format_error(tokens);
-          return None;
+        [kind] => {
+          if let Some(argument) = parser.operand(kind, operand)? {
+            arguments.push(argument);
+          }
         }
-        arguments.push(ImplementationArg::Imm12(val.unwrap()));
-      } else if expected.eq(&"imm20") {
-        let val = parse_imm::<20>(actual.to_string());
-        if val.is_none() {
-          log!("Failed to get val");
-          self.format_error(tokens);
-          return None;
+        _ => unreachable!("Unknown syntax {:?}", syntax),
+      }
+    }
+    Ok(arguments)
+  }
+
+  /// The names of the operands, in the order they're passed to the
+  /// implementation.
+  pub fn arg_names(&self) -> impl Iterator<Item = &'static str> {
+    arg_names(self.syntax)
+  }
+}
+
+/// Parses one operand of `instruction` from `text`.
+struct OperandParser<'a> {
+  instruction: &'a InstructionSource,
+  text: &'a str,
+  symbols: &'a mut Symbols,
+  address: u64,
+}
+
+impl OperandParser<'_> {
+  fn register(&self, lexemes: &[Lexeme]) -> Result<ImplementationArg, String> {
+    let register = match lexemes {
+      [Lexeme {
+        token: Token::Name(name),
+        ..
+      }] => REGISTERS.get(name.as_str()),
+      _ => None,
+    };
+    match register {
+      Some(register) => {
+        Ok(ImplementationArg::Register((*register).try_into().unwrap()))
+      }
+      None => Err(format!(
+        "expected register, found {}",
+        quote(self.text, lexemes)
+      )),
+    }
+  }
+
+  fn value(&mut self, lexemes: &[Lexeme]) -> Result<Value, String> {
+    let value = evaluate(self.text, lexemes, self.symbols, self.address)?;
+    if let Some(target) = value.pcrel_target {
+      self.symbols.pcrel_hi.insert(self.address, Some(target));
+    }
+    if let Some(Reference {
+      function: Some("%pcrel_hi"),
+      ..
+    }) = value.reference
+    {
+      self.symbols.pcrel_hi.insert(self.address, None);
+    }
+    Ok(value)
+  }
+
+  /// The value, or 0 if it depends on an address that isn't known yet, in
+  /// which case it's left to the linker. `relocations` are the relocation
+  /// functions which can be used for this operand, and what the linker does
+  /// for each of them.
+  fn relocate(
+    &mut self,
+    value: Value,
+    lexemes: &[Lexeme],
+    expected: &str,
+    relocations: &[(&str, RelocationKind)],
+  ) -> Result<i64, String> {
+    let reference = match value.reference {
+      Some(reference) => reference,
+      None => return Ok(value.value),
+    };
+    let function = match reference.function {
+      Some(function) => function,
+      None => {
+        return Err(format!(
+          "expected {}, found {}, which is an address that isn't known until \
+           the program is linked",
+          expected,
+          quote(self.text, lexemes)
+        ))
+      }
+    };
+    match relocations.iter().find(|(f, _)| *f == function) {
+      Some((_, kind)) => {
+        self.symbols.relocations.push(Relocation {
+          offset: self.address,
+          kind: *kind,
+          symbol: reference.symbol,
+          addend: reference.addend,
+        });
+        Ok(0)
+      }
+      None => Err(format!(
+        "`{}` can't be used in `{}`",
+        function, self.instruction.mnemonic
+      )),
+    }
+  }
+
+  /// The error for a value which is out of range, showing what it came to if
+  /// it wasn't written as a number.
+  fn range_error(
+    &self,
+    expected: &str,
+    lexemes: &[Lexeme],
+    value: i64,
+  ) -> String {
+    let found = quote(self.text, lexemes);
+    if found == format!("`{}`", value) {
+      format!("expected {}, found {}", expected, found)
+    } else {
+      format!("expected {}, found {} ({})", expected, found, value)
+    }
+  }
+
+  fn immediate<const ARRLEN: usize>(
+    &mut self,
+    lexemes: &[Lexeme],
+    relocations: &[(&str, RelocationKind)],
+  ) -> Result<[bool; ARRLEN], String> {
+    let expected = format!("{} bit immediate", ARRLEN);
+    let value = self.value(lexemes)?;
+    let value = self.relocate(value, lexemes, &expected, relocations)?;
+    imm_bits(value).ok_or_else(|| self.range_error(&expected, lexemes, value))
+  }
+
+  /// A branch or jump target, which is an offset from this instruction, or
+  /// an address if it refers to a label.
+  fn target(
+    &mut self,
+    lexemes: &[Lexeme],
+  ) -> Result<ImplementationArg, String> {
+    let value = self.value(lexemes)?;
+    let format = encoding(self.instruction.mnemonic).map(|e| e.format);
+    let offset = match value.reference {
+      // A label in another file, which the linker will fill in
+      Some(reference) if !value.uses_label => {
+        if reference.function.is_some() {
+          return Err(format!(
+            "expected branch target, found {}",
+            quote(self.text, lexemes)
+          ));
         }
-        arguments.push(ImplementationArg::Imm20(val.unwrap()));
-      } else if expected.eq(&"shamt") {
-        arguments.push(ImplementationArg::Shamt(
-          parse_int::parse::<u64>(actual).ok().unwrap(),
-        ));
-      } else if actual == expected {
-        // If it matches, we're good
-      } else {
-        log!("Nothing matched");
-        self.format_error(tokens);
-        return None;
+        let kind = match format {
+          Some(Format::B) => RelocationKind::Branch,
+          _ => RelocationKind::Jal,
+        };
+        self.symbols.relocations.push(Relocation {
+          offset: self.address,
+          kind,
+          symbol: reference.symbol,
+          addend: reference.addend,
+        });
+        0
       }
+      _ if value.uses_label => value.value.wrapping_sub(self.address as i64),
+      _ => value.value,
+    };
+    let (range, limit) = match format {
+      Some(Format::B) => ("4 KiB", 1 << 12),
+      _ => ("1 MiB", 1 << 20),
+    };
+    if offset < -limit || offset >= limit {
+      let expected = format!("target within {} of the instruction", range);
+      return Err(self.range_error(&expected, lexemes, offset));
     }
-    Some(arguments)
+    if offset % 2 != 0 {
+      return Err(self.range_error("even offset", lexemes, offset));
+    }
+    Ok(match format {
+      Some(Format::B) => ImplementationArg::Imm20(imm_bits(offset).unwrap()),
+      _ => ImplementationArg::Imm21(imm_bits(offset).unwrap()),
+    })
+  }
+
+  fn unsigned(
+    &mut self,
+    lexemes: &[Lexeme],
+    expected: &str,
+    limit: u64,
+  ) -> Result<u64, String> {
+    let value = self.value(lexemes)?;
+    let value = self.relocate(value, lexemes, expected, &[])?;
+    match u64::try_from(value).ok().filter(|v| *v < limit) {
+      Some(value) => Ok(value),
+      None => Err(self.range_error(expected, lexemes, value)),
+    }
+  }
+
+  fn csr(&mut self, lexemes: &[Lexeme]) -> Result<u16, String> {
+    if let [Lexeme {
+      token: Token::Name(name),
+      ..
+    }] = lexemes
+    {
+      if let Some(address) = csr_address(name) {
+        return Ok(address);
+      }
+    }
+    let found = quote(self.text, lexemes);
+    match self.value(lexemes) {
+      Ok(value)
+        if value.reference.is_none() && (0..0x1000).contains(&value.value) =>
+      {
+        Ok(value.value as u16)
+      }
+      _ => Err(format!("expected CSR, found {}", found)),
+    }
+  }
+
+  /// `offset(rs1)`, where the offset is optional.
+  fn memory(
+    &mut self,
+    lexemes: &[Lexeme],
+  ) -> Result<(ImplementationArg, ImplementationArg), String> {
+    let mut depth = 0;
+    let open = lexemes.iter().rposition(|lexeme| {
+      match lexeme.token {
+        Token::Punctuation(")") => depth += 1,
+        Token::Punctuation("(") => depth -= 1,
+        _ => (),
+      }
+      depth == 0
+    });
+    let closed = matches!(
+      lexemes.last(),
+      Some(Lexeme {
+        token: Token::Punctuation(")"),
+        ..
+      })
+    );
+    let open = match open {
+      Some(open) if closed => open,
+      _ => {
+        return Err(format!(
+          "expected offset(register), found {}",
+          quote(self.text, lexemes)
+        ))
+      }
+    };
+    let base = self.register(&lexemes[open + 1..lexemes.len() - 1])?;
+    let offset = match open {
+      0 => [false; 12],
+      _ => {
+        let kind = match encoding(self.instruction.mnemonic) {
+          Some(Encoding {
+            format: Format::S, ..
+          }) => RelocationKind::Lo12S,
+          _ => RelocationKind::Lo12I,
+        };
+        self.immediate(&lexemes[..open], &[("%lo", kind)])?
+      }
+    };
+    Ok((ImplementationArg::Imm12(offset), base))
+  }
+
+  fn operand(
+    &mut self,
+    kind: &str,
+    lexemes: &[Lexeme],
+  ) -> Result<Option<ImplementationArg>, String> {
+    let argument = match kind {
+      "rd" | "rs1" | "rs2" => self.register(lexemes)?,
+      "imm" => {
+        let relocations = [("%lo", RelocationKind::Lo12I)];
+        ImplementationArg::Imm12(self.immediate(lexemes, &relocations)?)
+      }
+      "imm20" => {
+        // auipc's %pcrel_hi can only be resolved with the jalr after it
+        let relocation = match self.instruction.mnemonic {
+          "auipc" => ("%pcrel_hi", RelocationKind::Call),
+          _ => ("%hi", RelocationKind::Hi20),
+        };
+        ImplementationArg::Imm20(self.immediate(lexemes, &[relocation])?)
+      }
+      "offset" => self.target(lexemes)?,
+      "shamt" => {
+        let bits = match encoding(self.instruction.mnemonic) {
+          Some(Encoding {
+            format: Format::ShiftW,
+            ..
+          }) => 5,
+          _ => 6,
+        };
+        let expected = format!("shift amount below {}", 1 << bits);
+        ImplementationArg::Shamt(self.unsigned(
+          lexemes,
+          &expected,
+          1 << bits,
+        )?)
+      }
+      "uimm" => {
+        let expected = "5 bit unsigned immediate";
+        ImplementationArg::Uimm(self.unsigned(lexemes, expected, 32)?)
+      }
+      "csr" => ImplementationArg::Csr(self.csr(lexemes)?),
+      _ => {
+        // Operands which have to be written exactly as they are in the syntax
+        let found = quote(self.text, lexemes);
+        if found != format!("`{}`", kind) {
+          return Err(format!("expected `{}`, found {}", kind, found));
+        }
+        return Ok(None);
+      }
+    };
+    Ok(Some(argument))
   }
 }
 
+/// How an instruction moves between functions, using the standard calling
+/// convention idioms.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CallKind {
+  Call,   // jal ra, offset / jalr ra, offset(rs1)
+  Return, // jalr x0, 0(ra)
+}
+
+/// The instructions which can change the pc, other than by trapping.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum BranchKind {
+  Conditional,  // beq, bne, blt, bge, bltu, bgeu
+  Jump,         // jal, where the target is known when it's decoded
+  IndirectJump, // jalr, where the target comes from a register
+}
+
+/// What a branch or jump did when it executed.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BranchOutcome {
+  pub pc: u64,
+  pub kind: BranchKind,
+  pub taken: bool,
+  pub target: u64, // Where it goes when it's taken
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum AccessKind {
+  Load,
+  Store,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct MemoryAccess {
+  pub kind: AccessKind,
+  pub address: u64,
+  pub size: u32, // In bytes
+}
+
 #[allow(dead_code)] // TODO - connect source and line_num to front end.
 pub struct Instruction {
   pub source: &'static InstructionSource,
   pub line_num: u32, // 1 indexed
+  // Where it came from, innermost first, so a line in a macro is followed
+  // by the line which used the macro. The last is line_num.
+  pub locations: Vec<Location>,
   pub breakpoint: bool,
-  pub implementation: MachineInstruction,
+  pub args: Vec<ImplementationArg>,
+  pub op: Op, // The args, decoded ready to run
+}
+
+impl Instruction {
+  /// The register number of the named operand, e.g. "rd"
+  pub fn register_arg(&self, name: &str) -> Option<usize> {
+    core::iter::zip(self.source.arg_names(), self.args.iter()).find_map(
+      |(arg_name, arg)| match arg {
+        ImplementationArg::Register(r) if arg_name == name => Some(*r),
+        _ => None,
+      },
+    )
+  }
+
+  /// The sign extended value of the named immediate operand, e.g. "offset"
+  pub fn imm_arg(&self, name: &str) -> Option<Register> {
+    core::iter::zip(self.source.arg_names(), self.args.iter()).find_map(
+      |(arg_name, arg)| match arg {
+        ImplementationArg::Imm12(imm) if arg_name == name => Some(sext(*imm)),
+        ImplementationArg::Imm20(imm) if arg_name == name => Some(sext(*imm)),
+        ImplementationArg::Imm21(imm) if arg_name == name => Some(sext(*imm)),
+        ImplementationArg::Shamt(shamt) if arg_name == name => {
+          Some(Register { value: *shamt })
+        }
+        _ => None,
+      },
+    )
+  }
+
+  /// The memory this instruction will access if it's executed with the given
+  /// register values.
+  pub fn memory_access(
+    &self,
+    registers: &[Register; 32],
+  ) -> Option<MemoryAccess> {
+    let (kind, size, rs1, offset) = match self.op {
+      Op::Lb { offset, rs1, .. } | Op::Lbu { offset, rs1, .. } => {
+        (AccessKind::Load, 1, rs1, offset)
+      }
+      Op::Lh { offset, rs1, .. } | Op::Lhu { offset, rs1, .. } => {
+        (AccessKind::Load, 2, rs1, offset)
+      }
+      Op::Lw { offset, rs1, .. } | Op::Lwu { offset, rs1, .. } => {
+        (AccessKind::Load, 4, rs1, offset)
+      }
+      Op::Ld { offset, rs1, .. } => (AccessKind::Load, 8, rs1, offset),
+      Op::Sb { offset, rs1, .. } => (AccessKind::Store, 1, rs1, offset),
+      Op::Sh { offset, rs1, .. } => (AccessKind::Store, 2, rs1, offset),
+      Op::Sw { offset, rs1, .. } => (AccessKind::Store, 4, rs1, offset),
+      Op::Sd { offset, rs1, .. } => (AccessKind::Store, 8, rs1, offset),
+      _ => return None,
+    };
+    let address = registers[rs1 as usize] + sext(offset);
+    Some(MemoryAccess {
+      kind,
+      address: address.value,
+      size,
+    })
+  }
+
+  pub fn branch_kind(&self) -> Option<BranchKind> {
+    match self.op {
+      Op::Beq { .. }
+      | Op::Bne { .. }
+      | Op::Blt { .. }
+      | Op::Bge { .. }
+      | Op::Bltu { .. }
+      | Op::Bgeu { .. } => Some(BranchKind::Conditional),
+      Op::Jal { .. } => Some(BranchKind::Jump),
+      Op::Jalr { .. } => Some(BranchKind::IndirectJump),
+      _ => None,
+    }
+  }
+
+  pub fn call_kind(&self) -> Option<CallKind> {
+    match self.op {
+      Op::Jal { rd: 1, .. } | Op::Jalr { rd: 1, .. } => Some(CallKind::Call),
+      Op::Jalr {
+        rd: 0,
+        rs1: 1,
+        offset: Immediate(Register { value: 0 }),
+      } => Some(CallKind::Return),
+      _ => None,
+    }
+  }
 }
 
 impl fmt::Debug for Instruction {
@@ -328,16 +820,16 @@ impl fmt::Debug for Instruction {
   }
 }
 
-fn parse_imm<const ARRLEN: usize>(input: String) -> Option<[bool; ARRLEN]> {
+/// The bits of `val` as an immediate, if it fits in ARRLEN bits either signed
+/// or unsigned.
+pub fn imm_bits<const ARRLEN: usize>(val: i64) -> Option<[bool; ARRLEN]> {
   let mut bitvec: [bool; ARRLEN] = [false; ARRLEN];
-  let val: i32 = parse_int::parse::<i32>(&input).ok()?;
-  let upper_bound: i32 = 1 << ARRLEN;
-  let lower_bound: i32 = -(1 << (ARRLEN - 1));
+  let upper_bound: i64 = 1 << ARRLEN;
+  let lower_bound: i64 = -(1 << (ARRLEN - 1));
   if val < lower_bound || val >= upper_bound {
     log!(
-      "parse_imm<{}>({}): Require {} < {} <= {}",
+      "imm_bits<{}>: Require {} <= {} < {}",
       ARRLEN,
-      input,
       lower_bound,
       val,
       upper_bound
@@ -354,6 +846,11 @@ fn parse_imm<const ARRLEN: usize>(input: String) -> Option<[bool; ARRLEN]> {
 mod tests {
   use super::*;
 
+  fn parse_imm<const ARRLEN: usize>(input: String) -> Option<[bool; ARRLEN]> {
+    let value = evaluate_str(&input, &Symbols::default()).ok()?;
+    imm_bits(value)
+  }
+
   /*
    * Note: the imm arrays are LSB first, MSB last (i.e. the opposite of the
    * way that we write a binary number)
@@ -1,4 +1,8 @@
+use crate::cache::{CacheConfig, Caches, Replacement, WritePolicy};
 use crate::interpreter::*;
+use crate::pipeline::{Pipeline, PipelineConfig, Prediction};
+use crate::predictor::{BranchPredictor, PredictorConfig, PredictorKind};
+use crate::trace::{SharedBuffer, Trace, TraceFormat};
 use crate::utils;
 use std::fmt::Write;
 use std::sync::{Arc, Mutex};
@@ -20,11 +24,15 @@ macro_rules! log_inner {
     }
 }
 
+// Natively the log would drown out the program's own output, so even with the
+// `log` feature it's only written when RISCV_INTERPRETER_LOG is set.
 #[cfg(not(target_family = "wasm"))]
 #[macro_export]
 macro_rules! log_inner {
     ( $( $t:tt )* ) => {
-      println!($( $t )* );
+      if std::env::var_os("RISCV_INTERPRETER_LOG").is_some() {
+        eprintln!($( $t )* );
+      }
     }
 }
 
@@ -40,10 +48,14 @@ macro_rules! function {
   }};
 }
 
+// Logging happens on every step, which is far slower than the step itself,
+// so it's only compiled in with the `log` feature.
 #[macro_export]
 macro_rules! log {
     ($($tts:tt)*) => {
-      $crate::log_inner!("{}:{} - {}", $crate::function!(), std::line!(), format!($($tts)*));
+      if cfg!(feature = "log") {
+        $crate::log_inner!("{}:{} - {}", $crate::function!(), std::line!(), format!($($tts)*));
+      }
     }
 }
 
@@ -68,11 +80,37 @@ fn get_initial_registers() -> Vec<String> {
   values
 }
 
+// Browsers don't run intervals much more often than this, so faster
+// frequencies run a batch of instructions each tick instead.
+const TICK_MS: u64 = 10;
+// Instructions to run each tick when the speed is unrestricted. That's a few
+// ms of work, so the page still responds between ticks.
+const UNRESTRICTED_BATCH: u64 = 100_000;
+
+/// How often to tick in ms, and how many instructions to run each time, to
+/// run at `frequency` instructions per second.
+fn run_schedule(frequency: Option<u32>) -> (i32, u64) {
+  match frequency.map(u64::from) {
+    None => (TICK_MS as i32, UNRESTRICTED_BATCH),
+    Some(freq) if freq * TICK_MS >= 1000 => {
+      (TICK_MS as i32, freq * TICK_MS / 1000)
+    }
+    Some(freq) => ((1000 / freq.max(1)) as i32, 1),
+  }
+}
+
+/// A number entered in one of the page's settings.
+fn parse_setting(name: &str, value: &str) -> Result<u64, String> {
+  parse_int::parse::<u64>(value.trim())
+    .map_err(|_| format!("Invalid {} \"{}\"", name, value))
+}
+
 #[wasm_bindgen]
 pub struct WebInterface {
   rci: Arc<Mutex<Interpreter>>,
   code_changed: bool,
   step_func_token: Option<i32>,
+  trace: Option<SharedBuffer>,
 }
 
 impl Default for WebInterface {
@@ -83,6 +121,8 @@ impl Default for WebInterface {
 
 #[wasm_bindgen]
 impl WebInterface {
+  // The interpreter is only ever touched from the single JS thread
+  #[allow(clippy::arc_with_non_send_sync)]
   pub fn new() -> WebInterface {
     utils::set_panic_hook();
     let interpreter = Interpreter::create_RiscV64_i(get_initial_registers());
@@ -91,6 +131,7 @@ impl WebInterface {
       rci: Arc::new(Mutex::new(interpreter)),
       code_changed: false,
       step_func_token: None,
+      trace: None,
     }
   }
 
@@ -134,11 +175,14 @@ impl WebInterface {
       .value();
     let interpreter = self.rci.lock().unwrap();
     log!("memory_address: \"{}\"\n", memory_address_str);
-    let memory_address: u32 =
-      parse_int::parse::<u32>(&memory_address_str).ok()?;
+    let memory_address: u64 =
+      parse_int::parse::<u64>(&memory_address_str).ok()?;
+    if memory_address >= interpreter.memory_size() {
+      return None;
+    }
 
-    const BYTES_PER_ROW: u32 = 16; // Must be a power of 2
-    const NUM_ROWS: u32 = 10;
+    const BYTES_PER_ROW: u64 = 16; // Must be a power of 2
+    const NUM_ROWS: u64 = 10;
     let mut start = memory_address & (!(BYTES_PER_ROW - 1));
     if start > ((NUM_ROWS / 2) * BYTES_PER_ROW) {
       start -= (NUM_ROWS / 2) * BYTES_PER_ROW;
@@ -158,12 +202,12 @@ impl WebInterface {
         "<td>0x{:08x}</td><td>{}</td>",
         row_start,
         interpreter
-          .memory_byte_repr(row_start as usize, BYTES_PER_ROW as usize)
+          .memory_byte_repr(row_start, BYTES_PER_ROW as usize)
           .join("</td><td>")
       )
       .ok()?;
-      let build_string_vec: Vec<String> = interpreter
-        .memory_ascii_repr(row_start as usize, BYTES_PER_ROW as usize);
+      let build_string_vec: Vec<String> =
+        interpreter.memory_ascii_repr(row_start, BYTES_PER_ROW as usize);
       write!(
         memory_table,
         "<td>{}</td></tr>",
@@ -188,24 +232,306 @@ impl WebInterface {
     match self.try_update_memory(&document, &memory_element) {
       Some(()) => (),
       None => {
+        let memory_size = self.rci.lock().unwrap().memory_size();
         memory_element.set_inner_html(
-          "<tr class=\"danger\">
+          format!(
+            "<tr class=\"danger\">
 <td>Invalid memory location or error printing memory.
-Memory addresses must be from 0x00000000 - 0x7fffffff</td></tr>",
+Memory addresses must be from 0x00000000 - 0x{:08x}</td></tr>",
+            memory_size - 1
+          )
+          .as_str(),
         );
       }
     }
   }
 
+  fn update_memory_regions(&self) {
+    let regions = self.rci.lock().unwrap().memory_regions();
+    let mut html = String::new();
+    for region in regions {
+      write!(
+        html,
+        "<tr><td>{}</td><td>0x{:08x}</td><td>0x{:08x}</td><td>{}</td></tr>",
+        region.name,
+        region.start,
+        region.start + region.size - 1,
+        region.permissions
+      )
+      .ok();
+    }
+    self.set_inner_html("memory-regions", &html);
+  }
+
+  fn update_devices(&self) {
+    let interpreter = self.rci.lock().unwrap();
+    let document = web_sys::window().unwrap().document().unwrap();
+    // The program's output is text, not HTML
+    document
+      .get_element_by_id("console-output")
+      .unwrap()
+      .set_text_content(Some(&interpreter.console_output()));
+    for (i, on) in interpreter.leds().iter().enumerate() {
+      let led = document
+        .get_element_by_id(&format!("led_{}", i))
+        .unwrap()
+        .dyn_into::<web_sys::HtmlElement>()
+        .unwrap();
+      if *on {
+        self.add_class_if_missing(&led, "led-on");
+      } else {
+        self.remove_class_if_present(&led, "led-on");
+      }
+    }
+  }
+
+  fn update_interrupts(&self) {
+    let interpreter = self.rci.lock().unwrap();
+    let mut html = String::new();
+    for (name, enabled, pending) in interpreter.interrupts() {
+      write!(
+        html,
+        "<tr{}><td>{}</td><td>{}</td><td>{}</td></tr>",
+        if enabled && pending {
+          " class=\"warning\""
+        } else {
+          ""
+        },
+        name,
+        if enabled { "Yes" } else { "No" },
+        if pending { "Yes" } else { "No" }
+      )
+      .ok();
+    }
+    self.set_inner_html("interrupts", &html);
+    let (tlb_entries, tlb_hits, tlb_misses) = interpreter.tlb_stats();
+    let machine_status = format!(
+      "Running in {} mode. The TLB holds {} translations, and has had {} \
+       hits and {} misses.",
+      interpreter.privilege(),
+      tlb_entries,
+      tlb_hits,
+      tlb_misses
+    );
+    self.set_inner_html("machine-status", &machine_status);
+    let status = format!(
+      "Interrupts are {} in {} mode{}",
+      if interpreter.interrupts_enabled() {
+        "enabled"
+      } else {
+        "disabled"
+      },
+      interpreter.privilege(),
+      if interpreter.waiting_for_interrupt() {
+        ", and the processor is waiting for an interrupt (wfi)"
+      } else {
+        ""
+      }
+    );
+    self.set_inner_html("interrupt-status", &status);
+  }
+
+  fn update_caches(&self) {
+    let interpreter = self.rci.lock().unwrap();
+    let caches = match interpreter.caches() {
+      Some(caches) => caches,
+      None => {
+        self.set_id_visibility("caches-container", false);
+        return;
+      }
+    };
+    self.set_id_visibility("caches-container", true);
+    let mut html = String::new();
+    for cache in caches.all() {
+      write!(
+        html,
+        "<div class=\"col-md-6\"><h4>{}</h4><p>{}, {} writebacks, {} \
+         writes through to memory</p>\
+         <table class=\"table table-condensed\"><thead><tr><th>Set</th>\
+         <th>Way</th><th>Valid</th><th>Dirty</th><th>Tag</th>\
+         <th>Address</th><th>Hits</th><th>Misses</th></tr></thead><tbody>",
+        cache.name,
+        cache.stats,
+        cache.stats.writebacks,
+        cache.stats.write_throughs
+      )
+      .ok();
+      for (i, (set, way, line)) in cache.lines().enumerate() {
+        write!(
+          html,
+          "<tr{}><td>{}</td><td>{}</td><td>{}</td><td>{}</td>",
+          if cache.last_access == Some(i) {
+            " class=\"info\""
+          } else {
+            ""
+          },
+          set,
+          way,
+          if line.valid { "Yes" } else { "No" },
+          if line.dirty { "Yes" } else { "No" }
+        )
+        .ok();
+        if line.valid {
+          write!(
+            html,
+            "<td>0x{:x}</td><td>0x{:08x}</td>",
+            line.tag,
+            cache.line_address(set, line)
+          )
+          .ok();
+        } else {
+          html.push_str("<td></td><td></td>");
+        }
+        write!(html, "<td>{}</td><td>{}</td></tr>", line.hits, line.misses)
+          .ok();
+      }
+      html.push_str("</tbody></table></div>");
+    }
+    self.set_inner_html("caches", &html);
+
+    let mut html = String::new();
+    for (line_num, stats) in &caches.by_line {
+      write!(
+        html,
+        "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+        line_num,
+        stats.fetch.hits,
+        stats.fetch.misses,
+        stats.data.hits,
+        stats.data.misses
+      )
+      .ok();
+    }
+    self.set_inner_html("cache-lines", &html);
+  }
+
+  fn update_pipeline(&self) {
+    let interpreter = self.rci.lock().unwrap();
+    let pipeline = match interpreter.pipeline() {
+      Some(pipeline) => pipeline,
+      None => {
+        self.set_id_visibility("pipeline-container", false);
+        return;
+      }
+    };
+    self.set_id_visibility("pipeline-container", true);
+    self.set_inner_html("pipeline-stats", &pipeline.stats.to_string());
+
+    let cycles = pipeline.diagram_cycles();
+    let mut html = String::from("<tr><th>Line</th><th>Instruction</th>");
+    for cycle in cycles.clone() {
+      write!(html, "<th>{}</th>", cycle).ok();
+    }
+    html.push_str("<th>Notes</th></tr>");
+    self.set_inner_html("pipeline-cycles", &html);
+
+    let mut html = String::new();
+    for row in pipeline.rows() {
+      write!(html, "<tr><td>{}</td><td>{}</td>", row.line_num, row.text).ok();
+      for cycle in cycles.clone() {
+        write!(html, "<td>{}</td>", row.cell(cycle)).ok();
+      }
+      write!(html, "<td>{}</td></tr>", row.note).ok();
+    }
+    self.set_inner_html("pipeline-diagram", &html);
+  }
+
+  fn update_branch_predictor(&self) {
+    let interpreter = self.rci.lock().unwrap();
+    let predictor = match interpreter.branch_predictor() {
+      Some(predictor) => predictor,
+      None => {
+        self.set_id_visibility("predictor-container", false);
+        return;
+      }
+    };
+    self.set_id_visibility("predictor-container", true);
+    self.set_inner_html(
+      "predictor-stats",
+      &format!(
+        "Conditional branches: {}<br>jal: {}<br>jalr: {}",
+        predictor.conditional, predictor.jumps, predictor.indirect
+      ),
+    );
+    let mut html = String::new();
+    for (line_num, stats) in &predictor.by_line {
+      write!(
+        html,
+        "<tr{}><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{:.1}%</td>\
+         </tr>",
+        if stats.mispredicted() > 0 {
+          " class=\"warning\""
+        } else {
+          ""
+        },
+        line_num,
+        stats.executed,
+        stats.taken,
+        stats.mispredicted(),
+        stats.accuracy()
+      )
+      .ok();
+    }
+    self.set_inner_html("predictor-lines", &html);
+  }
+
+  fn update_profile(&self) {
+    let report = self.rci.lock().unwrap().profile_report();
+    web_sys::window()
+      .unwrap()
+      .document()
+      .unwrap()
+      .get_element_by_id("profile-report")
+      .unwrap()
+      .set_text_content(Some(&report));
+  }
+
+  fn update_listing(&self) {
+    let listing = self.rci.lock().unwrap().listing();
+    web_sys::window()
+      .unwrap()
+      .document()
+      .unwrap()
+      .get_element_by_id("listing")
+      .unwrap()
+      .set_text_content(Some(&listing));
+  }
+
+  fn update_call_stack(&self) {
+    let call_stack = self.rci.lock().unwrap().call_stack();
+    let mut html = String::new();
+    for (depth, frame) in call_stack.iter().rev().enumerate() {
+      write!(
+        html,
+        "<tr><td>#{}</td><td>{}</td><td>{}</td><td>0x{:016x}</td></tr>",
+        depth, frame.callee, frame.caller_line, frame.sp
+      )
+      .ok();
+    }
+    self.set_inner_html("call-stack", &html);
+    self.set_id_visibility("call-stack-container", !call_stack.is_empty());
+  }
+
   pub fn update_ui(&self) {
     self.update_registers();
     self.update_memory();
+    self.update_memory_regions();
+    self.update_devices();
+    self.update_interrupts();
+    self.update_caches();
+    self.update_pipeline();
+    self.update_branch_predictor();
+    self.update_profile();
+    self.update_listing();
+    self.update_call_stack();
 
     {
       let interpreter = self.rci.lock().unwrap();
       let running = interpreter.running();
       self.set_parent_visibility("reset", true);
       self.set_parent_visibility("step", !running);
+      self.set_parent_visibility("step-over", !running);
+      self.set_parent_visibility("step-out", !running);
       self.set_parent_visibility("run", !running);
       self.set_parent_visibility("stop", running);
 
@@ -225,15 +551,13 @@ Memory addresses must be from 0x00000000 - 0x7fffffff</td></tr>",
       self.code_changed = false;
     }
     self.rci.lock().unwrap().set_running(true);
+    let (interval, batch) =
+      run_schedule(self.rci.lock().unwrap().get_frequency());
     let interpreter = self.rci.clone();
     let step_func: Closure<dyn FnMut()> = Closure::new(move || {
-      interpreter.lock().unwrap().step();
+      interpreter.lock().unwrap().run_for(batch);
     });
     let window = web_sys::window().expect("global window does not exists");
-    let interval: i32 = match self.rci.lock().unwrap().get_frequency() {
-      Some(freq) => (1000 / freq) as i32,
-      None => 1000,
-    };
     let token = window
       .set_interval_with_callback_and_timeout_and_arguments_0(
         step_func.as_ref().unchecked_ref(),
@@ -257,6 +581,28 @@ Memory addresses must be from 0x00000000 - 0x7fffffff</td></tr>",
     self.update_ui();
   }
 
+  pub fn step_over_button(&mut self) {
+    if self.code_changed {
+      self.update_code();
+      self.code_changed = false;
+    }
+    self.rci.lock().unwrap().set_running(true);
+    self.rci.lock().unwrap().step_over();
+    self.rci.lock().unwrap().set_running(false);
+    self.update_ui();
+  }
+
+  pub fn step_out_button(&mut self) {
+    if self.code_changed {
+      self.update_code();
+      self.code_changed = false;
+    }
+    self.rci.lock().unwrap().set_running(true);
+    self.rci.lock().unwrap().step_out();
+    self.rci.lock().unwrap().set_running(false);
+    self.update_ui();
+  }
+
   pub fn reset_button(&mut self) {
     panic!("Not implemented yet")
     /*
@@ -301,6 +647,149 @@ Memory addresses must be from 0x00000000 - 0x7fffffff</td></tr>",
     }
   }
 
+  /// Returns an error for the page to show if the size isn't a number.
+  pub fn set_memory_model_button(
+    &mut self,
+    sparse: bool,
+    size: &str,
+  ) -> Result<(), String> {
+    let size = parse_setting("memory size", size)?;
+    self.rci.lock().unwrap().set_memory_model(sparse, size);
+    self.update_ui();
+    Ok(())
+  }
+
+  /// Returns an error for the page to show if the configuration isn't valid.
+  #[allow(clippy::too_many_arguments)]
+  pub fn set_caches_button(
+    &mut self,
+    enabled: bool,
+    size: &str,
+    associativity: &str,
+    block_size: &str,
+    replacement: &str,
+    write_policy: &str,
+    split: bool,
+  ) -> Result<(), String> {
+    let config = CacheConfig {
+      size: parse_setting("cache size", size)?,
+      associativity: parse_setting("associativity", associativity)?,
+      block_size: parse_setting("block size", block_size)?,
+      replacement: Replacement::parse(replacement)
+        .ok_or(format!("Unknown replacement policy \"{}\"", replacement))?,
+      write_policy: WritePolicy::parse(write_policy)
+        .ok_or(format!("Unknown write policy \"{}\"", write_policy))?,
+    };
+    let caches = if enabled {
+      config.validate()?;
+      Some(Caches::new(config, split))
+    } else {
+      None
+    };
+    self.rci.lock().unwrap().set_caches(caches);
+    self.update_ui();
+    Ok(())
+  }
+
+  pub fn set_pipeline_button(
+    &mut self,
+    enabled: bool,
+    forwarding: bool,
+    prediction: &str,
+  ) {
+    let config = PipelineConfig {
+      forwarding,
+      prediction: Prediction::parse(prediction)
+        .expect("Valid branch prediction"),
+    };
+    self.rci.lock().unwrap().set_pipeline(if enabled {
+      Some(Pipeline::new(config))
+    } else {
+      None
+    });
+    self.update_ui();
+  }
+
+  /// Returns an error for the page to show if the configuration isn't valid.
+  pub fn set_branch_predictor_button(
+    &mut self,
+    enabled: bool,
+    kind: &str,
+    table_bits: u32,
+    history_bits: u32,
+    btb_entries: usize,
+  ) -> Result<(), String> {
+    let config = PredictorConfig {
+      kind: PredictorKind::parse(kind)
+        .ok_or(format!("Unknown branch predictor \"{}\"", kind))?,
+      table_bits,
+      history_bits,
+      btb_entries,
+    };
+    let predictor = if enabled {
+      config.validate()?;
+      Some(BranchPredictor::new(config))
+    } else {
+      None
+    };
+    self.rci.lock().unwrap().set_branch_predictor(predictor);
+    self.update_ui();
+    Ok(())
+  }
+
+  /// Starts a new trace, throwing away the old one, or stops tracing.
+  pub fn set_trace_button(&mut self, enabled: bool, format: &str) {
+    let format = TraceFormat::parse(format).expect("Valid trace format");
+    self.trace = if enabled {
+      Some(SharedBuffer::default())
+    } else {
+      None
+    };
+    self.rci.lock().unwrap().set_trace(
+      self
+        .trace
+        .as_ref()
+        .map(|buffer| Trace::new(format, Box::new(buffer.clone()))),
+    );
+  }
+
+  pub fn trace_contents(&self) -> String {
+    self
+      .trace
+      .as_ref()
+      .map(|buffer| buffer.contents())
+      .unwrap_or_default()
+  }
+
+  pub fn set_virtual_memory_view_button(&mut self, virtual_addresses: bool) {
+    self
+      .rci
+      .lock()
+      .unwrap()
+      .set_virtual_memory_view(virtual_addresses);
+    self.update_memory();
+  }
+
+  pub fn set_preserve_state_button(&mut self, preserve: bool) {
+    self.rci.lock().unwrap().set_preserve_state(preserve);
+  }
+
+  pub fn set_check_calling_convention_button(&mut self, check: bool) {
+    self.rci.lock().unwrap().set_check_calling_convention(check);
+  }
+
+  pub fn set_check_uninitialised_button(&mut self, check: bool) {
+    self.rci.lock().unwrap().set_check_uninitialised(check);
+  }
+
+  pub fn console_input_button(&mut self, input: &str) {
+    self.rci.lock().unwrap().console_input(input.to_string());
+  }
+
+  pub fn set_switch_button(&mut self, switch: usize, on: bool) {
+    self.rci.lock().unwrap().set_switch(switch, on);
+  }
+
   fn set_parent_visibility(&self, id: &str, visible: bool) {
     //log!("set_parent_visibility({}, {})", id, visible);
     let window = web_sys::window().expect("global window does not exists");
@@ -379,13 +868,16 @@ Memory addresses must be from 0x00000000 - 0x7fffffff</td></tr>",
 
     let is_break: Vec<bool>;
     let next_inst_line_num: u32;
+    let counts: Vec<u64>;
 
     // Extra scope for interpreter lock
     {
       let interpreter = self.rci.lock().unwrap();
       is_break = interpreter.breakpoints();
       next_inst_line_num = interpreter.next_inst_line_num();
+      counts = interpreter.line_counts();
     }
+    let max_count = counts.iter().copied().max().unwrap_or(0);
 
     for line_num in 0..std::cmp::min(lines.length(), is_break.len() as u32) {
       let line = lines
@@ -401,6 +893,23 @@ Memory addresses must be from 0x00000000 - 0x7fffffff</td></tr>",
         self.remove_class_if_present(&line, "lineselect");
       }
 
+      // Shade the line by how often it's been executed
+      let count = counts.get(line_num as usize).copied().unwrap_or(0);
+      if count > 0 {
+        let heat = 0.1 + 0.6 * count as f64 / max_count as f64;
+        line
+          .style()
+          .set_property(
+            "background-color",
+            &format!("rgba(255, 80, 0, {:.2})", heat),
+          )
+          .ok();
+        line.set_title(&format!("Executed {} times", count));
+      } else {
+        line.style().remove_property("background-color").ok();
+        line.set_title("");
+      }
+
       // Add breakpoint symbol if required
       if is_break[line_num as usize] {
         line
@@ -1,25 +1,84 @@
-use crate::codegen::INSTRUCTIONS;
+use crate::cache::Caches;
+use crate::linker::Executable;
+use crate::memory_map::Region;
+use crate::pipeline::Pipeline;
+use crate::predictor::BranchPredictor;
+use crate::profile::Profile;
+use crate::trace::Trace;
+use crate::MemoryAccess;
 
 mod rv64_i;
 use rv64_i::RiscV64_i;
 
+/// One entry in the shadow call stack, pushed when a call is made and popped
+/// when the matching return executes.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CallFrame {
+  pub caller_line: u32, // 1 indexed
+  // The label at the call target, or its address if there isn't one
+  pub callee: String,
+  pub sp: u64, // Stack pointer on entry
+  pub return_address: u64,
+}
+
 pub trait InterpreterTrait {
-  fn memory_size(&self) -> u32;
+  fn memory_size(&self) -> u64;
+  fn set_memory_model(&mut self, sparse: bool, size: u64);
+  fn memory_regions(&self) -> Vec<Region>;
+  fn console_output(&self) -> String;
+  fn console_input(&mut self, input: String);
+  fn leds(&self) -> Vec<bool>;
+  fn switches(&self) -> Vec<bool>;
+  fn set_switch(&mut self, switch: usize, on: bool);
+  fn interrupts(&self) -> Vec<(String, bool, bool)>;
+  fn interrupts_enabled(&self) -> bool;
+  fn waiting_for_interrupt(&self) -> bool;
+  fn privilege(&self) -> String;
+  fn tlb_stats(&self) -> (usize, u64, u64);
+  fn set_caches(&mut self, caches: Option<Caches>);
+  fn caches(&self) -> Option<&Caches>;
+  fn set_pipeline(&mut self, pipeline: Option<Pipeline>);
+  fn pipeline(&self) -> Option<&Pipeline>;
+  fn profile(&self) -> &Profile;
+  fn line_counts(&self) -> Vec<u64>;
+  fn profile_report(&self) -> String;
+  fn listing(&self) -> String;
+  fn set_trace(&mut self, trace: Option<Trace>);
+  fn set_branch_predictor(&mut self, predictor: Option<BranchPredictor>);
+  fn branch_predictor(&self) -> Option<&BranchPredictor>;
   fn set_code(&mut self, code: String);
+  fn set_executable(&mut self, executable: &Executable);
+  fn add_file(&mut self, name: String, code: String);
+  fn set_preserve_state(&mut self, preserve: bool);
+  fn set_check_calling_convention(&mut self, check: bool);
+  fn set_check_uninitialised(&mut self, check: bool);
   fn running(&self) -> bool;
   fn set_running(&mut self, running: bool);
   fn errors(&self) -> &Vec<String>;
   fn warnings(&self) -> &Vec<String>;
   fn registers_repr(&self) -> Vec<(String, String, String)>;
-  fn memory_byte_repr(&self, start: usize, len: usize) -> Vec<String>;
-  fn memory_ascii_repr(&self, start: usize, len: usize) -> Vec<String>;
+  fn register(&self, register: usize) -> u64;
+  fn set_register(&mut self, register: usize, value: u64);
+  fn pc(&self) -> u64;
+  fn set_pc(&mut self, pc: u64);
+  fn last_access(&self) -> Option<MemoryAccess>;
+  fn debug_read(&self, start: u64, len: usize) -> Vec<Option<u8>>;
+  fn debug_write(&mut self, start: u64, data: &[u8]) -> bool;
+  fn set_virtual_memory_view(&mut self, virtual_addresses: bool);
+  fn memory_byte_repr(&self, start: u64, len: usize) -> Vec<String>;
+  fn memory_ascii_repr(&self, start: u64, len: usize) -> Vec<String>;
   fn toggle_breakpoint(&mut self, line_num: u32);
   fn breakpoints(&self) -> Vec<bool>;
+  fn at_breakpoint(&self) -> bool;
   fn set_frequency(&mut self, frequency: Option<u32>);
   fn get_frequency(&self) -> Option<u32>;
   fn next_inst_line_num(&self) -> u32;
   fn run(&mut self);
+  fn run_for(&mut self, instructions: u64) -> u64;
   fn step(&mut self);
+  fn step_over(&mut self);
+  fn step_out(&mut self);
+  fn call_stack(&self) -> Vec<CallFrame>;
   fn stop(&mut self);
 }
 
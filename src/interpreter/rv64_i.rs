@@ -1,95 +1,590 @@
+use super::CallFrame;
 use super::InterpreterTrait;
-use crate::interpreter::INSTRUCTIONS;
-use crate::rv64_i::MEMORY_SIZE;
+use crate::assembler::{assemble, Assembly};
+use crate::cache::Caches;
+use crate::calling_convention::CallingConventionChecker;
+use crate::codegen::{execute, Op};
+use crate::csr::{CsrFile, Privilege, Trap, INTERRUPTS, SATP};
+use crate::devices::{Clint, Gpio, Uart};
+use crate::diff;
+use crate::disassembler::{listing, ListingLine};
+use crate::encoding::{encode, instruction};
+use crate::linker::Executable;
+use crate::macros::{Files, Location};
+use crate::memory::{Memory, MemoryConfig, MemoryModel};
+use crate::memory_map::{AccessType, MemoryMap, Region};
+use crate::mmu::{virtual_to_physical, Mmu, Tlb};
+use crate::pipeline::{Control, Pipeline, Retired};
+use crate::predictor::BranchPredictor;
+use crate::profile::{InstructionClass, Profile};
+use crate::trace::{Trace, TraceRecord};
+use crate::uninitialised::UninitialisedChecker;
+use crate::AccessKind;
+use crate::BranchKind;
+use crate::BranchOutcome;
+use crate::CallKind;
 use crate::Instruction;
-use crate::InstructionSource;
+use crate::MemoryAccess;
 use crate::Register;
 use crate::PC;
-use std::convert::TryInto;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Range;
+use std::rc::Rc;
+
+// Step over/out run synchronously, so give up eventually in case the
+// function never returns.
+const MAX_STEPS_PER_COMMAND: u32 = 1_000_000;
 
 #[allow(non_camel_case_types)]
 pub struct RiscV64_i {
   code: String,
   instructions: Vec<Instruction>,
+  labels: HashMap<String, u64>,
+  files: Files, // Which the code can include
+  initial_registers: [Register; 32],
+  // Which registers had an initial value entered, rather than left blank
+  given_registers: [bool; 32],
   registers: [Register; 32],
-  memory: [u8; crate::rv64_i::MEMORY_SIZE],
+  memory_config: MemoryConfig,
+  memory: MemoryMap,
+  uart: Rc<RefCell<Uart>>,
+  clint: Rc<RefCell<Clint>>,
+  gpio: Rc<RefCell<Gpio>>,
   pc: PC,
+  csrs: CsrFile,
+  tlb: Tlb,
+  caches: Option<Caches>,
+  pipeline: Option<Pipeline>,
+  branch_predictor: Option<BranchPredictor>,
+  profile: Profile,
+  trace: Option<Trace>,
+  // The load or store made by the last instruction retired, for watchpoints
+  last_access: Option<MemoryAccess>,
+  call_stack: Vec<CallFrame>,
   errors: Vec<String>,
+  // False if the code failed to parse. Errors from running the program don't
+  // affect this, so it can be run again once they're fixed.
+  valid_program: bool,
   warnings: Vec<String>,
   // Some(0) means single step
   // None means "as fast as possible"
   frequency: Option<u32>,
   running: bool,
+  // Whether registers, memory and pc survive the code being edited
+  preserve_state: bool,
+  // Whether the memory view shows virtual addresses, translated with satp
+  virtual_memory_view: bool,
+  calling_convention_checker: Option<CallingConventionChecker>,
+  uninitialised_checker: Option<UninitialisedChecker>,
+}
+
+/// The lines which breakpoints can be set on, with the instruction each one
+/// stops at. An instruction from a macro can be stopped at from its line in
+/// the macro, which stops in every use of the macro, or from the line using
+/// the macro, which stops at the first instruction that the use expands to.
+fn breakpoint_lines(
+  instructions: &[Instruction],
+) -> impl Iterator<Item = (u32, usize)> + '_ {
+  instructions
+    .iter()
+    .enumerate()
+    .flat_map(move |(i, instruction)| {
+      let locations = &instruction.locations;
+      (0..locations.len()).filter_map(move |depth| {
+        let location: &Location = &locations[depth];
+        let expansion = &locations[depth..];
+        // Whether the previous instruction came from the same expansion
+        let continued = i.checked_sub(1).is_some_and(|previous| {
+          let previous = &instructions[previous].locations;
+          previous.ends_with(expansion)
+        });
+        match location.file {
+          None if !continued => Some((location.line, i)),
+          _ => None,
+        }
+      })
+    })
 }
 
 impl RiscV64_i {
+  fn set_breakpoint(&mut self, line_num: u32, set: bool) {
+    let stops: Vec<usize> = breakpoint_lines(&self.instructions)
+      .filter(|(line, _)| *line == line_num)
+      .map(|(_, i)| i)
+      .collect();
+    for i in stops {
+      self.instructions[i].breakpoint = set;
+      log!("{:?}", self.instructions[i]);
+    }
+  }
+
   pub fn create(initial_registers: Vec<String>) -> Self {
     let mut interpreter = RiscV64_i {
       code: "".to_string(),
       instructions: Vec::new(),
+      labels: HashMap::new(),
+      files: Files::new(),
+      initial_registers: [Register { value: 0 }; 32],
+      given_registers: [false; 32],
       registers: [Register { value: 0 }; 32],
-      memory: [0; MEMORY_SIZE],
+      memory_config: MemoryConfig::default(),
+      memory: MemoryMap::new(MemoryConfig::default()),
+      uart: Rc::new(RefCell::new(Uart::new())),
+      clint: Rc::new(RefCell::new(Clint::new())),
+      gpio: Rc::new(RefCell::new(Gpio::new())),
       pc: PC::new(),
+      csrs: CsrFile::new(),
+      tlb: Tlb::new(),
+      caches: None,
+      pipeline: None,
+      branch_predictor: None,
+      profile: Profile::default(),
+      trace: None,
+      last_access: None,
+      call_stack: Vec::new(),
       errors: Vec::new(),
+      valid_program: true,
       warnings: Vec::new(),
       frequency: Some(0),
       running: false,
+      preserve_state: false,
+      virtual_memory_view: false,
+      calling_convention_checker: None,
+      uninitialised_checker: None,
     };
 
     for (i, r) in initial_registers.iter().enumerate() {
-      interpreter.registers[i + 1/* Skip 0 register because it's fixed*/] =
-        Register {
-          value: parse_int::parse::<u64>(r).expect("Successful conversion"),
-        };
+      // Blank inputs start at zero but count as never written
+      if r.trim().is_empty() {
+        continue;
+      }
+      interpreter.initial_registers
+        [i + 1/* Skip 0 register because it's fixed*/] = Register {
+        value: parse_int::parse::<u64>(r.trim())
+          .expect("Successful conversion"),
+      };
+      interpreter.given_registers[i + 1] = true;
     }
+    interpreter.registers = interpreter.initial_registers;
+    interpreter.reset_memory();
 
     interpreter
   }
 
-  fn parse(&mut self) {
-    for (ln, line) in self.code.lines().enumerate() {
-      let line_num: u32 = (ln + 1).try_into().unwrap(); // Source is 1 indexed
-      let instruction: &str = line.split("//").next().unwrap().trim();
-      if instruction.is_empty() {
-        continue;
+  /// Puts the machine back into the state it was created in, without
+  /// touching the code or breakpoints.
+  fn reset_state(&mut self) {
+    self.registers = self.initial_registers;
+    self.reset_memory();
+    self.pc = PC::new();
+    self.csrs = CsrFile::new();
+    self.tlb = Tlb::new();
+    if let Some(caches) = &mut self.caches {
+      caches.reset();
+    }
+    if let Some(pipeline) = &mut self.pipeline {
+      pipeline.reset();
+    }
+    if let Some(predictor) = &mut self.branch_predictor {
+      predictor.reset();
+    }
+    self.profile = Profile::new(self.instructions.len());
+    self.last_access = None;
+    self.call_stack.clear();
+    if self.calling_convention_checker.is_some() {
+      self.calling_convention_checker = Some(CallingConventionChecker::new());
+    }
+    if self.uninitialised_checker.is_some() {
+      self.uninitialised_checker = Some(UninitialisedChecker::new(
+        &self.given_registers,
+        self.image(),
+      ));
+    }
+  }
+
+  /// Recreates memory and the devices mapped into it.
+  fn reset_memory(&mut self) {
+    self.uart.borrow_mut().reset();
+    self.clint = Rc::new(RefCell::new(Clint::new()));
+    self.gpio.borrow_mut().reset();
+    self.memory = MemoryMap::new(self.memory_config);
+    self.memory.add_device(Box::new(self.uart.clone()));
+    self.memory.add_device(Box::new(self.clint.clone()));
+    self.memory.add_device(Box::new(self.gpio.clone()));
+    self.load_image();
+  }
+
+  /// Where the program's machine code is in memory.
+  fn image(&self) -> Range<u64> {
+    0..self.instructions.len() as u64 * 4
+  }
+
+  /// Puts the program's machine code in memory, so that loading from it
+  /// gets what it would on hardware.
+  fn load_image(&mut self) {
+    let bytes: Vec<u8> = (self.instructions.iter())
+      .flat_map(|instruction| encode(instruction).unwrap_or(0).to_le_bytes())
+      .collect();
+    self.memory.load(0, &bytes);
+  }
+
+  /// Bytes for the memory view, which are None for unmapped virtual
+  /// addresses.
+  fn view_bytes(&self, start: u64, len: usize) -> Vec<Option<u8>> {
+    let satp = self.csrs.get(SATP).unwrap();
+    (0..len as u64)
+      .map(|i| {
+        let mut address = start.wrapping_add(i);
+        if self.virtual_memory_view {
+          address = virtual_to_physical(&self.memory, satp, address)?;
+        }
+        let mut byte = [0];
+        self.memory.peek(address, &mut byte);
+        Some(byte[0])
+      })
+      .collect()
+  }
+
+  /// Index of the first instruction on or after `index` whose line still
+  /// exists in the new code, mapped to its index in the new program.
+  fn map_instruction_index(
+    &self,
+    index: usize,
+    line_map: &[Option<u32>],
+    new_instructions: &[Instruction],
+  ) -> usize {
+    for old_instruction in self.instructions.iter().skip(index) {
+      let new_line = line_map
+        .get((old_instruction.line_num - 1/* 1 indexed */) as usize)
+        .copied()
+        .flatten();
+      if let Some(new_line) = new_line {
+        if let Some(new_index) =
+          new_instructions.iter().position(|i| i.line_num == new_line)
+        {
+          return new_index;
+        }
       }
+    }
+    new_instructions.len()
+  }
 
-      let opt_inst: Option<&InstructionSource> =
-        INSTRUCTIONS.get(instruction.split_whitespace().next().unwrap());
-      if opt_inst.is_none() {
-        self.errors.push(format!(
-          "Invalid instruction on line {}: {}",
-          line_num, instruction
-        ));
-        continue;
+  fn label_at(&self, address: u64) -> String {
+    let mut names: Vec<&String> = self
+      .labels
+      .iter()
+      .filter(|(_, a)| **a == address)
+      .map(|(name, _)| name)
+      .collect();
+    // Several labels can share an address; be consistent about which is used
+    names.sort();
+    match names.first() {
+      Some(name) => name.to_string(),
+      None => format!("0x{:x}", address),
+    }
+  }
+
+  /// The instruction at `pc`, following the page tables if they're in use.
+  fn instruction_at(&self, pc: u64) -> Option<&Instruction> {
+    let translation = self.csrs.translation();
+    let physical = if translation.privilege < Privilege::Machine {
+      virtual_to_physical(&self.memory, translation.satp, pc)?
+    } else {
+      pc
+    };
+    self.instructions.get((physical / 4) as usize)
+  }
+
+  /// Whether `address`, as the program sees it, is mapped to a device.
+  fn is_device(&self, address: u64) -> bool {
+    let translation = self.csrs.translation();
+    let physical = if translation.privilege < Privilege::Machine {
+      virtual_to_physical(&self.memory, translation.satp, address)
+    } else {
+      Some(address)
+    };
+    physical.is_some_and(|physical| self.memory.is_device(physical))
+  }
+
+  /// How an instruction which has just executed affected the flow of control,
+  /// for the pipeline model. Anything other than a branch or jump which
+  /// changes the pc, e.g. mret, does so in EX like jalr.
+  fn control(branch: Option<BranchOutcome>, jumped: bool) -> Control {
+    match branch.map(|branch| (branch.kind, branch.taken)) {
+      Some((BranchKind::Conditional, taken)) => Control::Branch { taken },
+      Some((BranchKind::Jump, _)) => Control::Jump,
+      Some((BranchKind::IndirectJump, _)) => Control::IndirectJump,
+      None if jumped => Control::IndirectJump,
+      None => Control::Sequential,
+    }
+  }
+
+  fn classify(
+    inst: &Instruction,
+    access: Option<MemoryAccess>,
+    branch: Option<BranchOutcome>,
+  ) -> InstructionClass {
+    if let Some(access) = access {
+      return match access.kind {
+        AccessKind::Load => InstructionClass::Load,
+        AccessKind::Store => InstructionClass::Store,
+      };
+    }
+    match branch {
+      Some(BranchOutcome {
+        kind: BranchKind::Conditional,
+        taken: true,
+        ..
+      }) => InstructionClass::BranchTaken,
+      Some(BranchOutcome {
+        kind: BranchKind::Conditional,
+        ..
+      }) => InstructionClass::BranchNotTaken,
+      Some(_) => InstructionClass::Jump,
+      None => match inst.op {
+        Op::Ecall
+        | Op::Ebreak
+        | Op::Mret
+        | Op::Sret
+        | Op::Wfi
+        | Op::SfenceVma { .. }
+        | Op::Csrrw { .. }
+        | Op::Csrrs { .. }
+        | Op::Csrrc { .. }
+        | Op::Csrrwi { .. }
+        | Op::Csrrsi { .. }
+        | Op::Csrrci { .. } => InstructionClass::System,
+        _ => InstructionClass::Alu,
+      },
+    }
+  }
+
+  /// Jumps to the trap handler, or stops with an error if there isn't one.
+  fn take_trap(&mut self, trap: Trap, pc: u64) {
+    self.memory.tick();
+    self.csrs.tick(false);
+    if !self.csrs.handler_installed(trap) {
+      let location = match self.instruction_at(pc) {
+        Some(instruction) => format!("on line {}", instruction.line_num),
+        None => format!("at 0x{:x}", pc),
+      };
+      self.add_error(format!(
+        "Unhandled {} {} in {} mode: no trap handler has been set up",
+        trap, location, self.csrs.privilege
+      ));
+      self.running = false;
+      return;
+    }
+    let handler = self.csrs.trap(trap, pc);
+    self.pc.set(handler);
+    if let Some(pipeline) = &mut self.pipeline {
+      pipeline.trap();
+    }
+  }
+
+  fn add_error(&mut self, error: String) {
+    if !self.errors.contains(&error) {
+      self.errors.push(error);
+    }
+  }
+
+  fn add_warnings(&mut self, warnings: Vec<String>) {
+    for warning in warnings {
+      if !self.warnings.contains(&warning) {
+        self.warnings.push(warning);
       }
-      let inst: &InstructionSource = opt_inst.unwrap();
-      let args = inst.parse(instruction);
-      if args.is_none() {
+    }
+  }
+
+  /// Keeps stepping until the call stack is shallower than `depth`, the
+  /// program stops, or a breakpoint is reached.
+  fn step_until_depth(&mut self, depth: usize) {
+    for _ in 0..MAX_STEPS_PER_COMMAND {
+      if !self.running || self.call_stack.len() < depth || self.at_breakpoint()
+      {
         return;
       }
-      let impl_func = (inst.implementation)(args.unwrap());
-      let actual_instruction = Instruction {
-        source: inst,
-        line_num,
-        breakpoint: false,
-        implementation: impl_func,
-      };
-      self.instructions.push(actual_instruction);
+      self.step();
     }
+    self.warnings.push(format!(
+      "Stopped after {} instructions without returning",
+      MAX_STEPS_PER_COMMAND
+    ));
   }
 }
 
+/// The source of a line, without indentation, for showing alongside it.
+fn source_line(code: &str, line_num: u32) -> String {
+  code
+    .lines()
+    .nth(line_num as usize - 1)
+    .unwrap_or("")
+    .trim()
+    .to_string()
+}
+
 impl InterpreterTrait for RiscV64_i {
-  fn memory_size(&self) -> u32 {
-    MEMORY_SIZE as u32
+  fn memory_size(&self) -> u64 {
+    self.memory.size()
+  }
+
+  fn set_memory_model(&mut self, sparse: bool, size: u64) {
+    self.memory_config = MemoryConfig {
+      model: if sparse {
+        MemoryModel::Sparse
+      } else {
+        MemoryModel::Flat
+      },
+      size,
+    };
+    self.reset_memory();
+  }
+
+  fn memory_regions(&self) -> Vec<Region> {
+    self.memory.layout()
+  }
+
+  fn console_output(&self) -> String {
+    self.uart.borrow().output()
+  }
+
+  fn console_input(&mut self, input: String) {
+    self.uart.borrow_mut().receive(&input);
+  }
+
+  fn leds(&self) -> Vec<bool> {
+    self.gpio.borrow().leds()
+  }
+
+  fn switches(&self) -> Vec<bool> {
+    self.gpio.borrow().switches()
+  }
+
+  fn set_switch(&mut self, switch: usize, on: bool) {
+    self.gpio.borrow_mut().set_switch(switch, on);
+  }
+
+  fn interrupts(&self) -> Vec<(String, bool, bool)> {
+    let pending = self.csrs.mip() | self.memory.pending_interrupts();
+    INTERRUPTS
+      .iter()
+      .map(|(name, bit)| {
+        (
+          name.to_string(),
+          self.csrs.mie() & bit != 0,
+          pending & bit != 0,
+        )
+      })
+      .collect()
+  }
+
+  fn interrupts_enabled(&self) -> bool {
+    self.csrs.enabled()
+  }
+
+  fn waiting_for_interrupt(&self) -> bool {
+    self.csrs.waiting
   }
 
   fn set_code(&mut self, code: String) {
-    if code.ne(&self.code) {
-      self.code = code;
-      self.parse();
+    if code.eq(&self.code) {
+      return;
+    }
+
+    let line_map = diff::line_map(&self.code, &code);
+    let breakpoints = self.breakpoints();
+    let Assembly {
+      instructions,
+      symbols,
+      errors,
+    } = assemble(&code, &self.files, false);
+
+    if self.preserve_state {
+      let old_index = (self.pc.get().value / 4) as usize;
+      let new_index =
+        self.map_instruction_index(old_index, &line_map, &instructions);
+      self.pc.set(Register {
+        value: new_index as u64 * 4,
+      });
+      self.instructions = instructions;
+      self.load_image();
+      let image = self.image();
+      if let Some(checker) = &mut self.uninitialised_checker {
+        checker.load(image);
+      }
+    } else {
+      self.instructions = instructions;
+      self.reset_state();
     }
+
+    // Return addresses refer to the old code, so they can't be trusted
+    self.call_stack.clear();
+
+    self.code = code;
+    // Breakpoints follow their lines to wherever they moved to
+    for (line, _) in breakpoints.iter().enumerate().filter(|(_, set)| **set) {
+      if let Some(Some(new_line)) = line_map.get(line) {
+        self.set_breakpoint(*new_line, true);
+      }
+    }
+    self.profile = Profile::new(self.instructions.len());
+    self.labels = symbols.labels;
+    self.valid_program = errors.is_empty();
+    self.errors = errors.iter().map(ToString::to_string).collect();
+    self.warnings.clear();
+  }
+
+  fn set_executable(&mut self, executable: &Executable) {
+    let mut instructions = Vec::new();
+    let mut errors = Vec::new();
+    for (word, line_num) in executable.text.iter().zip(&executable.lines) {
+      match instruction(*word, *line_num) {
+        Some(instruction) => instructions.push(instruction),
+        None => errors.push(format!(
+          "Invalid instruction on line {}: 0x{:08x}",
+          line_num, word
+        )),
+      }
+    }
+    self.instructions = instructions;
+    self.reset_state();
+    self.call_stack.clear();
+    self.code = executable.source.clone();
+    self.profile = Profile::new(self.instructions.len());
+    self.labels = executable.labels.clone();
+    self.valid_program = errors.is_empty();
+    self.errors = errors;
+    self.warnings.clear();
+  }
+
+  fn add_file(&mut self, name: String, code: String) {
+    self.files.insert(name, code);
+  }
+
+  fn set_preserve_state(&mut self, preserve: bool) {
+    self.preserve_state = preserve;
+  }
+
+  fn set_check_calling_convention(&mut self, check: bool) {
+    self.calling_convention_checker = if check {
+      Some(CallingConventionChecker::new())
+    } else {
+      None
+    };
+  }
+
+  fn set_check_uninitialised(&mut self, check: bool) {
+    // Anything written before the check was turned on is unknown, so it's
+    // only accurate from the start of the program.
+    self.uninitialised_checker = if check {
+      Some(UninitialisedChecker::new(
+        &self.given_registers,
+        self.image(),
+      ))
+    } else {
+      None
+    };
   }
 
   fn running(&self) -> bool {
@@ -121,52 +616,185 @@ impl InterpreterTrait for RiscV64_i {
     representations
   }
 
-  fn memory_byte_repr(&self, start: usize, len: usize) -> Vec<String> {
-    let mut strings: Vec<String> = Vec::new();
-    for b in start..start + len {
-      strings.push(format!("{:02x}", self.memory[b as usize]));
+  fn register(&self, register: usize) -> u64 {
+    self.registers[register].value
+  }
+
+  fn set_register(&mut self, register: usize, value: u64) {
+    if register != 0 {
+      self.registers[register] = Register { value };
     }
-    strings
   }
 
-  fn memory_ascii_repr(&self, start: usize, len: usize) -> Vec<String> {
-    vec![self.memory[start..(start + len)]
-      .iter()
-      .map(|num| {
-        if *num >= 32 && *num <= 126 {
-          (*num as char).to_string()
-        } else {
-          '.'.to_string()
+  fn pc(&self) -> u64 {
+    self.pc.get().value
+  }
+
+  fn set_pc(&mut self, pc: u64) {
+    self.pc.set(Register { value: pc });
+  }
+
+  fn last_access(&self) -> Option<MemoryAccess> {
+    self.last_access
+  }
+
+  fn debug_read(&self, start: u64, len: usize) -> Vec<Option<u8>> {
+    (start..start.saturating_add(len as u64))
+      .map(|address| {
+        // The program, even if it was too big to be put in memory
+        if let Some(instruction) = self.instructions.get((address / 4) as usize)
+        {
+          let word = encode(instruction).unwrap_or(0);
+          return Some(word.to_le_bytes()[(address % 4) as usize]);
         }
+        let readable = self.memory.is_device(address)
+          || self.memory.check(address, 1, AccessType::Read).is_ok();
+        readable.then(|| {
+          let mut byte = [0];
+          self.memory.peek(address, &mut byte);
+          byte[0]
+        })
       })
-      .collect()]
+      .collect()
   }
 
-  fn toggle_breakpoint(&mut self, line_num: u32) {
-    for mut instruction in self.instructions.iter_mut() {
-      if instruction.line_num == line_num {
-        instruction.breakpoint = !instruction.breakpoint;
-        log!("{:?}", instruction);
-      }
+  fn debug_write(&mut self, start: u64, data: &[u8]) -> bool {
+    if self
+      .memory
+      .check(start, data.len() as u64, AccessType::Write)
+      .is_err()
+    {
+      return false;
     }
+    self.memory.write(start, data);
+    self.memory.take_fault().is_none()
   }
 
-  fn breakpoints(&self) -> Vec<bool> {
-    let max_line_num = self
+  fn set_virtual_memory_view(&mut self, virtual_addresses: bool) {
+    self.virtual_memory_view = virtual_addresses;
+  }
+
+  fn memory_byte_repr(&self, start: u64, len: usize) -> Vec<String> {
+    self
+      .view_bytes(start, len)
+      .iter()
+      .map(|b| match b {
+        Some(b) => format!("{:02x}", b),
+        None => "--".to_string(),
+      })
+      .collect()
+  }
+
+  fn memory_ascii_repr(&self, start: u64, len: usize) -> Vec<String> {
+    vec![self
+      .view_bytes(start, len)
+      .iter()
+      .map(|byte| match byte {
+        Some(num) if *num >= 32 && *num <= 126 => (*num as char).to_string(),
+        _ => '.'.to_string(),
+      })
+      .collect()]
+  }
+
+  fn privilege(&self) -> String {
+    self.csrs.privilege.to_string()
+  }
+
+  fn tlb_stats(&self) -> (usize, u64, u64) {
+    (self.tlb.len(), self.tlb.hits, self.tlb.misses)
+  }
+
+  fn set_caches(&mut self, caches: Option<Caches>) {
+    self.caches = caches;
+  }
+
+  fn caches(&self) -> Option<&Caches> {
+    self.caches.as_ref()
+  }
+
+  fn set_pipeline(&mut self, pipeline: Option<Pipeline>) {
+    self.pipeline = pipeline;
+  }
+
+  fn pipeline(&self) -> Option<&Pipeline> {
+    self.pipeline.as_ref()
+  }
+
+  fn profile(&self) -> &Profile {
+    &self.profile
+  }
+
+  fn line_counts(&self) -> Vec<u64> {
+    let line_nums: Vec<u32> =
+      self.instructions.iter().map(|i| i.line_num).collect();
+    let by_line = self.profile.by_line(&line_nums);
+    (1..=self.code.lines().count() as u32)
+      .map(|line_num| by_line.get(&line_num).copied().unwrap_or(0))
+      .collect()
+  }
+
+  fn profile_report(&self) -> String {
+    let line_nums: Vec<u32> =
+      self.instructions.iter().map(|i| i.line_num).collect();
+    self.profile.report(&self.code, &line_nums, 10)
+  }
+
+  fn listing(&self) -> String {
+    let lines: Vec<ListingLine> = self
       .instructions
       .iter()
-      .map(|i| i.line_num)
+      .enumerate()
+      .map(|(i, instruction)| ListingLine {
+        address: i as u64 * 4,
+        bytes: encode(instruction).unwrap_or(0).to_le_bytes().to_vec(),
+        source: Some(source_line(&self.code, instruction.line_num)),
+      })
+      .collect();
+    let mut labels: BTreeMap<u64, Vec<String>> = BTreeMap::new();
+    for (label, address) in &self.labels {
+      labels.entry(*address).or_default().push(label.clone());
+    }
+    for names in labels.values_mut() {
+      names.sort();
+    }
+    listing(&lines, &labels)
+  }
+
+  fn set_trace(&mut self, trace: Option<Trace>) {
+    self.trace = trace;
+  }
+
+  fn set_branch_predictor(&mut self, predictor: Option<BranchPredictor>) {
+    self.branch_predictor = predictor;
+  }
+
+  fn branch_predictor(&self) -> Option<&BranchPredictor> {
+    self.branch_predictor.as_ref()
+  }
+
+  fn toggle_breakpoint(&mut self, line_num: u32) {
+    let set = breakpoint_lines(&self.instructions)
+      .any(|(line, i)| line == line_num && self.instructions[i].breakpoint);
+    self.set_breakpoint(line_num, !set);
+  }
+
+  fn at_breakpoint(&self) -> bool {
+    self
+      .instruction_at(self.pc.get().value)
+      .is_some_and(|i| i.breakpoint)
+  }
+
+  fn breakpoints(&self) -> Vec<bool> {
+    let max_line_num = breakpoint_lines(&self.instructions)
+      .map(|(line, _)| line)
       .max()
       .unwrap_or(0) as usize;
 
     // Have to create and then set, because of blank lines
-    let mut is_break: Vec<bool> = Vec::with_capacity(max_line_num);
-    for _i in 0..max_line_num {
-      is_break.push(false);
-    }
-    for instruction in &self.instructions {
-      is_break[(instruction.line_num - 1/* 1 indexed */) as usize] =
-        instruction.breakpoint;
+    let mut is_break: Vec<bool> = vec![false; max_line_num];
+    for (line, i) in breakpoint_lines(&self.instructions) {
+      is_break[(line - 1/* 1 indexed */) as usize] |=
+        self.instructions[i].breakpoint;
     }
     is_break
   }
@@ -180,11 +808,9 @@ impl InterpreterTrait for RiscV64_i {
   }
 
   fn next_inst_line_num(&self) -> u32 {
-    let next_inst_num = (self.pc.get().value / 4) as usize;
-    if next_inst_num < self.instructions.len() {
-      self.instructions[next_inst_num].line_num - 1 /* 1 indexed */
-    } else {
-      0
+    match self.instruction_at(self.pc.get().value) {
+      Some(instruction) => instruction.line_num - 1, /* 1 indexed */
+      None => 0,
     }
   }
 
@@ -196,30 +822,1232 @@ impl InterpreterTrait for RiscV64_i {
     }
   }
 
+  /// Steps until the program stops, or `instructions` have been stepped,
+  /// returning how many were.
+  fn run_for(&mut self, instructions: u64) -> u64 {
+    let mut steps = 0;
+    while self.running && steps < instructions {
+      self.step();
+      steps += 1;
+    }
+    steps
+  }
+
   fn step(&mut self) {
     if !self.running {
       return;
     }
+    self.last_access = None;
+
+    // The program is incomplete, so running it would be meaningless
+    if !self.valid_program {
+      self.running = false;
+      return;
+    }
+
+    let pc = self.pc.get().value;
+    self.csrs.set_pending(self.memory.pending_interrupts());
+    if self.csrs.waiting {
+      if !self.csrs.wake() {
+        // Time passes while the hart idles
+        self.memory.tick();
+        self.csrs.tick(false);
+        return;
+      }
+      self.csrs.waiting = false;
+    }
+    if let Some(trap) = self.csrs.pending_interrupt() {
+      self.take_trap(trap, pc);
+      return;
+    }
+
+    let translation = self.csrs.translation();
+    let physical_pc =
+      match Mmu::new(&mut self.memory, &mut self.tlb, translation)
+        .translate(pc, AccessType::Execute)
+      {
+        Ok(physical_pc) => physical_pc,
+        Err(trap) => {
+          self.take_trap(trap, pc);
+          return;
+        }
+      };
+    if let Err(fault) = self.memory.check(physical_pc, 4, AccessType::Execute) {
+      self.add_error(format!("Memory fault: {}", fault));
+      self.running = false;
+      return;
+    }
 
     // 4 bytes/instruction
     let max_pc: u64 = self.instructions.len() as u64 * 4;
-    if self.pc.get().value >= max_pc {
+    if physical_pc >= max_pc {
       self.running = false;
       return;
     }
 
-    log!("{:?}; {}", self.registers, self.pc.get().value);
+    log!("{:?}; {}", self.registers, pc);
+    let registers_before = self.registers;
+    let privilege = self.csrs.privilege;
+    let sp = self.registers[2].value;
     self.pc.changed = false;
-    let inst = &self.instructions[(self.pc.get().value / 4) as usize];
+    let inst = &self.instructions[(physical_pc / 4) as usize];
     log!("{:?}", inst);
-    (inst.implementation)(&mut self.registers, &mut self.pc, &mut self.memory);
+    let line_num = inst.line_num;
+    let call_kind = inst.call_kind();
+    let access = inst.memory_access(&self.registers);
+    let mut warnings: Vec<String> = Vec::new();
+    if let Some(checker) = &mut self.calling_convention_checker {
+      warnings.append(&mut checker.check_reads(inst, line_num));
+    }
+    if self.uninitialised_checker.is_some() {
+      // Device registers hold whatever the device puts there, not memory
+      let memory_access = access.filter(|a| !self.is_device(a.address));
+      if let Some(checker) = &mut self.uninitialised_checker {
+        let mut reads = checker.check_reads(inst, line_num, memory_access);
+        warnings.append(&mut reads);
+      }
+    }
+    if let Some(caches) = &mut self.caches {
+      caches.fetch(line_num, physical_pc);
+    }
+    let mut mmu = Mmu::new(&mut self.memory, &mut self.tlb, translation);
+    execute(
+      inst.op,
+      &mut self.registers,
+      &mut self.pc,
+      &mut mmu,
+      &mut self.csrs,
+    );
+    let page_fault = mmu.take_fault();
+    let accesses = mmu.take_accesses();
+    if let Some(caches) = &mut self.caches {
+      for (address, len, access) in accesses {
+        if !self.memory.is_device(address) {
+          let write = access == AccessType::Write;
+          caches.data(line_num, address, len as u64, write);
+        }
+      }
+    }
+    if self.csrs.take_tlb_flush() {
+      self.tlb.flush();
+    }
+    if let Some(trap) = self.csrs.take_exception().or(page_fault) {
+      self.registers = registers_before;
+      self.pc.set(Register { value: pc });
+      self.add_warnings(warnings);
+      self.take_trap(trap, pc);
+      return;
+    }
+    if let Some(fault) = self.memory.take_fault() {
+      // Undo the instruction, so the state is as it was just before the fault
+      self.registers = registers_before;
+      self.pc.set(Register { value: pc });
+      self.add_warnings(warnings);
+      self.add_error(format!("Memory fault on line {}: {}", line_num, fault));
+      self.running = false;
+      return;
+    }
+    let jumped = self.pc.changed;
+    let branch = inst.branch_kind().map(|kind| BranchOutcome {
+      pc,
+      kind,
+      taken: jumped,
+      target: match kind {
+        BranchKind::Conditional => {
+          (Register { value: pc } + inst.imm_arg("offset").unwrap()).value
+        }
+        _ => self.pc.get().value,
+      },
+    });
     if !self.pc.changed {
       self.pc.inc(Register { value: 4 });
     }
     self.registers[0] = Register { value: 0 };
+    if let (Some(predictor), Some(branch)) =
+      (&mut self.branch_predictor, &branch)
+    {
+      predictor.observe(line_num, branch);
+    }
+    self.profile.record(
+      (physical_pc / 4) as usize,
+      Self::classify(inst, access, branch),
+    );
+    self.last_access = access;
+    if let Some(trace) = &mut self.trace {
+      let stored = match (access, inst.register_arg("rs2")) {
+        (Some(a), Some(rs2)) if a.kind == AccessKind::Store => {
+          let value = registers_before[rs2].value;
+          Some(if a.size == 8 {
+            value
+          } else {
+            value & ((1 << (a.size * 8)) - 1)
+          })
+        }
+        _ => None,
+      };
+      let registers = &self.registers;
+      let write = inst
+        .register_arg("rd")
+        .filter(|rd| *rd != 0)
+        .map(|rd| (rd, registers[rd].value));
+      trace.record(&TraceRecord {
+        pc,
+        encoding: encode(inst).unwrap_or(0),
+        text: source_line(&self.code, line_num),
+        line_num,
+        privilege,
+        write,
+        access,
+        stored,
+      });
+    }
+    if let Some(pipeline) = &mut self.pipeline {
+      pipeline.retire(Retired {
+        pc,
+        line_num,
+        text: source_line(&self.code, line_num),
+        reads: ["rs1", "rs2"]
+          .iter()
+          .filter_map(|name| inst.register_arg(name))
+          .collect(),
+        write: inst.register_arg("rd"),
+        load: access.is_some_and(|a| a.kind == AccessKind::Load),
+        control: Self::control(branch, jumped),
+      });
+    }
+    self.memory.tick();
+    self.csrs.tick(true);
+    if let Some(checker) = &mut self.calling_convention_checker {
+      checker.record_writes(inst);
+    }
+    if let Some(checker) = &mut self.uninitialised_checker {
+      checker.record_writes(inst, access);
+    }
+
+    match call_kind {
+      Some(CallKind::Call) => {
+        let frame = CallFrame {
+          caller_line: line_num,
+          callee: self.label_at(self.pc.get().value),
+          sp,
+          return_address: pc + 4,
+        };
+        if let Some(checker) = &mut self.calling_convention_checker {
+          checker.call(
+            line_num,
+            frame.callee.clone(),
+            frame.return_address,
+            &self.registers,
+          );
+        }
+        self.call_stack.push(frame);
+      }
+      Some(CallKind::Return) => {
+        // Usually this is the top frame, but if the program has skipped a
+        // return (e.g. a tail call) unwind to the frame it returned to.
+        let target = self.pc.get().value;
+        if let Some(depth) = self
+          .call_stack
+          .iter()
+          .rposition(|frame| frame.return_address == target)
+        {
+          self.call_stack.truncate(depth);
+        }
+        if let Some(checker) = &mut self.calling_convention_checker {
+          warnings.append(&mut checker.ret(line_num, target, &self.registers));
+        }
+      }
+      None => (),
+    }
+    self.add_warnings(warnings);
+  }
+
+  fn step_over(&mut self) {
+    let depth = self.call_stack.len();
+    self.step();
+    // If that was a call, run until it returns
+    self.step_until_depth(depth + 1);
+  }
+
+  fn step_out(&mut self) {
+    let depth = self.call_stack.len();
+    self.step();
+    self.step_until_depth(depth);
+  }
+
+  fn call_stack(&self) -> Vec<CallFrame> {
+    self.call_stack.clone()
   }
 
   fn stop(&mut self) {
     self.running = false;
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::cache::CacheConfig;
+  use crate::csr::MSCRATCH;
+  use crate::linker::{link, Object};
+  use crate::pipeline::PipelineConfig;
+  use crate::predictor::{PredictorConfig, PredictorKind, StaticPolicy};
+  use crate::trace::{SharedBuffer, TraceFormat};
+
+  fn interpreter(code: &str) -> RiscV64_i {
+    let mut interpreter = RiscV64_i::create(Vec::new());
+    interpreter.set_code(code.to_string());
+    interpreter
+  }
+
+  fn run(interpreter: &mut RiscV64_i) {
+    interpreter.running = true;
+    interpreter.run();
+  }
+
+  #[test]
+  fn reparse_replaces_program() {
+    let mut interpreter = interpreter("addi x1, x0, 1\naddi x2, x0, 2");
+    interpreter.set_code("addi x1, x0, 1\naddi x2, x0, 3".to_string());
+    assert_eq!(interpreter.instructions.len(), 2);
+    run(&mut interpreter);
+    assert_eq!(interpreter.registers[2].value, 3);
+  }
+
+  #[test]
+  fn runs_in_batches() {
+    let mut interpreter = interpreter("loop: addi a0, a0, 1\nblt a0, a1, loop");
+    interpreter.registers[11] = Register { value: 3 };
+    interpreter.running = true;
+    assert_eq!(interpreter.run_for(4), 4);
+    assert_eq!(interpreter.registers[10].value, 2);
+    assert!(interpreter.running());
+    // It stops early when the program ends
+    assert_eq!(interpreter.run_for(100), 3);
+    assert_eq!(interpreter.registers[10].value, 3);
+    assert!(!interpreter.running());
+  }
+
+  #[test]
+  fn reparse_drops_stale_errors() {
+    let mut interpreter = interpreter("not_an_instruction x1");
+    assert_eq!(interpreter.errors().len(), 1);
+    interpreter.set_code("addi x1, x0, 1".to_string());
+    assert!(interpreter.errors().is_empty());
+  }
+
+  #[test]
+  fn breakpoints_follow_lines() {
+    let mut interpreter = interpreter("addi x1, x0, 1\naddi x2, x0, 2");
+    interpreter.toggle_breakpoint(2);
+    interpreter
+      .set_code("// comment\naddi x1, x0, 1\naddi x2, x0, 2".to_string());
+    assert_eq!(interpreter.breakpoints(), vec![false, false, true]);
+  }
+
+  #[test]
+  fn runs_linked_programs() {
+    let harness = "addi a0, zero, 20\n\
+                   jal ra, main\n\
+                   jal ra, double\n\
+                   jal zero, end";
+    let student = ".globl main, double, end\n\
+                   main: addi a0, a0, 1\n\
+                   jalr zero, 0(ra)\n\
+                   double: add a0, a0, a0\n\
+                   jalr zero, 0(ra)\n\
+                   end:";
+    let objects = [
+      Object::assemble("harness.s", harness, &Files::new()).unwrap(),
+      Object::assemble("student.s", student, &Files::new()).unwrap(),
+    ];
+    let mut interpreter = RiscV64_i::create(Vec::new());
+    interpreter.set_executable(&link(&objects).unwrap());
+    assert!(interpreter.errors().is_empty());
+    run(&mut interpreter);
+    assert_eq!(interpreter.registers[10].value, 42);
+    assert_eq!(interpreter.instructions[4].line_num, 4 + 2);
+  }
+
+  #[test]
+  fn state_reset_by_default() {
+    let mut interpreter = interpreter("addi x1, x0, 1");
+    run(&mut interpreter);
+    interpreter.set_code("addi x2, x0, 2".to_string());
+    assert_eq!(interpreter.registers[1].value, 0);
+    assert_eq!(interpreter.pc.get().value, 0);
+  }
+
+  #[test]
+  fn state_preserved_when_requested() {
+    let mut interpreter = interpreter("addi x1, x0, 1\naddi x2, x0, 2");
+    interpreter.set_preserve_state(true);
+    interpreter.running = true;
+    interpreter.step();
+    interpreter
+      .set_code("addi x1, x0, 1\naddi x3, x0, 3\naddi x2, x0, 2".to_string());
+    assert_eq!(interpreter.registers[1].value, 1);
+    // The next instruction to run was "addi x2", which has moved down one
+    assert_eq!(interpreter.pc.get().value, 8);
+    run(&mut interpreter);
+    assert_eq!(interpreter.registers[3].value, 0);
+    assert_eq!(interpreter.registers[2].value, 2);
+  }
+
+  const RECURSIVE_SUM: &str = "lui sp, 0x7ffff
+addi a0, x0, 3
+jal ra, sum
+jal x0, end
+sum:
+bne a0, x0, recurse
+jalr x0, 0(ra)
+recurse:
+addi sp, sp, -16
+sd ra, 8(sp)
+sd a0, 0(sp)
+addi a0, a0, -1
+jal ra, sum
+ld t0, 0(sp)
+add a0, a0, t0
+ld ra, 8(sp)
+addi sp, sp, 16
+jalr x0, 0(ra)
+end:";
+
+  fn run_to_breakpoint(interpreter: &mut RiscV64_i) {
+    interpreter.running = true;
+    interpreter.step();
+    while interpreter.running && !interpreter.at_breakpoint() {
+      interpreter.step();
+    }
+  }
+
+  #[test]
+  fn macros_keep_both_locations() {
+    let code = ".macro bump reg\n\
+                addi \\reg, \\reg, 1\n\
+                addi \\reg, \\reg, 2\n\
+                .endm\n\
+                bump a0\n\
+                bump a1";
+    let mut interpreter = interpreter(code);
+    assert!(interpreter.errors().is_empty());
+    let lines: Vec<u32> = interpreter
+      .instructions
+      .iter()
+      .map(|i| i.line_num)
+      .collect();
+    assert_eq!(lines, [5, 5, 6, 6]);
+
+    // A line in the macro stops in each use of it
+    interpreter.toggle_breakpoint(3);
+    run_to_breakpoint(&mut interpreter);
+    assert_eq!(interpreter.pc.get().value, 4);
+    run_to_breakpoint(&mut interpreter);
+    assert_eq!(interpreter.pc.get().value, 12);
+    interpreter.toggle_breakpoint(3);
+
+    // A use of the macro stops at its first instruction
+    interpreter.toggle_breakpoint(6);
+    assert_eq!(
+      interpreter.breakpoints(),
+      [false, true, false, false, false, true]
+    );
+    interpreter.reset_state();
+    run_to_breakpoint(&mut interpreter);
+    assert_eq!(interpreter.pc.get().value, 8);
+
+    interpreter.set_code(format!("{}\nbump x99", code));
+    assert_eq!(
+      interpreter.errors(),
+      &[
+        "Error on line 7, from line 2: expected register, found `x99`",
+        "Error on line 7, from line 3: expected register, found `x99`",
+      ]
+    );
+  }
+
+  #[test]
+  fn several_instructions_per_line() {
+    let code = "# Compiler Explorer style output\n\
+                main: /* start */ addi a0, zero, 1; addi a1, zero, '#'\n\
+                addi a0, a0, 1 # bump; not an instruction\n\
+                /* a\n\
+                comment */ addi a0, a0, 2; addi a1, a1, 1 // done";
+    let mut interpreter = interpreter(code);
+    assert!(interpreter.errors().is_empty());
+    let lines: Vec<u32> = interpreter
+      .instructions
+      .iter()
+      .map(|i| i.line_num)
+      .collect();
+    assert_eq!(lines, [2, 2, 3, 5, 5]);
+
+    interpreter.toggle_breakpoint(5);
+    run_to_breakpoint(&mut interpreter);
+    assert_eq!(interpreter.pc.get().value, 12);
+    assert_eq!(interpreter.next_inst_line_num(), 4);
+    interpreter.step();
+    assert_eq!(interpreter.next_inst_line_num(), 4);
+    run(&mut interpreter);
+    assert_eq!(interpreter.registers[10].value, 4);
+    assert_eq!(interpreter.registers[11].value, '#' as u64 + 1);
+  }
+
+  #[test]
+  fn labels_as_branch_targets() {
+    let mut interpreter = interpreter(RECURSIVE_SUM);
+    assert!(interpreter.errors().is_empty());
+    run(&mut interpreter);
+    assert_eq!(interpreter.registers[10].value, 6);
+    assert!(interpreter.call_stack().is_empty());
+  }
+
+  #[test]
+  fn undefined_label() {
+    let interpreter = interpreter("jal ra, nowhere");
+    assert_eq!(interpreter.errors().len(), 1);
+  }
+
+  #[test]
+  fn expanded_errors_are_reported_once() {
+    let errors = |code: &str| interpreter(code).errors().to_vec();
+    assert_eq!(
+      errors(".rept 3\naddi a0, a0, 1\nfoo a0\n.endr\nbar"),
+      [
+        "Invalid instruction on line 3: foo a0",
+        "Invalid instruction on line 5: bar"
+      ]
+    );
+    assert_eq!(
+      errors(".macro m x\naddi a0, a0, \\x\n.endm\nm 5000\nm 5000\nm 1"),
+      [
+        "Error on line 4, from line 2: expected 12 bit immediate, found \
+         `5000`",
+        "Error on line 5, from line 2: expected 12 bit immediate, found \
+         `5000`"
+      ]
+    );
+    assert_eq!(
+      errors(".macro m\nm\n.endm\nm"),
+      ["Error on line 4, from line 2 (×65): macros or includes are nested too \
+        deeply"]
+    );
+  }
+
+  #[test]
+  fn operand_errors() {
+    let errors = |code: &str| interpreter(code).errors().to_vec();
+    assert_eq!(
+      errors("add a0, a1, x32"),
+      ["Error on line 1: expected register, found `x32`"]
+    );
+    assert_eq!(
+      errors("lw a0, 4(zero) + 1"),
+      ["Error on line 1: expected offset(register), found `4(zero) + 1`"]
+    );
+    assert_eq!(
+      errors("addi a0, a0, 4096 * 2"),
+      ["Error on line 1: expected 12 bit immediate, found `4096 * 2` (8192)"]
+    );
+    assert_eq!(
+      errors("slli a0, a0, 64\nsraiw a0, a0, 32"),
+      [
+        "Error on line 1: expected shift amount below 64, found `64`",
+        "Error on line 2: expected shift amount below 32, found `32`"
+      ]
+    );
+    assert_eq!(
+      errors("beq a0, a1, 5000\nbne a0, a1, 3"),
+      [
+        "Error on line 1: expected target within 4 KiB of the instruction, \
+         found `5000`",
+        "Error on line 2: expected even offset, found `3`"
+      ]
+    );
+    assert!(errors("jal x0, 600000\njal x0, -0x100000").is_empty());
+    assert_eq!(
+      errors("jal x0, 0x100000"),
+      [
+        "Error on line 1: expected target within 1 MiB of the instruction, \
+         found `0x100000` (1048576)"
+      ]
+    );
+    assert_eq!(
+      errors("add a0, a1"),
+      ["Error on line 1: expected 3 operands for `add rd, rs1, rs2`, found 2"]
+    );
+    assert_eq!(
+      errors("addi a0, a0, SIZE"),
+      ["Error on line 1: unknown symbol `SIZE`"]
+    );
+  }
+
+  #[test]
+  fn constants_and_expressions() {
+    let mut interpreter = interpreter(
+      ".equ COUNT, 4*8+1
+       .set LETTER, 'A'
+       addi a0, zero, COUNT
+       addi a1, zero, LETTER + ('a' - 'A')
+       lui t0, %hi(0x10010a10)
+       addi t0, t0, %lo(0x10010a10)
+       here: auipc t1, %pcrel_hi(end)
+       addi t1, t1, %pcrel_lo(here)
+       beq zero, zero, end - 4 + 4
+       addi a0, zero, 0
+       end: lw a2, (sp)",
+    );
+    assert!(
+      interpreter.errors().is_empty(),
+      "{:?}",
+      interpreter.errors()
+    );
+    run(&mut interpreter);
+    assert_eq!(interpreter.registers[10].value, 33);
+    assert_eq!(interpreter.registers[11].value, 'a' as u64);
+    assert_eq!(interpreter.registers[5].value, 0x1001_0a10);
+    assert_eq!(interpreter.registers[6].value, 8 * 4); // end
+  }
+
+  #[test]
+  fn branches_reach_4_kib() {
+    let interpreter = interpreter("beq a0, a1, 4094\njal ra, -2000*4");
+    assert!(
+      interpreter.errors().is_empty(),
+      "{:?}",
+      interpreter.errors()
+    );
+    let offset = |i: usize| interpreter.instructions[i].imm_arg("offset");
+    assert_eq!(offset(0).unwrap().value, 4094);
+    assert_eq!(offset(1).unwrap().value as i64, -8000);
+  }
+
+  #[test]
+  fn call_stack_tracks_recursion() {
+    let mut interpreter = interpreter(RECURSIVE_SUM);
+    interpreter.toggle_breakpoint(7);
+    run_to_breakpoint(&mut interpreter);
+    let call_stack = interpreter.call_stack();
+    assert_eq!(
+      call_stack
+        .iter()
+        .map(|f| f.caller_line)
+        .collect::<Vec<u32>>(),
+      vec![3, 13, 13, 13]
+    );
+    assert!(call_stack.iter().all(|f| f.callee == "sum"));
+    assert_eq!(call_stack[0].sp, 0x7fff_f000);
+    assert_eq!(call_stack[3].sp, 0x7fff_f000 - 3 * 16);
+  }
+
+  #[test]
+  fn step_over_call() {
+    let mut interpreter = interpreter(RECURSIVE_SUM);
+    interpreter.running = true;
+    interpreter.step();
+    interpreter.step();
+    interpreter.step_over();
+    assert_eq!(interpreter.next_inst_line_num(), 4 - 1);
+    assert_eq!(interpreter.registers[10].value, 6);
+    assert!(interpreter.call_stack().is_empty());
+  }
+
+  #[test]
+  fn calling_convention_clean() {
+    let mut interpreter = interpreter(RECURSIVE_SUM);
+    interpreter.set_check_calling_convention(true);
+    run(&mut interpreter);
+    assert!(
+      interpreter.warnings().is_empty(),
+      "{:?}",
+      interpreter.warnings()
+    );
+  }
+
+  #[test]
+  fn calling_convention_callee_saved_changed() {
+    let mut interpreter = interpreter(
+      "jal ra, f
+jal x0, end
+f:
+addi s1, s1, 1
+jalr x0, 0(ra)
+end:",
+    );
+    interpreter.set_check_calling_convention(true);
+    run(&mut interpreter);
+    assert_eq!(interpreter.warnings().len(), 1);
+    assert!(interpreter.warnings()[0].contains("line 5"));
+    assert!(interpreter.warnings()[0].contains("s1"));
+  }
+
+  #[test]
+  fn calling_convention_caller_saved_read() {
+    let mut interpreter = interpreter(
+      "addi t0, x0, 1
+jal ra, f
+add a0, a0, t0
+jal x0, end
+f:
+addi a0, x0, 2
+jalr x0, 0(ra)
+end:",
+    );
+    interpreter.set_check_calling_convention(true);
+    run(&mut interpreter);
+    assert_eq!(interpreter.warnings().len(), 1);
+    assert!(interpreter.warnings()[0].contains("line 3"));
+    assert!(interpreter.warnings()[0].contains("t0"));
+  }
+
+  #[test]
+  fn uninitialised_register_read() {
+    let mut interpreter = interpreter("addi t0, x0, 1\nadd a0, t0, t1");
+    interpreter.set_check_uninitialised(true);
+    run(&mut interpreter);
+    assert_eq!(interpreter.warnings().len(), 1);
+    assert!(interpreter.warnings()[0].contains("line 2"));
+    assert!(interpreter.warnings()[0].contains("t1"));
+  }
+
+  #[test]
+  fn initial_register_values_are_defined() {
+    let mut interpreter =
+      RiscV64_i::create(vec!["".to_string(), "0".to_string()]);
+    interpreter.set_code("addi a0, sp, 0\naddi a1, ra, 0".to_string());
+    interpreter.set_check_uninitialised(true);
+    run(&mut interpreter);
+    // sp was entered as zero, ra was left blank
+    assert_eq!(interpreter.warnings().len(), 1);
+    assert!(interpreter.warnings()[0].contains("line 2"));
+  }
+
+  #[test]
+  fn uninitialised_memory_read() {
+    let mut interpreter = interpreter(
+      "lui t0, 0x10010
+sw t0, 0(t0)
+lw a0, 0(t0)
+ld a1, 0(t0)",
+    );
+    interpreter.set_check_uninitialised(true);
+    run(&mut interpreter);
+    assert_eq!(interpreter.warnings().len(), 1);
+    assert!(interpreter.warnings()[0].contains("line 4"));
+    assert!(interpreter.warnings()[0].contains("0x10010004"));
+  }
+
+  #[test]
+  fn program_image_is_initialised() {
+    // The program's own machine code is in memory from the start
+    let mut program = interpreter(
+      "addi a0, x0, 5
+lw a1, 0(zero)
+lw a2, 8(zero)",
+    );
+    program.set_check_uninitialised(true);
+    run(&mut program);
+    assert!(program.warnings().is_empty(), "{:?}", program.warnings());
+    assert_eq!(program.registers[11].value, 0x0050_0513);
+    // Past the end of it isn't
+    let mut past_the_end = interpreter("lw a0, 4(zero)");
+    past_the_end.set_check_uninitialised(true);
+    run(&mut past_the_end);
+    assert_eq!(past_the_end.warnings().len(), 1);
+  }
+
+  #[test]
+  fn uninitialised_check_at_the_top_of_memory() {
+    let mut interpreter = interpreter("ld a0, -8(zero)");
+    interpreter.set_check_uninitialised(true);
+    run(&mut interpreter);
+    assert_eq!(interpreter.errors().len(), 1);
+    assert!(interpreter.errors()[0].contains("Memory fault"));
+  }
+
+  #[test]
+  fn device_reads_are_initialised() {
+    let mut interpreter = interpreter(
+      "lui t0, 0x10000
+lbu a0, 5(t0)",
+    );
+    interpreter.set_check_uninitialised(true);
+    run(&mut interpreter);
+    assert!(interpreter.warnings().is_empty());
+  }
+
+  #[test]
+  fn stack_and_data_far_apart() {
+    let mut interpreter = interpreter(
+      "lui sp, 0x7ffff
+lui t0, 0x10010
+addi t1, x0, 42
+sd t1, -8(sp)
+sd t1, 0(t0)
+ld a0, -8(sp)
+ld a1, 0(t0)",
+    );
+    run(&mut interpreter);
+    assert_eq!(interpreter.registers[10].value, 42);
+    assert_eq!(interpreter.registers[11].value, 42);
+    assert_eq!(
+      interpreter.memory_byte_repr(0x7fff_eff8, 2),
+      vec!["2a", "00"]
+    );
+  }
+
+  #[test]
+  fn cache_hits_and_misses() {
+    let mut interpreter = interpreter(
+      "lui t0, 0x10010
+addi t1, x0, 8
+loop:
+ld t2, 0(t0)
+addi t0, t0, 8
+addi t1, t1, -1
+bne t1, x0, loop",
+    );
+    interpreter.set_caches(Some(Caches::new(CacheConfig::default(), true)));
+    run(&mut interpreter);
+    let caches = interpreter.caches().unwrap();
+    // Two doublewords per 16 byte block
+    assert_eq!(caches.data.as_ref().unwrap().stats.misses, 4);
+    assert_eq!(caches.data.as_ref().unwrap().stats.hits, 4);
+    assert_eq!(caches.by_line[&4].data.misses, 4);
+    // Six instructions over two blocks
+    assert_eq!(caches.instruction.stats.misses, 2);
+    assert_eq!(caches.instruction.stats.hits, 32);
+
+    // A new program starts with empty caches
+    interpreter.set_code("addi t1, x0, 1".to_string());
+    let caches = interpreter.caches().unwrap();
+    assert_eq!(caches.instruction.stats.misses, 0);
+    assert!(caches.by_line.is_empty());
+  }
+
+  #[test]
+  fn pipeline_timing() {
+    let mut interpreter = interpreter(
+      "lui t0, 0x10010
+addi t1, x0, 4
+loop:
+ld t2, 0(t0)
+add t3, t2, t2
+addi t1, t1, -1
+bne t1, x0, loop",
+    );
+    interpreter.set_pipeline(Some(Pipeline::new(PipelineConfig::default())));
+    run(&mut interpreter);
+    assert_eq!(interpreter.registers[6].value, 0);
+    let pipeline = interpreter.pipeline().unwrap();
+    assert_eq!(pipeline.stats.instructions, 18);
+    // A load-use stall each time round the loop
+    assert_eq!(pipeline.stats.data_stalls, 4);
+    // Three taken branches predicted not taken
+    assert_eq!(pipeline.stats.branches, 4);
+    assert_eq!(pipeline.stats.mispredictions, 3);
+    assert_eq!(pipeline.stats.control_stalls, 6);
+    assert_eq!(pipeline.stats.cycles, 18 + 4 + 4 + 6);
+    let row = &pipeline.rows()[3];
+    assert_eq!(row.text, "add t3, t2, t2");
+    assert_eq!(row.line_num, 5);
+  }
+
+  #[test]
+  fn branch_outcomes_reach_predictor() {
+    let mut interpreter = interpreter(RECURSIVE_SUM);
+    interpreter.set_branch_predictor(Some(BranchPredictor::new(
+      PredictorConfig::default(),
+    )));
+    run(&mut interpreter);
+    let predictor = interpreter.branch_predictor().unwrap();
+    // bne a0, x0, recurse
+    assert_eq!(predictor.by_line[&6].executed, 4);
+    assert_eq!(predictor.by_line[&6].taken, 3);
+    assert_eq!(predictor.conditional.executed, 4);
+    assert_eq!(predictor.jumps.executed, 5);
+    assert_eq!(predictor.jumps.mispredicted(), 0);
+    // The BTB only knows where a return went last time, so it misses the
+    // first return and the one back to the top level
+    assert_eq!(predictor.indirect.executed, 4);
+    assert_eq!(predictor.by_line[&18].executed, 3);
+    assert_eq!(predictor.by_line[&18].correct, 1);
+  }
+
+  #[test]
+  fn static_prediction_of_loop() {
+    let mut interpreter = interpreter(
+      "addi t1, x0, 4
+loop:
+addi t1, t1, -1
+bne t1, x0, loop",
+    );
+    interpreter.set_branch_predictor(Some(BranchPredictor::new(
+      PredictorConfig {
+        kind: PredictorKind::Static(StaticPolicy::Btfn),
+        ..PredictorConfig::default()
+      },
+    )));
+    run(&mut interpreter);
+    let predictor = interpreter.branch_predictor().unwrap();
+    assert_eq!(predictor.by_line[&4].executed, 4);
+    assert_eq!(predictor.by_line[&4].mispredicted(), 1);
+  }
+
+  #[test]
+  fn profile_counts_lines_and_classes() {
+    let mut interpreter = interpreter(
+      "lui t0, 0x10010
+addi t1, x0, 4
+loop:
+sd t1, 0(t0)
+addi t1, t1, -1
+bne t1, x0, loop",
+    );
+    run(&mut interpreter);
+    assert_eq!(interpreter.line_counts(), vec![1, 1, 0, 4, 4, 4]);
+    let profile = interpreter.profile();
+    assert_eq!(profile.total, 14);
+    assert_eq!(profile.by_class[&InstructionClass::Alu], 6);
+    assert_eq!(profile.by_class[&InstructionClass::Store], 4);
+    assert_eq!(profile.by_class[&InstructionClass::BranchTaken], 3);
+    assert_eq!(profile.by_class[&InstructionClass::BranchNotTaken], 1);
+    assert!(interpreter
+      .profile_report()
+      .contains("14 instructions executed"));
+  }
+
+  #[test]
+  fn trace_in_spike_format() {
+    let mut interpreter = interpreter(
+      "lui t0, 0x10010
+addi t1, x0, -2
+sw t1, 4(t0)
+lw t2, 4(t0)",
+    );
+    let buffer = SharedBuffer::default();
+    interpreter.set_trace(Some(Trace::new(
+      TraceFormat::Spike,
+      Box::new(buffer.clone()),
+    )));
+    run(&mut interpreter);
+    assert_eq!(
+      buffer.contents(),
+      "core   0: 3 0x0000000000000000 (0x100102b7) x5  0x0000000010010000
+core   0: 3 0x0000000000000004 (0xffe00313) x6  0xfffffffffffffffe
+core   0: 3 0x0000000000000008 (0x0062a223) mem 0x0000000010010004 0xfffffffe
+core   0: 3 0x000000000000000c (0x0042a383) x7  0xfffffffffffffffe \
+mem 0x0000000010010004
+"
+    );
+  }
+
+  #[test]
+  fn listing_shows_source() {
+    let interpreter = interpreter(RECURSIVE_SUM);
+    let listing = interpreter.listing();
+    let lines: Vec<&str> = listing.lines().collect();
+    assert_eq!(
+      lines[2],
+      "       8:\t008000ef\tjal 0x10 <sum>                  // jal ra, sum"
+    );
+    assert_eq!(lines[4], "");
+    assert_eq!(lines[5], "0000000000000010 <sum>:");
+    assert!(
+      lines[7].ends_with("\tret                             // jalr x0, 0(ra)")
+    );
+  }
+
+  #[test]
+  fn store_to_text_is_a_precise_fault() {
+    let mut interpreter = interpreter(
+      "addi t0, x0, 5
+addi t1, x0, 16
+sw t0, 0(t1)
+addi t0, x0, 6",
+    );
+    run(&mut interpreter);
+    assert_eq!(interpreter.errors().len(), 1);
+    assert!(interpreter.errors()[0].contains("line 3"));
+    assert!(interpreter.errors()[0].contains("text"));
+    assert_eq!(interpreter.pc.get().value, 8);
+    assert_eq!(interpreter.registers[5].value, 5);
+  }
+
+  #[test]
+  fn execute_from_stack_faults() {
+    let mut interpreter = interpreter("lui t0, 0x7ffff\njalr x0, 0(t0)");
+    run(&mut interpreter);
+    assert_eq!(interpreter.errors().len(), 1);
+    assert!(interpreter.errors()[0].contains("stack"));
+  }
+
+  #[test]
+  fn uart_console() {
+    let mut interpreter = interpreter(
+      "lui t0, 0x10000
+lbu t1, 0(t0)
+addi t1, t1, 1
+sb t1, 0(t0)",
+    );
+    interpreter.console_input("a".to_string());
+    run(&mut interpreter);
+    assert!(interpreter.errors().is_empty());
+    assert_eq!(interpreter.console_output(), "b");
+  }
+
+  #[test]
+  fn console_input_survives_reparsing() {
+    let mut interpreter = interpreter("");
+    interpreter.console_input("a".to_string());
+    interpreter.set_code(
+      "lui t0, 0x10000
+lbu t1, 0(t0)
+sb t1, 0(t0)"
+        .to_string(),
+    );
+    run(&mut interpreter);
+    assert_eq!(interpreter.console_output(), "a");
+  }
+
+  #[test]
+  fn leds_and_switches() {
+    let mut interpreter = interpreter(
+      "lui t0, 0x10001
+addi t1, x0, -1
+sw t1, 4(t0)
+sw t1, 8(t0)
+lw t2, 0(t0)
+srli t2, t2, 8
+sw t2, 12(t0)",
+    );
+    interpreter.set_switch(2, true);
+    run(&mut interpreter);
+    assert_eq!(
+      interpreter.leds(),
+      vec![false, false, true, false, false, false, false, false]
+    );
+    interpreter.set_code("".to_string());
+    assert_eq!(interpreter.leds(), vec![false; 8]);
+    assert!(interpreter.switches()[2]);
+  }
+
+  const TIMER_INTERRUPT: &str = "lui t0, 0x2004
+addi t1, x0, 20
+sd t1, 0(t0)
+addi t2, x0, 40
+csrrw x0, mtvec, t2
+addi t2, x0, 128
+csrrw x0, mie, t2
+csrrsi x0, mstatus, 8
+wfi
+jal x0, end
+handler:
+addi a0, x0, 1
+csrrs a1, mcause, x0
+end:";
+
+  #[test]
+  fn timer_interrupt_wakes_wfi() {
+    let mut interpreter = interpreter(TIMER_INTERRUPT);
+    assert!(
+      interpreter.errors().is_empty(),
+      "{:?}",
+      interpreter.errors()
+    );
+    run(&mut interpreter);
+    assert!(
+      interpreter.errors().is_empty(),
+      "{:?}",
+      interpreter.errors()
+    );
+    assert_eq!(interpreter.registers[10].value, 1);
+    assert_eq!(interpreter.registers[11].value, 1 << 63 | 7);
+    assert!(!interpreter.interrupts_enabled());
+  }
+
+  #[test]
+  fn interrupts_masked_by_mstatus() {
+    // Without mstatus.MIE the interrupt still wakes wfi, but isn't taken
+    let mut interpreter = interpreter(
+      &TIMER_INTERRUPT.replace("csrrsi x0, mstatus, 8", "addi x0, x0, 0"),
+    );
+    run(&mut interpreter);
+    assert_eq!(interpreter.registers[10].value, 0);
+    assert!(interpreter.interrupts()[2].2);
+  }
+
+  #[test]
+  fn uart_transmit_interrupts() {
+    // The handler sends three bytes, one per interrupt, then acknowledges
+    // the last one without sending anything so the program can carry on
+    let mut interpreter = interpreter(
+      "lui t0, 0x10000
+addi s1, x0, 3
+addi t1, x0, handler
+csrrw x0, mtvec, t1
+addi t1, x0, 1
+slli t1, t1, 11
+csrrw x0, mie, t1
+csrrsi x0, mstatus, 8
+addi t1, x0, 2
+sb t1, 1(t0)
+addi a0, x0, 1
+jal x0, end
+handler:
+lbu t2, 2(t0)
+beq s1, x0, done
+addi t3, x0, 120
+sb t3, 0(t0)
+addi s1, s1, -1
+done:
+mret
+end:",
+    );
+    interpreter.running = true;
+    interpreter.run_for(1000);
+    assert!(
+      !interpreter.running,
+      "The handler never stopped being called"
+    );
+    assert!(
+      interpreter.errors().is_empty(),
+      "{:?}",
+      interpreter.errors()
+    );
+    assert_eq!(interpreter.console_output(), "xxx");
+    assert_eq!(interpreter.registers[10].value, 1);
+  }
+
+  #[test]
+  fn csr_writes_to_x0_dont_read() {
+    // No CSR here has side effects when it's read, so the reads are checked
+    // directly
+    let mut interpreter = interpreter(
+      "addi t0, x0, 1
+csrrw x0, mscratch, t0
+csrrwi x0, mscratch, 3
+csrrw a0, mscratch, t0
+csrrwi a1, mscratch, 2",
+    );
+    run(&mut interpreter);
+    assert_eq!(interpreter.csrs.reads, [MSCRATCH, MSCRATCH]);
+    assert_eq!(interpreter.registers[10].value, 3);
+    assert_eq!(interpreter.registers[11].value, 1);
+    assert_eq!(interpreter.csrs.get(MSCRATCH), Some(2));
+  }
+
+  #[test]
+  fn unhandled_illegal_csr() {
+    let mut interpreter = interpreter("addi t0, x0, 1\ncsrrw x0, 0x7c0, t0");
+    run(&mut interpreter);
+    assert_eq!(interpreter.errors().len(), 1);
+    assert!(interpreter.errors()[0].contains("illegal instruction"));
+    assert!(interpreter.errors()[0].contains("line 2"));
+  }
+
+  // Identity maps the first GiB, aliases it at 0x80000000, then drops to
+  // supervisor mode to use the alias and fault on an unmapped address.
+  const SV39: &str = "lui t0, 0x10010
+addi t1, x0, 0xcf
+sd t1, 0(t0)
+addi t1, x0, 0xc7
+sd t1, 16(t0)
+addi t1, x0, 1
+slli t1, t1, 63
+lui t2, 0x10
+addi t2, t2, 0x10
+or t1, t1, t2
+csrrw x0, satp, t1
+addi t1, x0, 112
+csrrw x0, mtvec, t1
+addi t1, x0, 76
+csrrw x0, mepc, t1
+addi t1, x0, 1
+slli t1, t1, 11
+csrrs x0, mstatus, t1
+mret
+supervisor:
+addi a0, x0, 1
+slli a0, a0, 31
+lui t0, 0x10020
+add a0, a0, t0
+addi t1, x0, 42
+sd t1, 0(a0)
+addi t0, x0, 3
+slli t0, t0, 30
+ld a2, 0(t0)
+handler:
+csrrs a3, mcause, x0
+csrrs a4, mtval, x0";
+
+  #[test]
+  fn sv39_translation_and_page_faults() {
+    let mut interpreter = interpreter(SV39);
+    assert!(
+      interpreter.errors().is_empty(),
+      "{:?}",
+      interpreter.errors()
+    );
+    run(&mut interpreter);
+    assert!(
+      interpreter.errors().is_empty(),
+      "{:?}",
+      interpreter.errors()
+    );
+    // The store went through the alias to physical 0x10020000
+    let mut bytes = [0; 8];
+    interpreter.memory.peek(0x1002_0000, &mut bytes);
+    assert_eq!(u64::from_le_bytes(bytes), 42);
+    assert_eq!(interpreter.registers[13].value, 13); // Load page fault
+    assert_eq!(interpreter.registers[14].value, 0xc000_0000);
+    assert_eq!(interpreter.privilege(), "machine");
+    // The page fault happened before a2 was written
+    assert_eq!(interpreter.registers[12].value, 0);
+    let (entries, _, misses) = interpreter.tlb_stats();
+    assert_eq!(entries, 2);
+    assert_eq!(misses, 3);
+  }
+
+  #[test]
+  fn virtual_memory_view() {
+    let mut interpreter = interpreter(SV39);
+    run(&mut interpreter);
+    interpreter.set_virtual_memory_view(true);
+    assert_eq!(interpreter.memory_byte_repr(0x9002_0000, 1), vec!["2a"]);
+    assert_eq!(interpreter.memory_byte_repr(0xc000_0000, 1), vec!["--"]);
+    interpreter.set_virtual_memory_view(false);
+    assert_eq!(interpreter.memory_byte_repr(0x9002_0000, 1), vec!["00"]);
+  }
+
+  #[test]
+  fn ecall_without_handler() {
+    let mut interpreter = interpreter("ecall");
+    run(&mut interpreter);
+    assert_eq!(interpreter.errors().len(), 1);
+    assert!(interpreter.errors()[0]
+      .contains("environment call from machine mode on line 1"));
+  }
+
+  #[test]
+  fn flat_memory_model() {
+    let mut interpreter =
+      interpreter("addi t0, x0, 7\naddi t1, x0, 2000\nsb t0, 2000(t1)");
+    interpreter.set_memory_model(false, 8192);
+    assert_eq!(interpreter.memory_size(), 8192);
+    run(&mut interpreter);
+    assert_eq!(interpreter.memory_byte_repr(4000, 1), vec!["07"]);
+  }
+
+  #[test]
+  fn step_out_of_call() {
+    let mut interpreter = interpreter(RECURSIVE_SUM);
+    interpreter.toggle_breakpoint(7);
+    run_to_breakpoint(&mut interpreter);
+    interpreter.toggle_breakpoint(7);
+    interpreter.step_out();
+    assert_eq!(interpreter.next_inst_line_num(), 14 - 1);
+    assert_eq!(interpreter.call_stack().len(), 3);
+  }
+}
@@ -1,11 +1,51 @@
+mod assembler;
 mod build_common;
+mod cache;
+mod calling_convention;
 mod codegen;
+mod csr;
+#[cfg(not(target_family = "wasm"))]
+mod dap;
+#[cfg(not(target_family = "wasm"))]
+pub use dap::DapServer;
+mod devices;
+mod diff;
+mod disassembler;
+pub use disassembler::{disassemble, disassemble_elf, disassemble_raw};
+mod elf;
+pub use elf::is_elf;
+mod encoding;
+#[cfg(not(target_family = "wasm"))]
+mod gdb;
+#[cfg(not(target_family = "wasm"))]
+pub use gdb::{Connection, GdbStub};
 mod instruction;
 use instruction::*;
 #[macro_use]
 mod interface;
 mod interpreter;
+pub use interpreter::Interpreter;
+mod linker;
+pub use linker::{link, Executable, Object};
+#[cfg(not(target_family = "wasm"))]
+mod lsp;
+#[cfg(not(target_family = "wasm"))]
+pub use lsp::LanguageServer;
+mod macros;
+#[cfg(not(target_family = "wasm"))]
+pub use macros::read_includes;
+pub use macros::Files;
+mod memory;
+mod memory_map;
+mod mmu;
+mod operand;
+mod pipeline;
+mod predictor;
+mod profile;
 mod rv64_i;
+mod trace;
+pub use trace::{SharedBuffer, Trace, TraceFormat};
+mod uninitialised;
 mod utils;
 
 // When the `wee_alloc` feature is enabled, use `wee_alloc` as the global
@@ -0,0 +1,823 @@
+//! Assembling source files separately and linking them together, so that a
+//! program can be split across files, such as an assignment's harness and a
+//! student's solution. Each file is assembled into an object, where labels
+//! are local to the file unless they're declared with `.globl`, and any
+//! instruction which depends on the address of a label is left with a
+//! relocation for the linker to fill in. Objects can be read from and written
+//! to ELF relocatable object files, as `as` produces.
+
+use crate::assembler::assemble;
+use crate::disassembler::disassemble;
+use crate::elf::{self, NewSection};
+use crate::elf::{ET_REL, SHF_ALLOC, SHF_EXECINSTR, SHF_INFO_LINK};
+use crate::elf::{SHN_UNDEF, SHT_PROGBITS, SHT_RELA, SHT_STRTAB, SHT_SYMTAB};
+use crate::elf::{STB_GLOBAL, STB_LOCAL, STT_NOTYPE, STT_SECTION};
+use crate::encoding::encode;
+use crate::macros::Files;
+use std::collections::{BTreeSet, HashMap};
+use std::convert::TryInto;
+
+/// The relocations from the RISC-V ELF psABI which the assembler produces.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RelocationKind {
+  Branch, // The offset in a B type instruction
+  Jal,    // The offset in a jal
+  Call,   // The offset split across an auipc and the jalr after it
+  Hi20,   // The upper 20 bits of an address, from %hi
+  Lo12I,  // The lower 12 bits of an address in an I type instruction
+  Lo12S,  // The lower 12 bits of an address in an S type instruction
+}
+
+const R_RISCV_CALL_PLT: u32 = 19;
+const R_RISCV_RELAX: u32 = 51;
+
+impl RelocationKind {
+  fn elf_type(self) -> u32 {
+    match self {
+      RelocationKind::Branch => 16,
+      RelocationKind::Jal => 17,
+      RelocationKind::Call => 18,
+      RelocationKind::Hi20 => 26,
+      RelocationKind::Lo12I => 27,
+      RelocationKind::Lo12S => 28,
+    }
+  }
+
+  fn from_elf_type(elf_type: u32) -> Option<RelocationKind> {
+    match elf_type {
+      R_RISCV_CALL_PLT => Some(RelocationKind::Call),
+      _ => [
+        RelocationKind::Branch,
+        RelocationKind::Jal,
+        RelocationKind::Call,
+        RelocationKind::Hi20,
+        RelocationKind::Lo12I,
+        RelocationKind::Lo12S,
+      ]
+      .iter()
+      .copied()
+      .find(|kind| kind.elf_type() == elf_type),
+    }
+  }
+}
+
+/// Where the linker has to fill in the address of `symbol` (plus `addend`)
+/// in the instruction at `offset`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Relocation {
+  pub offset: u64,
+  pub kind: RelocationKind,
+  pub symbol: String,
+  pub addend: i64,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Symbol {
+  pub name: String,
+  pub offset: Option<u64>, // None if it's defined in another file
+  pub global: bool,
+}
+
+/// The name of the symbol for the start of an object's code, which
+/// relocations can be relative to.
+const TEXT: &str = ".text";
+
+pub struct Object {
+  pub name: String,
+  pub source: String,
+  pub text: Vec<u32>,
+  pub lines: Vec<u32>, // The line in the source of each instruction
+  pub symbols: Vec<Symbol>,
+  pub relocations: Vec<Relocation>,
+}
+
+impl Object {
+  /// Assembles the source file `name`, which can include `files`. The
+  /// errors are all given with the file's name.
+  pub fn assemble(
+    name: &str,
+    source: &str,
+    files: &Files,
+  ) -> Result<Object, Vec<String>> {
+    let assembly = assemble(source, files, true);
+    if !assembly.errors.is_empty() {
+      let errors = assembly.errors.iter();
+      return Err(errors.map(|e| format!("{}: {}", name, e)).collect());
+    }
+    let mut text = Vec::new();
+    let mut errors = Vec::new();
+    for instruction in &assembly.instructions {
+      match encode(instruction) {
+        Some(word) => text.push(word),
+        None => errors.push(format!(
+          "{}: Error on line {}: `{}` has no machine code encoding",
+          name, instruction.line_num, instruction.source.mnemonic
+        )),
+      }
+    }
+    if !errors.is_empty() {
+      return Err(errors);
+    }
+    let symbols = assembly.symbols;
+    let mut defined: Vec<(&String, &u64)> = symbols.labels.iter().collect();
+    defined.sort_by_key(|(name, offset)| (**offset, name.to_string()));
+    let mut object_symbols: Vec<Symbol> = defined
+      .into_iter()
+      .map(|(name, offset)| Symbol {
+        name: name.clone(),
+        offset: Some(*offset),
+        global: symbols.globals.contains(name),
+      })
+      .collect();
+    let undefined: BTreeSet<&String> = symbols
+      .relocations
+      .iter()
+      .map(|relocation| &relocation.symbol)
+      .filter(|symbol| !symbols.labels.contains_key(*symbol))
+      .collect();
+    object_symbols.extend(undefined.into_iter().map(|name| Symbol {
+      name: name.clone(),
+      offset: None,
+      global: true,
+    }));
+    Ok(Object {
+      name: name.to_string(),
+      source: source.to_string(),
+      text,
+      lines: assembly.instructions.iter().map(|i| i.line_num).collect(),
+      symbols: object_symbols,
+      relocations: symbols.relocations,
+    })
+  }
+
+  /// The object as an ELF relocatable object file.
+  pub fn to_elf(&self) -> Vec<u8> {
+    let mut strings = vec![0u8];
+    let mut symbol_table = elf::symbol_entry(0, 0, 0, 0);
+    symbol_table.extend(elf::symbol_entry(0, STT_SECTION, 1, 0));
+    let mut indices: HashMap<&str, u32> = HashMap::from([(TEXT, 1)]);
+    // Local symbols have to come before global ones
+    let mut symbols: Vec<&Symbol> = self.symbols.iter().collect();
+    symbols.sort_by_key(|symbol| symbol.global);
+    let first_global = 2 + symbols.iter().filter(|s| !s.global).count();
+    for symbol in symbols {
+      let binding = if symbol.global { STB_GLOBAL } else { STB_LOCAL };
+      let section = if symbol.offset.is_some() {
+        1
+      } else {
+        SHN_UNDEF
+      };
+      indices.insert(&symbol.name, (symbol_table.len() / 24) as u32);
+      symbol_table.extend(elf::symbol_entry(
+        strings.len() as u32,
+        binding << 4 | STT_NOTYPE,
+        section as u16,
+        symbol.offset.unwrap_or(0),
+      ));
+      strings.extend(symbol.name.as_bytes());
+      strings.push(0);
+    }
+    let relocations = self.relocations.iter().flat_map(|relocation| {
+      elf::rela_entry(
+        relocation.offset,
+        indices[relocation.symbol.as_str()],
+        relocation.kind.elf_type(),
+        relocation.addend,
+      )
+    });
+    let section = |name, kind, flags, contents, link, info, alignment| {
+      let entry_size = match kind {
+        SHT_SYMTAB | SHT_RELA => 24,
+        _ => 0,
+      };
+      NewSection {
+        name,
+        kind,
+        flags,
+        contents,
+        link,
+        info,
+        alignment,
+        entry_size,
+      }
+    };
+    let text = self
+      .text
+      .iter()
+      .flat_map(|word| word.to_le_bytes())
+      .collect();
+    elf::write(
+      ET_REL as u16,
+      &[
+        section(
+          ".text",
+          SHT_PROGBITS,
+          SHF_ALLOC | SHF_EXECINSTR,
+          text,
+          0,
+          0,
+          4,
+        ),
+        section(
+          ".rela.text",
+          SHT_RELA,
+          SHF_INFO_LINK,
+          relocations.collect(),
+          3,
+          1,
+          8,
+        ),
+        section(
+          ".symtab",
+          SHT_SYMTAB,
+          0,
+          symbol_table,
+          4,
+          first_global as u32,
+          8,
+        ),
+        section(".strtab", SHT_STRTAB, 0, strings, 0, 0, 1),
+      ],
+    )
+  }
+
+  /// Reads an ELF relocatable object file, such as `as` produces. The code
+  /// has to be in one section, without compressed instructions, and the
+  /// source shown for it is its disassembly.
+  pub fn from_elf(name: &str, bytes: &[u8]) -> Result<Object, String> {
+    if elf::file_type(bytes)? != ET_REL {
+      return Err(format!("{} isn't a relocatable object file", name));
+    }
+    let sections = elf::sections(bytes)?;
+    let code: Vec<usize> = (0..sections.len())
+      .filter(|i| sections[*i].is_code() && sections[*i].size > 0)
+      .collect();
+    let text_index = match code[..] {
+      [index] => index,
+      [] => return Err(format!("{} has no code", name)),
+      _ => return Err(format!("{} has more than one code section", name)),
+    };
+    let text: Vec<u32> = sections[text_index]
+      .contents(bytes)?
+      .chunks(4)
+      .map(|word| word.try_into().map(u32::from_le_bytes).unwrap_or(0))
+      .collect();
+    if let Some(i) = text.iter().position(|word| word & 0b11 != 0b11) {
+      return Err(format!(
+        "{} has a compressed instruction at 0x{:x}, which isn't supported",
+        name,
+        i * 4
+      ));
+    }
+
+    // The symbols' names by their index, which relocations refer to them by
+    let mut names = Vec::new();
+    let mut symbols = Vec::new();
+    let mut relocations = Vec::new();
+    for table in sections.iter().filter(|s| s.kind == SHT_SYMTAB) {
+      for symbol in elf::symbols(bytes, &sections, table)? {
+        let in_text = symbol.section == text_index as u64;
+        names.push(match symbol.kind {
+          STT_SECTION if in_text => Some(TEXT.to_string()),
+          _ if in_text || symbol.section == SHN_UNDEF => Some(symbol.name),
+          _ => None,
+        });
+        let name = names.last().unwrap().clone().unwrap_or_default();
+        if name.is_empty() || name.starts_with('$') {
+          continue; // Mapping symbols such as $x, and the null symbol
+        }
+        symbols.push(Symbol {
+          name,
+          offset: Some(symbol.value).filter(|_| in_text),
+          global: symbol.binding != STB_LOCAL,
+        });
+      }
+    }
+    symbols.push(Symbol {
+      name: TEXT.to_string(),
+      offset: Some(0),
+      global: false,
+    });
+    for table in sections
+      .iter()
+      .filter(|s| s.kind == SHT_RELA && s.info == text_index as u64)
+    {
+      for rela in elf::relocations(bytes, table)? {
+        if rela.kind == R_RISCV_RELAX {
+          continue; // Linker relaxation is optional
+        }
+        let kind =
+          RelocationKind::from_elf_type(rela.kind).ok_or_else(|| {
+            format!(
+              "{} has an unsupported relocation, type {}",
+              name, rela.kind
+            )
+          })?;
+        if rela.offset % 4 != 0 || rela.offset / 4 >= text.len() as u64 {
+          return Err(format!(
+            "{} has a relocation at 0x{:x}, which isn't an instruction in \
+             its code",
+            name, rela.offset
+          ));
+        }
+        let symbol =
+          names.get(rela.symbol).cloned().flatten().ok_or_else(|| {
+            format!(
+              "{} refers to data at 0x{:x}, but only code is supported",
+              name, rela.offset
+            )
+          })?;
+        relocations.push(Relocation {
+          offset: rela.offset,
+          kind,
+          symbol,
+          addend: rela.addend,
+        });
+      }
+    }
+
+    let mut source = String::new();
+    for (i, word) in text.iter().enumerate() {
+      let offset = i as u64 * 4;
+      let label = symbols
+        .iter()
+        .find(|s| s.offset == Some(offset) && s.name != TEXT)
+        .map_or(String::new(), |symbol| format!("{}: ", symbol.name));
+      let instruction = disassemble(*word, offset).unwrap_or_default();
+      source.push_str(&format!("{}{}\n", label, instruction));
+    }
+    Ok(Object {
+      name: name.to_string(),
+      source,
+      lines: (1..=text.len() as u32).collect(),
+      text,
+      symbols,
+      relocations,
+    })
+  }
+}
+
+/// A linked program, with the code of each object one after the other from
+/// address 0.
+pub struct Executable {
+  pub text: Vec<u32>,
+  pub source: String, // The objects' sources, one after the other
+  pub lines: Vec<u32>, // The line in the source of each instruction
+  pub labels: HashMap<String, u64>,
+  files: Vec<(String, u32)>, // Each object's name, and its first line - 1
+}
+
+impl Executable {
+  /// The file and line in it of a line in the combined source, or None if
+  /// nothing was linked.
+  pub fn location(&self, line: u32) -> Option<(&str, u32)> {
+    let (name, before) = self
+      .files
+      .iter()
+      .rev()
+      .find(|(_, before)| *before < line)
+      .or_else(|| self.files.first())?;
+    Some((name, line - before))
+  }
+
+  /// Rewrites the line numbers in a message (e.g. "Memory fault on line 12")
+  /// to be the line in the file it's from. Lines which already say which
+  /// file they're in, such as in an included file, are left alone.
+  pub fn locate(&self, message: &str) -> String {
+    let mut located = String::new();
+    let mut rest = message;
+    while let Some(start) = rest.find("line ") {
+      let digits = &rest[start + 5..];
+      let length = digits.find(|c: char| !c.is_ascii_digit());
+      let number = &digits[..length.unwrap_or(digits.len())];
+      located.push_str(&rest[..start + 5]);
+      rest = &digits[number.len()..];
+      match number.parse::<u32>() {
+        Ok(line) if line > 0 && !rest.starts_with(" of ") => {
+          match self.location(line) {
+            Some((name, line)) => {
+              located.push_str(&format!("{} of {}", line, name))
+            }
+            None => located.push_str(number),
+          }
+        }
+        _ => located.push_str(number),
+      }
+    }
+    located.push_str(rest);
+    located
+  }
+}
+
+fn bits(value: i64, high: u32, low: u32) -> u32 {
+  ((value >> low) as u32) & ((1 << (high - low + 1)) - 1)
+}
+
+fn low_12_bits(value: i64) -> i64 {
+  (value << 52) >> 52
+}
+
+fn fits(value: i64, bits: u32) -> bool {
+  (-(1 << (bits - 1))..(1 << (bits - 1))).contains(&value)
+}
+
+/// Fills in the relocation, which is `value` (the symbol's address plus the
+/// addend) for the instruction at `pc`, which is `text[index]`.
+fn relocate(
+  text: &mut [u32],
+  index: usize,
+  kind: RelocationKind,
+  value: i64,
+  pc: u64,
+) -> Result<(), &'static str> {
+  let offset = value.wrapping_sub(pc as i64);
+  let word = text[index];
+  text[index] = match kind {
+    RelocationKind::Branch => {
+      if !fits(offset, 13) || offset % 2 != 0 {
+        return Err("out of range of the branch");
+      }
+      word & 0x01ff_f07f
+        | bits(offset, 12, 12) << 31
+        | bits(offset, 10, 5) << 25
+        | bits(offset, 4, 1) << 8
+        | bits(offset, 11, 11) << 7
+    }
+    RelocationKind::Jal => {
+      if !fits(offset, 21) || offset % 2 != 0 {
+        return Err("out of range of the jal");
+      }
+      word & 0xfff
+        | bits(offset, 20, 20) << 31
+        | bits(offset, 10, 1) << 21
+        | bits(offset, 11, 11) << 20
+        | bits(offset, 19, 12) << 12
+    }
+    RelocationKind::Call => {
+      let next = text.get(index + 1).copied().unwrap_or(0);
+      // jalr, or anything else I type which uses the register
+      if ![0x67, 0x13, 0x03].contains(&(next & 0x7f)) {
+        return Err("for a call, but the auipc isn't followed by a jalr");
+      }
+      let high = offset.wrapping_add(0x800) >> 12;
+      if !fits(high, 20) {
+        return Err("out of range of the call");
+      }
+      text[index + 1] = next & 0xfffff | bits(low_12_bits(offset), 11, 0) << 20;
+      word & 0xfff | bits(high, 19, 0) << 12
+    }
+    RelocationKind::Hi20 => {
+      if !fits(value.wrapping_add(0x800), 32) {
+        return Err("too big for %hi");
+      }
+      word & 0xfff | bits(value.wrapping_add(0x800) >> 12, 19, 0) << 12
+    }
+    RelocationKind::Lo12I => {
+      word & 0xfffff | bits(low_12_bits(value), 11, 0) << 20
+    }
+    RelocationKind::Lo12S => {
+      let low = low_12_bits(value);
+      word & 0x01ff_f07f | bits(low, 11, 5) << 25 | bits(low, 4, 0) << 7
+    }
+  };
+  Ok(())
+}
+
+/// Links the objects into a program, in order, so the program starts at
+/// the beginning of the first object.
+pub fn link(objects: &[Object]) -> Result<Executable, Vec<String>> {
+  let mut errors = Vec::new();
+  let mut bases = Vec::new();
+  let mut text = Vec::new();
+  for object in objects {
+    bases.push(text.len() as u64 * 4);
+    text.extend(&object.text);
+  }
+
+  let mut globals: HashMap<&str, (u64, &str)> = HashMap::new();
+  for (object, base) in objects.iter().zip(&bases) {
+    for symbol in object.symbols.iter().filter(|s| s.global) {
+      if let Some(offset) = symbol.offset {
+        let definition = (base.wrapping_add(offset), object.name.as_str());
+        if let Some((_, other)) = globals.insert(&symbol.name, definition) {
+          errors.push(format!(
+            "`{}` is defined in both {} and {}",
+            symbol.name, other, object.name
+          ));
+        }
+      }
+    }
+  }
+
+  for (object, base) in objects.iter().zip(&bases) {
+    for relocation in &object.relocations {
+      let local = object
+        .symbols
+        .iter()
+        .find(|s| s.name == relocation.symbol && s.offset.is_some())
+        .map(|symbol| base.wrapping_add(symbol.offset.unwrap()));
+      let address = match local
+        .or_else(|| globals.get(relocation.symbol.as_str()).map(|(a, _)| *a))
+      {
+        Some(address) => address,
+        None => {
+          let owner = objects.iter().find(|o| {
+            o.symbols
+              .iter()
+              .any(|s| s.name == relocation.symbol && s.offset.is_some())
+          });
+          errors.push(match owner {
+            Some(owner) => format!(
+              "{}: `{}` is local to {}, so it needs `.globl {}` there",
+              object.name, relocation.symbol, owner.name, relocation.symbol
+            ),
+            None => format!(
+              "{}: undefined symbol `{}`",
+              object.name, relocation.symbol
+            ),
+          });
+          continue;
+        }
+      };
+      let pc = base + relocation.offset;
+      let value = (address as i64).wrapping_add(relocation.addend);
+      let index = (pc / 4) as usize;
+      if let Err(problem) =
+        relocate(&mut text, index, relocation.kind, value, pc)
+      {
+        errors.push(format!(
+          "{}: `{}` is {} at 0x{:x}",
+          object.name, relocation.symbol, problem, relocation.offset
+        ));
+      }
+    }
+  }
+  if !errors.is_empty() {
+    return Err(errors);
+  }
+
+  let mut source = String::new();
+  let mut lines = Vec::new();
+  let mut files = Vec::new();
+  let mut labels = HashMap::new();
+  for (object, base) in objects.iter().zip(&bases) {
+    let before = source.lines().count() as u32;
+    files.push((object.name.clone(), before));
+    lines.extend(object.lines.iter().map(|line| line + before));
+    source.push_str(&object.source);
+    if !source.ends_with('\n') {
+      source.push('\n');
+    }
+    // Local labels can have the same name in different files
+    for symbol in object.symbols.iter().filter(|s| s.name != TEXT) {
+      if let Some(offset) = symbol.offset {
+        labels
+          .entry(symbol.name.clone())
+          .or_insert(base.wrapping_add(offset));
+      }
+    }
+  }
+  Ok(Executable {
+    text,
+    source,
+    lines,
+    labels,
+    files,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const HARNESS: &str = "\
+    .globl main
+    addi a0, zero, 20
+    jal ra, main
+    lui t0, %hi(result)
+    sw a0, %lo(result)(t0)
+    call: auipc ra, %pcrel_hi(double)
+    jalr ra, %pcrel_lo(call)(ra)
+    beq zero, zero, done
+    done: ecall";
+
+  const STUDENT: &str = "\
+    .globl main
+    .global double, result
+    main: addi a0, a0, 1
+    jalr zero, 0(ra)
+    double: add a0, a0, a0
+    result: jalr zero, 0(ra)";
+
+  fn object(name: &str, source: &str) -> Result<Object, Vec<String>> {
+    Object::assemble(name, source, &Files::new())
+  }
+
+  fn objects() -> Vec<Object> {
+    vec![
+      object("harness.s", HARNESS).unwrap(),
+      object("student.s", STUDENT).unwrap(),
+    ]
+  }
+
+  fn disassembly(text: &[u32]) -> Vec<String> {
+    (text.iter().enumerate())
+      .map(|(i, word)| disassemble(*word, i as u64 * 4).unwrap())
+      .collect()
+  }
+
+  fn relocation(offset: u64, kind: RelocationKind, symbol: &str) -> Relocation {
+    Relocation {
+      offset,
+      kind,
+      symbol: symbol.to_string(),
+      addend: 0,
+    }
+  }
+
+  #[test]
+  fn objects_leave_other_files_to_the_linker() {
+    let harness = &objects()[0];
+    assert_eq!(
+      harness.relocations,
+      [
+        relocation(4, RelocationKind::Jal, "main"),
+        relocation(8, RelocationKind::Hi20, "result"),
+        relocation(12, RelocationKind::Lo12S, "result"),
+        relocation(16, RelocationKind::Call, "double"),
+      ]
+    );
+    // The branch is to a label in the same file, so it's already resolved
+    assert_eq!(disassembly(&harness.text)[6], "beqz zero,0x1c");
+  }
+
+  #[test]
+  fn links_files_together() {
+    let executable = link(&objects()).unwrap();
+    assert_eq!(
+      disassembly(&executable.text),
+      [
+        "li a0,20",
+        "jal 0x20",
+        "lui t0,0x0",
+        "sw a0,44(t0)",
+        "auipc ra,0x0",
+        "jalr ra,24(ra)",
+        "beqz zero,0x1c",
+        "ecall",
+        "addi a0,a0,1",
+        "ret",
+        "add a0,a0,a0",
+        "ret",
+      ]
+    );
+    assert_eq!(executable.labels["double"], 0x28);
+    assert_eq!(executable.lines[8], 9 + 3);
+    assert_eq!(executable.location(12), Some(("student.s", 3)));
+    assert_eq!(
+      executable.locate("Memory fault on line 12, from line 2"),
+      "Memory fault on line 3 of student.s, from line 2 of harness.s"
+    );
+    assert_eq!(
+      executable.locate("Error on line 12, from line 4 of macros.s"),
+      "Error on line 3 of student.s, from line 4 of macros.s"
+    );
+  }
+
+  #[test]
+  fn link_errors() {
+    let harness = object("harness.s", "jal ra, main").unwrap();
+    let student = object("student.s", "main: ecall").unwrap();
+    assert_eq!(
+      link(&[harness, student]).err().unwrap(),
+      [
+        "harness.s: `main` is local to student.s, so it needs `.globl main` \
+        there"
+      ]
+    );
+    let a = object("a.s", ".globl f\nf: ecall").unwrap();
+    let b = object("b.s", ".globl f\nf: ecall\njal ra, g").unwrap();
+    assert_eq!(
+      link(&[a, b]).err().unwrap(),
+      [
+        "`f` is defined in both a.s and b.s",
+        "b.s: undefined symbol `g`"
+      ]
+    );
+    assert_eq!(
+      object("a.s", "addi a0, zero, f").err().unwrap(),
+      [
+        "a.s: Error on line 1: expected 12 bit immediate, found `f`, which \
+         is an address that isn't known until the program is linked"
+      ]
+    );
+  }
+
+  #[test]
+  fn elf_objects_round_trip() {
+    for object in objects() {
+      let elf = object.to_elf();
+      assert!(crate::disassemble_elf(&elf).is_ok());
+      let read = Object::from_elf(&object.name, &elf).unwrap();
+      assert_eq!(read.text, object.text);
+      assert_eq!(read.relocations, object.relocations);
+      for symbol in &object.symbols {
+        assert!(read.symbols.contains(symbol), "{:?}", symbol);
+      }
+    }
+    let executable = link(&objects()).unwrap();
+    let read: Vec<Object> = objects()
+      .iter()
+      .map(|object| Object::from_elf(&object.name, &object.to_elf()).unwrap())
+      .collect();
+    assert_eq!(link(&read).unwrap().text, executable.text);
+
+    // Relocations have to be at an instruction
+    for offset in [2, 0x1000] {
+      let mut object = object("a.s", "jal ra, f").unwrap();
+      object.relocations[0].offset = offset;
+      assert_eq!(
+        Object::from_elf("a.o", &object.to_elf()).err().unwrap(),
+        format!(
+          "a.o has a relocation at 0x{:x}, which isn't an instruction in its \
+           code",
+          offset
+        )
+      );
+    }
+  }
+
+  /// Where the `field` of section `section`'s header is in an ELF file.
+  /// Section 1 is .text, 2 .rela.text, 3 .symtab and 4 .strtab.
+  fn header_field(elf: &[u8], section: usize, field: usize) -> usize {
+    let headers = u64::from_le_bytes(elf[0x28..0x30].try_into().unwrap());
+    headers as usize + section * 64 + field
+  }
+
+  fn patched(elf: &[u8], at: usize, value: u64) -> Vec<u8> {
+    let mut bytes = elf.to_vec();
+    bytes[at..at + 8].copy_from_slice(&value.to_le_bytes());
+    bytes
+  }
+
+  #[test]
+  fn malformed_elf_objects() {
+    let elf = object("harness.s", HARNESS).unwrap().to_elf();
+    let bad = patched(&elf, 0x28, 0xffff_ffff_ffff_fffe);
+    let truncated = "The ELF file is truncated at 0xfffffffffffffffe";
+    assert_eq!(Object::from_elf("a.o", &bad).err().unwrap(), truncated);
+    assert_eq!(crate::disassemble_elf(&bad).err().unwrap(), truncated);
+    // Each section's offset and size
+    for section in 1..=3 {
+      for field in [0x18, 0x20] {
+        let at = header_field(&elf, section, field);
+        let bad = patched(&elf, at, u64::MAX - 1);
+        assert!(Object::from_elf("a.o", &bad).is_err());
+        // The disassembler doesn't look at relocations
+        assert_eq!(crate::disassemble_elf(&bad).is_err(), section != 2);
+      }
+    }
+    // Names which aren't in the string table are just empty
+    let bad = patched(&elf, header_field(&elf, 4, 0x18), u64::MAX - 1);
+    assert!(Object::from_elf("a.o", &bad).is_ok());
+    assert!(crate::disassemble_elf(&bad).is_ok());
+
+    // Symbols at addresses which wrap around are out of range of everything
+    let mut bad = object("student.s", STUDENT).unwrap().to_elf();
+    let at = header_field(&bad, 3, 0x18);
+    let symbols = u64::from_le_bytes(bad[at..at + 8].try_into().unwrap());
+    // After the null symbol and the one for .text
+    for entry in (symbols as usize + 48..bad.len()).step_by(24).take(3) {
+      bad = patched(&bad, entry + 8, u64::MAX);
+    }
+    let student = Object::from_elf("student.o", &bad).unwrap();
+    assert!(link(&[object("harness.s", HARNESS).unwrap(), student]).is_err());
+  }
+
+  #[test]
+  fn jal_relocations_reach_1_mib() {
+    for offset in [600_000, 0xf_fffe, -0x10_0000, 0x10_0000, -0x10_0002] {
+      let mut text = [0x0000_00ef]; // jal ra, 0
+      let result = relocate(
+        &mut text,
+        0,
+        RelocationKind::Jal,
+        0x20_0000 + offset,
+        0x20_0000,
+      );
+      let fits = (-0x10_0000..0x10_0000).contains(&offset);
+      assert_eq!(result.is_ok(), fits, "{}", offset);
+      if fits {
+        let jal = crate::encoding::instruction(text[0], 1).unwrap();
+        assert_eq!(jal.imm_arg("offset").unwrap().value as i64, offset);
+      }
+    }
+  }
+
+  #[test]
+  fn nothing_linked() {
+    let executable = link(&[]).unwrap();
+    assert_eq!(executable.location(1), None);
+    assert_eq!(executable.locate("Error on line 1"), "Error on line 1");
+  }
+}
@@ -0,0 +1,567 @@
+//! A Language Server Protocol server for the assembly the interpreter runs,
+//! talking over stdin and stdout (`riscv-interpreter --lsp`). Everything it
+//! knows comes from the same places as the interpreter: diagnostics are the
+//! parser's errors, and the documentation is the instruction table.
+
+use crate::assembler::assemble;
+use crate::codegen::{INSTRUCTIONS, REGISTERS, REGISTER_INFO};
+use crate::dap::{read_message, write_message};
+use crate::macros::{read_includes, statements};
+use crate::Saver;
+use crate::{split_label, valid_label};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+
+// CompletionItemKind
+const KEYWORD: u32 = 14;
+const VARIABLE: u32 = 6;
+const REFERENCE: u32 = 18;
+
+/// The length of `text` in UTF-16 code units, which is how positions in a
+/// line are given.
+fn utf16_len(text: &str) -> u32 {
+  text.encode_utf16().count() as u32
+}
+
+/// Which character of `line` a position `column` UTF-16 code units into it
+/// is at.
+fn char_index(line: &str, column: usize) -> usize {
+  let mut units = 0;
+  line
+    .chars()
+    .take_while(|c| {
+      units += c.len_utf16();
+      units <= column
+    })
+    .count()
+}
+
+/// A label definition, with its position in the source (0 indexed).
+#[derive(Clone, Debug, PartialEq)]
+struct Label {
+  name: String,
+  line: u32,
+  character: u32,
+}
+
+fn labels(text: &str) -> Vec<Label> {
+  let lines: Vec<&str> = text.lines().collect();
+  statements(text)
+    .unwrap_or_default()
+    .into_iter()
+    .filter_map(|(line, statement)| {
+      let (label, _) = split_label(&statement);
+      let label = label.filter(|label| valid_label(label))?;
+      let source = lines.get(line as usize - 1).unwrap_or(&"");
+      let start = source.find(label).unwrap_or(0);
+      Some(Label {
+        name: label.to_string(),
+        line: line - 1,
+        character: utf16_len(&source[..start]),
+      })
+    })
+    .collect()
+}
+
+fn is_word_char(c: char) -> bool {
+  c.is_alphanumeric() || c == '_' || c == '.'
+}
+
+/// The word containing the given character of the line.
+fn word_at(line: &str, character: usize) -> Option<&str> {
+  let chars: Vec<(usize, char)> = line.char_indices().collect();
+  let is_word = |i: usize| chars.get(i).is_some_and(|(_, c)| is_word_char(*c));
+  // The cursor can be just after the word
+  let i = if is_word(character) {
+    character
+  } else if character > 0 && is_word(character - 1) {
+    character - 1
+  } else {
+    return None;
+  };
+  let start = (0..=i).rev().take_while(|&j| is_word(j)).last()?;
+  let end = (i..chars.len()).take_while(|&j| is_word(j)).last()?;
+  let end = chars.get(end + 1).map_or(line.len(), |(offset, _)| *offset);
+  Some(&line[chars[start].0..end])
+}
+
+/// The instruction on a line, and how far into its operands the cursor is.
+/// Returns the mnemonic and the number of separators before the cursor, or
+/// None if the cursor's still on the label or mnemonic.
+fn operand_position(line: &str, character: usize) -> Option<(&str, usize)> {
+  let before: String = line.chars().take(character).collect();
+  // Only the last statement on the line matters
+  let start = before.rfind(';').map_or(0, |i| i + 1);
+  let (_, instruction) = split_label(&before[start..]);
+  let (mnemonic, operands) = instruction.split_once(char::is_whitespace)?;
+  let separators = operands.matches([',', '(']).count();
+  let mnemonic_start = start + line[start..].find(mnemonic)?;
+  Some((&line[mnemonic_start..][..mnemonic.len()], separators))
+}
+
+fn instruction_documentation(mnemonic: &str) -> Option<String> {
+  let instruction = INSTRUCTIONS.get(mnemonic)?;
+  let mut documentation = format!("```\n{}\n```\n", instruction.signature());
+  if !instruction.expansion.is_empty() {
+    documentation.push_str(&format!("**{}**\n\n", instruction.expansion));
+  }
+  documentation.push_str(instruction.description);
+  Some(documentation)
+}
+
+fn register_documentation(register: &str) -> Option<String> {
+  let info = &REGISTER_INFO[*REGISTERS.get(register)? as usize];
+  let saver = match info.saver {
+    Saver::Caller => ", caller saved",
+    Saver::Callee => ", callee saved",
+    Saver::Neither => "",
+  };
+  Some(format!("`{}` is `{}`{}", register, info.name, saver))
+}
+
+struct Document {
+  text: String,
+}
+
+pub struct LanguageServer<R: BufRead, W: Write> {
+  input: R,
+  output: W,
+  documents: HashMap<String, Document>,
+}
+
+impl<R: BufRead, W: Write> LanguageServer<R, W> {
+  pub fn new(input: R, output: W) -> Self {
+    LanguageServer {
+      input,
+      output,
+      documents: HashMap::new(),
+    }
+  }
+
+  /// Handles messages until the client sends exit or closes the input.
+  pub fn serve(&mut self) -> io::Result<()> {
+    while let Some(message) = read_message(&mut self.input)? {
+      let method = message["method"].as_str().unwrap_or("");
+      if method == "exit" {
+        return Ok(());
+      }
+      let params = &message["params"];
+      let result = self.handle(method, params)?;
+      // Notifications don't have an id, and don't get a response
+      if message.get("id").is_some() {
+        let response = match result {
+          Some(result) => json!({ "id": message["id"], "result": result }),
+          None => json!({
+            "id": message["id"],
+            "error": { "code": -32601,
+                       "message": format!("Unsupported method {}", method) },
+          }),
+        };
+        self.send(response)?;
+      }
+    }
+    Ok(())
+  }
+
+  fn send(&mut self, mut message: Value) -> io::Result<()> {
+    message["jsonrpc"] = json!("2.0");
+    write_message(&mut self.output, &message)
+  }
+
+  /// The result of a request, or None if it isn't supported.
+  fn handle(
+    &mut self,
+    method: &str,
+    params: &Value,
+  ) -> io::Result<Option<Value>> {
+    let uri = params["textDocument"]["uri"].as_str().unwrap_or("");
+    let result = match method {
+      "initialize" => json!({
+        "capabilities": {
+          "textDocumentSync": 1, // The whole document is sent each change
+          "hoverProvider": true,
+          "completionProvider": {},
+          "definitionProvider": true,
+          "signatureHelpProvider": { "triggerCharacters": [" ", ",", "("] },
+        },
+        "serverInfo": { "name": "riscv-interpreter" },
+      }),
+      "shutdown" => Value::Null,
+      "textDocument/didOpen" => {
+        let text = params["textDocument"]["text"].as_str().unwrap_or("");
+        self.update(uri, text.to_string())?;
+        Value::Null
+      }
+      "textDocument/didChange" => {
+        let changes = params["contentChanges"].as_array();
+        if let Some(text) = changes
+          .and_then(|changes| changes.last())
+          .and_then(|change| change["text"].as_str())
+        {
+          self.update(uri, text.to_string())?;
+        }
+        Value::Null
+      }
+      "textDocument/didClose" => {
+        self.documents.remove(uri);
+        self.publish_diagnostics(uri, Vec::new())?;
+        Value::Null
+      }
+      "textDocument/hover" => self.at_position(params, Self::hover),
+      "textDocument/completion" => self.at_position(params, Self::complete),
+      "textDocument/definition" => self.at_position(params, Self::definition),
+      "textDocument/signatureHelp" => {
+        self.at_position(params, Self::signature_help)
+      }
+      _ if method.starts_with("$/") || method == "initialized" => Value::Null,
+      _ => return Ok(None),
+    };
+    Ok(Some(result))
+  }
+
+  fn update(&mut self, uri: &str, text: String) -> io::Result<()> {
+    // Included files come from disk, unless they're open with changes
+    let path = Path::new(uri.strip_prefix("file://").unwrap_or(uri));
+    let directory = path.parent().unwrap_or(Path::new(""));
+    let mut files = read_includes(directory, &text).unwrap_or_default();
+    for (other, document) in &self.documents {
+      let name = other.rsplit('/').next().unwrap_or(other);
+      if let Some(code) = files.get_mut(name) {
+        code.clone_from(&document.text);
+      }
+    }
+    let lines: Vec<&str> = text.lines().collect();
+    let diagnostics = assemble(&text, &files, false)
+      .errors
+      .into_iter()
+      .map(|error| {
+        let line = error.line.saturating_sub(1); /* 1 indexed */
+        let source = lines.get(line as usize).copied().unwrap_or("");
+        let column = |byte| utf16_len(source.get(..byte).unwrap_or(source));
+        json!({
+          "range": {
+            "start": { "line": line, "character": column(error.columns.start) },
+            "end": { "line": line, "character": column(error.columns.end) },
+          },
+          "severity": 1,
+          "source": "riscv-interpreter",
+          "message": error.message,
+        })
+      })
+      .collect();
+    self.documents.insert(uri.to_string(), Document { text });
+    self.publish_diagnostics(uri, diagnostics)
+  }
+
+  fn publish_diagnostics(
+    &mut self,
+    uri: &str,
+    diagnostics: Vec<Value>,
+  ) -> io::Result<()> {
+    self.send(json!({
+      "method": "textDocument/publishDiagnostics",
+      "params": { "uri": uri, "diagnostics": diagnostics },
+    }))
+  }
+
+  /// Calls `f` with the document, its line at the position and the index of
+  /// the character within the line. The result is null if the document isn't open.
+  fn at_position(
+    &self,
+    params: &Value,
+    f: fn(&str, &Document, &str, usize) -> Value,
+  ) -> Value {
+    let uri = params["textDocument"]["uri"].as_str().unwrap_or("");
+    let line = params["position"]["line"].as_u64().unwrap_or(0) as usize;
+    let column = params["position"]["character"].as_u64().unwrap_or(0);
+    match self.documents.get(uri) {
+      Some(document) => {
+        let source = document.text.lines().nth(line).unwrap_or("");
+        f(uri, document, source, char_index(source, column as usize))
+      }
+      None => Value::Null,
+    }
+  }
+
+  fn hover(
+    _: &str,
+    document: &Document,
+    line: &str,
+    character: usize,
+  ) -> Value {
+    let documentation = word_at(line, character).and_then(|word| {
+      instruction_documentation(word)
+        .or_else(|| register_documentation(word))
+        .or_else(|| {
+          let label = labels(&document.text)
+            .into_iter()
+            .find(|label| label.name == word)?;
+          Some(format!("Label on line {}", label.line + 1))
+        })
+    });
+    match documentation {
+      Some(documentation) => json!({
+        "contents": { "kind": "markdown", "value": documentation },
+      }),
+      None => Value::Null,
+    }
+  }
+
+  /// Mnemonics where the instruction goes, and registers and labels in its
+  /// operands.
+  fn complete(
+    _: &str,
+    document: &Document,
+    line: &str,
+    character: usize,
+  ) -> Value {
+    let mut items: Vec<Value> = Vec::new();
+    if operand_position(line, character).is_none() {
+      let mut mnemonics: Vec<_> = INSTRUCTIONS.values().collect();
+      mnemonics.sort_by_key(|instruction| instruction.mnemonic);
+      for instruction in mnemonics {
+        items.push(json!({
+          "label": instruction.mnemonic,
+          "kind": KEYWORD,
+          "detail": instruction.signature(),
+          "documentation": instruction.expansion,
+        }));
+      }
+    } else {
+      for info in REGISTER_INFO.iter() {
+        for name in [info.abi_name, info.name] {
+          items.push(json!({
+            "label": name,
+            "kind": VARIABLE,
+            "detail": format!("{} ({})", info.abi_name, info.name),
+          }));
+        }
+      }
+      for label in labels(&document.text) {
+        items.push(json!({
+          "label": label.name,
+          "kind": REFERENCE,
+          "detail": format!("Label on line {}", label.line + 1),
+        }));
+      }
+    }
+    json!(items)
+  }
+
+  fn definition(
+    uri: &str,
+    document: &Document,
+    line: &str,
+    character: usize,
+  ) -> Value {
+    let label = word_at(line, character).and_then(|word| {
+      labels(&document.text)
+        .into_iter()
+        .find(|label| label.name == word)
+    });
+    match label {
+      Some(label) => json!({
+        "uri": uri,
+        "range": {
+          "start": { "line": label.line, "character": label.character },
+          "end": { "line": label.line,
+                   "character": label.character + utf16_len(&label.name) },
+        },
+      }),
+      None => Value::Null,
+    }
+  }
+
+  fn signature_help(
+    _: &str,
+    _: &Document,
+    line: &str,
+    character: usize,
+  ) -> Value {
+    let (mnemonic, separators) = match operand_position(line, character) {
+      Some(position) => position,
+      None => return Value::Null,
+    };
+    let instruction = match INSTRUCTIONS.get(mnemonic) {
+      Some(instruction) => instruction,
+      None => return Value::Null,
+    };
+    let label = instruction.signature();
+    // Each operand's offsets in the label
+    let mut parameters = Vec::new();
+    let mut search_from = instruction.mnemonic.len();
+    for operand in instruction.syntax[1..]
+      .iter()
+      .filter(|part| !matches!(**part, "," | "(" | ")"))
+    {
+      let start = search_from + label[search_from..].find(operand).unwrap_or(0);
+      search_from = start + operand.len();
+      parameters.push(json!({ "label": [start, search_from] }));
+    }
+    json!({
+      "signatures": [{
+        "label": label,
+        "documentation": instruction.expansion,
+        "parameters": parameters,
+      }],
+      "activeSignature": 0,
+      "activeParameter": separators,
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const URI: &str = "file:///sum.s";
+  const PROGRAM: &str = "addi a0, zero, 5
+loop: addi a0, a0, -1
+bne a0, zero, loop
+lw a1, 4(sp)";
+
+  /// Sends the requests after opening PROGRAM, returning the server's
+  /// responses and notifications.
+  fn session(requests: &[(&str, Value)]) -> Vec<Value> {
+    let mut input = Vec::new();
+    let open = json!({ "textDocument": { "uri": URI, "text": PROGRAM } });
+    let mut messages = vec![json!({ "method": "textDocument/didOpen",
+                                    "params": open })];
+    for (id, (method, params)) in requests.iter().enumerate() {
+      messages.push(json!({ "id": id, "method": method, "params": params }));
+    }
+    for message in messages {
+      write_message(&mut input, &message).unwrap();
+    }
+    let mut server = LanguageServer::new(io::Cursor::new(input), Vec::new());
+    server.serve().unwrap();
+    let mut output = io::Cursor::new(server.output);
+    std::iter::from_fn(|| read_message(&mut output).unwrap()).collect()
+  }
+
+  fn at(line: u32, character: u32) -> Value {
+    json!({
+      "textDocument": { "uri": URI },
+      "position": { "line": line, "character": character },
+    })
+  }
+
+  fn result(messages: &[Value], id: usize) -> &Value {
+    &messages
+      .iter()
+      .find(|message| message["id"] == id)
+      .unwrap_or_else(|| panic!("No response to {}", id))["result"]
+  }
+
+  #[test]
+  fn diagnostics() {
+    let change = json!({
+      "textDocument": { "uri": URI },
+      "contentChanges": [{ "text": "addi a0, zero\n\n/* \u{e9} */ foo a0" }],
+    });
+    let messages = session(&[("textDocument/didChange", change)]);
+    let diagnostics: Vec<&Value> = messages
+      .iter()
+      .filter(|m| m["method"] == "textDocument/publishDiagnostics")
+      .map(|m| &m["params"]["diagnostics"])
+      .collect();
+    assert_eq!(diagnostics[0], &json!([]));
+    assert_eq!(diagnostics[1][0]["range"]["start"]["line"], 0);
+    assert_eq!(diagnostics[1][0]["range"]["end"]["character"], 13);
+    // Columns count UTF-16 code units, and only cover the statement
+    assert_eq!(
+      diagnostics[1][1]["range"],
+      json!({
+        "start": { "line": 2, "character": 8 },
+        "end": { "line": 2, "character": 14 },
+      })
+    );
+    assert!(diagnostics[1][1]["message"]
+      .as_str()
+      .unwrap()
+      .contains("Invalid instruction"));
+  }
+
+  #[test]
+  fn hover() {
+    let messages = session(&[
+      ("textDocument/hover", at(1, 7)),
+      ("textDocument/hover", at(2, 5)),
+      ("textDocument/hover", at(2, 16)),
+      ("textDocument/hover", at(0, 14)),
+    ]);
+    let addi = result(&messages, 0)["contents"]["value"].as_str().unwrap();
+    assert!(addi.starts_with("```\naddi rd, rs1, imm\n```\n**add immediate**"));
+    let a0 = result(&messages, 1)["contents"]["value"].as_str().unwrap();
+    assert_eq!(a0, "`a0` is `x10`, caller saved");
+    let label = &result(&messages, 2)["contents"]["value"];
+    assert_eq!(label, "Label on line 2");
+    assert_eq!(result(&messages, 3), &Value::Null);
+  }
+
+  #[test]
+  fn completion() {
+    let messages = session(&[
+      ("textDocument/completion", at(0, 2)),
+      ("textDocument/completion", at(2, 15)),
+    ]);
+    let mnemonics = result(&messages, 0).as_array().unwrap();
+    assert!(mnemonics.iter().any(|item| item["label"] == "addiw"));
+    assert!(mnemonics.iter().all(|item| item["kind"] == KEYWORD));
+    let operands = result(&messages, 1).as_array().unwrap();
+    assert!(operands.iter().any(|item| item["label"] == "s11"));
+    assert!(operands.iter().any(|item| item["label"] == "x31"));
+    assert!(operands
+      .iter()
+      .any(|item| item["label"] == "loop" && item["kind"] == REFERENCE));
+  }
+
+  #[test]
+  fn definition() {
+    let messages = session(&[
+      ("textDocument/definition", at(2, 16)),
+      ("textDocument/definition", at(2, 1)),
+    ]);
+    assert_eq!(
+      result(&messages, 0)["range"]["start"],
+      json!({ "line": 1, "character": 0 })
+    );
+    assert_eq!(result(&messages, 0)["range"]["end"]["character"], 4);
+    assert_eq!(result(&messages, 1), &Value::Null);
+  }
+
+  #[test]
+  fn signature_help() {
+    let messages = session(&[
+      ("textDocument/signatureHelp", at(2, 8)),
+      ("textDocument/signatureHelp", at(3, 10)),
+      ("textDocument/signatureHelp", at(0, 2)),
+    ]);
+    let bne = result(&messages, 0);
+    assert_eq!(bne["signatures"][0]["label"], "bne rs1, rs2, offset");
+    assert_eq!(bne["activeParameter"], 1);
+    assert_eq!(
+      bne["signatures"][0]["parameters"][1]["label"],
+      json!([9, 12])
+    );
+    let lw = result(&messages, 1);
+    assert_eq!(lw["signatures"][0]["label"], "lw rd, offset(rs1)");
+    assert_eq!(lw["activeParameter"], 2);
+    assert_eq!(result(&messages, 2), &Value::Null);
+  }
+
+  #[test]
+  fn words() {
+    assert_eq!(word_at("bne a0, zero, loop", 18), Some("loop"));
+    assert_eq!(word_at("bne a0, zero, loop", 0), Some("bne"));
+    assert_eq!(word_at("bne a0, zero, loop", 7), None);
+    assert_eq!(char_index("l\u{e9}\u{1f600}a", 4), 3);
+    let line = "nop; addi a0, a1";
+    assert_eq!(operand_position(line, 16), Some(("addi", 1)));
+    let labels = labels("nop # x: y\nnop; end: ret");
+    assert_eq!(labels.len(), 1);
+    assert_eq!((labels[0].line, labels[0].character), (1, 5));
+  }
+}
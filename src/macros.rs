@@ -0,0 +1,929 @@
+//! The assembler's front end, which expands macros, loops, conditional blocks
+//! and included files into plain lines of instructions and directives, as
+//! GNU as does. First the code is split into statements, which are separated
+//! by newlines or `;`, without comments: `#` or `//` to the end of the line,
+//! or `/* */`, which can span lines. Then:
+//!
+//! - `.macro name param, param=default` ... `.endm` defines a macro, whose
+//!   body refers to its parameters as `\param`. `\@` is the number of macros
+//!   expanded so far, for making unique labels, `\()` separates a parameter
+//!   from text after it, and `.exitm` stops the expansion early
+//! - `.rept count` ... `.endr` repeats its body, and `.irp name, values...`
+//!   (or `.irpc name, characters`) repeats it with `\name` as each value
+//! - `.if expression`, `.ifdef name` and friends, with `.elseif`, `.else` and
+//!   `.endif`, assemble their body only if the condition holds
+//! - `.include "file"` assembles another file in its place
+//!
+//! Each line keeps the chain of places it came from, so that errors and
+//! breakpoints can refer to both a macro's use and its definition.
+
+use crate::operand::{evaluate_str, Symbols};
+use crate::split_label;
+use crate::valid_label;
+use std::collections::{HashMap, HashSet};
+#[cfg(not(target_family = "wasm"))]
+use std::path::Path;
+
+/// The files which can be included, by name.
+pub type Files = HashMap<String, String>;
+
+// Limits, so that a runaway macro or .rept doesn't take all the memory
+const MAX_DEPTH: usize = 64;
+const MAX_LINES: usize = 1_000_000;
+
+/// A line in a source file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Location {
+  pub file: Option<String>, // None for the file being assembled
+  pub line: u32,            // 1 indexed
+}
+
+/// A line after expansion.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Line {
+  pub text: String,
+  // Where the text came from, innermost first. The last is in the file
+  // being assembled, e.g. the line where a macro was used, after the line
+  // of its definition.
+  pub locations: Vec<Location>,
+}
+
+impl Line {
+  /// The line in the file being assembled.
+  pub fn line_num(&self) -> u32 {
+    self.locations.last().map_or(0, |location| location.line)
+  }
+
+  /// Where the line came from, for messages, such as "line 12, from line 3
+  /// of macros.s". A line that comes from the same place several times in a
+  /// row, as in a recursive macro, is only given once, e.g. "line 4, from
+  /// line 2 (×64)".
+  pub fn location(&self) -> String {
+    let mut text = format!("line {}", self.line_num());
+    let mut frames = self.locations.iter().rev().skip(1).peekable();
+    while let Some(location) = frames.next() {
+      let mut repeats = 1;
+      while frames.next_if_eq(&location).is_some() {
+        repeats += 1;
+      }
+      text.push_str(&format!(", from line {}", location.line));
+      if let Some(file) = &location.file {
+        text.push_str(&format!(" of {}", file));
+      }
+      if repeats > 1 {
+        text.push_str(&format!(" (×{})", repeats));
+      }
+    }
+    text
+  }
+}
+
+/// The quoted string or character literal at the start of `text`, up to the
+/// closing quote or the end of the line.
+fn quoted(text: &str) -> &str {
+  let quote = text.chars().next().unwrap();
+  let mut escaped = false;
+  for (i, c) in text.char_indices().skip(1) {
+    match c {
+      '\n' => return &text[..i],
+      _ if escaped => escaped = false,
+      '\\' => escaped = true,
+      _ if c == quote => return &text[..i + 1],
+      _ => (),
+    }
+  }
+  text
+}
+
+/// The comment at the start of `text`, if there is one, and whether it's a
+/// block comment.
+fn comment(text: &str) -> Option<bool> {
+  if text.starts_with('#') || text.starts_with("//") {
+    Some(false)
+  } else if text.starts_with("/*") {
+    Some(true)
+  } else {
+    None
+  }
+}
+
+/// `line` up to the first comment on it.
+pub fn strip_comment(line: &str) -> &str {
+  let mut i = 0;
+  while let Some(c) = line[i..].chars().next() {
+    if comment(&line[i..]).is_some() {
+      return &line[..i];
+    }
+    i += match c {
+      '"' | '\'' => quoted(&line[i..]).len(),
+      _ => c.len_utf8(),
+    };
+  }
+  line
+}
+
+/// Splits `code` into statements, without comments, with the (1 indexed)
+/// line each one starts on. A `/*` comment which is never closed is an
+/// error, giving the line it starts on.
+pub fn statements(code: &str) -> Result<Vec<(u32, String)>, u32> {
+  let mut statements = Vec::new();
+  let mut statement = String::new();
+  let mut line = 1;
+  let mut i = 0;
+  let mut end_statement = |statement: &mut String, line| {
+    if !statement.trim().is_empty() {
+      statements.push((line, statement.trim().to_string()));
+    }
+    statement.clear();
+  };
+  while let Some(c) = code[i..].chars().next() {
+    let rest = &code[i..];
+    match (c, comment(rest)) {
+      (_, Some(false)) => i += rest.find('\n').unwrap_or(rest.len()),
+      (_, Some(true)) => {
+        let length = rest[2..].find("*/").ok_or(line)? + 4;
+        // The statement before the comment ends at the end of its line
+        let newlines = rest[..length].matches('\n').count() as u32;
+        if newlines > 0 {
+          end_statement(&mut statement, line);
+          line += newlines;
+        }
+        statement.push(' ');
+        i += length;
+      }
+      ('\n', _) => {
+        end_statement(&mut statement, line);
+        line += 1;
+        i += 1;
+      }
+      (';', _) => {
+        end_statement(&mut statement, line);
+        i += 1;
+      }
+      ('"' | '\'', _) => {
+        let text = quoted(rest);
+        statement.push_str(text);
+        i += text.len();
+      }
+      _ => {
+        statement.push(c);
+        i += c.len_utf8();
+      }
+    }
+  }
+  end_statement(&mut statement, line);
+  Ok(statements)
+}
+
+/// The statements of a file as lines to expand, where the file's lines are
+/// `location` with the line number filled in. If it has an unterminated
+/// comment, the error is the (empty) line the comment starts on.
+fn file_lines(code: &str, location: &[Location]) -> Result<Vec<Line>, Line> {
+  let line = |(line, text)| {
+    let mut locations = location.to_vec();
+    locations[0].line = line;
+    Line { text, locations }
+  };
+  match statements(code) {
+    Ok(statements) => Ok(statements.into_iter().map(line).collect()),
+    Err(start) => Err(line((start, String::new()))),
+  }
+}
+
+struct Parameter {
+  name: String,
+  default: String,
+  required: bool,
+  vararg: bool, // Takes the rest of the arguments
+}
+
+struct Macro {
+  parameters: Vec<Parameter>,
+  body: Vec<Line>,
+}
+
+/// The directive (or macro) a line starts with, and the rest of it.
+fn directive(line: &Line) -> (Option<&str>, &str, &str) {
+  let (label, code) = split_label(&line.text);
+  let (name, rest) = code.split_once(char::is_whitespace).unwrap_or((code, ""));
+  (label, name, rest.trim())
+}
+
+/// Splits a list of arguments at commas, or at spaces if there are no
+/// commas, as GNU as allows both.
+fn split_arguments(text: &str) -> Vec<String> {
+  if text.is_empty() {
+    return Vec::new();
+  }
+  let mut arguments = Vec::new();
+  let mut depth = 0;
+  let mut current = String::new();
+  for c in text.chars() {
+    match c {
+      '(' => depth += 1,
+      ')' => depth -= 1,
+      ',' if depth == 0 => {
+        arguments.push(current.trim().to_string());
+        current.clear();
+        continue;
+      }
+      _ => (),
+    }
+    current.push(c);
+  }
+  arguments.push(current.trim().to_string());
+  if arguments.len() == 1 && depth == 0 {
+    return text.split_whitespace().map(str::to_string).collect();
+  }
+  arguments
+}
+
+/// Replaces `\name` with its value, `\@` with `count` and removes `\()`.
+fn substitute(
+  text: &str,
+  values: &HashMap<&str, &str>,
+  count: usize,
+) -> String {
+  let mut result = String::new();
+  let mut rest = text;
+  while let Some(start) = rest.find('\\') {
+    result.push_str(&rest[..start]);
+    let after = &rest[start + 1..];
+    let length = after
+      .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+      .unwrap_or(after.len());
+    if let Some(value) = values.get(&after[..length]) {
+      result.push_str(value);
+      rest = &after[length..];
+    } else if let Some(after) = after.strip_prefix('@') {
+      result.push_str(&count.to_string());
+      rest = after;
+    } else if let Some(after) = after.strip_prefix("()") {
+      rest = after;
+    } else {
+      result.push('\\');
+      rest = after;
+    }
+  }
+  result.push_str(rest);
+  result
+}
+
+struct Expander<'a> {
+  files: &'a Files,
+  macros: HashMap<String, Macro>,
+  symbols: Symbols, // The constants so far, for conditions
+  defined: HashSet<String>, // The labels and constants so far, for .ifdef
+  expansions: usize, // For \@
+  lines: Vec<Line>,
+  errors: Vec<(Line, String)>,
+}
+
+/// What to do after a block.
+enum Flow {
+  Continue,
+  Exit, // .exitm
+}
+
+impl Expander<'_> {
+  fn error(&mut self, line: &Line, message: String) {
+    let message = format!("Error on {}: {}", line.location(), message);
+    self.errors.push((line.clone(), message));
+  }
+
+  fn expand_file(&mut self, code: &str, location: &[Location], depth: usize) {
+    match file_lines(code, location) {
+      Ok(lines) => {
+        self.expand(&lines, depth);
+      }
+      Err(start) => {
+        self.error(&start, String::from("unterminated `/*` comment"))
+      }
+    }
+  }
+
+  fn emit(&mut self, line: Line) {
+    if self.lines.len() == MAX_LINES {
+      self.error(&line, String::from("the expanded program is too long"));
+    }
+    if self.lines.len() <= MAX_LINES {
+      self.lines.push(line);
+    }
+  }
+
+  /// The index of the line which ends the block starting before `start`,
+  /// allowing for nested blocks, which start with a directive that `opens`.
+  fn end_of_block(
+    lines: &[Line],
+    start: usize,
+    opens: fn(&str) -> bool,
+    closing: &str,
+  ) -> Option<usize> {
+    let mut depth = 0;
+    for (i, line) in lines.iter().enumerate().skip(start) {
+      let (_, name, _) = directive(line);
+      if opens(name) {
+        depth += 1;
+      } else if name == closing {
+        if depth == 0 {
+          return Some(i);
+        }
+        depth -= 1;
+      }
+    }
+    None
+  }
+
+  fn value(&mut self, line: &Line, expression: &str) -> Option<i64> {
+    match evaluate_str(expression, &self.symbols) {
+      Ok(value) => Some(value),
+      Err(message) => {
+        self.error(line, message);
+        None
+      }
+    }
+  }
+
+  fn condition(&mut self, line: &Line, name: &str, rest: &str) -> bool {
+    match name {
+      ".ifdef" => self.defined.contains(rest),
+      ".ifndef" | ".ifnotdef" => !self.defined.contains(rest),
+      ".ifeq" => self.value(line, rest) == Some(0),
+      ".ifc" | ".ifnc" => {
+        let arguments = split_arguments(rest);
+        let same = arguments.len() == 2 && arguments[0] == arguments[1];
+        same == (name == ".ifc")
+      }
+      // .if, .ifne and .elseif
+      _ => self.value(line, rest).is_some_and(|value| value != 0),
+    }
+  }
+
+  /// Expands `lines`, which are `depth` macros or includes deep.
+  fn expand(&mut self, lines: &[Line], depth: usize) -> Flow {
+    if depth > MAX_DEPTH {
+      if let Some(line) = lines.first() {
+        let message = "macros or includes are nested too deeply".to_string();
+        self.error(line, message);
+      }
+      return Flow::Exit;
+    }
+    let mut i = 0;
+    while i < lines.len() {
+      let line = &lines[i];
+      i += 1;
+      let (label, name, rest) = directive(line);
+      let is_block = name.starts_with(".if")
+        || [".macro", ".rept", ".irp", ".irpc", ".include", ".exitm"]
+          .contains(&name)
+        || self.macros.contains_key(name);
+      if let Some(label) = label {
+        self.defined.insert(label.to_string());
+        if is_block {
+          // The label is for the first line of the block
+          self.emit(Line {
+            text: format!("{}:", label),
+            locations: line.locations.clone(),
+          });
+        }
+      }
+      if !is_block {
+        if matches!(name, ".equ" | ".set") {
+          if let Some((constant, value)) = rest.split_once(',') {
+            let constant = constant.trim();
+            self.defined.insert(constant.to_string());
+            if let Ok(value) = evaluate_str(value.trim(), &self.symbols) {
+              self.symbols.constants.insert(constant.to_string(), value);
+            }
+          }
+        }
+        if [".endm", ".endr", ".else", ".elseif", ".endif"].contains(&name) {
+          self.error(line, format!("`{}` without a block to end", name));
+          continue;
+        }
+        self.emit(line.clone());
+        continue;
+      }
+
+      match name {
+        ".macro" => {
+          let end = Self::end_of_block(lines, i, |n| n == ".macro", ".endm");
+          let end = match end {
+            Some(end) => end,
+            None => {
+              self.error(line, String::from("`.macro` without `.endm`"));
+              return Flow::Continue;
+            }
+          };
+          self.define(line, rest, &lines[i..end]);
+          i = end + 1;
+        }
+        ".rept" | ".irp" | ".irpc" => {
+          let opens = |n: &str| [".rept", ".irp", ".irpc"].contains(&n);
+          let end = Self::end_of_block(lines, i, opens, ".endr");
+          let end = match end {
+            Some(end) => end,
+            None => {
+              self.error(line, format!("`{}` without `.endr`", name));
+              return Flow::Continue;
+            }
+          };
+          let body = &lines[i..end];
+          i = end + 1;
+          if let Flow::Exit = self.repeat(line, name, rest, body, depth) {
+            return Flow::Exit;
+          }
+        }
+        ".include" => {
+          let file = rest.trim_matches('"');
+          match self.files.get(file) {
+            Some(code) => {
+              let mut location = vec![Location {
+                file: Some(file.to_string()),
+                line: 0,
+              }];
+              location.extend(line.locations.iter().cloned());
+              self.expand_file(code, &location, depth + 1);
+            }
+            None => {
+              self.error(line, format!("can't find `{}` to include", file))
+            }
+          }
+        }
+        ".exitm" => return Flow::Exit,
+        _ if name.starts_with(".if") => {
+          let opens = |n: &str| n.starts_with(".if");
+          let end = Self::end_of_block(lines, i, opens, ".endif");
+          let end = match end {
+            Some(end) => end,
+            None => {
+              self.error(line, format!("`{}` without `.endif`", name));
+              return Flow::Continue;
+            }
+          };
+          let flow = self.conditional(line, name, rest, &lines[i..end], depth);
+          i = end + 1;
+          if let Flow::Exit = flow {
+            return Flow::Exit;
+          }
+        }
+        _ => {
+          if let Flow::Exit = self.call(line, name, rest, depth) {
+            return Flow::Exit;
+          }
+        }
+      }
+    }
+    Flow::Continue
+  }
+
+  fn define(&mut self, line: &Line, declaration: &str, body: &[Line]) {
+    let (name, parameters) = declaration
+      .split_once(|c: char| c.is_whitespace() || c == ',')
+      .unwrap_or((declaration, ""));
+    if !valid_label(name) {
+      self.error(line, format!("invalid macro name `{}`", name));
+      return;
+    }
+    let mut list = Vec::new();
+    for parameter in split_arguments(parameters.trim()) {
+      let (parameter, default) =
+        parameter.split_once('=').unwrap_or((&parameter, ""));
+      let (parameter, qualifier) =
+        parameter.split_once(':').unwrap_or((parameter, ""));
+      if !valid_label(parameter.trim())
+        || !["", "req", "vararg"].contains(&qualifier)
+      {
+        self.error(line, format!("invalid macro parameter `{}`", parameter));
+        return;
+      }
+      list.push(Parameter {
+        name: parameter.trim().to_string(),
+        default: default.trim().to_string(),
+        required: qualifier == "req",
+        vararg: qualifier == "vararg",
+      });
+    }
+    self.macros.insert(
+      name.to_string(),
+      Macro {
+        parameters: list,
+        body: body.to_vec(),
+      },
+    );
+  }
+
+  /// The lines of `body`, substituting `values`, as used on `line`.
+  fn instantiate(
+    &self,
+    line: &Line,
+    body: &[Line],
+    values: &HashMap<&str, &str>,
+    chain: bool,
+  ) -> Vec<Line> {
+    body
+      .iter()
+      .map(|body_line| Line {
+        text: substitute(&body_line.text, values, self.expansions),
+        locations: match chain {
+          // A macro's body is wherever it was defined
+          true => [body_line.locations[0].clone()]
+            .iter()
+            .chain(&line.locations)
+            .cloned()
+            .collect(),
+          false => body_line.locations.clone(),
+        },
+      })
+      .collect()
+  }
+
+  fn repeat(
+    &mut self,
+    line: &Line,
+    name: &str,
+    rest: &str,
+    body: &[Line],
+    depth: usize,
+  ) -> Flow {
+    let iterations: Vec<(String, String)> = match name {
+      ".rept" => match self.value(line, rest) {
+        Some(count) if (0..=MAX_LINES as i64).contains(&count) => {
+          vec![(String::new(), String::new()); count as usize]
+        }
+        Some(count) => {
+          self.error(line, format!("invalid repeat count {}", count));
+          return Flow::Continue;
+        }
+        None => return Flow::Continue,
+      },
+      _ => {
+        let (parameter, values) = rest.split_once(',').unwrap_or((rest, ""));
+        let parameter = parameter.trim().to_string();
+        let values: Vec<String> = match name {
+          ".irp" => split_arguments(values.trim()),
+          _ => values.trim().chars().map(String::from).collect(),
+        };
+        values.into_iter().map(|v| (parameter.clone(), v)).collect()
+      }
+    };
+    for (parameter, value) in &iterations {
+      let mut values = HashMap::new();
+      if !parameter.is_empty() {
+        values.insert(parameter.as_str(), value.as_str());
+      }
+      let lines = self.instantiate(line, body, &values, false);
+      if let Flow::Exit = self.expand(&lines, depth) {
+        return Flow::Exit;
+      }
+      if self.lines.len() > MAX_LINES {
+        break;
+      }
+    }
+    Flow::Continue
+  }
+
+  fn conditional(
+    &mut self,
+    line: &Line,
+    name: &str,
+    rest: &str,
+    body: &[Line],
+    depth: usize,
+  ) -> Flow {
+    // Split the body into the branches, at the .elseif and .else which
+    // aren't in nested blocks
+    let mut branches = vec![(line, name, rest, 0)];
+    let mut nesting = 0;
+    for (i, body_line) in body.iter().enumerate() {
+      let (_, name, rest) = directive(body_line);
+      match name {
+        _ if name.starts_with(".if") => nesting += 1,
+        ".endif" => nesting -= 1,
+        ".elseif" | ".else" if nesting == 0 => {
+          branches.push((body_line, name, rest, i + 1))
+        }
+        _ => (),
+      }
+    }
+    for (b, (branch_line, name, rest, start)) in branches.iter().enumerate() {
+      let end = branches.get(b + 1).map_or(body.len(), |next| next.3 - 1);
+      if *name == ".else" || self.condition(branch_line, name, rest) {
+        return self.expand(&body[*start..end], depth);
+      }
+    }
+    Flow::Continue
+  }
+
+  fn call(
+    &mut self,
+    line: &Line,
+    name: &str,
+    rest: &str,
+    depth: usize,
+  ) -> Flow {
+    let definition = &self.macros[name];
+    let mut arguments = split_arguments(rest);
+    let mut values: HashMap<&str, String> = HashMap::new();
+    // Named arguments, as in `push reg=a0`
+    arguments.retain(|argument| {
+      let named = argument.split_once('=').and_then(|(name, value)| {
+        let parameter = definition
+          .parameters
+          .iter()
+          .find(|p| p.name == name.trim())?;
+        Some((parameter.name.as_str(), value.trim().to_string()))
+      });
+      match named {
+        Some((name, value)) => {
+          values.insert(name, value);
+          false
+        }
+        None => true,
+      }
+    });
+    let mut arguments = arguments.into_iter();
+    let mut errors = Vec::new();
+    for parameter in &definition.parameters {
+      let value = match parameter.vararg {
+        true => arguments.by_ref().collect::<Vec<_>>().join(", "),
+        false => arguments.next().unwrap_or_default(),
+      };
+      if !value.is_empty() && !values.contains_key(parameter.name.as_str()) {
+        values.insert(&parameter.name, value);
+      }
+      let value = values.entry(&parameter.name).or_default();
+      if value.is_empty() {
+        if parameter.required {
+          errors.push(format!(
+            "missing argument `{}` for macro `{}`",
+            parameter.name, name
+          ));
+        }
+        *value = parameter.default.clone();
+      }
+    }
+    if arguments.next().is_some() {
+      errors.push(format!("too many arguments for macro `{}`", name));
+    }
+    if !errors.is_empty() {
+      for error in errors {
+        self.error(line, error);
+      }
+      return Flow::Continue;
+    }
+    let values: HashMap<&str, &str> =
+      values.iter().map(|(k, v)| (*k, v.as_str())).collect();
+    let lines = self.instantiate(line, &definition.body, &values, true);
+    self.expansions += 1;
+    self.expand(&lines, depth + 1);
+    Flow::Continue
+  }
+}
+
+/// Expands the macros, loops, conditions and includes in `code`, which can
+/// include `files`. Each error comes with the line it's on.
+pub fn expand(code: &str, files: &Files) -> (Vec<Line>, Vec<(Line, String)>) {
+  let location = Location {
+    file: None,
+    line: 0,
+  };
+  let mut expander = Expander {
+    files,
+    macros: HashMap::new(),
+    symbols: Symbols::default(),
+    defined: HashSet::new(),
+    expansions: 0,
+    lines: Vec::new(),
+    errors: Vec::new(),
+  };
+  expander.expand_file(code, &[location], 0);
+  (expander.lines, expander.errors)
+}
+
+/// The names of the files `code` includes directly, so they can be found
+/// before it's assembled.
+pub fn includes(code: &str) -> Vec<String> {
+  // An unterminated comment is reported when the code is expanded
+  statements(code)
+    .unwrap_or_default()
+    .into_iter()
+    .filter_map(|(_, statement)| {
+      let (_, code) = split_label(&statement);
+      let file = code.strip_prefix(".include")?;
+      Some(file.trim().trim_matches('"').to_string())
+    })
+    .filter(|file| !file.is_empty())
+    .collect()
+}
+
+/// Reads the files which `code` includes, and the files they include, from
+/// `directory`.
+#[cfg(not(target_family = "wasm"))]
+pub fn read_includes(directory: &Path, code: &str) -> Result<Files, String> {
+  let mut files = Files::new();
+  let mut pending = includes(code);
+  while let Some(name) = pending.pop() {
+    if files.contains_key(&name) {
+      continue;
+    }
+    let code = std::fs::read_to_string(directory.join(&name))
+      .map_err(|error| format!("Couldn't read {}: {}", name, error))?;
+    pending.extend(includes(&code));
+    files.insert(name, code);
+  }
+  Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn expanded_with(code: &str, files: &Files) -> Vec<String> {
+    let (lines, errors) = expand(code, files);
+    assert_eq!(errors, []);
+    lines
+      .into_iter()
+      .map(|line| line.text.trim().to_string())
+      .filter(|text| !text.is_empty())
+      .collect()
+  }
+
+  fn expansion_errors(code: &str, files: &Files) -> Vec<String> {
+    let errors = expand(code, files).1.into_iter();
+    errors.map(|(_, message)| message).collect()
+  }
+
+  fn expanded(code: &str) -> Vec<String> {
+    expanded_with(code, &Files::new())
+  }
+
+  #[test]
+  fn comments_and_statements() {
+    let code = "addi a0, a0, 1 # c: x\n\
+                li a1, '#'; li a2, ';' // c\n\
+                nop /* a\n\
+                b */ nop; /* c */ nop\n\
+                \n\
+                .ascii \"a;b#c\"";
+    let statements = statements(code).unwrap();
+    let statements: Vec<(u32, &str)> = statements
+      .iter()
+      .map(|(line, text)| (*line, text.as_str()))
+      .collect();
+    assert_eq!(
+      statements,
+      [
+        (1, "addi a0, a0, 1"),
+        (2, "li a1, '#'"),
+        (2, "li a2, ';'"),
+        (3, "nop"),
+        (4, "nop"),
+        (4, "nop"),
+        (6, ".ascii \"a;b#c\""),
+      ]
+    );
+    assert_eq!(strip_comment("li a0, '#' # c"), "li a0, '#' ");
+    assert_eq!(strip_comment("nop /* c */"), "nop ");
+  }
+
+  #[test]
+  fn macros() {
+    let code = "
+      .macro push reg, size=8
+        addi sp, sp, -\\size
+        sd \\reg, 0(sp)
+      .endm
+      push ra
+      push reg=s0, size=16
+      push a0 4
+      .macro suffix name
+        \\name\\()_end:
+      .endm
+      suffix done";
+    assert_eq!(
+      expanded(code),
+      [
+        "addi sp, sp, -8",
+        "sd ra, 0(sp)",
+        "addi sp, sp, -16",
+        "sd s0, 0(sp)",
+        "addi sp, sp, -4",
+        "sd a0, 0(sp)",
+        "done_end:",
+      ]
+    );
+    let unique = ".macro spin\nloop\\@: jal zero, loop\\@\n.endm\nspin\nspin";
+    assert_eq!(
+      expanded(unique),
+      ["loop0: jal zero, loop0", "loop1: jal zero, loop1"]
+    );
+  }
+
+  #[test]
+  fn loops() {
+    let code = "
+      .rept 2
+      nop
+      .endr
+      .irp reg, a0, a1
+      mv \\reg, zero
+      .endr
+      .irpc n, 12
+      li t\\n, \\n
+      .endr";
+    assert_eq!(
+      expanded(code),
+      [
+        "nop",
+        "nop",
+        "mv a0, zero",
+        "mv a1, zero",
+        "li t1, 1",
+        "li t2, 2"
+      ]
+    );
+  }
+
+  #[test]
+  fn conditions() {
+    let code = "
+      .equ DEBUG, 1
+      .if DEBUG == 1
+        .ifdef DEBUG
+          debug
+        .else
+          not debug
+        .endif
+      .elseif 1
+        elseif
+      .endif
+      .ifndef RELEASE
+        release
+      .endif
+      .set i, 0
+      .rept 3
+        .if i == 2
+          two
+        .endif
+        .set i, i + 1
+      .endr";
+    let lines: Vec<String> = expanded(code)
+      .into_iter()
+      .filter(|line| !line.starts_with('.'))
+      .collect();
+    assert_eq!(lines, ["debug", "release", "two"]);
+  }
+
+  #[test]
+  fn includes_and_locations() {
+    let files = Files::from([(
+      String::from("macros.s"),
+      String::from(
+        "// Macros\n.macro double reg\nadd \\reg, \\reg, \\reg\n.endm",
+      ),
+    )]);
+    let code = "addi a0, zero, 1\n.include \"macros.s\"\ndouble a0";
+    let (lines, errors) = expand(code, &files);
+    assert!(errors.is_empty());
+    let last = lines.last().unwrap();
+    assert_eq!(last.text, "add a0, a0, a0");
+    assert_eq!(last.line_num(), 3);
+    assert_eq!(last.location(), "line 3, from line 3 of macros.s");
+    assert_eq!(includes(code), ["macros.s"]);
+  }
+
+  #[test]
+  fn errors() {
+    let errors = |code| expansion_errors(code, &Files::new());
+    assert_eq!(
+      errors(".macro one a\nnop\n.endm\none 1, 2\n.include \"x.s\""),
+      [
+        "Error on line 4: too many arguments for macro `one`",
+        "Error on line 5: can't find `x.s` to include",
+      ]
+    );
+    assert_eq!(
+      errors(".rept 2\nnop"),
+      ["Error on line 1: `.rept` without `.endr`"]
+    );
+    assert_eq!(
+      errors(".macro m\nm\n.endm\nm"),
+      ["Error on line 4, from line 2 (×65): macros or includes are nested too \
+        deeply"]
+    );
+    assert_eq!(
+      errors(".endif"),
+      ["Error on line 1: `.endif` without a block to end"]
+    );
+    assert_eq!(
+      errors("nop\nnop /* a\nnop"),
+      ["Error on line 2: unterminated `/*` comment"]
+    );
+    let files = Files::from([("x.s".to_string(), "/*".to_string())]);
+    assert_eq!(
+      expansion_errors("nop\n.include \"x.s\"", &files),
+      ["Error on line 2, from line 1 of x.s: unterminated `/*` comment"]
+    );
+  }
+}
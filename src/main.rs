@@ -0,0 +1,416 @@
+//! Runs a program from the command line, with the same interpreter as the
+//! web UI. Anything the program writes to the UART goes to stdout, and
+//! errors and warnings go to stderr.
+
+use riscv_interpreter::{
+  disassemble_elf, disassemble_raw, is_elf, link, read_includes, DapServer,
+  Executable, Files, GdbStub, Interpreter, LanguageServer, Object, Trace,
+  TraceFormat,
+};
+use std::fs::File;
+use std::io;
+use std::net::TcpListener;
+use std::path::Path;
+use std::process::ExitCode;
+
+const USAGE: &str = "Usage: riscv-interpreter [--profile] [--max-steps N] \
+                     [--trace spike|jsonl] [--trace-file PATH] \
+                     [--gdb PORT] <program.s|object.o>...\n       \
+                     riscv-interpreter --object <object.o> <program.s>\n       \
+                     riscv-interpreter --listing <program.s|binary|elf>\n       \
+                     riscv-interpreter --dap | --lsp";
+
+#[derive(Debug, PartialEq)]
+struct Options {
+  paths: Vec<String>, // Not needed with --dap or --lsp
+  profile: bool,
+  max_steps: u64,
+  trace: Option<TraceFormat>,
+  trace_file: Option<String>,
+  gdb: Option<u16>,
+  dap: bool,
+  lsp: bool,
+  listing: bool,
+  object: Option<String>,
+}
+
+fn parse_args(
+  mut args: impl Iterator<Item = String>,
+) -> Result<Options, String> {
+  let mut paths = Vec::new();
+  let mut profile = false;
+  let mut max_steps = 100_000_000;
+  let mut trace = None;
+  let mut trace_file = None;
+  let mut gdb = None;
+  let mut dap = false;
+  let mut lsp = false;
+  let mut listing = false;
+  let mut object = None;
+  while let Some(arg) = args.next() {
+    match arg.as_str() {
+      "--profile" => profile = true,
+      "--max-steps" => {
+        let steps = args.next().ok_or("--max-steps needs a number")?;
+        max_steps = parse_int::parse::<u64>(&steps)
+          .map_err(|_| format!("Invalid step count \"{}\"", steps))?;
+      }
+      "--trace" => {
+        let format = args.next().ok_or("--trace needs a format")?;
+        trace = Some(
+          TraceFormat::parse(&format)
+            .ok_or(format!("Unknown trace format \"{}\"", format))?,
+        );
+      }
+      "--trace-file" => {
+        trace_file = Some(args.next().ok_or("--trace-file needs a path")?);
+      }
+      "--gdb" => {
+        let port = args.next().ok_or("--gdb needs a port")?;
+        gdb = Some(
+          port
+            .parse::<u16>()
+            .map_err(|_| format!("Invalid port \"{}\"", port))?,
+        );
+      }
+      "--dap" => dap = true,
+      "--lsp" => lsp = true,
+      "--listing" => listing = true,
+      "--object" => {
+        object = Some(args.next().ok_or("--object needs a path")?);
+      }
+      _ if arg.starts_with("--") => {
+        return Err(format!("Unknown option \"{}\"", arg))
+      }
+      _ => paths.push(arg),
+    }
+  }
+  // The client says which program to debug when it launches it, and which
+  // files to check as they're opened
+  if paths.is_empty() && !dap && !lsp {
+    return Err("No program given".to_string());
+  }
+  if paths.len() > 1 && listing {
+    return Err("Only one file can be listed at a time".to_string());
+  }
+  if paths.len() > 1 && object.is_some() {
+    return Err("Only one file can be assembled at a time".to_string());
+  }
+  Ok(Options {
+    paths,
+    profile,
+    max_steps,
+    trace,
+    trace_file,
+    gdb,
+    dap,
+    lsp,
+    listing,
+    object,
+  })
+}
+
+/// Waits for GDB to connect on localhost, then lets it control the program
+/// until it kills it or detaches.
+fn debug(interpreter: &mut Interpreter, port: u16) -> io::Result<()> {
+  let listener = TcpListener::bind(("127.0.0.1", port))?;
+  eprintln!(
+    "Waiting for GDB on port {} (target remote localhost:{})",
+    port, port
+  );
+  let (stream, _) = listener.accept()?;
+  GdbStub::new(interpreter, stream).serve()
+}
+
+/// Reads the files which the program at `path` includes, from its directory.
+fn read_program_includes(path: &str, code: &str) -> Result<Files, ExitCode> {
+  let directory = Path::new(path).parent().unwrap_or(Path::new(""));
+  read_includes(directory, code).map_err(|error| {
+    eprintln!("{} (included by {})", error, path);
+    ExitCode::from(2)
+  })
+}
+
+/// Prints the listing of a program, or of the machine code in an ELF file or
+/// raw binary (which is loaded at address 0).
+fn print_listing(path: &str) -> ExitCode {
+  let bytes = match std::fs::read(path) {
+    Ok(bytes) => bytes,
+    Err(error) => {
+      eprintln!("Couldn't read {}: {}", path, error);
+      return ExitCode::from(2);
+    }
+  };
+  if is_elf(&bytes) {
+    return match disassemble_elf(&bytes) {
+      Ok(listing) => {
+        print!("{}", listing);
+        ExitCode::SUCCESS
+      }
+      Err(error) => {
+        eprintln!("error: {}", error);
+        ExitCode::FAILURE
+      }
+    };
+  }
+  let code = match String::from_utf8(bytes) {
+    Ok(code) => code,
+    Err(error) => {
+      print!("{}", disassemble_raw(error.as_bytes(), 0));
+      return ExitCode::SUCCESS;
+    }
+  };
+  let mut interpreter = Interpreter::create_RiscV64_i(Vec::new());
+  let files = match read_program_includes(path, &code) {
+    Ok(files) => files,
+    Err(exit) => return exit,
+  };
+  for (name, code) in files {
+    interpreter.add_file(name, code);
+  }
+  interpreter.set_code(code);
+  for error in interpreter.errors() {
+    eprintln!("error: {}", error);
+  }
+  if !interpreter.errors().is_empty() {
+    return ExitCode::FAILURE;
+  }
+  print!("{}", interpreter.listing());
+  ExitCode::SUCCESS
+}
+
+/// Assembles a source file into an ELF relocatable object file, so it can
+/// be linked with others later (or by another toolchain).
+fn write_object(path: &str, object_path: &str) -> ExitCode {
+  let code = match std::fs::read_to_string(path) {
+    Ok(code) => code,
+    Err(error) => {
+      eprintln!("Couldn't read {}: {}", path, error);
+      return ExitCode::from(2);
+    }
+  };
+  let files = match read_program_includes(path, &code) {
+    Ok(files) => files,
+    Err(exit) => return exit,
+  };
+  let object = match Object::assemble(path, &code, &files) {
+    Ok(object) => object,
+    Err(errors) => {
+      for error in errors {
+        eprintln!("error: {}", error);
+      }
+      return ExitCode::FAILURE;
+    }
+  };
+  if let Err(error) = std::fs::write(object_path, object.to_elf()) {
+    eprintln!("Couldn't write {}: {}", object_path, error);
+    return ExitCode::from(2);
+  }
+  ExitCode::SUCCESS
+}
+
+/// Loads the program into the interpreter. A single source file is
+/// assembled as usual, but several files (or any object files) are
+/// assembled separately and linked, returning the linked program so that
+/// messages can say which file they're about.
+fn load(
+  interpreter: &mut Interpreter,
+  paths: &[String],
+) -> Result<Option<Executable>, ExitCode> {
+  let mut files = Vec::new();
+  for path in paths {
+    match std::fs::read(path) {
+      Ok(bytes) => files.push((path, bytes)),
+      Err(error) => {
+        eprintln!("Couldn't read {}: {}", path, error);
+        return Err(ExitCode::from(2));
+      }
+    }
+  }
+  if let [(path, bytes)] = &files[..] {
+    if !is_elf(bytes) {
+      match String::from_utf8(bytes.clone()) {
+        Ok(code) => {
+          for (name, code) in read_program_includes(path, &code)? {
+            interpreter.add_file(name, code);
+          }
+          interpreter.set_code(code);
+        }
+        Err(_) => {
+          eprintln!("{} isn't a source file or an ELF object file", path);
+          return Err(ExitCode::from(2));
+        }
+      }
+      return Ok(None);
+    }
+  }
+
+  let mut objects = Vec::new();
+  let mut errors = Vec::new();
+  for (path, bytes) in &files {
+    let object = if is_elf(bytes) {
+      Object::from_elf(path, bytes).map_err(|error| vec![error])
+    } else {
+      let code = String::from_utf8_lossy(bytes);
+      Object::assemble(path, &code, &read_program_includes(path, &code)?)
+    };
+    match object {
+      Ok(object) => objects.push(object),
+      Err(object_errors) => errors.extend(object_errors),
+    }
+  }
+  let executable = match errors.is_empty() {
+    true => link(&objects),
+    false => Err(errors),
+  };
+  match executable {
+    Ok(executable) => {
+      interpreter.set_executable(&executable);
+      Ok(Some(executable))
+    }
+    Err(errors) => {
+      for error in errors {
+        eprintln!("error: {}", error);
+      }
+      Err(ExitCode::FAILURE)
+    }
+  }
+}
+
+fn main() -> ExitCode {
+  let options = match parse_args(std::env::args().skip(1)) {
+    Ok(options) => options,
+    Err(error) => {
+      eprintln!("{}\n{}", error, USAGE);
+      return ExitCode::from(2);
+    }
+  };
+  if options.dap {
+    let input = io::BufReader::new(io::stdin());
+    return match DapServer::new(input, io::stdout()).serve() {
+      Ok(()) => ExitCode::SUCCESS,
+      Err(error) => {
+        eprintln!("Debug adapter failed: {}", error);
+        ExitCode::FAILURE
+      }
+    };
+  }
+  if options.lsp {
+    let input = io::BufReader::new(io::stdin());
+    return match LanguageServer::new(input, io::stdout()).serve() {
+      Ok(()) => ExitCode::SUCCESS,
+      Err(error) => {
+        eprintln!("Language server failed: {}", error);
+        ExitCode::FAILURE
+      }
+    };
+  }
+  if options.listing {
+    return print_listing(&options.paths[0]);
+  }
+  if let Some(object_path) = &options.object {
+    return write_object(&options.paths[0], object_path);
+  }
+
+  let mut interpreter = Interpreter::create_RiscV64_i(Vec::new());
+  let executable = match load(&mut interpreter, &options.paths) {
+    Ok(executable) => executable,
+    Err(code) => return code,
+  };
+  if let Some(format) = options.trace {
+    let sink: Box<dyn io::Write> = match &options.trace_file {
+      Some(path) => match File::create(path) {
+        Ok(file) => Box::new(io::BufWriter::new(file)),
+        Err(error) => {
+          eprintln!("Couldn't create {}: {}", path, error);
+          return ExitCode::from(2);
+        }
+      },
+      None => Box::new(io::stderr()),
+    };
+    interpreter.set_trace(Some(Trace::new(format, sink)));
+  }
+  if let Some(port) = options.gdb {
+    // A program which doesn't assemble can't be debugged, so just report why
+    if interpreter.errors().is_empty() {
+      if let Err(error) = debug(&mut interpreter, port) {
+        eprintln!("GDB connection failed: {}", error);
+        return ExitCode::from(2);
+      }
+    }
+  } else {
+    interpreter.set_running(true);
+    let steps = interpreter.run_for(options.max_steps);
+    if interpreter.running() {
+      eprintln!("warning: Stopped after {} steps", steps);
+    }
+  }
+  // Flushes the trace
+  interpreter.set_trace(None);
+
+  // Line numbers in a linked program are in all of the files together
+  let locate = |message: &str| match &executable {
+    Some(executable) => executable.locate(message),
+    None => message.to_string(),
+  };
+  print!("{}", interpreter.console_output());
+  for warning in interpreter.warnings() {
+    eprintln!("warning: {}", locate(warning));
+  }
+  for error in interpreter.errors() {
+    eprintln!("error: {}", locate(error));
+  }
+  if options.profile {
+    eprint!("{}", interpreter.profile_report());
+  }
+  if interpreter.errors().is_empty() {
+    ExitCode::SUCCESS
+  } else {
+    ExitCode::FAILURE
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn args(args: &[&str]) -> Result<Options, String> {
+    parse_args(args.iter().map(|arg| arg.to_string()))
+  }
+
+  #[test]
+  fn arguments() {
+    assert_eq!(
+      args(&["--profile", "sum.s", "--max-steps", "0x100"]),
+      Ok(Options {
+        paths: vec!["sum.s".to_string()],
+        profile: true,
+        max_steps: 256,
+        trace: None,
+        trace_file: None,
+        gdb: None,
+        dap: false,
+        lsp: false,
+        listing: false,
+        object: None,
+      })
+    );
+    assert_eq!(args(&["--gdb", "1234", "a.s"]).unwrap().gdb, Some(1234));
+    assert!(args(&["--gdb", "localhost", "a.s"]).is_err());
+    let traced = args(&["--trace", "jsonl", "--trace-file", "t.log", "a.s"]);
+    assert_eq!(traced.unwrap().trace, Some(TraceFormat::JsonLines));
+    assert!(args(&["--trace", "qemu", "a.s"]).is_err());
+    assert!(args(&[]).is_err());
+    assert!(args(&["--dap"]).unwrap().paths.is_empty());
+    assert!(args(&["--lsp"]).unwrap().lsp);
+    assert!(args(&["--listing", "a.out"]).unwrap().listing);
+    assert!(args(&["--listing"]).is_err());
+    assert_eq!(args(&["a.s", "b.o"]).unwrap().paths, ["a.s", "b.o"]);
+    assert!(args(&["--listing", "a.s", "b.s"]).is_err());
+    let object = args(&["--object", "a.o", "a.s"]).unwrap().object;
+    assert_eq!(object, Some("a.o".to_string()));
+    assert!(args(&["--object", "a.o", "a.s", "b.s"]).is_err());
+    assert!(args(&["--max-steps"]).is_err());
+    assert!(args(&["--verbose", "a.s"]).is_err());
+  }
+}
@@ -0,0 +1,187 @@
+use std::collections::HashMap;
+use std::ops::Range;
+
+/// Anything the interpreter can load from and store to. Addresses run from 0
+/// to size() - 1, and callers are expected to check that accesses are in
+/// range before making them.
+pub trait Memory {
+  fn size(&self) -> u64;
+  /// Reads may have side effects, e.g. on memory mapped devices.
+  fn read(&mut self, address: u64, buf: &mut [u8]);
+  fn write(&mut self, address: u64, data: &[u8]);
+  /// Read without side effects, for displaying memory.
+  fn peek(&self, address: u64, buf: &mut [u8]);
+}
+
+/// A single contiguous block of bytes. Only suitable for small memories, as
+/// all of it is allocated up front.
+pub struct FlatMemory {
+  bytes: Vec<u8>,
+}
+
+impl FlatMemory {
+  pub fn new(size: u64) -> Self {
+    FlatMemory {
+      bytes: vec![0; size as usize],
+    }
+  }
+}
+
+impl Memory for FlatMemory {
+  fn size(&self) -> u64 {
+    self.bytes.len() as u64
+  }
+
+  fn read(&mut self, address: u64, buf: &mut [u8]) {
+    self.peek(address, buf);
+  }
+
+  fn write(&mut self, address: u64, data: &[u8]) {
+    let start = address as usize;
+    self.bytes[start..start + data.len()].copy_from_slice(data);
+  }
+
+  fn peek(&self, address: u64, buf: &mut [u8]) {
+    let start = address as usize;
+    buf.copy_from_slice(&self.bytes[start..start + buf.len()]);
+  }
+}
+
+pub const PAGE_SIZE: u64 = 4096;
+
+/// A large address space where pages are only allocated once they're written
+/// to, so that a program can use e.g. a stack near the top of memory and data
+/// near the bottom without allocating everything in between. Pages which
+/// haven't been written read as zero.
+pub struct SparseMemory {
+  size: u64,
+  pages: HashMap<u64, Box<[u8; PAGE_SIZE as usize]>>,
+}
+
+impl SparseMemory {
+  pub fn new(size: u64) -> Self {
+    SparseMemory {
+      size,
+      pages: HashMap::new(),
+    }
+  }
+}
+
+impl Memory for SparseMemory {
+  fn size(&self) -> u64 {
+    self.size
+  }
+
+  fn read(&mut self, address: u64, buf: &mut [u8]) {
+    self.peek(address, buf);
+  }
+
+  fn peek(&self, address: u64, buf: &mut [u8]) {
+    for (a, range) in page_pieces(address, buf.len()) {
+      let offset = (a % PAGE_SIZE) as usize;
+      let piece = &mut buf[range];
+      match self.pages.get(&(a / PAGE_SIZE)) {
+        Some(page) => {
+          piece.copy_from_slice(&page[offset..offset + piece.len()])
+        }
+        None => piece.fill(0),
+      }
+    }
+  }
+
+  fn write(&mut self, address: u64, data: &[u8]) {
+    for (a, range) in page_pieces(address, data.len()) {
+      let offset = (a % PAGE_SIZE) as usize;
+      let piece = &data[range];
+      let page = self
+        .pages
+        .entry(a / PAGE_SIZE)
+        .or_insert_with(|| Box::new([0; PAGE_SIZE as usize]));
+      page[offset..offset + piece.len()].copy_from_slice(piece);
+    }
+  }
+}
+
+/// Splits `len` bytes from `address` where they cross into another page,
+/// giving the address each piece starts at and where it is in the buffer.
+fn page_pieces(
+  address: u64,
+  len: usize,
+) -> impl Iterator<Item = (u64, Range<usize>)> {
+  let mut done = 0;
+  std::iter::from_fn(move || {
+    if done == len {
+      return None;
+    }
+    let a = address + done as u64;
+    let piece = std::cmp::min((PAGE_SIZE - a % PAGE_SIZE) as usize, len - done);
+    done += piece;
+    Some((a, done - piece..done))
+  })
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum MemoryModel {
+  Flat,
+  Sparse,
+}
+
+/// Describes the memory to create, so that it can be recreated on reset.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct MemoryConfig {
+  pub model: MemoryModel,
+  pub size: u64,
+}
+
+impl Default for MemoryConfig {
+  /// 2GiB, i.e. addresses 0x00000000 - 0x7fffffff, which is enough room for
+  /// the usual layout of data at 0x10000000 and a stack below 0x7ffff000.
+  fn default() -> Self {
+    MemoryConfig {
+      model: MemoryModel::Sparse,
+      size: 0x8000_0000,
+    }
+  }
+}
+
+impl MemoryConfig {
+  pub fn create(&self) -> Box<dyn Memory> {
+    match self.model {
+      MemoryModel::Flat => Box::new(FlatMemory::new(self.size)),
+      MemoryModel::Sparse => Box::new(SparseMemory::new(self.size)),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn round_trip(memory: &mut dyn Memory, address: u64) {
+    memory.write(address, &[1, 2, 3, 4]);
+    let mut buf = [0; 4];
+    memory.read(address, &mut buf);
+    assert_eq!(buf, [1, 2, 3, 4]);
+  }
+
+  #[test]
+  fn flat_round_trip() {
+    round_trip(&mut FlatMemory::new(4096), 100);
+  }
+
+  #[test]
+  fn sparse_round_trip_across_pages() {
+    round_trip(&mut SparseMemory::new(0x8000_0000), PAGE_SIZE - 2);
+  }
+
+  #[test]
+  fn sparse_reads_zero_and_only_allocates_written_pages() {
+    let mut memory = SparseMemory::new(0x8000_0000);
+    let mut buf = [0xff; 8];
+    memory.read(0x7fff_f000, &mut buf);
+    assert_eq!(buf, [0; 8]);
+    memory.write(0x1000_0000, &[1]);
+    memory.write(0x7fff_eff8, &[1]);
+    assert_eq!(memory.pages.len(), 2);
+  }
+}
@@ -0,0 +1,373 @@
+use crate::memory::{Memory, MemoryConfig};
+use std::fmt;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Permissions {
+  pub read: bool,
+  pub write: bool,
+  pub execute: bool,
+}
+
+impl Permissions {
+  pub const RW: Permissions = Permissions {
+    read: true,
+    write: true,
+    execute: false,
+  };
+  pub const RX: Permissions = Permissions {
+    read: true,
+    write: false,
+    execute: true,
+  };
+  pub const RWX: Permissions = Permissions {
+    read: true,
+    write: true,
+    execute: true,
+  };
+
+  fn allows(&self, access: AccessType) -> bool {
+    match access {
+      AccessType::Read => self.read,
+      AccessType::Write => self.write,
+      AccessType::Execute => self.execute,
+    }
+  }
+}
+
+impl fmt::Display for Permissions {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(
+      f,
+      "{}{}{}",
+      if self.read { 'r' } else { '-' },
+      if self.write { 'w' } else { '-' },
+      if self.execute { 'x' } else { '-' }
+    )
+  }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Region {
+  pub name: String,
+  pub start: u64,
+  pub size: u64,
+  pub permissions: Permissions,
+}
+
+impl Region {
+  fn new(name: &str, start: u64, size: u64, permissions: Permissions) -> Self {
+    Region {
+      name: name.to_string(),
+      start,
+      size,
+      permissions,
+    }
+  }
+
+  fn contains(&self, address: u64, len: u64) -> bool {
+    address >= self.start
+      && address.saturating_add(len) <= self.start + self.size
+  }
+}
+
+/// A peripheral which claims the addresses base()..base() + size(). Offsets
+/// passed to it are relative to base().
+pub trait Device {
+  fn name(&self) -> &'static str;
+  fn base(&self) -> u64;
+  fn size(&self) -> u64;
+  fn read(&mut self, offset: u64, buf: &mut [u8]);
+  fn write(&mut self, offset: u64, data: &[u8]);
+  /// Read without side effects, for displaying memory.
+  fn peek(&self, offset: u64, buf: &mut [u8]);
+  /// Called once per instruction executed.
+  fn tick(&mut self) {}
+  /// The bits of mip for the interrupts this device is raising.
+  fn interrupts(&self) -> u64 {
+    0
+  }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum AccessType {
+  Read,
+  Write,
+  Execute,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MemoryFault {
+  pub address: u64,
+  pub len: u64,
+  pub access: AccessType,
+  // The region the access was in, if any
+  pub region: Option<String>,
+}
+
+impl fmt::Display for MemoryFault {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let (description, adjective) = match self.access {
+      AccessType::Read => ("load from", "readable"),
+      AccessType::Write => ("store to", "writable"),
+      AccessType::Execute => ("instruction fetch from", "executable"),
+    };
+    match &self.region {
+      Some(region) => write!(
+        f,
+        "{} 0x{:08x} ({} bytes) is in the {} region, which is not {}",
+        description, self.address, self.len, region, adjective
+      ),
+      None => write!(
+        f,
+        "{} 0x{:08x} ({} bytes) is not in any memory region",
+        description, self.address, self.len
+      ),
+    }
+  }
+}
+
+/// The address space as the program sees it: named regions with
+/// permissions, backed by a Memory, plus devices which claim address ranges
+/// of their own. Accesses which aren't allowed don't happen; instead the
+/// fault is recorded for the interpreter to pick up with take_fault().
+pub struct MemoryMap {
+  backing: Box<dyn Memory>,
+  regions: Vec<Region>,
+  devices: Vec<Box<dyn Device>>,
+  fault: Option<MemoryFault>,
+}
+
+pub const STACK_SIZE: u64 = 0x0100_0000;
+const HEAP_END: u64 = 0x2000_0000;
+
+impl MemoryMap {
+  /// Small memories are one read/write/execute region covering everything.
+  /// Ones big enough are laid out like a real machine:
+  ///  - text at 0x00000000, where the program lives
+  ///  - data at 0x10010000, heap above that
+  ///  - the stack at the top of memory
+  pub fn new(config: MemoryConfig) -> Self {
+    let regions = if config.size < HEAP_END + STACK_SIZE {
+      vec![Region::new("ram", 0, config.size, Permissions::RWX)]
+    } else {
+      vec![
+        Region::new("text", 0x0000_0000, 0x0010_0000, Permissions::RX),
+        Region::new("data", 0x1001_0000, 0x000f_0000, Permissions::RW),
+        Region::new(
+          "heap",
+          0x1010_0000,
+          HEAP_END - 0x1010_0000,
+          Permissions::RW,
+        ),
+        Region::new(
+          "stack",
+          config.size - STACK_SIZE,
+          STACK_SIZE,
+          Permissions::RW,
+        ),
+      ]
+    };
+    MemoryMap {
+      backing: config.create(),
+      regions,
+      devices: Vec::new(),
+      fault: None,
+    }
+  }
+
+  pub fn add_device(&mut self, device: Box<dyn Device>) {
+    self.devices.push(device);
+  }
+
+  /// All the regions, including those claimed by devices, in address order.
+  pub fn layout(&self) -> Vec<Region> {
+    let mut layout: Vec<Region> = self.regions.clone();
+    for device in &self.devices {
+      layout.push(Region::new(
+        device.name(),
+        device.base(),
+        device.size(),
+        Permissions::RW,
+      ));
+    }
+    layout.sort_by_key(|region| region.start);
+    layout
+  }
+
+  fn device_index(&self, address: u64, len: u64) -> Option<usize> {
+    self.devices.iter().position(|device| {
+      address >= device.base()
+        && address.saturating_add(len) <= device.base() + device.size()
+    })
+  }
+
+  /// Whether an address belongs to a device. Device registers aren't cached.
+  pub fn is_device(&self, address: u64) -> bool {
+    self.device_index(address, 1).is_some()
+  }
+
+  /// Writes the program's machine code, which goes in the text region even
+  /// though that's read only. It's left out if it isn't all in one region.
+  pub fn load(&mut self, address: u64, data: &[u8]) {
+    let len = data.len() as u64;
+    if self.check(address, len, AccessType::Read).is_ok() {
+      self.backing.write(address, data);
+    }
+  }
+
+  pub fn check(
+    &self,
+    address: u64,
+    len: u64,
+    access: AccessType,
+  ) -> Result<(), MemoryFault> {
+    let region = self.regions.iter().find(|r| r.contains(address, len));
+    match region {
+      Some(region) if region.permissions.allows(access) => Ok(()),
+      _ => Err(MemoryFault {
+        address,
+        len,
+        access,
+        region: region.map(|r| r.name.clone()),
+      }),
+    }
+  }
+
+  pub fn tick(&mut self) {
+    for device in &mut self.devices {
+      device.tick();
+    }
+  }
+
+  pub fn pending_interrupts(&self) -> u64 {
+    self
+      .devices
+      .iter()
+      .fold(0, |mip, device| mip | device.interrupts())
+  }
+
+  pub fn take_fault(&mut self) -> Option<MemoryFault> {
+    self.fault.take()
+  }
+}
+
+impl Memory for MemoryMap {
+  fn size(&self) -> u64 {
+    self.backing.size()
+  }
+
+  fn read(&mut self, address: u64, buf: &mut [u8]) {
+    if let Some(i) = self.device_index(address, buf.len() as u64) {
+      let base = self.devices[i].base();
+      self.devices[i].read(address - base, buf);
+      return;
+    }
+    match self.check(address, buf.len() as u64, AccessType::Read) {
+      Ok(()) => self.backing.read(address, buf),
+      Err(fault) => {
+        buf.fill(0);
+        self.fault.get_or_insert(fault);
+      }
+    }
+  }
+
+  fn write(&mut self, address: u64, data: &[u8]) {
+    if let Some(i) = self.device_index(address, data.len() as u64) {
+      let base = self.devices[i].base();
+      self.devices[i].write(address - base, data);
+      return;
+    }
+    match self.check(address, data.len() as u64, AccessType::Write) {
+      Ok(()) => self.backing.write(address, data),
+      Err(fault) => {
+        self.fault.get_or_insert(fault);
+      }
+    }
+  }
+
+  fn peek(&self, address: u64, buf: &mut [u8]) {
+    if let Some(i) = self.device_index(address, buf.len() as u64) {
+      self.devices[i].peek(address - self.devices[i].base(), buf);
+    } else if address.saturating_add(buf.len() as u64) <= self.size() {
+      self.backing.peek(address, buf);
+    } else {
+      buf.fill(0);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn sparse() -> MemoryMap {
+    MemoryMap::new(MemoryConfig::default())
+  }
+
+  #[test]
+  fn store_to_text_faults() {
+    let mut map = sparse();
+    map.write(0x100, &[1, 2, 3, 4]);
+    let fault = map.take_fault().unwrap();
+    assert_eq!(fault.access, AccessType::Write);
+    assert_eq!(fault.region, Some("text".to_string()));
+    let mut buf = [0xff; 4];
+    map.read(0x100, &mut buf);
+    assert_eq!(buf, [0; 4]);
+    assert_eq!(map.take_fault(), None);
+  }
+
+  #[test]
+  fn execute_from_stack_faults() {
+    let map = sparse();
+    assert!(map.check(0x7fff_f000, 4, AccessType::Execute).is_err());
+    assert!(map.check(0x0000_1000, 4, AccessType::Execute).is_ok());
+  }
+
+  #[test]
+  fn unmapped_load_faults() {
+    let mut map = sparse();
+    let mut buf = [0; 8];
+    map.read(0x4000_0000, &mut buf);
+    assert_eq!(map.take_fault().unwrap().region, None);
+  }
+
+  struct Scratch {
+    value: u8,
+    reads: u32,
+  }
+
+  impl Device for Scratch {
+    fn name(&self) -> &'static str {
+      "scratch"
+    }
+    fn base(&self) -> u64 {
+      0x4000_0000
+    }
+    fn size(&self) -> u64 {
+      1
+    }
+    fn read(&mut self, _offset: u64, buf: &mut [u8]) {
+      self.reads += 1;
+      self.peek(0, buf);
+    }
+    fn write(&mut self, _offset: u64, data: &[u8]) {
+      self.value = data[0];
+    }
+    fn peek(&self, _offset: u64, buf: &mut [u8]) {
+      buf[0] = self.value;
+    }
+  }
+
+  #[test]
+  fn devices_claim_addresses() {
+    let mut map = sparse();
+    map.add_device(Box::new(Scratch { value: 0, reads: 0 }));
+    map.write(0x4000_0000, &[42]);
+    let mut buf = [0; 1];
+    map.read(0x4000_0000, &mut buf);
+    assert_eq!(buf, [42]);
+    assert_eq!(map.take_fault(), None);
+    assert!(map.layout().iter().any(|r| r.name == "scratch"));
+  }
+}
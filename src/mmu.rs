@@ -0,0 +1,477 @@
+use crate::csr::{
+  Privilege, Translation, Trap, INSTRUCTION_PAGE_FAULT, LOAD_PAGE_FAULT,
+  STORE_PAGE_FAULT,
+};
+use crate::memory::{Memory, PAGE_SIZE};
+use crate::memory_map::{AccessType, MemoryMap};
+use std::collections::VecDeque;
+
+const PTE_V: u64 = 1 << 0;
+const PTE_R: u64 = 1 << 1;
+const PTE_W: u64 = 1 << 2;
+const PTE_X: u64 = 1 << 3;
+const PTE_U: u64 = 1 << 4;
+const PTE_A: u64 = 1 << 6;
+const PTE_D: u64 = 1 << 7;
+
+const SATP_MODE_SV39: u64 = 8;
+const SATP_PPN: u64 = (1 << 44) - 1;
+const VPN_MASK: u64 = (1 << 27) - 1;
+const LEVELS: u32 = 3;
+const VPN_BITS: u32 = 9;
+const PAGE_SHIFT: u32 = 12;
+
+pub const TLB_ENTRIES: usize = 16;
+
+/// A cached translation: the leaf page table entry for a page, which may be
+/// a superpage at level 1 (2MiB) or 2 (1GiB).
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct TlbEntry {
+  vpn: u64,
+  level: u32,
+  pte: u64,
+  // Where the PTE lives, so that the A and D bits can be updated
+  pte_address: u64,
+}
+
+impl TlbEntry {
+  fn page_shift(&self) -> u32 {
+    PAGE_SHIFT + VPN_BITS * self.level
+  }
+
+  fn contains(&self, va: u64) -> bool {
+    let shift = self.page_shift() - PAGE_SHIFT;
+    ((va >> PAGE_SHIFT) & VPN_MASK) >> shift == self.vpn >> shift
+  }
+
+  fn physical_address(&self, va: u64) -> u64 {
+    let shift = self.page_shift();
+    let ppn = (self.pte >> 10) & SATP_PPN;
+    ((ppn << PAGE_SHIFT) >> shift << shift) | (va & ((1 << shift) - 1))
+  }
+}
+
+/// A small fully associative TLB with first in, first out replacement. It
+/// isn't kept coherent with the page tables, so like real hardware it needs
+/// an sfence.vma after they're changed.
+pub struct Tlb {
+  entries: VecDeque<TlbEntry>,
+  pub hits: u64,
+  pub misses: u64,
+}
+
+impl Tlb {
+  pub fn new() -> Self {
+    Tlb {
+      entries: VecDeque::with_capacity(TLB_ENTRIES),
+      hits: 0,
+      misses: 0,
+    }
+  }
+
+  pub fn flush(&mut self) {
+    self.entries.clear();
+  }
+
+  pub fn len(&self) -> usize {
+    self.entries.len()
+  }
+
+  fn lookup(&mut self, va: u64) -> Option<TlbEntry> {
+    let entry = self.entries.iter().find(|e| e.contains(va)).copied();
+    match entry {
+      Some(_) => self.hits += 1,
+      None => self.misses += 1,
+    }
+    entry
+  }
+
+  fn insert(&mut self, entry: TlbEntry) {
+    self
+      .entries
+      .retain(|e| !e.contains(entry.vpn << PAGE_SHIFT));
+    if self.entries.len() == TLB_ENTRIES {
+      self.entries.pop_front();
+    }
+    self.entries.push_back(entry);
+  }
+}
+
+/// Sv39 addresses are 39 bits, sign extended to 64.
+fn canonical(va: u64) -> bool {
+  ((va as i64) << 25 >> 25) as u64 == va
+}
+
+/// Walks the page tables for `va`, returning its leaf entry without checking
+/// permissions.
+fn walk(memory: &MemoryMap, satp: u64, va: u64) -> Option<TlbEntry> {
+  if !canonical(va) {
+    return None;
+  }
+  let mut table = (satp & SATP_PPN) << PAGE_SHIFT;
+  for level in (0..LEVELS).rev() {
+    let vpn = (va >> (PAGE_SHIFT + VPN_BITS * level)) & 0x1ff;
+    let pte_address = table + vpn * 8;
+    memory.check(pte_address, 8, AccessType::Read).ok()?;
+    let mut bytes = [0; 8];
+    memory.peek(pte_address, &mut bytes);
+    let pte = u64::from_le_bytes(bytes);
+    if pte & PTE_V == 0 || (pte & PTE_R == 0 && pte & PTE_W != 0) {
+      return None;
+    }
+    if pte & (PTE_R | PTE_X) != 0 {
+      let ppn = (pte >> 10) & SATP_PPN;
+      // Superpages must be aligned to their size
+      if ppn & ((1 << (VPN_BITS * level)) - 1) != 0 {
+        return None;
+      }
+      return Some(TlbEntry {
+        vpn: (va >> PAGE_SHIFT) & VPN_MASK,
+        level,
+        pte,
+        pte_address,
+      });
+    }
+    table = ((pte >> 10) & SATP_PPN) << PAGE_SHIFT;
+  }
+  None
+}
+
+fn translation_active(satp: u64, privilege: Privilege) -> bool {
+  privilege < Privilege::Machine && satp >> 60 == SATP_MODE_SV39
+}
+
+/// The physical address `va` maps to under `satp`, as supervisor mode would
+/// see it, without touching the TLB or the A and D bits. For displaying
+/// memory.
+pub fn virtual_to_physical(
+  memory: &MemoryMap,
+  satp: u64,
+  va: u64,
+) -> Option<u64> {
+  if !translation_active(satp, Privilege::Supervisor) {
+    return Some(va);
+  }
+  walk(memory, satp, va).map(|entry| entry.physical_address(va))
+}
+
+/// The memory an instruction sees: physical memory, seen through the page
+/// tables when running below machine mode with paging enabled in satp. Page
+/// faults are recorded for the interpreter to pick up with take_fault(), and
+/// the access doesn't happen. The A and D bits are set by the hardware as
+/// pages are accessed. The physical addresses accessed are recorded too, for
+/// the cache model.
+pub struct Mmu<'a> {
+  memory: &'a mut MemoryMap,
+  tlb: &'a mut Tlb,
+  translation: Translation,
+  fault: Option<Trap>,
+  accesses: Vec<(u64, usize, AccessType)>,
+}
+
+impl<'a> Mmu<'a> {
+  pub fn new(
+    memory: &'a mut MemoryMap,
+    tlb: &'a mut Tlb,
+    translation: Translation,
+  ) -> Self {
+    Mmu {
+      memory,
+      tlb,
+      translation,
+      fault: None,
+      accesses: Vec::new(),
+    }
+  }
+
+  fn permitted(&self, pte: u64, access: AccessType) -> bool {
+    let allowed = match access {
+      AccessType::Read => {
+        pte & PTE_R != 0 || (self.translation.mxr && pte & PTE_X != 0)
+      }
+      AccessType::Write => pte & PTE_W != 0,
+      AccessType::Execute => pte & PTE_X != 0,
+    };
+    let user_page = pte & PTE_U != 0;
+    let privilege_ok = match self.translation.privilege {
+      Privilege::User => user_page,
+      Privilege::Supervisor => {
+        !user_page || (self.translation.sum && access != AccessType::Execute)
+      }
+      Privilege::Machine => true,
+    };
+    allowed && privilege_ok
+  }
+
+  pub fn translate(
+    &mut self,
+    va: u64,
+    access: AccessType,
+  ) -> Result<u64, Trap> {
+    if !translation_active(self.translation.satp, self.translation.privilege) {
+      return Ok(va);
+    }
+    let fault = Trap::Exception {
+      code: match access {
+        AccessType::Read => LOAD_PAGE_FAULT,
+        AccessType::Write => STORE_PAGE_FAULT,
+        AccessType::Execute => INSTRUCTION_PAGE_FAULT,
+      },
+      tval: va,
+    };
+    if !canonical(va) {
+      return Err(fault);
+    }
+    let mut entry = match self.tlb.lookup(va) {
+      Some(entry) => entry,
+      None => {
+        let entry =
+          walk(self.memory, self.translation.satp, va).ok_or(fault)?;
+        self.tlb.insert(entry);
+        entry
+      }
+    };
+    if !self.permitted(entry.pte, access) {
+      return Err(fault);
+    }
+
+    let mut pte = entry.pte | PTE_A;
+    if access == AccessType::Write {
+      pte |= PTE_D;
+    }
+    if pte != entry.pte {
+      self.memory.write(entry.pte_address, &pte.to_le_bytes());
+      entry.pte = pte;
+      self.tlb.insert(entry);
+    }
+    Ok(entry.physical_address(va))
+  }
+
+  pub fn take_fault(&mut self) -> Option<Trap> {
+    self.fault.take()
+  }
+
+  /// The physical address and length of each access made, in order.
+  pub fn take_accesses(&mut self) -> Vec<(u64, usize, AccessType)> {
+    std::mem::take(&mut self.accesses)
+  }
+
+  /// Translates each page an access touches, so that an access which
+  /// crosses into an unmapped page faults before any of it happens. No
+  /// instruction accesses more than 8 bytes, so that's at most two pages,
+  /// and the second is empty if it's only one.
+  fn translate_range(
+    &mut self,
+    address: u64,
+    len: usize,
+    access: AccessType,
+  ) -> Option<[(u64, usize); 2]> {
+    let in_page = (PAGE_SIZE - address % PAGE_SIZE) as usize;
+    let first = std::cmp::min(in_page, len);
+    let mut chunks = [(0, first), (0, len - first)];
+    assert!(chunks[1].1 as u64 <= PAGE_SIZE, "Access is too long");
+    let mut va = address;
+    for (pa, len) in &mut chunks {
+      if *len == 0 {
+        continue;
+      }
+      match self.translate(va, access) {
+        Ok(translated) => *pa = translated,
+        Err(fault) => {
+          self.fault.get_or_insert(fault);
+          return None;
+        }
+      }
+      va = va.wrapping_add(*len as u64);
+    }
+    Some(chunks)
+  }
+}
+
+impl Memory for Mmu<'_> {
+  fn size(&self) -> u64 {
+    self.memory.size()
+  }
+
+  fn read(&mut self, address: u64, buf: &mut [u8]) {
+    match self.translate_range(address, buf.len(), AccessType::Read) {
+      Some(chunks) => {
+        let mut done = 0;
+        for &(pa, len) in chunks.iter().filter(|(_, len)| *len > 0) {
+          self.memory.read(pa, &mut buf[done..done + len]);
+          self.accesses.push((pa, len, AccessType::Read));
+          done += len;
+        }
+      }
+      None => buf.fill(0),
+    }
+  }
+
+  fn write(&mut self, address: u64, data: &[u8]) {
+    if let Some(chunks) =
+      self.translate_range(address, data.len(), AccessType::Write)
+    {
+      let mut done = 0;
+      for &(pa, len) in chunks.iter().filter(|(_, len)| *len > 0) {
+        self.memory.write(pa, &data[done..done + len]);
+        self.accesses.push((pa, len, AccessType::Write));
+        done += len;
+      }
+    }
+  }
+
+  fn peek(&self, address: u64, buf: &mut [u8]) {
+    for (i, byte) in buf.iter_mut().enumerate() {
+      let va = address.wrapping_add(i as u64);
+      *byte = match virtual_to_physical(self.memory, self.translation.satp, va)
+      {
+        Some(pa) => {
+          let mut b = [0];
+          self.memory.peek(pa, &mut b);
+          b[0]
+        }
+        None => 0,
+      };
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::memory::MemoryConfig;
+
+  const ROOT: u64 = 0x1001_0000;
+
+  fn pte(physical: u64, flags: u64) -> u64 {
+    (physical >> PAGE_SHIFT) << 10 | flags | PTE_V
+  }
+
+  fn store(memory: &mut MemoryMap, address: u64, value: u64) {
+    memory.write(address, &value.to_le_bytes());
+  }
+
+  fn load(memory: &mut MemoryMap, address: u64) -> u64 {
+    let mut bytes = [0; 8];
+    memory.read(address, &mut bytes);
+    u64::from_le_bytes(bytes)
+  }
+
+  /// Maps virtual 0x4000_0000 (VPN[2] = 1) to physical 0x1002_0000 with
+  /// `flags`, through a three level walk.
+  fn page_tables(flags: u64) -> MemoryMap {
+    let mut memory = MemoryMap::new(MemoryConfig::default());
+    store(&mut memory, ROOT + 8, pte(ROOT + 0x1000, 0));
+    store(&mut memory, ROOT + 0x1000, pte(ROOT + 0x2000, 0));
+    store(&mut memory, ROOT + 0x2000, pte(0x1002_0000, flags));
+    memory
+  }
+
+  fn supervisor() -> Translation {
+    Translation {
+      satp: SATP_MODE_SV39 << 60 | ROOT >> PAGE_SHIFT,
+      privilege: Privilege::Supervisor,
+      sum: false,
+      mxr: false,
+    }
+  }
+
+  #[test]
+  fn three_level_walk_sets_accessed_and_dirty() {
+    let mut memory = page_tables(PTE_R | PTE_W);
+    let mut tlb = Tlb::new();
+    let mut mmu = Mmu::new(&mut memory, &mut tlb, supervisor());
+    mmu.write(0x4000_0010, &[42]);
+    let mut buf = [0];
+    mmu.read(0x4000_0010, &mut buf);
+    assert_eq!(buf, [42]);
+    assert_eq!(mmu.take_fault(), None);
+    assert_eq!(tlb.hits, 1);
+    assert_eq!(tlb.misses, 1);
+    let leaf = load(&mut memory, ROOT + 0x2000);
+    assert_eq!(leaf & (PTE_A | PTE_D), PTE_A | PTE_D);
+    let mut buf = [0];
+    memory.read(0x1002_0010, &mut buf);
+    assert_eq!(buf, [42]);
+  }
+
+  #[test]
+  fn page_faults() {
+    let mut memory = page_tables(PTE_R);
+    let mut tlb = Tlb::new();
+    let mut mmu = Mmu::new(&mut memory, &mut tlb, supervisor());
+    mmu.write(0x4000_0000, &[1]);
+    assert_eq!(
+      mmu.take_fault(),
+      Some(Trap::Exception {
+        code: STORE_PAGE_FAULT,
+        tval: 0x4000_0000
+      })
+    );
+    let mut buf = [0];
+    mmu.read(0x8000_0000, &mut buf);
+    assert_eq!(
+      mmu.take_fault().map(|t| matches!(
+        t,
+        Trap::Exception {
+          code: LOAD_PAGE_FAULT,
+          ..
+        }
+      )),
+      Some(true)
+    );
+    assert!(mmu.translate(0x4000_0000, AccessType::Execute).is_err());
+  }
+
+  #[test]
+  fn user_pages_need_sum() {
+    let mut memory = page_tables(PTE_R | PTE_U);
+    let mut tlb = Tlb::new();
+    let mut translation = supervisor();
+    let mut mmu = Mmu::new(&mut memory, &mut tlb, translation);
+    assert!(mmu.translate(0x4000_0000, AccessType::Read).is_err());
+    translation.sum = true;
+    let mut mmu = Mmu::new(&mut memory, &mut tlb, translation);
+    assert!(mmu.translate(0x4000_0000, AccessType::Read).is_ok());
+  }
+
+  #[test]
+  fn tlb_is_stale_until_flushed() {
+    let mut memory = page_tables(PTE_R);
+    let mut tlb = Tlb::new();
+    let mut mmu = Mmu::new(&mut memory, &mut tlb, supervisor());
+    assert_eq!(
+      mmu.translate(0x4000_0000, AccessType::Read),
+      Ok(0x1002_0000)
+    );
+    store(&mut memory, ROOT + 0x2000, pte(0x1003_0000, PTE_R));
+    let mut mmu = Mmu::new(&mut memory, &mut tlb, supervisor());
+    assert_eq!(
+      mmu.translate(0x4000_0000, AccessType::Read),
+      Ok(0x1002_0000)
+    );
+    tlb.flush();
+    let mut mmu = Mmu::new(&mut memory, &mut tlb, supervisor());
+    assert_eq!(
+      mmu.translate(0x4000_0000, AccessType::Read),
+      Ok(0x1003_0000)
+    );
+  }
+
+  #[test]
+  fn machine_mode_is_untranslated() {
+    let mut memory = page_tables(PTE_R);
+    let mut tlb = Tlb::new();
+    let mut translation = supervisor();
+    translation.privilege = Privilege::Machine;
+    let mut mmu = Mmu::new(&mut memory, &mut tlb, translation);
+    assert_eq!(
+      mmu.translate(0x4000_0000, AccessType::Read),
+      Ok(0x4000_0000)
+    );
+    assert_eq!(
+      virtual_to_physical(&memory, supervisor().satp, 0x4000_0123),
+      Some(0x1002_0123)
+    );
+  }
+}
@@ -0,0 +1,533 @@
+//! The grammar of operands. An operand is a register, a CSR, or a constant
+//! expression made of numbers, character literals, symbols, C's arithmetic,
+//! comparison and logical operators, and the relocation functions `%hi`,
+//! `%lo`, `%pcrel_hi` and `%pcrel_lo`.
+
+use crate::linker::Relocation;
+use std::collections::{HashMap, HashSet};
+
+/// The names that expressions can refer to.
+#[derive(Default)]
+pub struct Symbols {
+  pub labels: HashMap<String, u64>,
+  pub constants: HashMap<String, i64>, // From .equ and .set
+  // The target of each auipc using %pcrel_hi, by the auipc's address, so
+  // that %pcrel_lo can find it from the auipc's label. It's None if the
+  // target is in another file.
+  pub pcrel_hi: HashMap<u64, Option<u64>>,
+  pub globals: HashSet<String>, // From .globl
+  // When a file is assembled to be linked with others, its addresses aren't
+  // known yet. Names which aren't defined are taken to be in another file,
+  // and anything which depends on an address is left to the linker.
+  pub relocatable: bool,
+  pub relocations: Vec<Relocation>,
+}
+
+/// A use of a symbol whose address isn't known until the program is linked.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Reference {
+  pub symbol: String,
+  pub function: Option<&'static str>, // e.g. "%hi"
+  pub addend: i64,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Token {
+  Number(i64),
+  Name(String),
+  Function(&'static str), // e.g. "%hi"
+  Punctuation(&'static str),
+}
+
+/// A token, and where it is in the text it came from.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Lexeme {
+  pub token: Token,
+  pub start: usize,
+  pub end: usize,
+}
+
+const FUNCTIONS: [&str; 4] = ["%hi", "%lo", "%pcrel_hi", "%pcrel_lo"];
+// Longest first, so that "<<" isn't lexed as two "<"s
+const PUNCTUATION: [&str; 23] = [
+  "<<", ">>", "<=", ">=", "==", "!=", "&&", "||", "<", ">", "+", "-", "*", "/",
+  "%", "&", "|", "^", "~", "!", "(", ")", ",",
+];
+
+fn is_name_char(c: char) -> bool {
+  c.is_alphanumeric() || c == '_' || c == '.' || c == '$'
+}
+
+/// The end of the run of characters from `start` which satisfy `predicate`.
+fn run_end(text: &str, start: usize, predicate: fn(char) -> bool) -> usize {
+  text[start..]
+    .find(|c: char| !predicate(c))
+    .map_or(text.len(), |length| start + length)
+}
+
+/// The value of the character literal starting at `start`, and its length.
+fn character(text: &str, start: usize) -> Result<(i64, usize), String> {
+  let mut chars = text[start + 1..].chars();
+  let (value, escaped) = match chars.next() {
+    Some('\\') => match chars.next() {
+      Some('n') => ('\n', true),
+      Some('t') => ('\t', true),
+      Some('r') => ('\r', true),
+      Some('0') => ('\0', true),
+      Some(c @ ('\\' | '\'' | '"')) => (c, true),
+      _ => return Err(format!("invalid escape in `{}`", &text[start..])),
+    },
+    Some(c) if c != '\'' => (c, false),
+    _ => return Err(format!("empty character literal in `{}`", text)),
+  };
+  if chars.next() != Some('\'') {
+    return Err(format!(
+      "unterminated character literal `{}`",
+      &text[start..]
+    ));
+  }
+  let length = value.len_utf8() + escaped as usize + 2;
+  Ok((value as i64, length))
+}
+
+pub fn lex(text: &str) -> Result<Vec<Lexeme>, String> {
+  let mut lexemes = Vec::new();
+  let mut start = 0;
+  while let Some(c) = text[start..].chars().next() {
+    let (token, end) = if c.is_whitespace() {
+      start += c.len_utf8();
+      continue;
+    } else if c.is_ascii_digit() {
+      let end = run_end(text, start, is_name_char);
+      let number = &text[start..end];
+      let value = parse_int::parse::<i64>(number)
+        .or_else(|_| parse_int::parse::<u64>(number).map(|v| v as i64))
+        .map_err(|_| format!("invalid number `{}`", number))?;
+      (Token::Number(value), end)
+    } else if is_name_char(c) {
+      let end = run_end(text, start, is_name_char);
+      (Token::Name(text[start..end].to_string()), end)
+    } else if c == '\'' {
+      let (value, length) = character(text, start)?;
+      (Token::Number(value), start + length)
+    } else if c == '%' && text[start + 1..].starts_with(char::is_alphabetic) {
+      // A relocation function, as % is only an operator between operands
+      let end = run_end(text, start + 1, is_name_char);
+      let name = &text[start..end];
+      match FUNCTIONS.iter().find(|f| **f == name) {
+        Some(function) => (Token::Function(function), end),
+        None => return Err(format!("unknown function `{}`", name)),
+      }
+    } else {
+      match PUNCTUATION.iter().find(|p| text[start..].starts_with(**p)) {
+        Some(p) => (Token::Punctuation(p), start + p.len()),
+        None => return Err(format!("unexpected `{}`", c)),
+      }
+    };
+    lexemes.push(Lexeme { token, start, end });
+    start = end;
+  }
+  Ok(lexemes)
+}
+
+/// The text `lexemes` came from, quoted for an error message.
+pub fn quote(text: &str, lexemes: &[Lexeme]) -> String {
+  match (lexemes.first(), lexemes.last()) {
+    (Some(first), Some(last)) => format!("`{}`", &text[first.start..last.end]),
+    _ => String::from("nothing"),
+  }
+}
+
+/// Splits `lexemes` at the commas which aren't inside parentheses.
+pub fn split_operands(lexemes: &[Lexeme]) -> Vec<&[Lexeme]> {
+  if lexemes.is_empty() {
+    return Vec::new();
+  }
+  let mut operands = Vec::new();
+  let mut depth = 0;
+  let mut start = 0;
+  for (i, lexeme) in lexemes.iter().enumerate() {
+    match lexeme.token {
+      Token::Punctuation("(") => depth += 1,
+      Token::Punctuation(")") => depth -= 1,
+      Token::Punctuation(",") if depth == 0 => {
+        operands.push(&lexemes[start..i]);
+        start = i + 1;
+      }
+      _ => (),
+    }
+  }
+  operands.push(&lexemes[start..]);
+  operands
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Value {
+  pub value: i64,
+  pub uses_label: bool, // So it's an address rather than an offset
+  pub pcrel_target: Option<u64>, // The argument of %pcrel_hi
+  pub reference: Option<Reference>,
+}
+
+struct Evaluator<'a> {
+  text: &'a str,
+  lexemes: &'a [Lexeme],
+  position: usize,
+  symbols: &'a Symbols,
+  address: u64,
+  uses_label: bool,
+  pcrel_target: Option<u64>,
+  reference: Option<Reference>,
+}
+
+/// Binary operators, from the loosest binding to the tightest.
+const PRECEDENCE: [&[&str]; 10] = [
+  &["||"],
+  &["&&"],
+  &["|"],
+  &["^"],
+  &["&"],
+  &["==", "!="],
+  &["<", "<=", ">", ">="],
+  &["<<", ">>"],
+  &["+", "-"],
+  &["*", "/", "%"],
+];
+
+fn low_12_bits(value: i64) -> i64 {
+  (value << 52) >> 52
+}
+
+fn high_20_bits(value: i64) -> i64 {
+  (value.wrapping_add(0x800) >> 12) & 0xfffff
+}
+
+impl Evaluator<'_> {
+  fn peek(&self) -> Option<&Token> {
+    self.lexemes.get(self.position).map(|lexeme| &lexeme.token)
+  }
+
+  /// The next token, quoted for an error message.
+  fn found(&self) -> String {
+    let end = self.lexemes.len().min(self.position + 1);
+    quote(self.text, &self.lexemes[self.position..end])
+  }
+
+  fn remaining(&self) -> usize {
+    self.lexemes.len() - self.position
+  }
+
+  fn expect(&mut self, punctuation: &'static str) -> Result<(), String> {
+    if self.peek() != Some(&Token::Punctuation(punctuation)) {
+      return Err(format!(
+        "expected `{}`, found {}",
+        punctuation,
+        self.found()
+      ));
+    }
+    self.position += 1;
+    Ok(())
+  }
+
+  fn binary(&mut self, level: usize) -> Result<i64, String> {
+    if level == PRECEDENCE.len() {
+      return self.unary();
+    }
+    let mut value = self.binary(level + 1)?;
+    while let Some(Token::Punctuation(operator)) = self.peek() {
+      let operator = *operator;
+      if !PRECEDENCE[level].contains(&operator) {
+        break;
+      }
+      self.position += 1;
+      let rhs = self.binary(level + 1)?;
+      value = match operator {
+        "||" => (value != 0 || rhs != 0) as i64,
+        "&&" => (value != 0 && rhs != 0) as i64,
+        "==" => (value == rhs) as i64,
+        "!=" => (value != rhs) as i64,
+        "<" => (value < rhs) as i64,
+        "<=" => (value <= rhs) as i64,
+        ">" => (value > rhs) as i64,
+        ">=" => (value >= rhs) as i64,
+        "|" => value | rhs,
+        "^" => value ^ rhs,
+        "&" => value & rhs,
+        "<<" => value.wrapping_shl(rhs as u32),
+        ">>" => value.wrapping_shr(rhs as u32),
+        "+" => value.wrapping_add(rhs),
+        "-" => value.wrapping_sub(rhs),
+        "*" => value.wrapping_mul(rhs),
+        _ if rhs == 0 => {
+          return Err(format!("division by zero in `{}`", self.text.trim()))
+        }
+        "/" => value.wrapping_div(rhs),
+        _ => value.wrapping_rem(rhs),
+      };
+    }
+    Ok(value)
+  }
+
+  fn unary(&mut self) -> Result<i64, String> {
+    match self.peek() {
+      Some(Token::Punctuation("-")) => {
+        self.position += 1;
+        Ok(self.unary()?.wrapping_neg())
+      }
+      Some(Token::Punctuation("+")) => {
+        self.position += 1;
+        self.unary()
+      }
+      Some(Token::Punctuation("~")) => {
+        self.position += 1;
+        Ok(!self.unary()?)
+      }
+      Some(Token::Punctuation("!")) => {
+        self.position += 1;
+        Ok((self.unary()? == 0) as i64)
+      }
+      _ => self.primary(),
+    }
+  }
+
+  fn primary(&mut self) -> Result<i64, String> {
+    let lexeme = match self.lexemes.get(self.position) {
+      Some(lexeme) => lexeme.clone(),
+      None => return Err(String::from("expected expression, found nothing")),
+    };
+    self.position += 1;
+    match lexeme.token {
+      Token::Number(value) => Ok(value),
+      Token::Name(name) => {
+        if let Some(value) = self.symbols.constants.get(&name) {
+          Ok(*value)
+        } else if self.symbols.relocatable {
+          if self.reference.is_some() {
+            return Err(format!(
+              "only one address can be used in `{}`",
+              self.text.trim()
+            ));
+          }
+          self.reference = Some(Reference {
+            symbol: name.clone(),
+            function: None,
+            addend: 0,
+          });
+          // Local labels are still useful relative to each other
+          let address = self.symbols.labels.get(&name);
+          self.uses_label = address.is_some();
+          Ok(address.map_or(0, |address| *address as i64))
+        } else if let Some(address) = self.symbols.labels.get(&name) {
+          self.uses_label = true;
+          Ok(*address as i64)
+        } else {
+          Err(format!("unknown symbol `{}`", name))
+        }
+      }
+      Token::Punctuation("(") => {
+        let value = self.binary(0)?;
+        self.expect(")")?;
+        Ok(value)
+      }
+      Token::Function(function) => self.function(function, lexeme.start),
+      Token::Punctuation(_) => {
+        self.position -= 1;
+        Err(format!("expected expression, found {}", self.found()))
+      }
+    }
+  }
+
+  fn function(
+    &mut self,
+    function: &'static str,
+    start: usize,
+  ) -> Result<i64, String> {
+    self.expect("(")?;
+    // The result is a constant, even if the argument is an address
+    let uses_label = self.uses_label;
+    let outer_reference = self.reference.is_some();
+    let argument = self.binary(0)?;
+    self.expect(")")?;
+    self.uses_label = uses_label;
+    if let Some(mut reference) = self.reference.take() {
+      let local = self.symbols.labels.get(&reference.symbol);
+      match (function, local) {
+        _ if outer_reference => self.reference = Some(reference),
+        // Labels in this file are a fixed distance from the pc
+        ("%pcrel_hi" | "%pcrel_lo", Some(_)) => (),
+        _ => {
+          reference.function = Some(function);
+          reference.addend = argument - local.map_or(0, |a| *a as i64);
+          self.reference = Some(reference);
+          return Ok(0);
+        }
+      }
+    }
+    Ok(match function {
+      "%hi" => high_20_bits(argument),
+      "%lo" => low_12_bits(argument),
+      "%pcrel_hi" => {
+        self.pcrel_target = Some(argument as u64);
+        high_20_bits(argument.wrapping_sub(self.address as i64))
+      }
+      _ => match self.symbols.pcrel_hi.get(&(argument as u64)) {
+        Some(Some(target)) => {
+          low_12_bits(target.wrapping_sub(argument as u64) as i64)
+        }
+        // The linker fills in both halves of the call
+        Some(None) if argument as u64 + 4 == self.address => 0,
+        Some(None) => {
+          return Err(String::from(
+            "`%pcrel_lo` of a symbol in another file has to be straight \
+             after the auipc",
+          ))
+        }
+        None => {
+          let end = self.lexemes[self.position - 1].end;
+          return Err(format!(
+            "`%pcrel_lo` needs the label of an auipc using `%pcrel_hi`, \
+             found `{}`",
+            &self.text[start..end]
+          ));
+        }
+      },
+    })
+  }
+}
+
+/// Evaluates the constant expression in `lexemes`, which came from `text`.
+/// `address` is the address of the instruction it's in.
+pub fn evaluate(
+  text: &str,
+  lexemes: &[Lexeme],
+  symbols: &Symbols,
+  address: u64,
+) -> Result<Value, String> {
+  let mut evaluator = Evaluator {
+    text,
+    lexemes,
+    position: 0,
+    symbols,
+    address,
+    uses_label: false,
+    pcrel_target: None,
+    reference: None,
+  };
+  let mut value = evaluator.binary(0)?;
+  if evaluator.remaining() > 0 {
+    return Err(format!(
+      "expected operator, found {} in {}",
+      evaluator.found(),
+      quote(text, lexemes)
+    ));
+  }
+  if let Some(reference) = &mut evaluator.reference {
+    if reference.function.is_none() {
+      let local = symbols.labels.get(&reference.symbol);
+      reference.addend = value - local.map_or(0, |a| *a as i64);
+      if local.is_none() {
+        value = 0;
+      }
+    }
+  }
+  Ok(Value {
+    value,
+    uses_label: evaluator.uses_label,
+    pcrel_target: evaluator.pcrel_target,
+    reference: evaluator.reference,
+  })
+}
+
+/// Evaluates an expression on its own, such as the value of a .equ.
+pub fn evaluate_str(text: &str, symbols: &Symbols) -> Result<i64, String> {
+  let lexemes = lex(text)?;
+  let value = evaluate(text, &lexemes, symbols, 0)?;
+  match value.reference {
+    Some(reference) => Err(format!(
+      "`{}` is an address, which isn't known until the program is linked",
+      reference.symbol
+    )),
+    None => Ok(value.value),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn value(text: &str) -> Result<i64, String> {
+    let mut symbols = Symbols::default();
+    symbols.labels.insert(String::from("data"), 0x1001_0a10);
+    symbols.constants.insert(String::from("SIZE"), 16);
+    evaluate_str(text, &symbols)
+  }
+
+  #[test]
+  fn arithmetic() {
+    assert_eq!(value("4*8+1"), Ok(33));
+    assert_eq!(value("4 * (8 + 1)"), Ok(36));
+    assert_eq!(value("-SIZE / 3"), Ok(-5));
+    assert_eq!(value("1 << 4 | 1"), Ok(17));
+    assert_eq!(value("~0 ^ 0xff & 7"), Ok(-8));
+    assert_eq!(value("10 % 4 - -1"), Ok(3));
+    assert_eq!(value("0xffffffffffffffff"), Ok(-1));
+    assert_eq!(value("SIZE == 16 && !(1 > 2)"), Ok(1));
+    assert_eq!(value("1 << 2 <= 3 || 0"), Ok(0));
+  }
+
+  #[test]
+  fn character_literals() {
+    assert_eq!(value("'A'"), Ok(65));
+    assert_eq!(value("'a' - 'A'"), Ok(32));
+    assert_eq!(value("'\\n'"), Ok(10));
+    assert_eq!(value("'\\''"), Ok(39));
+    assert_eq!(value("' '"), Ok(32));
+    assert!(value("'ab'").is_err());
+  }
+
+  #[test]
+  fn relocation_functions() {
+    assert_eq!(value("%hi(data)"), Ok(0x10011));
+    assert_eq!(value("%lo(data)"), Ok(-0x5f0));
+    assert_eq!(value("(%hi(data) << 12) + %lo(data)"), Ok(0x1001_0a10));
+    assert_eq!(value("%lo(0x7ff)"), Ok(0x7ff));
+  }
+
+  #[test]
+  fn errors() {
+    assert_eq!(value("foo + 1"), Err(String::from("unknown symbol `foo`")));
+    assert_eq!(
+      value("1 +"),
+      Err(String::from("expected expression, found nothing"))
+    );
+    assert_eq!(
+      value("4 4"),
+      Err(String::from("expected operator, found `4` in `4 4`"))
+    );
+    assert_eq!(
+      value("(1"),
+      Err(String::from("expected `)`, found nothing"))
+    );
+    assert_eq!(
+      value("1 / 0"),
+      Err(String::from("division by zero in `1 / 0`"))
+    );
+    assert_eq!(
+      value("%high(1)"),
+      Err(String::from("unknown function `%high`"))
+    );
+    assert_eq!(value("1 @ 2"), Err(String::from("unexpected `@`")));
+    assert_eq!(value("12ab"), Err(String::from("invalid number `12ab`")));
+  }
+
+  #[test]
+  fn operands_split_at_top_level_commas() {
+    let text = "a0, %lo(x)(sp), 3";
+    let lexemes = lex(text).unwrap();
+    let operands: Vec<String> = split_operands(&lexemes)
+      .iter()
+      .map(|operand| quote(text, operand))
+      .collect();
+    assert_eq!(operands, ["`a0`", "`%lo(x)(sp)`", "`3`"]);
+  }
+}
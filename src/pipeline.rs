@@ -0,0 +1,414 @@
+//! A timing model of the classic five stage pipeline (IF, ID, EX, MEM, WB).
+//! It's told about each instruction after the interpreter has executed it,
+//! and works out which cycle the instruction would have entered each stage,
+//! so it never changes what a program does. Only instructions which complete
+//! are shown; the ones fetched down a mispredicted path just appear as the
+//! gap before the next instruction is fetched.
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+
+const STAGES: [&str; 5] = ["IF", "ID", "EX", "MEM", "WB"];
+const IF: usize = 0;
+const ID: usize = 1;
+const EX: usize = 2;
+const MEM: usize = 3;
+const WB: usize = 4;
+
+/// How many instructions the pipeline diagram shows.
+const DIAGRAM_LENGTH: usize = 32;
+
+/// How conditional branches are predicted when they're fetched. Branches are
+/// resolved in EX, so a misprediction costs the two instructions fetched
+/// after it. Predicted taken branches are assumed to find their target in a
+/// branch target buffer, so they cost nothing when they're right.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Prediction {
+  NotTaken,
+  OneBit, // Taken if the branch was taken last time
+  TwoBit, // Saturating counters, which need two wrong guesses to change
+}
+
+impl Prediction {
+  pub fn parse(name: &str) -> Option<Self> {
+    match name {
+      "not-taken" => Some(Prediction::NotTaken),
+      "1-bit" => Some(Prediction::OneBit),
+      "2-bit" => Some(Prediction::TwoBit),
+      _ => None,
+    }
+  }
+
+  /// Every branch starts off predicted not taken, but two bit counters start
+  /// weakly not taken so that one taken branch flips them.
+  fn initial_state(&self) -> u8 {
+    match self {
+      Prediction::TwoBit => 1,
+      _ => 0,
+    }
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PipelineConfig {
+  pub forwarding: bool,
+  pub prediction: Prediction,
+}
+
+impl Default for PipelineConfig {
+  fn default() -> Self {
+    PipelineConfig {
+      forwarding: true,
+      prediction: Prediction::NotTaken,
+    }
+  }
+}
+
+/// How an instruction changes the flow of control.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Control {
+  Sequential,
+  Branch { taken: bool }, // Conditional
+  Jump,                   // Target known in ID, e.g. jal
+  IndirectJump,           // Target known in EX, e.g. jalr or mret
+}
+
+/// What the pipeline needs to know about an instruction which has executed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Retired {
+  pub pc: u64,
+  pub line_num: u32, // 1 indexed
+  pub text: String,
+  pub reads: Vec<usize>,
+  pub write: Option<usize>,
+  pub load: bool,
+  pub control: Control,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PipelineRow {
+  pub line_num: u32,
+  pub text: String,
+  pub stages: [u64; 5], // The cycle the instruction entered each stage
+  pub note: String,
+}
+
+impl PipelineRow {
+  /// What the instruction is doing in a cycle: the stage it entered, "-" if
+  /// it's stalled in the stage it's in, or "" if it's not in the pipeline.
+  pub fn cell(&self, cycle: u64) -> &'static str {
+    if cycle < self.stages[IF] || cycle > self.stages[WB] {
+      return "";
+    }
+    match self.stages.iter().position(|&start| start == cycle) {
+      Some(stage) => STAGES[stage],
+      None => "-",
+    }
+  }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PipelineStats {
+  pub instructions: u64,
+  pub cycles: u64,
+  pub data_stalls: u64, // Cycles lost waiting for operands
+  pub control_stalls: u64, // Cycles lost to jumps and mispredictions
+  pub branches: u64,
+  pub mispredictions: u64,
+}
+
+impl PipelineStats {
+  pub fn cpi(&self) -> f64 {
+    if self.instructions == 0 {
+      0.0
+    } else {
+      self.cycles as f64 / self.instructions as f64
+    }
+  }
+}
+
+impl fmt::Display for PipelineStats {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(
+      f,
+      "{} instructions in {} cycles (CPI {:.2}). {} cycles stalled on data \
+       hazards, {} on control hazards. {} of {} branches mispredicted.",
+      self.instructions,
+      self.cycles,
+      self.cpi(),
+      self.data_stalls,
+      self.control_stalls,
+      self.mispredictions,
+      self.branches
+    )
+  }
+}
+
+/// The most recent instruction to write a register.
+#[derive(Clone, Copy, Debug)]
+struct Producer {
+  stages: [u64; 5],
+  load: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct Pipeline {
+  pub config: PipelineConfig,
+  pub stats: PipelineStats,
+  rows: VecDeque<PipelineRow>,
+  previous: Option<[u64; 5]>,
+  producers: [Option<Producer>; 32],
+  fetch_ready: u64, // The first cycle the next instruction can be fetched
+  history: HashMap<u64, u8>, // Prediction state for each branch's pc
+}
+
+impl Pipeline {
+  pub fn new(config: PipelineConfig) -> Self {
+    Pipeline {
+      config,
+      stats: PipelineStats::default(),
+      rows: VecDeque::new(),
+      previous: None,
+      producers: [None; 32],
+      fetch_ready: 1,
+      history: HashMap::new(),
+    }
+  }
+
+  /// Empties the pipeline and forgets the statistics.
+  pub fn reset(&mut self) {
+    *self = Pipeline::new(self.config);
+  }
+
+  pub fn rows(&self) -> &VecDeque<PipelineRow> {
+    &self.rows
+  }
+
+  fn predict(&self, pc: u64) -> bool {
+    let state = self
+      .history
+      .get(&pc)
+      .copied()
+      .unwrap_or_else(|| self.config.prediction.initial_state());
+    match self.config.prediction {
+      Prediction::NotTaken => false,
+      Prediction::OneBit => state != 0,
+      Prediction::TwoBit => state >= 2,
+    }
+  }
+
+  fn train(&mut self, pc: u64, taken: bool) {
+    let initial = self.config.prediction.initial_state();
+    let state = self.history.entry(pc).or_insert(initial);
+    *state = match (self.config.prediction, taken) {
+      (Prediction::TwoBit, true) => std::cmp::min(*state + 1, 3),
+      (Prediction::TwoBit, false) => state.saturating_sub(1),
+      (_, taken) => taken as u8,
+    };
+  }
+
+  /// The first cycle the instruction can enter EX with its operands.
+  fn operands_ready(&self, reads: &[usize]) -> u64 {
+    reads
+      .iter()
+      .filter(|&&r| r != 0)
+      .filter_map(|&r| self.producers[r])
+      .map(|producer| {
+        if !self.config.forwarding {
+          // Written in the first half of WB, read in the second half of ID
+          producer.stages[WB] + 1
+        } else if producer.load {
+          // Forwarded from the end of MEM
+          producer.stages[MEM] + 1
+        } else {
+          // Forwarded from the end of EX
+          producer.stages[EX] + 1
+        }
+      })
+      .max()
+      .unwrap_or(0)
+  }
+
+  /// Works out when the instruction moved through each stage. An instruction
+  /// can't enter a stage until the one ahead of it has left it.
+  pub fn retire(&mut self, instruction: Retired) {
+    let previous = self.previous.unwrap_or([0; 5]);
+    let mut stages = [0; 5];
+    let mut notes: Vec<String> = Vec::new();
+    stages[IF] = (previous[IF] + 1).max(previous[ID]).max(self.fetch_ready);
+    stages[ID] = (stages[IF] + 1).max(previous[EX]);
+    let no_hazard = (stages[ID] + 1).max(previous[MEM]);
+    stages[EX] = no_hazard.max(self.operands_ready(&instruction.reads));
+    if stages[EX] > no_hazard {
+      let stalls = stages[EX] - no_hazard;
+      self.stats.data_stalls += stalls;
+      notes.push(format!("{} cycle data hazard stall", stalls));
+    }
+    stages[MEM] = (stages[EX] + 1).max(previous[WB]);
+    stages[WB] = stages[MEM] + 1;
+
+    // Where the next instruction could be fetched from without a stall
+    let next_fetch = stages[IF] + 1;
+    let redirect = match instruction.control {
+      Control::Sequential => None,
+      Control::Branch { taken } => {
+        self.stats.branches += 1;
+        let predicted = self.predict(instruction.pc);
+        self.train(instruction.pc, taken);
+        if predicted != taken {
+          self.stats.mispredictions += 1;
+          notes.push(format!(
+            "mispredicted {}",
+            if taken { "taken" } else { "not taken" }
+          ));
+          Some(stages[EX] + 1)
+        } else {
+          None
+        }
+      }
+      Control::Jump => Some(stages[ID] + 1),
+      Control::IndirectJump => Some(stages[EX] + 1),
+    };
+    if let Some(redirect) = redirect {
+      if redirect > next_fetch {
+        let stalls = redirect - next_fetch;
+        self.stats.control_stalls += stalls;
+        notes.push(format!("{} cycle control hazard stall", stalls));
+      }
+      self.fetch_ready = redirect;
+    }
+
+    if let Some(rd) = instruction.write.filter(|&rd| rd != 0) {
+      self.producers[rd] = Some(Producer {
+        stages,
+        load: instruction.load,
+      });
+    }
+    self.previous = Some(stages);
+    self.stats.instructions += 1;
+    self.stats.cycles = stages[WB];
+    self.rows.push_back(PipelineRow {
+      line_num: instruction.line_num,
+      text: instruction.text,
+      stages,
+      note: notes.join(", "),
+    });
+    if self.rows.len() > DIAGRAM_LENGTH {
+      self.rows.pop_front();
+    }
+  }
+
+  /// A trap flushes the pipeline: the handler is fetched once everything
+  /// ahead of the trapping instruction has completed.
+  pub fn trap(&mut self) {
+    if let Some(previous) = self.previous {
+      self.fetch_ready = self.fetch_ready.max(previous[WB] + 1);
+    }
+  }
+
+  /// The range of cycles the diagram covers.
+  pub fn diagram_cycles(&self) -> std::ops::Range<u64> {
+    match (self.rows.front(), self.rows.back()) {
+      (Some(first), Some(last)) => first.stages[IF]..last.stages[WB] + 1,
+      _ => 0..0,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn alu(rd: usize, rs1: usize, rs2: usize) -> Retired {
+    Retired {
+      pc: 0,
+      line_num: 1,
+      text: String::new(),
+      reads: vec![rs1, rs2],
+      write: Some(rd),
+      load: false,
+      control: Control::Sequential,
+    }
+  }
+
+  fn load(rd: usize, rs1: usize) -> Retired {
+    Retired {
+      reads: vec![rs1],
+      load: true,
+      ..alu(rd, 0, 0)
+    }
+  }
+
+  fn branch(pc: u64, taken: bool) -> Retired {
+    Retired {
+      pc,
+      reads: vec![],
+      write: None,
+      control: Control::Branch { taken },
+      ..alu(0, 0, 0)
+    }
+  }
+
+  fn run(config: PipelineConfig, program: Vec<Retired>) -> Pipeline {
+    let mut pipeline = Pipeline::new(config);
+    for instruction in program {
+      pipeline.retire(instruction);
+    }
+    pipeline
+  }
+
+  #[test]
+  fn independent_instructions_overlap() {
+    let pipeline = run(
+      PipelineConfig::default(),
+      vec![alu(5, 1, 2), alu(6, 1, 2), alu(7, 1, 2)],
+    );
+    assert_eq!(pipeline.stats.cycles, 7);
+    assert_eq!(pipeline.stats.data_stalls, 0);
+    assert_eq!(pipeline.rows()[1].stages, [2, 3, 4, 5, 6]);
+  }
+
+  #[test]
+  fn forwarding() {
+    let program = || vec![alu(5, 1, 2), alu(6, 5, 5)];
+    let forwarded = run(PipelineConfig::default(), program());
+    assert_eq!(forwarded.stats.data_stalls, 0);
+    let config = PipelineConfig {
+      forwarding: false,
+      ..PipelineConfig::default()
+    };
+    let stalled = run(config, program());
+    // EX waits until the cycle after the producer's WB, in cycle 5
+    assert_eq!(stalled.stats.data_stalls, 2);
+    assert_eq!(stalled.rows()[1].cell(4), "-");
+    assert_eq!(stalled.rows()[1].cell(6), "EX");
+  }
+
+  #[test]
+  fn load_use_stall() {
+    let pipeline =
+      run(PipelineConfig::default(), vec![load(5, 1), alu(6, 5, 0)]);
+    assert_eq!(pipeline.stats.data_stalls, 1);
+    assert_eq!(pipeline.stats.cycles, 7);
+  }
+
+  #[test]
+  fn branch_prediction() {
+    // The branch at the end of a loop which runs twice, taken three times
+    // then not taken each time
+    let outcomes = [true, true, true, false, true, true, true, false];
+    let program = || outcomes.iter().map(|&taken| branch(8, taken)).collect();
+    let config = |prediction| PipelineConfig {
+      prediction,
+      ..PipelineConfig::default()
+    };
+    let not_taken = run(config(Prediction::NotTaken), program());
+    assert_eq!(not_taken.stats.mispredictions, 6);
+    assert_eq!(not_taken.stats.control_stalls, 12);
+    // One bit mispredicts both the exit and the first iteration after it
+    let one_bit = run(config(Prediction::OneBit), program());
+    assert_eq!(one_bit.stats.mispredictions, 4);
+    let two_bit = run(config(Prediction::TwoBit), program());
+    assert_eq!(two_bit.stats.mispredictions, 3);
+  }
+}
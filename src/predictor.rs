@@ -0,0 +1,407 @@
+//! Branch predictors, which watch the branches and jumps a program executes
+//! and keep score of how often they'd have guessed right. Conditional
+//! branches go to a direction predictor. `jal` always jumps to a target known
+//! when it's decoded, so it can't be mispredicted. `jalr` targets come from a
+//! register, so they're looked up in a branch target buffer.
+
+use crate::BranchKind;
+use crate::BranchOutcome;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Guesses whether a conditional branch will be taken.
+pub trait DirectionPredictor {
+  fn predict(&self, pc: u64, target: u64) -> bool;
+  fn update(&mut self, pc: u64, taken: bool);
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StaticPolicy {
+  Taken,
+  NotTaken,
+  Btfn, // Backward taken, forward not taken, as backward ones are loops
+}
+
+pub struct StaticPredictor {
+  policy: StaticPolicy,
+}
+
+impl DirectionPredictor for StaticPredictor {
+  fn predict(&self, pc: u64, target: u64) -> bool {
+    match self.policy {
+      StaticPolicy::Taken => true,
+      StaticPolicy::NotTaken => false,
+      StaticPolicy::Btfn => target <= pc,
+    }
+  }
+
+  fn update(&mut self, _pc: u64, _taken: bool) {}
+}
+
+/// A table of two bit saturating counters, which are taken when they're 2 or
+/// 3. They start at 1, weakly not taken.
+struct Counters {
+  counters: Vec<u8>,
+}
+
+impl Counters {
+  fn new(index_bits: u32) -> Self {
+    Counters {
+      counters: vec![1; 1 << index_bits],
+    }
+  }
+
+  fn index(&self, i: u64) -> usize {
+    (i % self.counters.len() as u64) as usize
+  }
+
+  fn taken(&self, i: u64) -> bool {
+    self.counters[self.index(i)] >= 2
+  }
+
+  fn update(&mut self, i: u64, taken: bool) {
+    let i = self.index(i);
+    self.counters[i] = if taken {
+      std::cmp::min(self.counters[i] + 1, 3)
+    } else {
+      self.counters[i].saturating_sub(1)
+    };
+  }
+}
+
+/// Two bit counters indexed by the branch's address.
+pub struct Bimodal {
+  counters: Counters,
+}
+
+impl DirectionPredictor for Bimodal {
+  fn predict(&self, pc: u64, _target: u64) -> bool {
+    self.counters.taken(pc >> 2)
+  }
+
+  fn update(&mut self, pc: u64, taken: bool) {
+    self.counters.update(pc >> 2, taken);
+  }
+}
+
+/// Two bit counters indexed by the branch's address XORed with the outcomes
+/// of the most recent branches, so that branches which depend on the ones
+/// before them can be predicted.
+pub struct Gshare {
+  counters: Counters,
+  history: u64,
+  history_bits: u32,
+}
+
+impl Gshare {
+  fn index(&self, pc: u64) -> u64 {
+    (pc >> 2) ^ self.history
+  }
+}
+
+impl DirectionPredictor for Gshare {
+  fn predict(&self, pc: u64, _target: u64) -> bool {
+    self.counters.taken(self.index(pc))
+  }
+
+  fn update(&mut self, pc: u64, taken: bool) {
+    self.counters.update(self.index(pc), taken);
+    self.history =
+      ((self.history << 1) | taken as u64) & ((1 << self.history_bits) - 1);
+  }
+}
+
+/// A direct mapped cache of jump targets, indexed by the jump's address.
+pub struct Btb {
+  entries: Vec<Option<(u64, u64)>>, // The jump's pc and its last target
+}
+
+impl Btb {
+  pub fn new(entries: usize) -> Self {
+    Btb {
+      entries: vec![None; entries],
+    }
+  }
+
+  fn index(&self, pc: u64) -> usize {
+    ((pc >> 2) % self.entries.len() as u64) as usize
+  }
+
+  pub fn predict(&self, pc: u64) -> Option<u64> {
+    match self.entries[self.index(pc)] {
+      Some((tag, target)) if tag == pc => Some(target),
+      _ => None,
+    }
+  }
+
+  pub fn update(&mut self, pc: u64, target: u64) {
+    let i = self.index(pc);
+    self.entries[i] = Some((pc, target));
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PredictorKind {
+  Static(StaticPolicy),
+  Bimodal,
+  Gshare,
+}
+
+impl PredictorKind {
+  pub fn parse(name: &str) -> Option<Self> {
+    match name {
+      "always-taken" => Some(PredictorKind::Static(StaticPolicy::Taken)),
+      "never-taken" => Some(PredictorKind::Static(StaticPolicy::NotTaken)),
+      "backward-taken" => Some(PredictorKind::Static(StaticPolicy::Btfn)),
+      "bimodal" => Some(PredictorKind::Bimodal),
+      "gshare" => Some(PredictorKind::Gshare),
+      _ => None,
+    }
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PredictorConfig {
+  pub kind: PredictorKind,
+  pub table_bits: u32, // The counter table has 2^table_bits entries
+  pub history_bits: u32, // Only used by gshare
+  pub btb_entries: usize,
+}
+
+impl PredictorConfig {
+  pub fn validate(&self) -> Result<(), String> {
+    if self.table_bits > 20 {
+      return Err(format!(
+        "2^{} counters is too many, the most is 2^20",
+        self.table_bits
+      ));
+    }
+    if self.history_bits >= 64 {
+      return Err(format!(
+        "{} history bits is too many, the most is 63",
+        self.history_bits
+      ));
+    }
+    if self.btb_entries == 0 {
+      return Err(String::from("The BTB needs at least one entry"));
+    }
+    Ok(())
+  }
+}
+
+impl Default for PredictorConfig {
+  fn default() -> Self {
+    PredictorConfig {
+      kind: PredictorKind::Bimodal,
+      table_bits: 6,
+      history_bits: 4,
+      btb_entries: 16,
+    }
+  }
+}
+
+/// How one branch or jump has been predicted.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct BranchStats {
+  pub executed: u64,
+  pub taken: u64,
+  pub correct: u64,
+}
+
+impl BranchStats {
+  fn record(&mut self, taken: bool, correct: bool) {
+    self.executed += 1;
+    self.taken += taken as u64;
+    self.correct += correct as u64;
+  }
+
+  pub fn mispredicted(&self) -> u64 {
+    self.executed - self.correct
+  }
+
+  /// As a percentage.
+  pub fn accuracy(&self) -> f64 {
+    if self.executed == 0 {
+      100.0
+    } else {
+      self.correct as f64 * 100.0 / self.executed as f64
+    }
+  }
+}
+
+impl fmt::Display for BranchStats {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(
+      f,
+      "{} executed, {} taken, {} mispredicted ({:.1}% accuracy)",
+      self.executed,
+      self.taken,
+      self.mispredicted(),
+      self.accuracy()
+    )
+  }
+}
+
+pub struct BranchPredictor {
+  pub config: PredictorConfig,
+  direction: Box<dyn DirectionPredictor>,
+  btb: Btb,
+  pub conditional: BranchStats,
+  pub jumps: BranchStats,
+  pub indirect: BranchStats,
+  pub by_line: BTreeMap<u32, BranchStats>, // Keyed by 1 indexed line
+}
+
+impl BranchPredictor {
+  pub fn new(config: PredictorConfig) -> Self {
+    let direction: Box<dyn DirectionPredictor> = match config.kind {
+      PredictorKind::Static(policy) => Box::new(StaticPredictor { policy }),
+      PredictorKind::Bimodal => Box::new(Bimodal {
+        counters: Counters::new(config.table_bits),
+      }),
+      PredictorKind::Gshare => Box::new(Gshare {
+        counters: Counters::new(config.table_bits),
+        history: 0,
+        history_bits: config.history_bits,
+      }),
+    };
+    BranchPredictor {
+      config,
+      direction,
+      btb: Btb::new(config.btb_entries),
+      conditional: BranchStats::default(),
+      jumps: BranchStats::default(),
+      indirect: BranchStats::default(),
+      by_line: BTreeMap::new(),
+    }
+  }
+
+  /// Forgets everything that's been learned and the statistics.
+  pub fn reset(&mut self) {
+    *self = BranchPredictor::new(self.config);
+  }
+
+  /// Predicts the branch as if it hadn't executed yet, then learns from what
+  /// it actually did.
+  pub fn observe(&mut self, line_num: u32, outcome: &BranchOutcome) {
+    let correct = match outcome.kind {
+      BranchKind::Conditional => {
+        let predicted = self.direction.predict(outcome.pc, outcome.target);
+        self.direction.update(outcome.pc, outcome.taken);
+        predicted == outcome.taken
+      }
+      BranchKind::Jump => true,
+      BranchKind::IndirectJump => {
+        let predicted = self.btb.predict(outcome.pc);
+        self.btb.update(outcome.pc, outcome.target);
+        predicted == Some(outcome.target)
+      }
+    };
+    match outcome.kind {
+      BranchKind::Conditional => &mut self.conditional,
+      BranchKind::Jump => &mut self.jumps,
+      BranchKind::IndirectJump => &mut self.indirect,
+    }
+    .record(outcome.taken, correct);
+    self
+      .by_line
+      .entry(line_num)
+      .or_default()
+      .record(outcome.taken, correct);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn branch(pc: u64, taken: bool) -> BranchOutcome {
+    BranchOutcome {
+      pc,
+      kind: BranchKind::Conditional,
+      taken,
+      target: pc - 16,
+    }
+  }
+
+  fn predictor(kind: PredictorKind) -> BranchPredictor {
+    BranchPredictor::new(PredictorConfig {
+      kind,
+      ..PredictorConfig::default()
+    })
+  }
+
+  /// A loop branch taken three times then not taken, run `loops` times.
+  fn run_loop(predictor: &mut BranchPredictor, loops: usize) {
+    for _ in 0..loops {
+      for taken in [true, true, true, false] {
+        predictor.observe(1, &branch(0x40, taken));
+      }
+    }
+  }
+
+  #[test]
+  fn static_predictors() {
+    let mut always = predictor(PredictorKind::Static(StaticPolicy::Taken));
+    run_loop(&mut always, 2);
+    assert_eq!(always.conditional.mispredicted(), 2);
+    let mut never = predictor(PredictorKind::Static(StaticPolicy::NotTaken));
+    run_loop(&mut never, 2);
+    assert_eq!(never.conditional.mispredicted(), 6);
+  }
+
+  #[test]
+  fn bimodal_learns_loops() {
+    let mut bimodal = predictor(PredictorKind::Bimodal);
+    run_loop(&mut bimodal, 4);
+    // Once it's learned the loop only the exit is mispredicted
+    assert_eq!(bimodal.conditional.mispredicted(), 1 + 4);
+    assert_eq!(bimodal.by_line[&1].executed, 16);
+    assert_eq!(bimodal.by_line[&1].taken, 12);
+  }
+
+  #[test]
+  fn gshare_learns_patterns() {
+    // Alternating outcomes defeat a bimodal predictor, but the history lets
+    // gshare predict them
+    let mut gshare = predictor(PredictorKind::Gshare);
+    let mut bimodal = predictor(PredictorKind::Bimodal);
+    for i in 0..64 {
+      gshare.observe(1, &branch(0x40, i % 2 == 0));
+      bimodal.observe(1, &branch(0x40, i % 2 == 0));
+    }
+    assert!(gshare.conditional.mispredicted() < 8);
+    assert!(bimodal.conditional.mispredicted() >= 32);
+  }
+
+  #[test]
+  fn config_limits() {
+    let config = |table_bits, history_bits, btb_entries| PredictorConfig {
+      kind: PredictorKind::Gshare,
+      table_bits,
+      history_bits,
+      btb_entries,
+    };
+    assert!(PredictorConfig::default().validate().is_ok());
+    assert!(config(0, 63, 1).validate().is_ok());
+    assert!(config(21, 4, 16).validate().is_err());
+    assert!(config(6, 64, 16).validate().is_err());
+    assert!(config(6, 4, 0).validate().is_err());
+  }
+
+  #[test]
+  fn btb_predicts_indirect_targets() {
+    let mut predictor = predictor(PredictorKind::Bimodal);
+    let jump = |target| BranchOutcome {
+      pc: 0x80,
+      kind: BranchKind::IndirectJump,
+      taken: true,
+      target,
+    };
+    predictor.observe(2, &jump(0x100));
+    predictor.observe(2, &jump(0x100));
+    predictor.observe(2, &jump(0x200));
+    assert_eq!(predictor.indirect.executed, 3);
+    assert_eq!(predictor.indirect.correct, 1);
+  }
+}
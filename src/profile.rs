@@ -0,0 +1,126 @@
+//! Counts how often each instruction executes, to show where a program
+//! spends its time.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fmt::Write;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum InstructionClass {
+  Alu,
+  Load,
+  Store,
+  BranchTaken,
+  BranchNotTaken,
+  Jump,
+  System, // CSR accesses, ecall, mret etc.
+}
+
+impl fmt::Display for InstructionClass {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.write_str(match self {
+      InstructionClass::Alu => "ALU",
+      InstructionClass::Load => "Load",
+      InstructionClass::Store => "Store",
+      InstructionClass::BranchTaken => "Branch (taken)",
+      InstructionClass::BranchNotTaken => "Branch (not taken)",
+      InstructionClass::Jump => "Jump",
+      InstructionClass::System => "System",
+    })
+  }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Profile {
+  pub by_instruction: Vec<u64>, // Indexed like the program's instructions
+  pub by_class: BTreeMap<InstructionClass, u64>,
+  pub total: u64,
+}
+
+impl Profile {
+  pub fn new(instructions: usize) -> Self {
+    Profile {
+      by_instruction: vec![0; instructions],
+      ..Profile::default()
+    }
+  }
+
+  pub fn record(&mut self, index: usize, class: InstructionClass) {
+    self.by_instruction[index] += 1;
+    *self.by_class.entry(class).or_insert(0) += 1;
+    self.total += 1;
+  }
+
+  /// Execution counts summed over the instructions on each line, given the
+  /// (1 indexed) line of each instruction.
+  pub fn by_line(&self, line_nums: &[u32]) -> BTreeMap<u32, u64> {
+    let mut lines: BTreeMap<u32, u64> = BTreeMap::new();
+    for (line_num, count) in line_nums.iter().zip(&self.by_instruction) {
+      *lines.entry(*line_num).or_insert(0) += count;
+    }
+    lines
+  }
+
+  /// A plain text report: the instruction mix, then the `hot` most executed
+  /// lines of `code`.
+  pub fn report(&self, code: &str, line_nums: &[u32], hot: usize) -> String {
+    let mut report = String::new();
+    writeln!(report, "{} instructions executed", self.total).ok();
+    for (class, count) in &self.by_class {
+      writeln!(
+        report,
+        "  {:<20}{:>10} {:>6.1}%",
+        class.to_string(),
+        count,
+        *count as f64 * 100.0 / self.total as f64
+      )
+      .ok();
+    }
+    let mut lines: Vec<(u32, u64)> =
+      self.by_line(line_nums).into_iter().collect();
+    lines.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    writeln!(report, "Hot spots:").ok();
+    let source: Vec<&str> = code.lines().collect();
+    for (line_num, count) in lines.iter().take(hot).filter(|(_, c)| *c > 0) {
+      writeln!(
+        report,
+        "  {:>5}{:>10}  {}",
+        line_num,
+        count,
+        source
+          .get(*line_num as usize - 1)
+          .map(|line| line.trim())
+          .unwrap_or("")
+      )
+      .ok();
+    }
+    report
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn counts_and_report() {
+    let mut profile = Profile::new(3);
+    profile.record(0, InstructionClass::Alu);
+    for _ in 0..3 {
+      profile.record(1, InstructionClass::Load);
+      profile.record(2, InstructionClass::BranchTaken);
+    }
+    // The last two instructions share a line
+    let line_nums = [1, 3, 3];
+    assert_eq!(profile.total, 7);
+    assert_eq!(profile.by_class[&InstructionClass::Load], 3);
+    assert_eq!(
+      profile.by_line(&line_nums).into_iter().collect::<Vec<_>>(),
+      vec![(1, 1), (3, 6)]
+    );
+    let report = profile.report("a\nb\nc", &line_nums, 1);
+    assert!(report.contains("7 instructions executed"));
+    assert!(report.contains("    3         6  c"));
+    assert!(!report.contains("  a\n"));
+  }
+}
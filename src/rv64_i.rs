@@ -1,16 +1,38 @@
-pub const MEMORY_SIZE: usize = 4096;
 use crate::instruction::Register;
+use crate::memory::Memory;
 
-pub fn sext<const ARRLEN: usize>(input: [bool; ARRLEN]) -> Register {
-  let mut total: u64 = 0;
-  for (index, value) in input.iter().enumerate() {
-    total |= (*value as u64) << index;
+/// An immediate which was sign extended when its instruction was decoded,
+/// rather than every time the instruction runs.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Immediate(pub Register);
+
+/// The bits of an immediate, or an immediate that's already been extended.
+pub trait SignExtend {
+  fn sext(self) -> Register;
+}
+
+impl<const ARRLEN: usize> SignExtend for [bool; ARRLEN] {
+  fn sext(self) -> Register {
+    let mut total: u64 = 0;
+    for (index, value) in self.iter().enumerate() {
+      total |= (*value as u64) << index;
+    }
+    // Shifting the top bit up to bit 63 and back copies it into the rest
+    let unused = 64 - ARRLEN as u32;
+    let total = ((total << unused) as i64 >> unused) as u64;
+    log!("sext({:?}) = {}", self, total);
+    Register { value: total }
   }
-  for i in ARRLEN..64 {
-    total |= (input[ARRLEN - 1] as u64) << i;
+}
+
+impl SignExtend for Immediate {
+  fn sext(self) -> Register {
+    self.0
   }
-  log!("sext({:?}) = {}", input, total);
-  Register { value: total }
+}
+
+pub fn sext<T: SignExtend>(input: T) -> Register {
+  input.sext()
 }
 
 pub fn sext_n(input: Register, current_len: u32) -> Register {
@@ -45,24 +67,18 @@ pub fn arith_r_shift(val: Register, offset: Register) -> Register {
   arith_r_shift_i(val, offset.value)
 }
 
-pub fn read(
-  mem: &[u8; MEMORY_SIZE],
-  address: Register,
-  length: u32,
-) -> Register {
+pub fn read(mem: &mut dyn Memory, address: Register, length: u32) -> Register {
   assert!(length == 8 || length == 16 || length == 32 || length == 64);
-  assert!(address.value as usize + ((length / 8) as usize) <= MEMORY_SIZE);
 
-  let mut val: u64 = 0;
-
-  for i in 0..(length / 8) {
-    val += (mem[address.value as usize + i as usize] as u64) << (i * 8);
+  let mut bytes = [0u8; 8];
+  mem.read(address.value, &mut bytes[..(length / 8) as usize]);
+  Register {
+    value: u64::from_le_bytes(bytes),
   }
-  Register { value: val }
 }
 
 pub fn read_sext(
-  mem: &[u8; MEMORY_SIZE],
+  mem: &mut dyn Memory,
   address: Register,
   length: u32,
 ) -> Register {
@@ -70,16 +86,15 @@ pub fn read_sext(
 }
 
 pub fn write(
-  mem: &mut [u8; MEMORY_SIZE],
+  mem: &mut dyn Memory,
   address: Register,
   length: u32,
   val: Register,
 ) {
   log!("write(mem, {}, {}, {})", address, length, val);
   assert!(length == 8 || length == 16 || length == 32 || length == 64);
-  assert!(address.value as usize + ((length / 8) as usize) <= MEMORY_SIZE);
-  for i in 0..(length / 8) {
-    mem[address.value as usize + i as usize] =
-      ((val.value >> (i * 8)) & 0xFF) as u8;
-  }
+  mem.write(
+    address.value,
+    &val.value.to_le_bytes()[..(length / 8) as usize],
+  );
 }
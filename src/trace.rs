@@ -0,0 +1,231 @@
+//! Records every instruction the program retires, in a format which can be
+//! diffed against other simulators. `Spike` is the commit log Spike writes
+//! with `--log-commits`, and `JsonLines` is one JSON object per instruction.
+
+use crate::csr::Privilege;
+use crate::AccessKind;
+use crate::MemoryAccess;
+use std::cell::RefCell;
+use std::fmt::Write as _;
+use std::io;
+use std::rc::Rc;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TraceFormat {
+  Spike,
+  JsonLines,
+}
+
+impl TraceFormat {
+  pub fn parse(name: &str) -> Option<Self> {
+    match name {
+      "spike" => Some(TraceFormat::Spike),
+      "jsonl" => Some(TraceFormat::JsonLines),
+      _ => None,
+    }
+  }
+}
+
+/// One retired instruction. Instructions which trap aren't retired, so they
+/// don't appear in the trace.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TraceRecord {
+  pub pc: u64,
+  pub encoding: u32,
+  pub text: String,
+  pub line_num: u32,                // 1 indexed
+  pub privilege: Privilege,         // What it ran in, before any mret etc.
+  pub write: Option<(usize, u64)>,  // Register number and new value
+  pub access: Option<MemoryAccess>, // At the virtual address
+  pub stored: Option<u64>,          // The value written by a store
+}
+
+fn json_string(s: &str) -> String {
+  let mut json = String::from("\"");
+  for c in s.chars() {
+    match c {
+      '"' => json.push_str("\\\""),
+      '\\' => json.push_str("\\\\"),
+      '\n' => json.push_str("\\n"),
+      '\t' => json.push_str("\\t"),
+      c if (c as u32) < 0x20 => {
+        write!(json, "\\u{:04x}", c as u32).ok();
+      }
+      c => json.push(c),
+    }
+  }
+  json.push('"');
+  json
+}
+
+impl TraceRecord {
+  pub fn to_spike(&self) -> String {
+    let mut line = format!(
+      "core   0: {} 0x{:016x} (0x{:08x})",
+      self.privilege as u8, self.pc, self.encoding
+    );
+    if let Some((rd, value)) = self.write {
+      write!(line, " x{:<2} 0x{:016x}", rd, value).ok();
+    }
+    if let Some(access) = self.access {
+      write!(line, " mem 0x{:016x}", access.address).ok();
+      if let Some(value) = self.stored {
+        let digits = access.size as usize * 2;
+        write!(line, " 0x{:0width$x}", value, width = digits).ok();
+      }
+    }
+    line
+  }
+
+  pub fn to_json(&self) -> String {
+    let mut json = format!(
+      "{{\"pc\":\"0x{:016x}\",\"encoding\":\"0x{:08x}\",\"text\":{},\
+       \"line\":{},\"privilege\":{}",
+      self.pc,
+      self.encoding,
+      json_string(&self.text),
+      self.line_num,
+      self.privilege as u8
+    );
+    if let Some((rd, value)) = self.write {
+      write!(json, ",\"rd\":{},\"rd_value\":\"0x{:016x}\"", rd, value).ok();
+    }
+    if let Some(access) = self.access {
+      let kind = match access.kind {
+        AccessKind::Load => "load",
+        AccessKind::Store => "store",
+      };
+      write!(
+        json,
+        ",\"mem\":{{\"kind\":\"{}\",\"address\":\"0x{:016x}\",\"size\":{}",
+        kind, access.address, access.size
+      )
+      .ok();
+      if let Some(value) = self.stored {
+        write!(json, ",\"value\":\"0x{:x}\"", value).ok();
+      }
+      json.push('}');
+    }
+    json.push('}');
+    json
+  }
+}
+
+pub struct Trace {
+  format: TraceFormat,
+  sink: Box<dyn io::Write>,
+}
+
+impl Trace {
+  pub fn new(format: TraceFormat, sink: Box<dyn io::Write>) -> Self {
+    Trace { format, sink }
+  }
+
+  /// Writes one line to the sink. A sink that fails (e.g. a closed pipe)
+  /// doesn't stop the program.
+  pub fn record(&mut self, record: &TraceRecord) {
+    let line = match self.format {
+      TraceFormat::Spike => record.to_spike(),
+      TraceFormat::JsonLines => record.to_json(),
+    };
+    writeln!(self.sink, "{}", line).ok();
+  }
+}
+
+impl Drop for Trace {
+  fn drop(&mut self) {
+    self.sink.flush().ok();
+  }
+}
+
+/// An in memory sink which can still be read after it's been given to a
+/// `Trace`, for the web UI to download.
+#[derive(Clone, Default)]
+pub struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+impl SharedBuffer {
+  pub fn contents(&self) -> String {
+    String::from_utf8_lossy(&self.0.borrow()).into_owned()
+  }
+}
+
+impl io::Write for SharedBuffer {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    self.0.borrow_mut().extend_from_slice(buf);
+    Ok(buf.len())
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn store() -> TraceRecord {
+    TraceRecord {
+      pc: 0x10,
+      encoding: 0x00a1_3023,
+      text: "sd a0, 0(sp)".to_string(),
+      line_num: 5,
+      privilege: Privilege::Machine,
+      write: None,
+      access: Some(MemoryAccess {
+        kind: AccessKind::Store,
+        address: 0x1000,
+        size: 4,
+      }),
+      stored: Some(0x2a),
+    }
+  }
+
+  #[test]
+  fn spike_format() {
+    let load = TraceRecord {
+      write: Some((5, 0xffff_ffff_ffff_fffe)),
+      access: Some(MemoryAccess {
+        kind: AccessKind::Load,
+        address: 0x2000,
+        size: 8,
+      }),
+      stored: None,
+      ..store()
+    };
+    assert_eq!(
+      load.to_spike(),
+      "core   0: 3 0x0000000000000010 (0x00a13023) x5  0xfffffffffffffffe \
+       mem 0x0000000000002000"
+    );
+    assert_eq!(
+      store().to_spike(),
+      "core   0: 3 0x0000000000000010 (0x00a13023) \
+       mem 0x0000000000001000 0x0000002a"
+    );
+  }
+
+  #[test]
+  fn json_lines_format() {
+    let record = TraceRecord {
+      text: "la a0, \"x\"".to_string(),
+      ..store()
+    };
+    assert_eq!(
+      record.to_json(),
+      "{\"pc\":\"0x0000000000000010\",\"encoding\":\"0x00a13023\",\
+       \"text\":\"la a0, \\\"x\\\"\",\"line\":5,\"privilege\":3,\
+       \"mem\":{\"kind\":\"store\",\"address\":\"0x0000000000001000\",\
+       \"size\":4,\"value\":\"0x2a\"}}"
+    );
+  }
+
+  #[test]
+  fn shared_buffer() {
+    let buffer = SharedBuffer::default();
+    let mut trace = Trace::new(TraceFormat::Spike, Box::new(buffer.clone()));
+    trace.record(&store());
+    drop(trace);
+    assert_eq!(buffer.contents().lines().count(), 1);
+  }
+}
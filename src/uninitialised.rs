@@ -0,0 +1,105 @@
+use crate::codegen::REGISTER_INFO;
+use crate::instruction::{AccessKind, Instruction, MemoryAccess};
+use std::collections::HashSet;
+use std::ops::Range;
+
+/// The addresses of the bytes an access touches. One which runs off the end
+/// of the address space faults, so it doesn't touch any.
+fn bytes(access: &MemoryAccess) -> Range<u64> {
+  match access.address.checked_add(access.size as u64) {
+    Some(end) => access.address..end,
+    None => 0..0,
+  }
+}
+
+/// Tracks which registers and bytes of memory have been given a value, so
+/// that reads of values which were never written can be reported. Real
+/// hardware doesn't zero memory or registers for you, so a program which
+/// relies on it here will break there.
+pub struct UninitialisedChecker {
+  registers: [bool; 32],
+  // Addresses of every byte which has been written
+  memory: HashSet<u64>,
+}
+
+impl UninitialisedChecker {
+  /// Registers whose initial value was filled in count as written, even if
+  /// it was zero, as do the bytes of the program's `image` in memory. x0 is
+  /// always defined.
+  pub fn new(given_registers: &[bool; 32], image: Range<u64>) -> Self {
+    let mut registers = *given_registers;
+    registers[0] = true;
+    UninitialisedChecker {
+      registers,
+      memory: image.collect(),
+    }
+  }
+
+  /// Marks the bytes of a new image as written, when the program changes
+  /// while it's running.
+  pub fn load(&mut self, image: Range<u64>) {
+    self.memory.extend(image);
+  }
+
+  /// Called before `instruction` on `line_num` executes, making `access`.
+  /// Each undefined value is only reported once, after which it's treated as
+  /// defined.
+  pub fn check_reads(
+    &mut self,
+    instruction: &Instruction,
+    line_num: u32,
+    access: Option<MemoryAccess>,
+  ) -> Vec<String> {
+    let mut warnings: Vec<String> = Vec::new();
+    for name in ["rs1", "rs2"] {
+      if let Some(reg) = instruction.register_arg(name) {
+        if !self.registers[reg] {
+          warnings.push(format!(
+            "Uninitialised read on line {}: {} was read but has never been \
+             written",
+            line_num, REGISTER_INFO[reg].abi_name
+          ));
+          self.registers[reg] = true;
+        }
+      }
+    }
+
+    if let Some(access) = access {
+      if access.kind == AccessKind::Load {
+        let undefined: Vec<u64> = bytes(&access)
+          .filter(|address| !self.memory.contains(address))
+          .collect();
+        if let Some(first) = undefined.first() {
+          warnings.push(format!(
+            "Uninitialised read on line {}: {} read {} byte(s) from 0x{:x}, \
+             of which {} (starting at 0x{:x}) have never been written",
+            line_num,
+            instruction.source.mnemonic,
+            access.size,
+            access.address,
+            undefined.len(),
+            first
+          ));
+          self.memory.extend(undefined);
+        }
+      }
+    }
+    warnings
+  }
+
+  /// Called after `instruction` has executed, making `access`.
+  pub fn record_writes(
+    &mut self,
+    instruction: &Instruction,
+    access: Option<MemoryAccess>,
+  ) {
+    if let Some(reg) = instruction.register_arg("rd") {
+      self.registers[reg] = true;
+    }
+    if let Some(access) = access {
+      if access.kind == AccessKind::Store {
+        self.memory.extend(bytes(&access));
+      }
+    }
+  }
+}
@@ -0,0 +1,116 @@
+//! Runs the programs in tests/programs natively and checks their results:
+//!  - each `name.s` with a `name.trace` next to it must retire exactly the
+//!    instructions in the trace, which was recorded from the interpreter
+//!    with `--trace spike`, so any change in behaviour shows up
+//!  - each program in rv64ui checks itself, leaving 1 in gp if it passed or
+//!    the number of the test that failed
+//!  - so does each program in benchmarks, which run for too long to trace
+
+#![cfg(not(target_arch = "wasm32"))]
+
+use riscv_interpreter::{Interpreter, SharedBuffer, Trace, TraceFormat};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const MAX_STEPS: u64 = 1_000_000;
+const MAX_BENCHMARK_STEPS: u64 = 100_000_000;
+const GP: usize = 3;
+
+fn programs(directory: &str) -> Vec<PathBuf> {
+  let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join(directory);
+  let mut programs: Vec<PathBuf> = fs::read_dir(&directory)
+    .unwrap_or_else(|error| panic!("{}: {}", directory.display(), error))
+    .map(|entry| entry.unwrap().path())
+    .filter(|path| path.extension().is_some_and(|e| e == "s"))
+    .collect();
+  programs.sort();
+  assert!(
+    !programs.is_empty(),
+    "No programs in {}",
+    directory.display()
+  );
+  programs
+}
+
+/// Runs the program to completion, returning the interpreter and the trace.
+fn run(path: &Path) -> (Interpreter, String) {
+  let code = fs::read_to_string(path).unwrap();
+  let mut interpreter = Interpreter::create_RiscV64_i(Vec::new());
+  let buffer = SharedBuffer::default();
+  interpreter.set_code(code);
+  interpreter.set_trace(Some(Trace::new(
+    TraceFormat::Spike,
+    Box::new(buffer.clone()),
+  )));
+  interpreter.set_running(true);
+  interpreter.run_for(MAX_STEPS);
+  interpreter.set_trace(None);
+  let name = path.display();
+  assert!(
+    interpreter.errors().is_empty(),
+    "{}: {:?}",
+    name,
+    interpreter.errors()
+  );
+  assert!(!interpreter.running(), "{}: still running", name);
+  (interpreter, buffer.contents())
+}
+
+#[test]
+fn matches_recorded_traces() {
+  for path in programs("tests/programs") {
+    let (_, trace) = run(&path);
+    let recorded = fs::read_to_string(path.with_extension("trace"))
+      .unwrap_or_else(|_| panic!("{} has no trace", path.display()));
+    let mut actual = trace.lines();
+    for (i, expected) in recorded.lines().enumerate() {
+      assert_eq!(
+        actual.next(),
+        Some(expected),
+        "{} diverges at instruction {}",
+        path.display(),
+        i + 1
+      );
+    }
+    assert_eq!(
+      actual.next(),
+      None,
+      "{} runs for longer than the trace",
+      path.display()
+    );
+  }
+}
+
+#[test]
+fn rv64ui() {
+  let failures: Vec<String> = programs("tests/programs/rv64ui")
+    .iter()
+    .filter_map(|path| {
+      let (interpreter, _) = run(path);
+      match interpreter.register(GP) {
+        1 => None,
+        test => Some(format!("{} failed test {}", path.display(), test)),
+      }
+    })
+    .collect();
+  assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
+
+#[test]
+fn benchmarks() {
+  for path in programs("tests/programs/benchmarks") {
+    let mut interpreter = Interpreter::create_RiscV64_i(Vec::new());
+    interpreter.set_code(fs::read_to_string(&path).unwrap());
+    interpreter.set_running(true);
+    interpreter.run_for(MAX_BENCHMARK_STEPS);
+    let name = path.display();
+    assert!(
+      interpreter.errors().is_empty(),
+      "{}: {:?}",
+      name,
+      interpreter.errors()
+    );
+    assert!(!interpreter.running(), "{}: still running", name);
+    assert_eq!(interpreter.register(GP), 1, "{} failed", name);
+  }
+}
@@ -0,0 +1,23 @@
+// Addition, subtraction, comparisons and logic, around the signed and
+// unsigned limits
+addi t0, x0, -1
+addi t1, x0, 1
+add t2, t0, t1
+sub t3, x0, t1
+lui t4, 0x80000
+addiw t5, t4, -1
+addw t6, t5, t1
+subw a0, t4, t1
+slt a1, t0, t1
+sltu a2, t0, t1
+slti a3, t4, 0
+sltiu a4, t1, -1
+xor a5, t0, t4
+or a6, t4, t1
+and a7, t0, t5
+xori s1, t1, -1
+ori s2, x0, 0x7ff
+andi s3, t0, -2048
+auipc s4, 0x1
+lui s5, 0xfffff
+add x0, t0, t0
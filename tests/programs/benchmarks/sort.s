@@ -0,0 +1,97 @@
+# Sorts 10,000 pseudo-random numbers with heapsort, then checks that they're
+# in order, leaving 1 in gp if they are and 2 if not.
+.equ COUNT, 10000
+
+  lui s0, 0x10010         # s0: the array
+  addi s1, zero, COUNT / 8
+  slli s1, s1, 3          # s1: the number of elements
+
+  # Fill the array using xorshift
+  addi t0, zero, 0x5a5
+  slli t0, t0, 40
+  addi t0, t0, 1
+  add t1, s0, zero
+  slli t2, s1, 3
+  add t2, s0, t2
+fill:
+  slli t3, t0, 13
+  xor t0, t0, t3
+  srli t3, t0, 7
+  xor t0, t0, t3
+  slli t3, t0, 17
+  xor t0, t0, t3
+  sd t0, 0(t1)
+  addi t1, t1, 8
+  bltu t1, t2, fill
+
+  # Turn the array into a heap, with the biggest element first
+  srli a0, s1, 1
+heapify:
+  addi a0, a0, -1
+  blt a0, zero, heaped
+  add s2, a0, zero
+  add a1, s1, zero
+  jal ra, sift
+  add a0, s2, zero
+  jal zero, heapify
+heaped:
+
+  # Repeatedly move the biggest element to the end, and shrink the heap
+  addi s2, s1, -1
+extract:
+  beq s2, zero, check
+  slli t0, s2, 3
+  add t0, s0, t0
+  ld t1, 0(s0)
+  ld t2, 0(t0)
+  sd t2, 0(s0)
+  sd t1, 0(t0)
+  addi a0, zero, 0
+  add a1, s2, zero
+  jal ra, sift
+  addi s2, s2, -1
+  jal zero, extract
+
+  # Moves the element at index a0 down the heap of a1 elements, until it's
+  # bigger than both of its children
+sift:
+  slli t0, a0, 1
+  addi t0, t0, 1          # t0: the left child
+  bgeu t0, a1, sifted
+  addi t1, t0, 1          # t1: the right child
+  slli t2, t0, 3
+  add t2, s0, t2
+  bgeu t1, a1, compare
+  ld t3, 0(t2)
+  ld t4, 8(t2)
+  bgeu t3, t4, compare
+  add t0, t1, zero        # The right child is bigger
+  addi t2, t2, 8
+compare:
+  ld t3, 0(t2)
+  slli t5, a0, 3
+  add t5, s0, t5
+  ld t4, 0(t5)
+  bgeu t4, t3, sifted
+  sd t3, 0(t5)
+  sd t4, 0(t2)
+  add a0, t0, zero
+  jal zero, sift
+sifted:
+  jalr zero, 0(ra)
+
+check:
+  addi gp, zero, 2
+  addi t0, s0, 8
+  slli t2, s1, 3
+  add t2, s0, t2
+next:
+  bgeu t0, t2, passed
+  ld t3, -8(t0)
+  ld t4, 0(t0)
+  bltu t4, t3, failed
+  addi t0, t0, 8
+  jal zero, next
+passed:
+  addi gp, zero, 1
+failed:
@@ -0,0 +1,27 @@
+// Signed and unsigned comparisons disagree about negative numbers
+addi t0, x0, -1
+addi t1, x0, 1
+addi a0, x0, 0
+blt t0, t1, signed_less
+addi a0, a0, 1
+signed_less:
+bltu t0, t1, wrong
+addi a0, a0, 2
+bgeu t0, t1, unsigned_greater
+addi a0, a0, 4
+unsigned_greater:
+bge t1, t0, signed_greater
+wrong:
+addi a0, a0, 8
+signed_greater:
+beq t0, t0, equal
+addi a0, a0, 16
+equal:
+bne t0, t0, wrong
+// Count down from 5 with an unsigned comparison
+addi t2, x0, 5
+addi t3, x0, 0
+loop:
+addi t3, t3, 3
+addi t2, t2, -1
+bltu x0, t2, loop
@@ -0,0 +1,30 @@
+// A recursive function, with its frame on the stack
+lui sp, 0x7ffff
+addi a0, x0, 5
+jal ra, factorial
+jal x0, end
+
+// a0 = a0!
+factorial:
+addi sp, sp, -16
+sd ra, 8(sp)
+sd a0, 0(sp)
+addi t0, x0, 1
+bge t0, a0, base
+addi a0, a0, -1
+jal ra, factorial
+ld t1, 0(sp)
+addi t2, x0, 0
+multiply:
+add t2, t2, a0
+addi t1, t1, -1
+bne t1, x0, multiply
+addi a0, t2, 0
+jal x0, return
+base:
+addi a0, x0, 1
+return:
+ld ra, 8(sp)
+addi sp, sp, 16
+jalr x0, 0(ra)
+end:
@@ -0,0 +1,25 @@
+// Stores and loads of every size, with and without sign extension
+lui s0, 0x10010
+lui t0, 0x89abd
+addi t0, t0, -529
+slli t0, t0, 32
+lui t1, 0x76543
+addi t1, t1, 0x210
+add t0, t0, t1
+sd t0, 0(s0)
+ld a0, 0(s0)
+lw a1, 4(s0)
+lwu a2, 4(s0)
+lh a3, 6(s0)
+lhu a4, 6(s0)
+lb a5, 7(s0)
+lbu a6, 7(s0)
+lb a7, 0(s0)
+addi t2, x0, -2
+sb t2, 8(s0)
+sh t2, 10(s0)
+sw t2, 12(s0)
+ld s1, 8(s0)
+addi s3, s0, 16
+sw t0, -4(s3)
+lw s2, -4(s3)
@@ -0,0 +1,411 @@
+// rv64ui addw: 32 bit addition
+// In the style of riscv-tests isa/rv64ui/addw.S, with the TEST_*
+// macros expanded. gp holds the number of the test being run, and is
+// 1 at the end if they all passed.
+test_2:
+addi gp, x0, 2
+addi x1, x0, 0
+addi x2, x0, 0
+addw x14, x1, x2
+addi x7, x0, 0
+bne x14, x7, fail_0
+test_3:
+addi gp, x0, 3
+addi x1, x0, 1
+addi x2, x0, 1
+addw x14, x1, x2
+addi x7, x0, 2
+bne x14, x7, fail_0
+test_4:
+addi gp, x0, 4
+addi x1, x0, 3
+addi x2, x0, 7
+addw x14, x1, x2
+addi x7, x0, 10
+bne x14, x7, fail_0
+test_5:
+addi gp, x0, 5
+addi x1, x0, 0
+lui x2, 0xffff8
+addw x14, x1, x2
+lui x7, 0xffff8
+bne x14, x7, fail_0
+test_6:
+addi gp, x0, 6
+lui x1, 0x80000
+addi x2, x0, 0
+addw x14, x1, x2
+lui x7, 0x80000
+bne x14, x7, fail_0
+test_7:
+addi gp, x0, 7
+lui x1, 0x80000
+lui x2, 0xffff8
+addw x14, x1, x2
+lui x7, 0x7fff8
+bne x14, x7, fail_0
+test_8:
+addi gp, x0, 8
+addi x1, x0, 0
+lui x2, 0x8
+addiw x2, x2, -1
+addw x14, x1, x2
+lui x7, 0x8
+addiw x7, x7, -1
+bne x14, x7, fail_0
+test_9:
+addi gp, x0, 9
+lui x1, 0x80000
+addiw x1, x1, -1
+addi x2, x0, 0
+addw x14, x1, x2
+lui x7, 0x80000
+addiw x7, x7, -1
+bne x14, x7, fail_0
+test_10:
+addi gp, x0, 10
+lui x1, 0x80000
+addiw x1, x1, -1
+lui x2, 0x8
+addiw x2, x2, -1
+addw x14, x1, x2
+lui x7, 0x80008
+addiw x7, x7, -2
+bne x14, x7, fail_0
+test_11:
+addi gp, x0, 11
+lui x1, 0x80000
+lui x2, 0x8
+addiw x2, x2, -1
+addw x14, x1, x2
+lui x7, 0x80008
+addiw x7, x7, -1
+bne x14, x7, fail_0
+test_12:
+addi gp, x0, 12
+lui x1, 0x80000
+addiw x1, x1, -1
+lui x2, 0xffff8
+addw x14, x1, x2
+lui x7, 0x7fff8
+addiw x7, x7, -1
+bne x14, x7, fail_0
+test_13:
+addi gp, x0, 13
+addi x1, x0, 0
+addi x2, x0, -1
+addw x14, x1, x2
+addi x7, x0, -1
+bne x14, x7, fail_0
+test_14:
+addi gp, x0, 14
+addi x1, x0, -1
+addi x2, x0, 1
+addw x14, x1, x2
+addi x7, x0, 0
+bne x14, x7, fail_0
+test_15:
+addi gp, x0, 15
+addi x1, x0, -1
+addi x2, x0, -1
+addw x14, x1, x2
+addi x7, x0, -2
+bne x14, x7, fail_0
+test_16:
+addi gp, x0, 16
+addi x1, x0, 1
+lui x2, 0x80000
+addiw x2, x2, -1
+addw x14, x1, x2
+lui x7, 0x80000
+bne x14, x7, fail_0
+test_17:
+addi gp, x0, 17
+addi x1, x0, 1
+slli x1, x1, 63
+addi x1, x1, -1
+addi x2, x0, 1
+addw x14, x1, x2
+addi x7, x0, 0
+bne x14, x7, fail_0
+test_18:
+addi gp, x0, 18
+addi x1, x0, -1
+slli x1, x1, 63
+addi x2, x0, -1
+addw x14, x1, x2
+addi x7, x0, -1
+bne x14, x7, fail_0
+test_19:
+addi gp, x0, 19
+lui x1, 0x92
+addiw x1, x1, -1493
+slli x1, x1, 13
+addi x1, x1, 1929
+addi x2, x0, 1
+slli x2, x2, 32
+addw x14, x1, x2
+lui x7, 0x23456
+addiw x7, x7, 1929
+bne x14, x7, fail_0
+// Source and destination the same
+test_20:
+addi gp, x0, 20
+addi x1, x0, 13
+addi x2, x0, 11
+addw x1, x1, x2
+addi x7, x0, 24
+bne x1, x7, fail_0
+test_21:
+addi gp, x0, 21
+addi x1, x0, 14
+addi x2, x0, 11
+addw x2, x1, x2
+addi x7, x0, 25
+bne x2, x7, fail_0
+test_22:
+addi gp, x0, 22
+addi x1, x0, 13
+addw x1, x1, x1
+addi x7, x0, 26
+bne x1, x7, fail_0
+// Bypassing
+test_23:
+addi gp, x0, 23
+addi x4, x0, 0
+l1:
+addi x1, x0, 13
+addi x2, x0, 11
+addw x14, x1, x2
+addi x6, x14, 0
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l1
+addi x7, x0, 24
+bne x6, x7, fail_0
+// Bypassing
+test_24:
+addi gp, x0, 24
+addi x4, x0, 0
+l2:
+addi x1, x0, 14
+addi x2, x0, 11
+addw x14, x1, x2
+addi x0, x0, 0
+addi x6, x14, 0
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l2
+addi x7, x0, 25
+bne x6, x7, fail_0
+// Bypassing
+test_25:
+addi gp, x0, 25
+addi x4, x0, 0
+l3:
+addi x1, x0, 15
+addi x2, x0, 11
+addw x14, x1, x2
+addi x0, x0, 0
+addi x0, x0, 0
+addi x6, x14, 0
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l3
+addi x7, x0, 26
+bne x6, x7, fail_0
+test_26:
+addi gp, x0, 26
+addi x4, x0, 0
+l4:
+addi x1, x0, 13
+addi x2, x0, 11
+addw x14, x1, x2
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l4
+addi x7, x0, 24
+bne x14, x7, fail_0
+test_27:
+addi gp, x0, 27
+addi x4, x0, 0
+l5:
+addi x1, x0, 14
+addi x2, x0, 11
+addi x0, x0, 0
+addw x14, x1, x2
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l5
+addi x7, x0, 25
+bne x14, x7, fail_0
+jal x0, continue_0
+fail_0:
+jal x0, fail_1
+continue_0:
+test_28:
+addi gp, x0, 28
+addi x4, x0, 0
+l6:
+addi x1, x0, 15
+addi x2, x0, 11
+addi x0, x0, 0
+addi x0, x0, 0
+addw x14, x1, x2
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l6
+addi x7, x0, 26
+bne x14, x7, fail_1
+test_29:
+addi gp, x0, 29
+addi x4, x0, 0
+l7:
+addi x1, x0, 14
+addi x0, x0, 0
+addi x2, x0, 11
+addw x14, x1, x2
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l7
+addi x7, x0, 25
+bne x14, x7, fail_1
+test_30:
+addi gp, x0, 30
+addi x4, x0, 0
+l8:
+addi x1, x0, 15
+addi x0, x0, 0
+addi x2, x0, 11
+addi x0, x0, 0
+addw x14, x1, x2
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l8
+addi x7, x0, 26
+bne x14, x7, fail_1
+test_31:
+addi gp, x0, 31
+addi x4, x0, 0
+l9:
+addi x1, x0, 15
+addi x0, x0, 0
+addi x0, x0, 0
+addi x2, x0, 11
+addw x14, x1, x2
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l9
+addi x7, x0, 26
+bne x14, x7, fail_1
+test_32:
+addi gp, x0, 32
+addi x4, x0, 0
+l10:
+addi x2, x0, 11
+addi x1, x0, 13
+addw x14, x1, x2
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l10
+addi x7, x0, 24
+bne x14, x7, fail_1
+test_33:
+addi gp, x0, 33
+addi x4, x0, 0
+l11:
+addi x2, x0, 11
+addi x1, x0, 14
+addi x0, x0, 0
+addw x14, x1, x2
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l11
+addi x7, x0, 25
+bne x14, x7, fail_1
+test_34:
+addi gp, x0, 34
+addi x4, x0, 0
+l12:
+addi x2, x0, 11
+addi x1, x0, 15
+addi x0, x0, 0
+addi x0, x0, 0
+addw x14, x1, x2
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l12
+addi x7, x0, 26
+bne x14, x7, fail_1
+test_35:
+addi gp, x0, 35
+addi x4, x0, 0
+l13:
+addi x2, x0, 11
+addi x0, x0, 0
+addi x1, x0, 14
+addw x14, x1, x2
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l13
+addi x7, x0, 25
+bne x14, x7, fail_1
+test_36:
+addi gp, x0, 36
+addi x4, x0, 0
+l14:
+addi x2, x0, 11
+addi x0, x0, 0
+addi x1, x0, 15
+addi x0, x0, 0
+addw x14, x1, x2
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l14
+addi x7, x0, 26
+bne x14, x7, fail_1
+test_37:
+addi gp, x0, 37
+addi x4, x0, 0
+l15:
+addi x2, x0, 11
+addi x0, x0, 0
+addi x0, x0, 0
+addi x1, x0, 15
+addw x14, x1, x2
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l15
+addi x7, x0, 26
+bne x14, x7, fail_1
+// Zero sources and destination
+test_38:
+addi gp, x0, 38
+addi x1, x0, 15
+addw x2, x0, x1
+addi x7, x0, 15
+bne x2, x7, fail_1
+test_39:
+addi gp, x0, 39
+addi x1, x0, 32
+addw x2, x1, x0
+addi x7, x0, 32
+bne x2, x7, fail_1
+test_40:
+addi gp, x0, 40
+addw x1, x0, x0
+addi x7, x0, 0
+bne x1, x7, fail_1
+test_41:
+addi gp, x0, 41
+addi x1, x0, 16
+addi x2, x0, 30
+addw x0, x1, x2
+addi x7, x0, 0
+bne x0, x7, fail_1
+bne x0, gp, pass
+fail_1:
+jal x0, end
+pass:
+addi gp, x0, 1
+end:
@@ -0,0 +1,324 @@
+// rv64ui and: bitwise and
+// In the style of riscv-tests isa/rv64ui/and.S, with the TEST_*
+// macros expanded. gp holds the number of the test being run, and is
+// 1 at the end if they all passed.
+test_2:
+addi gp, x0, 2
+lui x1, 0xff010
+addiw x1, x1, -256
+lui x2, 0xf0f0f
+addiw x2, x2, 240
+and x14, x1, x2
+lui x7, 0xf000f
+bne x14, x7, fail_0
+test_3:
+addi gp, x0, 3
+lui x1, 0xff01
+addiw x1, x1, -16
+lui x2, 0xf0f0f
+addiw x2, x2, 240
+and x14, x1, x2
+lui x7, 0xf00
+addiw x7, x7, 240
+bne x14, x7, fail_0
+test_4:
+addi gp, x0, 4
+lui x1, 0xff0
+addiw x1, x1, 255
+lui x2, 0xf0f1
+addiw x2, x2, -241
+and x14, x1, x2
+lui x7, 0xf0
+addiw x7, x7, 15
+bne x14, x7, fail_0
+test_5:
+addi gp, x0, 5
+lui x1, 0xf00ff
+addiw x1, x1, 15
+lui x2, 0xf0f0f
+addiw x2, x2, 240
+and x14, x1, x2
+lui x7, 0xf000f
+bne x14, x7, fail_0
+test_6:
+addi gp, x0, 6
+lui x1, 0x247
+addiw x1, x1, -1875
+slli x1, x1, 14
+addi x1, x1, -947
+slli x1, x1, 12
+addi x1, x1, 1511
+slli x1, x1, 13
+addi x1, x1, -272
+lui x2, 0xffdb9
+addiw x2, x2, 1875
+slli x2, x2, 14
+addi x2, x2, 947
+slli x2, x2, 12
+addi x2, x2, -1511
+slli x2, x2, 13
+addi x2, x2, 271
+and x14, x1, x2
+addi x7, x0, 0
+bne x14, x7, fail_0
+// Source and destination the same
+test_7:
+addi gp, x0, 7
+addi x1, x0, 13
+addi x2, x0, 11
+and x1, x1, x2
+addi x7, x0, 9
+bne x1, x7, fail_0
+test_8:
+addi gp, x0, 8
+addi x1, x0, 14
+addi x2, x0, 11
+and x2, x1, x2
+addi x7, x0, 10
+bne x2, x7, fail_0
+test_9:
+addi gp, x0, 9
+addi x1, x0, 13
+and x1, x1, x1
+addi x7, x0, 13
+bne x1, x7, fail_0
+// Bypassing
+test_10:
+addi gp, x0, 10
+addi x4, x0, 0
+l1:
+addi x1, x0, 13
+addi x2, x0, 11
+and x14, x1, x2
+addi x6, x14, 0
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l1
+addi x7, x0, 9
+bne x6, x7, fail_0
+// Bypassing
+test_11:
+addi gp, x0, 11
+addi x4, x0, 0
+l2:
+addi x1, x0, 14
+addi x2, x0, 11
+and x14, x1, x2
+addi x0, x0, 0
+addi x6, x14, 0
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l2
+addi x7, x0, 10
+bne x6, x7, fail_0
+// Bypassing
+test_12:
+addi gp, x0, 12
+addi x4, x0, 0
+l3:
+addi x1, x0, 15
+addi x2, x0, 11
+and x14, x1, x2
+addi x0, x0, 0
+addi x0, x0, 0
+addi x6, x14, 0
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l3
+addi x7, x0, 11
+bne x6, x7, fail_0
+test_13:
+addi gp, x0, 13
+addi x4, x0, 0
+l4:
+addi x1, x0, 13
+addi x2, x0, 11
+and x14, x1, x2
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l4
+addi x7, x0, 9
+bne x14, x7, fail_0
+test_14:
+addi gp, x0, 14
+addi x4, x0, 0
+l5:
+addi x1, x0, 14
+addi x2, x0, 11
+addi x0, x0, 0
+and x14, x1, x2
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l5
+addi x7, x0, 10
+bne x14, x7, fail_0
+test_15:
+addi gp, x0, 15
+addi x4, x0, 0
+l6:
+addi x1, x0, 15
+addi x2, x0, 11
+addi x0, x0, 0
+addi x0, x0, 0
+and x14, x1, x2
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l6
+addi x7, x0, 11
+bne x14, x7, fail_0
+test_16:
+addi gp, x0, 16
+addi x4, x0, 0
+l7:
+addi x1, x0, 14
+addi x0, x0, 0
+addi x2, x0, 11
+and x14, x1, x2
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l7
+addi x7, x0, 10
+bne x14, x7, fail_0
+test_17:
+addi gp, x0, 17
+addi x4, x0, 0
+l8:
+addi x1, x0, 15
+addi x0, x0, 0
+addi x2, x0, 11
+addi x0, x0, 0
+and x14, x1, x2
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l8
+addi x7, x0, 11
+bne x14, x7, fail_0
+test_18:
+addi gp, x0, 18
+addi x4, x0, 0
+l9:
+addi x1, x0, 15
+addi x0, x0, 0
+addi x0, x0, 0
+addi x2, x0, 11
+and x14, x1, x2
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l9
+addi x7, x0, 11
+bne x14, x7, fail_0
+test_19:
+addi gp, x0, 19
+addi x4, x0, 0
+l10:
+addi x2, x0, 11
+addi x1, x0, 13
+and x14, x1, x2
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l10
+addi x7, x0, 9
+bne x14, x7, fail_0
+test_20:
+addi gp, x0, 20
+addi x4, x0, 0
+l11:
+addi x2, x0, 11
+addi x1, x0, 14
+addi x0, x0, 0
+and x14, x1, x2
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l11
+addi x7, x0, 10
+bne x14, x7, fail_0
+test_21:
+addi gp, x0, 21
+addi x4, x0, 0
+l12:
+addi x2, x0, 11
+addi x1, x0, 15
+addi x0, x0, 0
+addi x0, x0, 0
+and x14, x1, x2
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l12
+addi x7, x0, 11
+bne x14, x7, fail_0
+jal x0, continue_0
+fail_0:
+jal x0, fail_1
+continue_0:
+test_22:
+addi gp, x0, 22
+addi x4, x0, 0
+l13:
+addi x2, x0, 11
+addi x0, x0, 0
+addi x1, x0, 14
+and x14, x1, x2
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l13
+addi x7, x0, 10
+bne x14, x7, fail_1
+test_23:
+addi gp, x0, 23
+addi x4, x0, 0
+l14:
+addi x2, x0, 11
+addi x0, x0, 0
+addi x1, x0, 15
+addi x0, x0, 0
+and x14, x1, x2
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l14
+addi x7, x0, 11
+bne x14, x7, fail_1
+test_24:
+addi gp, x0, 24
+addi x4, x0, 0
+l15:
+addi x2, x0, 11
+addi x0, x0, 0
+addi x0, x0, 0
+addi x1, x0, 15
+and x14, x1, x2
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l15
+addi x7, x0, 11
+bne x14, x7, fail_1
+// Zero sources and destination
+test_25:
+addi gp, x0, 25
+addi x1, x0, 15
+and x2, x0, x1
+addi x7, x0, 0
+bne x2, x7, fail_1
+test_26:
+addi gp, x0, 26
+addi x1, x0, 32
+and x2, x1, x0
+addi x7, x0, 0
+bne x2, x7, fail_1
+test_27:
+addi gp, x0, 27
+and x1, x0, x0
+addi x7, x0, 0
+bne x1, x7, fail_1
+test_28:
+addi gp, x0, 28
+addi x1, x0, 16
+addi x2, x0, 30
+and x0, x1, x2
+addi x7, x0, 0
+bne x0, x7, fail_1
+bne x0, gp, pass
+fail_1:
+jal x0, end
+pass:
+addi gp, x0, 1
+end:
@@ -0,0 +1,179 @@
+// rv64ui andi: bitwise and with an immediate
+// In the style of riscv-tests isa/rv64ui/andi.S, with the TEST_*
+// macros expanded. gp holds the number of the test being run, and is
+// 1 at the end if they all passed.
+test_2:
+addi gp, x0, 2
+lui x1, 0xff010
+addiw x1, x1, -256
+andi x14, x1, -241
+lui x7, 0xff010
+addiw x7, x7, -256
+bne x14, x7, fail_0
+test_3:
+addi gp, x0, 3
+lui x1, 0xff01
+addiw x1, x1, -16
+andi x14, x1, 240
+addi x7, x0, 240
+bne x14, x7, fail_0
+test_4:
+addi gp, x0, 4
+lui x1, 0xff0
+addiw x1, x1, 255
+andi x14, x1, 1807
+addi x7, x0, 15
+bne x14, x7, fail_0
+test_5:
+addi gp, x0, 5
+lui x1, 0xf00ff
+addiw x1, x1, 15
+andi x14, x1, 240
+addi x7, x0, 0
+bne x14, x7, fail_0
+test_6:
+addi gp, x0, 6
+lui x1, 0x247
+addiw x1, x1, -1875
+slli x1, x1, 14
+addi x1, x1, -947
+slli x1, x1, 12
+addi x1, x1, 1511
+slli x1, x1, 13
+addi x1, x1, -272
+andi x14, x1, -1
+lui x7, 0x247
+addiw x7, x7, -1875
+slli x7, x7, 14
+addi x7, x7, -947
+slli x7, x7, 12
+addi x7, x7, 1511
+slli x7, x7, 13
+addi x7, x7, -272
+bne x14, x7, fail_0
+test_7:
+addi gp, x0, 7
+lui x1, 0x247
+addiw x1, x1, -1875
+slli x1, x1, 14
+addi x1, x1, -947
+slli x1, x1, 12
+addi x1, x1, 1511
+slli x1, x1, 13
+addi x1, x1, -272
+andi x14, x1, 0
+addi x7, x0, 0
+bne x14, x7, fail_0
+// Source and destination the same
+test_8:
+addi gp, x0, 8
+lui x1, 0xff01
+addiw x1, x1, -16
+andi x1, x1, 1807
+addi x7, x0, 1792
+bne x1, x7, fail_0
+// Bypassing
+test_9:
+addi gp, x0, 9
+addi x4, x0, 0
+l1:
+lui x1, 0xff01
+addiw x1, x1, -16
+andi x14, x1, 1807
+addi x6, x14, 0
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l1
+addi x7, x0, 1792
+bne x6, x7, fail_0
+// Bypassing
+test_10:
+addi gp, x0, 10
+addi x4, x0, 0
+l2:
+lui x1, 0xff01
+addiw x1, x1, -15
+andi x14, x1, 1807
+addi x0, x0, 0
+addi x6, x14, 0
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l2
+addi x7, x0, 1793
+bne x6, x7, fail_0
+// Bypassing
+test_11:
+addi gp, x0, 11
+addi x4, x0, 0
+l3:
+lui x1, 0xff01
+addiw x1, x1, -14
+andi x14, x1, 1807
+addi x0, x0, 0
+addi x0, x0, 0
+addi x6, x14, 0
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l3
+addi x7, x0, 1794
+bne x6, x7, fail_0
+test_12:
+addi gp, x0, 12
+addi x4, x0, 0
+l4:
+lui x1, 0xff01
+addiw x1, x1, -16
+andi x14, x1, 1807
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l4
+addi x7, x0, 1792
+bne x14, x7, fail_0
+test_13:
+addi gp, x0, 13
+addi x4, x0, 0
+l5:
+lui x1, 0xff01
+addiw x1, x1, -15
+addi x0, x0, 0
+andi x14, x1, 1807
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l5
+addi x7, x0, 1793
+bne x14, x7, fail_0
+test_14:
+addi gp, x0, 14
+addi x4, x0, 0
+l6:
+lui x1, 0xff01
+addiw x1, x1, -14
+addi x0, x0, 0
+addi x0, x0, 0
+andi x14, x1, 1807
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l6
+addi x7, x0, 1794
+bne x14, x7, fail_0
+// Zero source and destination
+test_15:
+addi gp, x0, 15
+andi x1, x0, 240
+addi x7, x0, 0
+bne x1, x7, fail_0
+test_16:
+addi gp, x0, 16
+lui x1, 0x10
+addiw x1, x1, -255
+slli x1, x1, 16
+addi x1, x1, -256
+andi x0, x1, 1807
+addi x7, x0, 0
+bne x0, x7, fail_0
+bne x0, gp, pass
+fail_0:
+jal x0, end
+pass:
+addi gp, x0, 1
+end:
@@ -0,0 +1,30 @@
+// rv64ui auipc: add upper immediate to pc
+// In the style of riscv-tests isa/rv64ui/auipc.S, with the TEST_*
+// macros expanded. gp holds the number of the test being run, and is
+// 1 at the end if they all passed.
+test_2:
+addi gp, x0, 2
+auipc a0, 2
+addi a0, a0, 1820
+jal a1, l1
+l1:
+sub a0, a0, a1
+lui x7, 0x2
+addiw x7, x7, 1808
+bne a0, x7, fail_0
+test_3:
+addi gp, x0, 3
+auipc a0, 0xffffe
+addi a0, a0, -1796
+jal a1, l2
+l2:
+sub a0, a0, a1
+lui x7, 0xffffe
+addiw x7, x7, -1808
+bne a0, x7, fail_0
+bne x0, gp, pass
+fail_0:
+jal x0, end
+pass:
+addi gp, x0, 1
+end:
@@ -0,0 +1,809 @@
+// rv64ui bltu: branch if less than, unsigned
+// In the style of riscv-tests isa/rv64ui/bltu.S, with the TEST_*
+// macros expanded. gp holds the number of the test being run, and is
+// 1 at the end if they all passed.
+test_2:
+addi gp, x0, 2
+addi x1, x0, 0
+addi x2, x0, 0
+bltu x1, x2, l1
+bne x0, gp, l2
+l1:
+bne x0, gp, fail_0
+l2:
+bltu x1, x2, l1
+test_3:
+addi gp, x0, 3
+addi x1, x0, 0
+addi x2, x0, 1
+bltu x1, x2, l5
+bne x0, gp, fail_0
+l4:
+bne x0, gp, l6
+l5:
+bltu x1, x2, l4
+bne x0, gp, fail_0
+l6:
+test_4:
+addi gp, x0, 4
+addi x1, x0, 0
+addi x2, x0, -1
+bltu x1, x2, l8
+bne x0, gp, fail_0
+l7:
+bne x0, gp, l9
+l8:
+bltu x1, x2, l7
+bne x0, gp, fail_0
+l9:
+test_5:
+addi gp, x0, 5
+addi x1, x0, 0
+addi x2, x0, 0
+bltu x1, x2, l10
+bne x0, gp, l11
+l10:
+bne x0, gp, fail_0
+l11:
+bltu x1, x2, l10
+test_6:
+addi gp, x0, 6
+addi x1, x0, 0
+addi x2, x0, 1
+bltu x1, x2, l14
+bne x0, gp, fail_0
+l13:
+bne x0, gp, l15
+l14:
+bltu x1, x2, l13
+bne x0, gp, fail_0
+l15:
+test_7:
+addi gp, x0, 7
+addi x1, x0, 1
+addi x2, x0, 0
+bltu x1, x2, l16
+bne x0, gp, l17
+l16:
+bne x0, gp, fail_0
+l17:
+bltu x1, x2, l16
+test_8:
+addi gp, x0, 8
+addi x1, x0, 1
+addi x2, x0, 1
+bltu x1, x2, l19
+bne x0, gp, l20
+l19:
+bne x0, gp, fail_0
+l20:
+bltu x1, x2, l19
+test_9:
+addi gp, x0, 9
+addi x1, x0, 1
+addi x2, x0, -1
+bltu x1, x2, l23
+bne x0, gp, fail_0
+l22:
+bne x0, gp, l24
+l23:
+bltu x1, x2, l22
+bne x0, gp, fail_0
+l24:
+test_10:
+addi gp, x0, 10
+addi x1, x0, 1
+addi x2, x0, 1
+bltu x1, x2, l25
+bne x0, gp, l26
+l25:
+bne x0, gp, fail_0
+l26:
+bltu x1, x2, l25
+test_11:
+addi gp, x0, 11
+addi x1, x0, 1
+addi x2, x0, 2
+bltu x1, x2, l29
+bne x0, gp, fail_0
+l28:
+bne x0, gp, l30
+l29:
+bltu x1, x2, l28
+bne x0, gp, fail_0
+l30:
+test_12:
+addi gp, x0, 12
+addi x1, x0, 3
+addi x2, x0, 0
+bltu x1, x2, l31
+bne x0, gp, l32
+l31:
+bne x0, gp, fail_0
+l32:
+bltu x1, x2, l31
+test_13:
+addi gp, x0, 13
+addi x1, x0, 3
+addi x2, x0, 1
+bltu x1, x2, l34
+bne x0, gp, l35
+l34:
+bne x0, gp, fail_0
+l35:
+bltu x1, x2, l34
+test_14:
+addi gp, x0, 14
+addi x1, x0, 3
+addi x2, x0, -1
+bltu x1, x2, l38
+bne x0, gp, fail_0
+l37:
+bne x0, gp, l39
+l38:
+bltu x1, x2, l37
+bne x0, gp, fail_0
+l39:
+test_15:
+addi gp, x0, 15
+addi x1, x0, 3
+addi x2, x0, 3
+bltu x1, x2, l40
+bne x0, gp, l41
+l40:
+bne x0, gp, fail_0
+l41:
+bltu x1, x2, l40
+test_16:
+addi gp, x0, 16
+addi x1, x0, 3
+addi x2, x0, 4
+bltu x1, x2, l44
+bne x0, gp, fail_0
+l43:
+bne x0, gp, l45
+l44:
+bltu x1, x2, l43
+bne x0, gp, fail_0
+l45:
+test_17:
+addi gp, x0, 17
+addi x1, x0, 7
+addi x2, x0, 0
+bltu x1, x2, l46
+bne x0, gp, l47
+l46:
+bne x0, gp, fail_0
+l47:
+bltu x1, x2, l46
+test_18:
+addi gp, x0, 18
+addi x1, x0, 7
+addi x2, x0, 1
+bltu x1, x2, l49
+bne x0, gp, l50
+l49:
+bne x0, gp, fail_0
+l50:
+bltu x1, x2, l49
+test_19:
+addi gp, x0, 19
+addi x1, x0, 7
+addi x2, x0, -1
+bltu x1, x2, l53
+bne x0, gp, fail_0
+l52:
+bne x0, gp, l54
+l53:
+bltu x1, x2, l52
+bne x0, gp, fail_0
+l54:
+test_20:
+addi gp, x0, 20
+addi x1, x0, 7
+addi x2, x0, 7
+bltu x1, x2, l55
+bne x0, gp, l56
+l55:
+bne x0, gp, fail_0
+l56:
+bltu x1, x2, l55
+test_21:
+addi gp, x0, 21
+addi x1, x0, 7
+addi x2, x0, 8
+bltu x1, x2, l59
+bne x0, gp, fail_0
+l58:
+bne x0, gp, l60
+l59:
+bltu x1, x2, l58
+bne x0, gp, fail_0
+l60:
+test_22:
+addi gp, x0, 22
+lui x1, 0xffff8
+addi x2, x0, 0
+bltu x1, x2, l61
+bne x0, gp, l62
+l61:
+bne x0, gp, fail_0
+l62:
+bltu x1, x2, l61
+test_23:
+addi gp, x0, 23
+lui x1, 0xffff8
+addi x2, x0, 1
+bltu x1, x2, l64
+bne x0, gp, l65
+l64:
+bne x0, gp, fail_0
+l65:
+bltu x1, x2, l64
+test_24:
+addi gp, x0, 24
+lui x1, 0xffff8
+addi x2, x0, -1
+bltu x1, x2, l68
+bne x0, gp, fail_0
+l67:
+bne x0, gp, l69
+l68:
+bltu x1, x2, l67
+bne x0, gp, fail_0
+l69:
+test_25:
+addi gp, x0, 25
+lui x1, 0xffff8
+lui x2, 0xffff8
+bltu x1, x2, l70
+bne x0, gp, l71
+l70:
+bne x0, gp, fail_0
+l71:
+bltu x1, x2, l70
+test_26:
+addi gp, x0, 26
+lui x1, 0xffff8
+lui x2, 0xffff8
+addiw x2, x2, 1
+bltu x1, x2, l74
+bne x0, gp, fail_0
+l73:
+bne x0, gp, l75
+l74:
+bltu x1, x2, l73
+bne x0, gp, fail_0
+l75:
+test_27:
+addi gp, x0, 27
+lui x1, 0x80000
+addi x2, x0, 0
+bltu x1, x2, l76
+bne x0, gp, l77
+l76:
+bne x0, gp, fail_0
+l77:
+bltu x1, x2, l76
+test_28:
+addi gp, x0, 28
+lui x1, 0x80000
+addi x2, x0, 1
+bltu x1, x2, l79
+bne x0, gp, l80
+l79:
+bne x0, gp, fail_0
+l80:
+bltu x1, x2, l79
+jal x0, continue_0
+fail_0:
+jal x0, fail_1
+continue_0:
+test_29:
+addi gp, x0, 29
+lui x1, 0x80000
+addi x2, x0, -1
+bltu x1, x2, l83
+bne x0, gp, fail_1
+l82:
+bne x0, gp, l84
+l83:
+bltu x1, x2, l82
+bne x0, gp, fail_1
+l84:
+test_30:
+addi gp, x0, 30
+lui x1, 0x80000
+lui x2, 0x80000
+bltu x1, x2, l85
+bne x0, gp, l86
+l85:
+bne x0, gp, fail_1
+l86:
+bltu x1, x2, l85
+test_31:
+addi gp, x0, 31
+lui x1, 0x80000
+lui x2, 0x80000
+addiw x2, x2, 1
+bltu x1, x2, l89
+bne x0, gp, fail_1
+l88:
+bne x0, gp, l90
+l89:
+bltu x1, x2, l88
+bne x0, gp, fail_1
+l90:
+test_32:
+addi gp, x0, 32
+lui x1, 0x8
+addiw x1, x1, -1
+addi x2, x0, 0
+bltu x1, x2, l91
+bne x0, gp, l92
+l91:
+bne x0, gp, fail_1
+l92:
+bltu x1, x2, l91
+test_33:
+addi gp, x0, 33
+lui x1, 0x8
+addiw x1, x1, -1
+addi x2, x0, 1
+bltu x1, x2, l94
+bne x0, gp, l95
+l94:
+bne x0, gp, fail_1
+l95:
+bltu x1, x2, l94
+test_34:
+addi gp, x0, 34
+lui x1, 0x8
+addiw x1, x1, -1
+addi x2, x0, -1
+bltu x1, x2, l98
+bne x0, gp, fail_1
+l97:
+bne x0, gp, l99
+l98:
+bltu x1, x2, l97
+bne x0, gp, fail_1
+l99:
+test_35:
+addi gp, x0, 35
+lui x1, 0x8
+addiw x1, x1, -1
+lui x2, 0x8
+addiw x2, x2, -1
+bltu x1, x2, l100
+bne x0, gp, l101
+l100:
+bne x0, gp, fail_1
+l101:
+bltu x1, x2, l100
+test_36:
+addi gp, x0, 36
+lui x1, 0x8
+addiw x1, x1, -1
+lui x2, 0x8
+bltu x1, x2, l104
+bne x0, gp, fail_1
+l103:
+bne x0, gp, l105
+l104:
+bltu x1, x2, l103
+bne x0, gp, fail_1
+l105:
+test_37:
+addi gp, x0, 37
+lui x1, 0x80000
+addiw x1, x1, -1
+addi x2, x0, 0
+bltu x1, x2, l106
+bne x0, gp, l107
+l106:
+bne x0, gp, fail_1
+l107:
+bltu x1, x2, l106
+test_38:
+addi gp, x0, 38
+lui x1, 0x80000
+addiw x1, x1, -1
+addi x2, x0, 1
+bltu x1, x2, l109
+bne x0, gp, l110
+l109:
+bne x0, gp, fail_1
+l110:
+bltu x1, x2, l109
+test_39:
+addi gp, x0, 39
+lui x1, 0x80000
+addiw x1, x1, -1
+addi x2, x0, -1
+bltu x1, x2, l113
+bne x0, gp, fail_1
+l112:
+bne x0, gp, l114
+l113:
+bltu x1, x2, l112
+bne x0, gp, fail_1
+l114:
+test_40:
+addi gp, x0, 40
+lui x1, 0x80000
+addiw x1, x1, -1
+lui x2, 0x80000
+addiw x2, x2, -1
+bltu x1, x2, l115
+bne x0, gp, l116
+l115:
+bne x0, gp, fail_1
+l116:
+bltu x1, x2, l115
+test_41:
+addi gp, x0, 41
+lui x1, 0x80000
+addiw x1, x1, -1
+addi x2, x0, 1
+slli x2, x2, 31
+bltu x1, x2, l119
+bne x0, gp, fail_1
+l118:
+bne x0, gp, l120
+l119:
+bltu x1, x2, l118
+bne x0, gp, fail_1
+l120:
+test_42:
+addi gp, x0, 42
+addi x1, x0, -1
+addi x2, x0, 0
+bltu x1, x2, l121
+bne x0, gp, l122
+l121:
+bne x0, gp, fail_1
+l122:
+bltu x1, x2, l121
+test_43:
+addi gp, x0, 43
+addi x1, x0, -1
+addi x2, x0, 1
+bltu x1, x2, l124
+bne x0, gp, l125
+l124:
+bne x0, gp, fail_1
+l125:
+bltu x1, x2, l124
+test_44:
+addi gp, x0, 44
+addi x1, x0, -1
+addi x2, x0, -1
+bltu x1, x2, l127
+bne x0, gp, l128
+l127:
+bne x0, gp, fail_1
+l128:
+bltu x1, x2, l127
+test_45:
+addi gp, x0, 45
+addi x1, x0, -1
+addi x2, x0, -1
+bltu x1, x2, l130
+bne x0, gp, l131
+l130:
+bne x0, gp, fail_1
+l131:
+bltu x1, x2, l130
+test_46:
+addi gp, x0, 46
+addi x1, x0, -1
+addi x2, x0, 0
+bltu x1, x2, l133
+bne x0, gp, l134
+l133:
+bne x0, gp, fail_1
+l134:
+bltu x1, x2, l133
+test_47:
+addi gp, x0, 47
+addi x1, x0, -1
+slli x1, x1, 63
+addi x2, x0, 0
+bltu x1, x2, l136
+bne x0, gp, l137
+l136:
+bne x0, gp, fail_1
+l137:
+bltu x1, x2, l136
+test_48:
+addi gp, x0, 48
+addi x1, x0, -1
+slli x1, x1, 63
+addi x2, x0, 1
+bltu x1, x2, l139
+bne x0, gp, l140
+l139:
+bne x0, gp, fail_1
+l140:
+bltu x1, x2, l139
+test_49:
+addi gp, x0, 49
+addi x1, x0, -1
+slli x1, x1, 63
+addi x2, x0, -1
+bltu x1, x2, l143
+bne x0, gp, fail_1
+l142:
+bne x0, gp, l144
+l143:
+bltu x1, x2, l142
+bne x0, gp, fail_1
+l144:
+test_50:
+addi gp, x0, 50
+addi x1, x0, -1
+slli x1, x1, 63
+addi x2, x0, -1
+slli x2, x2, 63
+bltu x1, x2, l145
+bne x0, gp, l146
+l145:
+bne x0, gp, fail_1
+l146:
+bltu x1, x2, l145
+test_51:
+addi gp, x0, 51
+addi x1, x0, -1
+slli x1, x1, 63
+addi x2, x0, -1
+slli x2, x2, 63
+addi x2, x2, 1
+bltu x1, x2, l149
+bne x0, gp, fail_1
+l148:
+bne x0, gp, l150
+l149:
+bltu x1, x2, l148
+bne x0, gp, fail_1
+l150:
+test_52:
+addi gp, x0, 52
+addi x1, x0, 1
+slli x1, x1, 63
+addi x1, x1, -1
+addi x2, x0, 0
+bltu x1, x2, l151
+bne x0, gp, l152
+l151:
+bne x0, gp, fail_1
+l152:
+bltu x1, x2, l151
+test_53:
+addi gp, x0, 53
+addi x1, x0, 1
+slli x1, x1, 63
+addi x1, x1, -1
+addi x2, x0, 1
+bltu x1, x2, l154
+bne x0, gp, l155
+l154:
+bne x0, gp, fail_1
+l155:
+bltu x1, x2, l154
+jal x0, continue_1
+fail_1:
+jal x0, fail_2
+continue_1:
+test_54:
+addi gp, x0, 54
+addi x1, x0, 1
+slli x1, x1, 63
+addi x1, x1, -1
+addi x2, x0, -1
+bltu x1, x2, l158
+bne x0, gp, fail_2
+l157:
+bne x0, gp, l159
+l158:
+bltu x1, x2, l157
+bne x0, gp, fail_2
+l159:
+test_55:
+addi gp, x0, 55
+addi x1, x0, 1
+slli x1, x1, 63
+addi x1, x1, -1
+addi x2, x0, 1
+slli x2, x2, 63
+addi x2, x2, -1
+bltu x1, x2, l160
+bne x0, gp, l161
+l160:
+bne x0, gp, fail_2
+l161:
+bltu x1, x2, l160
+test_56:
+addi gp, x0, 56
+addi x1, x0, 1
+slli x1, x1, 63
+addi x1, x1, -1
+addi x2, x0, 1
+slli x2, x2, 63
+addi x2, x2, -2
+bltu x1, x2, l163
+bne x0, gp, l164
+l163:
+bne x0, gp, fail_2
+l164:
+bltu x1, x2, l163
+// Bypassing
+test_57:
+addi gp, x0, 57
+addi x4, x0, 0
+l166:
+addi x1, x0, -1
+addi x2, x0, 0
+bltu x1, x2, fail_2
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l166
+// Bypassing
+test_58:
+addi gp, x0, 58
+addi x4, x0, 0
+l167:
+addi x1, x0, -1
+addi x2, x0, 0
+addi x0, x0, 0
+bltu x1, x2, fail_2
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l167
+// Bypassing
+test_59:
+addi gp, x0, 59
+addi x4, x0, 0
+l168:
+addi x1, x0, -1
+addi x2, x0, 0
+addi x0, x0, 0
+addi x0, x0, 0
+bltu x1, x2, fail_2
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l168
+// Bypassing
+test_60:
+addi gp, x0, 60
+addi x4, x0, 0
+l169:
+addi x1, x0, -1
+addi x0, x0, 0
+addi x2, x0, 0
+bltu x1, x2, fail_2
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l169
+// Bypassing
+test_61:
+addi gp, x0, 61
+addi x4, x0, 0
+l170:
+addi x1, x0, -1
+addi x0, x0, 0
+addi x2, x0, 0
+addi x0, x0, 0
+bltu x1, x2, fail_2
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l170
+// Bypassing
+test_62:
+addi gp, x0, 62
+addi x4, x0, 0
+l171:
+addi x1, x0, -1
+addi x0, x0, 0
+addi x0, x0, 0
+addi x2, x0, 0
+bltu x1, x2, fail_2
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l171
+// Bypassing
+test_63:
+addi gp, x0, 63
+addi x4, x0, 0
+l172:
+addi x2, x0, 0
+addi x1, x0, -1
+bltu x1, x2, fail_2
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l172
+// Bypassing
+test_64:
+addi gp, x0, 64
+addi x4, x0, 0
+l173:
+addi x2, x0, 0
+addi x1, x0, -1
+addi x0, x0, 0
+bltu x1, x2, fail_2
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l173
+// Bypassing
+test_65:
+addi gp, x0, 65
+addi x4, x0, 0
+l174:
+addi x2, x0, 0
+addi x1, x0, -1
+addi x0, x0, 0
+addi x0, x0, 0
+bltu x1, x2, fail_2
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l174
+// Bypassing
+test_66:
+addi gp, x0, 66
+addi x4, x0, 0
+l175:
+addi x2, x0, 0
+addi x0, x0, 0
+addi x1, x0, -1
+bltu x1, x2, fail_2
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l175
+// Bypassing
+test_67:
+addi gp, x0, 67
+addi x4, x0, 0
+l176:
+addi x2, x0, 0
+addi x0, x0, 0
+addi x1, x0, -1
+addi x0, x0, 0
+bltu x1, x2, fail_2
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l176
+// Bypassing
+test_68:
+addi gp, x0, 68
+addi x4, x0, 0
+l177:
+addi x2, x0, 0
+addi x0, x0, 0
+addi x0, x0, 0
+addi x1, x0, -1
+bltu x1, x2, fail_2
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l177
+// Instructions after a taken branch don't execute
+test_69:
+addi gp, x0, 69
+addi x1, x0, 1
+addi x5, x0, 0
+addi x6, x0, 1
+bltu x5, x6, l178
+addi x1, x1, 1
+addi x1, x1, 1
+addi x1, x1, 1
+addi x1, x1, 1
+l178:
+addi x1, x1, 1
+addi x1, x1, 1
+addi x7, x0, 3
+bne x1, x7, fail_2
+bne x0, gp, pass
+fail_2:
+jal x0, end
+pass:
+addi gp, x0, 1
+end:
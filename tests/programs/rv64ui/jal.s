@@ -0,0 +1,36 @@
+// rv64ui jal: jump and link
+// In the style of riscv-tests isa/rv64ui/jal.S, with the TEST_*
+// macros expanded. gp holds the number of the test being run, and is
+// 1 at the end if they all passed.
+test_2:
+addi gp, x0, 2
+addi ra, x0, 0
+jal x4, l1
+l2:
+addi x0, x0, 0
+addi x0, x0, 0
+jal x0, fail_0
+l1:
+auipc x2, 0
+addi x2, x2, -12
+bne x2, x4, fail_0
+// Instructions after the jump don't execute
+test_3:
+addi gp, x0, 3
+addi ra, x0, 1
+jal x0, l3
+addi ra, ra, 1
+addi ra, ra, 1
+addi ra, ra, 1
+addi ra, ra, 1
+l3:
+addi ra, ra, 1
+addi ra, ra, 1
+addi x7, x0, 3
+bne ra, x7, fail_0
+bne x0, gp, pass
+fail_0:
+jal x0, end
+pass:
+addi gp, x0, 1
+end:
@@ -0,0 +1,86 @@
+// rv64ui jalr: jump and link register
+// In the style of riscv-tests isa/rv64ui/jalr.S, with the TEST_*
+// macros expanded. gp holds the number of the test being run, and is
+// 1 at the end if they all passed.
+test_2:
+addi gp, x0, 2
+addi t0, x0, 0
+auipc t1, 0
+addi t1, t1, 16
+jalr t0, 0(t1)
+jal x0, fail_0
+l1:
+auipc t1, 0
+addi t1, t1, -4
+bne t0, t1, fail_0
+// The link and target register are the same
+test_3:
+addi gp, x0, 3
+auipc t0, 0
+addi t0, t0, 16
+jalr t0, 0(t0)
+jal x0, fail_0
+l2:
+auipc t1, 0
+addi t1, t1, -4
+bne t0, t1, fail_0
+// Bypassing
+test_4:
+addi gp, x0, 4
+addi x4, x0, 0
+l3:
+auipc x6, 0
+addi x6, x6, 16
+jalr x13, 0(x6)
+bne x4, gp, fail_0
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l3
+// Bypassing
+test_5:
+addi gp, x0, 5
+addi x4, x0, 0
+l4:
+auipc x6, 0
+addi x6, x6, 20
+addi x0, x0, 0
+jalr x13, 0(x6)
+bne x4, gp, fail_0
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l4
+// Bypassing
+test_6:
+addi gp, x0, 6
+addi x4, x0, 0
+l5:
+auipc x6, 0
+addi x6, x6, 24
+addi x0, x0, 0
+addi x0, x0, 0
+jalr x13, 0(x6)
+bne x4, gp, fail_0
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l5
+// A negative offset, with instructions after the jump skipped
+test_7:
+addi gp, x0, 7
+addi t0, x0, 1
+auipc t1, 0
+addi t1, t1, 28
+jalr x0, -4(t1)
+addi t0, t0, 1
+addi t0, t0, 1
+addi t0, t0, 1
+addi t0, t0, 1
+addi t0, t0, 1
+addi t0, t0, 1
+addi x7, x0, 4
+bne t0, x7, fail_0
+bne x0, gp, pass
+fail_0:
+jal x0, end
+pass:
+addi gp, x0, 1
+end:
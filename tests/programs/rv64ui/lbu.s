@@ -0,0 +1,187 @@
+// rv64ui lbu: load byte unsigned
+// In the style of riscv-tests isa/rv64ui/lbu.S, with the TEST_*
+// macros expanded. gp holds the number of the test being run, and is
+// 1 at the end if they all passed.
+// The test data
+lui x2, 0x10010
+lui x1, 0xff00
+addiw x1, x1, 255
+sd x1, 0(x2)
+test_2:
+addi gp, x0, 2
+lui x2, 0x10010
+lbu x14, 0(x2)
+addi x7, x0, 255
+bne x14, x7, fail_0
+test_3:
+addi gp, x0, 3
+lui x2, 0x10010
+lbu x14, 1(x2)
+addi x7, x0, 0
+bne x14, x7, fail_0
+test_4:
+addi gp, x0, 4
+lui x2, 0x10010
+lbu x14, 2(x2)
+addi x7, x0, 240
+bne x14, x7, fail_0
+test_5:
+addi gp, x0, 5
+lui x2, 0x10010
+lbu x14, 3(x2)
+addi x7, x0, 15
+bne x14, x7, fail_0
+// Negative offsets
+test_6:
+addi gp, x0, 6
+lui x2, 0x10010
+addi x2, x2, 3
+lbu x14, -3(x2)
+addi x7, x0, 255
+bne x14, x7, fail_0
+// Negative offsets
+test_7:
+addi gp, x0, 7
+lui x2, 0x10010
+addi x2, x2, 3
+lbu x14, -2(x2)
+addi x7, x0, 0
+bne x14, x7, fail_0
+// Negative offsets
+test_8:
+addi gp, x0, 8
+lui x2, 0x10010
+addi x2, x2, 3
+lbu x14, -1(x2)
+addi x7, x0, 240
+bne x14, x7, fail_0
+// Negative offsets
+test_9:
+addi gp, x0, 9
+lui x2, 0x10010
+addi x2, x2, 3
+lbu x14, 0(x2)
+addi x7, x0, 15
+bne x14, x7, fail_0
+// A negative base
+test_10:
+addi gp, x0, 10
+lui x1, 0x10010
+addi x1, x1, -32
+lbu x5, 32(x1)
+addi x7, x0, 255
+bne x5, x7, fail_0
+// An unaligned base
+test_11:
+addi gp, x0, 11
+lui x1, 0x10010
+addi x1, x1, -3
+lbu x5, 4(x1)
+addi x7, x0, 0
+bne x5, x7, fail_0
+// Bypassing
+test_12:
+addi gp, x0, 12
+addi x4, x0, 0
+l1:
+lui x13, 0x10010
+addi x13, x13, 1
+lbu x14, 0(x13)
+addi x6, x14, 0
+addi x29, x0, 0
+bne x6, x29, fail_0
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l1
+// Bypassing
+test_13:
+addi gp, x0, 13
+addi x4, x0, 0
+l2:
+lui x13, 0x10010
+addi x13, x13, 1
+lbu x14, 1(x13)
+addi x0, x0, 0
+addi x6, x14, 0
+addi x29, x0, 240
+bne x6, x29, fail_0
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l2
+// Bypassing
+test_14:
+addi gp, x0, 14
+addi x4, x0, 0
+l3:
+lui x13, 0x10010
+addi x13, x13, 1
+lbu x14, 2(x13)
+addi x0, x0, 0
+addi x0, x0, 0
+addi x6, x14, 0
+addi x29, x0, 15
+bne x6, x29, fail_0
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l3
+test_15:
+addi gp, x0, 15
+addi x4, x0, 0
+l4:
+lui x13, 0x10010
+addi x13, x13, 1
+lbu x14, 0(x13)
+addi x29, x0, 0
+bne x14, x29, fail_0
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l4
+test_16:
+addi gp, x0, 16
+addi x4, x0, 0
+l5:
+lui x13, 0x10010
+addi x13, x13, 1
+addi x0, x0, 0
+lbu x14, 1(x13)
+addi x29, x0, 240
+bne x14, x29, fail_0
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l5
+test_17:
+addi gp, x0, 17
+addi x4, x0, 0
+l6:
+lui x13, 0x10010
+addi x13, x13, 1
+addi x0, x0, 0
+addi x0, x0, 0
+lbu x14, 2(x13)
+addi x29, x0, 15
+bne x14, x29, fail_0
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l6
+// The load's destination is overwritten
+test_18:
+addi gp, x0, 18
+lui x5, 0x10010
+lbu x2, 0(x5)
+addi x2, x0, 2
+addi x7, x0, 2
+bne x2, x7, fail_0
+test_19:
+addi gp, x0, 19
+lui x5, 0x10010
+lbu x2, 0(x5)
+addi x0, x0, 0
+addi x2, x0, 2
+addi x7, x0, 2
+bne x2, x7, fail_0
+bne x0, gp, pass
+fail_0:
+jal x0, end
+pass:
+addi gp, x0, 1
+end:
@@ -0,0 +1,39 @@
+// rv64ui lui: load upper immediate
+// In the style of riscv-tests isa/rv64ui/lui.S, with the TEST_*
+// macros expanded. gp holds the number of the test being run, and is
+// 1 at the end if they all passed.
+test_2:
+addi gp, x0, 2
+lui x1, 0x0
+addi x7, x0, 0
+bne x1, x7, fail_0
+test_3:
+addi gp, x0, 3
+lui x1, 0xfffff
+srai x1, x1, 1
+addi x7, x0, -2048
+bne x1, x7, fail_0
+test_4:
+addi gp, x0, 4
+lui x1, 0x7ffff
+srai x1, x1, 20
+addi x7, x0, 2047
+bne x1, x7, fail_0
+test_5:
+addi gp, x0, 5
+lui x1, 0x80000
+srai x1, x1, 20
+addi x7, x0, -2048
+bne x1, x7, fail_0
+// Writes to x0 are ignored
+test_6:
+addi gp, x0, 6
+lui x0, 0x80000
+addi x7, x0, 0
+bne x0, x7, fail_0
+bne x0, gp, pass
+fail_0:
+jal x0, end
+pass:
+addi gp, x0, 1
+end:
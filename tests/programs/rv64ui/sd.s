@@ -0,0 +1,573 @@
+// rv64ui sd: store double word
+// In the style of riscv-tests isa/rv64ui/sd.S, with the TEST_*
+// macros expanded. gp holds the number of the test being run, and is
+// 1 at the end if they all passed.
+// The test data
+lui x2, 0x10010
+lui x1, 0xffff0
+addiw x1, x1, -257
+slli x1, x1, 16
+addi x1, x1, -257
+slli x1, x1, 16
+addi x1, x1, -257
+slli x1, x1, 12
+addi x1, x1, -17
+sd x1, 0(x2)
+lui x1, 0xffff0
+addiw x1, x1, -257
+slli x1, x1, 16
+addi x1, x1, -257
+slli x1, x1, 16
+addi x1, x1, -257
+slli x1, x1, 12
+addi x1, x1, -17
+sd x1, 8(x2)
+lui x1, 0xffff0
+addiw x1, x1, -257
+slli x1, x1, 16
+addi x1, x1, -257
+slli x1, x1, 16
+addi x1, x1, -257
+slli x1, x1, 12
+addi x1, x1, -17
+sd x1, 16(x2)
+lui x1, 0xffff0
+addiw x1, x1, -257
+slli x1, x1, 16
+addi x1, x1, -257
+slli x1, x1, 16
+addi x1, x1, -257
+slli x1, x1, 12
+addi x1, x1, -17
+sd x1, 24(x2)
+lui x1, 0xffff0
+addiw x1, x1, -257
+slli x1, x1, 16
+addi x1, x1, -257
+slli x1, x1, 16
+addi x1, x1, -257
+slli x1, x1, 12
+addi x1, x1, -17
+sd x1, 32(x2)
+lui x1, 0xffff0
+addiw x1, x1, -257
+slli x1, x1, 16
+addi x1, x1, -257
+slli x1, x1, 16
+addi x1, x1, -257
+slli x1, x1, 12
+addi x1, x1, -17
+sd x1, 40(x2)
+lui x1, 0xffff0
+addiw x1, x1, -257
+slli x1, x1, 16
+addi x1, x1, -257
+slli x1, x1, 16
+addi x1, x1, -257
+slli x1, x1, 12
+addi x1, x1, -17
+sd x1, 48(x2)
+lui x1, 0xffff0
+addiw x1, x1, -257
+slli x1, x1, 16
+addi x1, x1, -257
+slli x1, x1, 16
+addi x1, x1, -257
+slli x1, x1, 12
+addi x1, x1, -17
+sd x1, 56(x2)
+lui x1, 0xffff0
+addiw x1, x1, -257
+slli x1, x1, 16
+addi x1, x1, -257
+slli x1, x1, 16
+addi x1, x1, -257
+slli x1, x1, 12
+addi x1, x1, -17
+sd x1, 64(x2)
+lui x1, 0xffff0
+addiw x1, x1, -257
+slli x1, x1, 16
+addi x1, x1, -257
+slli x1, x1, 16
+addi x1, x1, -257
+slli x1, x1, 12
+addi x1, x1, -17
+sd x1, 72(x2)
+test_2:
+addi gp, x0, 2
+lui x2, 0x10010
+lui x1, 0x550
+addiw x1, x1, 85
+slli x1, x1, 16
+addi x1, x1, 85
+slli x1, x1, 17
+addi x1, x1, 170
+sd x1, 0(x2)
+ld x14, 0(x2)
+lui x7, 0x550
+addiw x7, x7, 85
+slli x7, x7, 16
+addi x7, x7, 85
+slli x7, x7, 17
+addi x7, x7, 170
+bne x14, x7, fail_0
+test_3:
+addi gp, x0, 3
+lui x2, 0x10010
+lui x1, 0xffd50
+addiw x1, x1, 85
+slli x1, x1, 16
+addi x1, x1, 85
+slli x1, x1, 13
+addi x1, x1, 11
+slli x1, x1, 12
+addi x1, x1, -1536
+sd x1, 8(x2)
+ld x14, 8(x2)
+lui x7, 0xffd50
+addiw x7, x7, 85
+slli x7, x7, 16
+addi x7, x7, 85
+slli x7, x7, 13
+addi x7, x7, 11
+slli x7, x7, 12
+addi x7, x7, -1536
+bne x14, x7, fail_0
+test_4:
+addi gp, x0, 4
+lui x2, 0x10010
+lui x1, 0x550
+addiw x1, x1, 85
+slli x1, x1, 13
+addi x1, x1, 11
+slli x1, x1, 12
+addi x1, x1, -1535
+slli x1, x1, 12
+addi x1, x1, -1376
+sd x1, 16(x2)
+ld x14, 16(x2)
+lui x7, 0x550
+addiw x7, x7, 85
+slli x7, x7, 13
+addi x7, x7, 11
+slli x7, x7, 12
+addi x7, x7, -1535
+slli x7, x7, 12
+addi x7, x7, -1376
+bne x14, x7, fail_0
+test_5:
+addi gp, x0, 5
+lui x2, 0x10010
+lui x1, 0xfffd0
+addiw x1, x1, 85
+slli x1, x1, 16
+addi x1, x1, 85
+slli x1, x1, 16
+addi x1, x1, 85
+slli x1, x1, 13
+addi x1, x1, 10
+sd x1, 24(x2)
+ld x14, 24(x2)
+lui x7, 0xfffd0
+addiw x7, x7, 85
+slli x7, x7, 16
+addi x7, x7, 85
+slli x7, x7, 16
+addi x7, x7, 85
+slli x7, x7, 13
+addi x7, x7, 10
+bne x14, x7, fail_0
+// Negative offsets
+test_6:
+addi gp, x0, 6
+lui x2, 0x10010
+addi x2, x2, 64
+lui x1, 0xaa
+addiw x1, x1, 11
+slli x1, x1, 12
+addi x1, x1, -1535
+slli x1, x1, 12
+addi x1, x1, -1375
+slli x1, x1, 12
+addi x1, x1, 670
+sd x1, -32(x2)
+ld x14, -32(x2)
+lui x7, 0xaa
+addiw x7, x7, 11
+slli x7, x7, 12
+addi x7, x7, -1535
+slli x7, x7, 12
+addi x7, x7, -1375
+slli x7, x7, 12
+addi x7, x7, 670
+bne x14, x7, fail_0
+// Negative offsets
+test_7:
+addi gp, x0, 7
+lui x2, 0x10010
+addi x2, x2, 64
+lui x1, 0xffd50
+addiw x1, x1, 85
+slli x1, x1, 15
+addi x1, x1, 43
+slli x1, x1, 12
+addi x1, x1, -2045
+slli x1, x1, 14
+addi x1, x1, -1996
+sd x1, -24(x2)
+ld x14, -24(x2)
+lui x7, 0xffd50
+addiw x7, x7, 85
+slli x7, x7, 15
+addi x7, x7, 43
+slli x7, x7, 12
+addi x7, x7, -2045
+slli x7, x7, 14
+addi x7, x7, -1996
+bne x14, x7, fail_0
+jal x0, continue_0
+fail_0:
+jal x0, fail_1
+continue_0:
+// Negative offsets
+test_8:
+addi gp, x0, 8
+lui x2, 0x10010
+addi x2, x2, 64
+lui x1, 0x550
+addiw x1, x1, 85
+slli x1, x1, 12
+addi x1, x1, 5
+slli x1, x1, 12
+addi x1, x1, 1281
+slli x1, x1, 13
+addi x1, x1, -1900
+sd x1, -16(x2)
+ld x14, -16(x2)
+lui x7, 0x550
+addiw x7, x7, 85
+slli x7, x7, 12
+addi x7, x7, 5
+slli x7, x7, 12
+addi x7, x7, 1281
+slli x7, x7, 13
+addi x7, x7, -1900
+bne x14, x7, fail_1
+// Negative offsets
+test_9:
+addi gp, x0, 9
+lui x2, 0x10010
+addi x2, x2, 64
+lui x1, 0xfffd0
+addiw x1, x1, 85
+slli x1, x1, 16
+addi x1, x1, 85
+slli x1, x1, 17
+addi x1, x1, 171
+slli x1, x1, 12
+addi x1, x1, 574
+sd x1, -8(x2)
+ld x14, -8(x2)
+lui x7, 0xfffd0
+addiw x7, x7, 85
+slli x7, x7, 16
+addi x7, x7, 85
+slli x7, x7, 17
+addi x7, x7, 171
+slli x7, x7, 12
+addi x7, x7, 574
+bne x14, x7, fail_1
+// A negative base
+test_10:
+addi gp, x0, 10
+lui x1, 0x10010
+addi x1, x1, 72
+lui x2, 0x12345
+addiw x2, x2, 1656
+addi x4, x1, -32
+sd x2, 32(x4)
+ld x5, 0(x1)
+lui x7, 0x12345
+addiw x7, x7, 1656
+bne x5, x7, fail_1
+// An unaligned base
+test_11:
+addi gp, x0, 11
+lui x1, 0x10010
+addi x1, x1, 72
+lui x2, 0x58213
+addiw x2, x2, 152
+addi x1, x1, -3
+sd x2, 3(x1)
+addi x4, x1, 3
+ld x5, 0(x4)
+lui x7, 0x58213
+addiw x7, x7, 152
+bne x5, x7, fail_1
+// Bypassing
+test_12:
+addi gp, x0, 12
+addi x4, x0, 0
+l1:
+lui x13, 0xab
+addiw x13, x13, -1091
+slli x13, x13, 12
+addi x13, x13, -803
+lui x12, 0x10010
+sd x13, 0(x12)
+ld x14, 0(x12)
+lui x29, 0xab
+addiw x29, x29, -1091
+slli x29, x29, 12
+addi x29, x29, -803
+bne x14, x29, fail_1
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l1
+// Bypassing
+test_13:
+addi gp, x0, 13
+addi x4, x0, 0
+l2:
+lui x13, 0x37
+addiw x13, x13, -1361
+slli x13, x13, 14
+addi x13, x13, -819
+lui x12, 0x10010
+addi x0, x0, 0
+sd x13, 8(x12)
+ld x14, 8(x12)
+lui x29, 0x37
+addiw x29, x29, -1361
+slli x29, x29, 14
+addi x29, x29, -819
+bne x14, x29, fail_1
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l2
+// Bypassing
+test_14:
+addi gp, x0, 14
+addi x4, x0, 0
+l3:
+lui x13, 0x37
+addiw x13, x13, 1707
+slli x13, x13, 14
+addi x13, x13, -1076
+lui x12, 0x10010
+addi x0, x0, 0
+addi x0, x0, 0
+sd x13, 16(x12)
+ld x14, 16(x12)
+lui x29, 0x37
+addiw x29, x29, 1707
+slli x29, x29, 14
+addi x29, x29, -1076
+bne x14, x29, fail_1
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l3
+// Bypassing
+test_15:
+addi gp, x0, 15
+addi x4, x0, 0
+l4:
+lui x13, 0xce
+addiw x13, x13, -597
+slli x13, x13, 12
+addi x13, x13, -1092
+addi x0, x0, 0
+lui x12, 0x10010
+sd x13, 8(x12)
+ld x14, 8(x12)
+lui x29, 0xce
+addiw x29, x29, -597
+slli x29, x29, 12
+addi x29, x29, -1092
+bne x14, x29, fail_1
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l4
+// Bypassing
+test_16:
+addi gp, x0, 16
+addi x4, x0, 0
+l5:
+lui x13, 0xcd
+addiw x13, x13, -549
+slli x13, x13, 12
+addi x13, x13, -1349
+addi x0, x0, 0
+lui x12, 0x10010
+addi x0, x0, 0
+sd x13, 16(x12)
+ld x14, 16(x12)
+lui x29, 0xcd
+addiw x29, x29, -549
+slli x29, x29, 12
+addi x29, x29, -1349
+bne x14, x29, fail_1
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l5
+// Bypassing
+test_17:
+addi gp, x0, 17
+addi x4, x0, 0
+l6:
+lui x13, 0x5e
+addiw x13, x13, 1647
+slli x13, x13, 13
+addi x13, x13, -1365
+addi x0, x0, 0
+addi x0, x0, 0
+lui x12, 0x10010
+sd x13, 16(x12)
+ld x14, 16(x12)
+lui x29, 0x5e
+addiw x29, x29, 1647
+slli x29, x29, 13
+addi x29, x29, -1365
+bne x14, x29, fail_1
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l6
+// Bypassing
+test_18:
+addi gp, x0, 18
+addi x4, x0, 0
+l7:
+lui x12, 0x10010
+lui x13, 0xab
+addiw x13, x13, -1091
+slli x13, x13, 12
+addi x13, x13, -803
+sd x13, 0(x12)
+ld x14, 0(x12)
+lui x29, 0xab
+addiw x29, x29, -1091
+slli x29, x29, 12
+addi x29, x29, -803
+bne x14, x29, fail_1
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l7
+// Bypassing
+test_19:
+addi gp, x0, 19
+addi x4, x0, 0
+l8:
+lui x12, 0x10010
+lui x13, 0x37
+addiw x13, x13, -1361
+slli x13, x13, 14
+addi x13, x13, -819
+addi x0, x0, 0
+sd x13, 8(x12)
+ld x14, 8(x12)
+lui x29, 0x37
+addiw x29, x29, -1361
+slli x29, x29, 14
+addi x29, x29, -819
+bne x14, x29, fail_1
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l8
+jal x0, continue_1
+fail_1:
+jal x0, fail_2
+continue_1:
+// Bypassing
+test_20:
+addi gp, x0, 20
+addi x4, x0, 0
+l9:
+lui x12, 0x10010
+lui x13, 0x37
+addiw x13, x13, 1707
+slli x13, x13, 14
+addi x13, x13, -1076
+addi x0, x0, 0
+addi x0, x0, 0
+sd x13, 16(x12)
+ld x14, 16(x12)
+lui x29, 0x37
+addiw x29, x29, 1707
+slli x29, x29, 14
+addi x29, x29, -1076
+bne x14, x29, fail_2
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l9
+// Bypassing
+test_21:
+addi gp, x0, 21
+addi x4, x0, 0
+l10:
+lui x12, 0x10010
+addi x0, x0, 0
+lui x13, 0xce
+addiw x13, x13, -597
+slli x13, x13, 12
+addi x13, x13, -1092
+sd x13, 8(x12)
+ld x14, 8(x12)
+lui x29, 0xce
+addiw x29, x29, -597
+slli x29, x29, 12
+addi x29, x29, -1092
+bne x14, x29, fail_2
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l10
+// Bypassing
+test_22:
+addi gp, x0, 22
+addi x4, x0, 0
+l11:
+lui x12, 0x10010
+addi x0, x0, 0
+lui x13, 0xcd
+addiw x13, x13, -549
+slli x13, x13, 12
+addi x13, x13, -1349
+addi x0, x0, 0
+sd x13, 16(x12)
+ld x14, 16(x12)
+lui x29, 0xcd
+addiw x29, x29, -549
+slli x29, x29, 12
+addi x29, x29, -1349
+bne x14, x29, fail_2
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l11
+// Bypassing
+test_23:
+addi gp, x0, 23
+addi x4, x0, 0
+l12:
+lui x12, 0x10010
+addi x0, x0, 0
+addi x0, x0, 0
+lui x13, 0x5e
+addiw x13, x13, 1647
+slli x13, x13, 13
+addi x13, x13, -1365
+sd x13, 16(x12)
+ld x14, 16(x12)
+lui x29, 0x5e
+addiw x29, x29, 1647
+slli x29, x29, 13
+addi x29, x29, -1365
+bne x14, x29, fail_2
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l12
+bne x0, gp, pass
+fail_2:
+jal x0, end
+pass:
+addi gp, x0, 1
+end:
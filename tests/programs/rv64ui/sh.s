@@ -0,0 +1,388 @@
+// rv64ui sh: store half word
+// In the style of riscv-tests isa/rv64ui/sh.S, with the TEST_*
+// macros expanded. gp holds the number of the test being run, and is
+// 1 at the end if they all passed.
+// The test data
+lui x2, 0x10010
+lui x1, 0xffff0
+addiw x1, x1, -257
+slli x1, x1, 16
+addi x1, x1, -257
+slli x1, x1, 16
+addi x1, x1, -257
+slli x1, x1, 12
+addi x1, x1, -17
+sd x1, 0(x2)
+lui x1, 0xffff0
+addiw x1, x1, -257
+slli x1, x1, 16
+addi x1, x1, -257
+slli x1, x1, 16
+addi x1, x1, -257
+slli x1, x1, 12
+addi x1, x1, -17
+sd x1, 8(x2)
+lui x1, 0xf0
+addiw x1, x1, -257
+slli x1, x1, 12
+addi x1, x1, -17
+sd x1, 16(x2)
+test_2:
+addi gp, x0, 2
+lui x2, 0x10010
+addi x1, x0, 170
+sh x1, 0(x2)
+lh x14, 0(x2)
+addi x7, x0, 170
+bne x14, x7, fail_0
+test_3:
+addi gp, x0, 3
+lui x2, 0x10010
+lui x1, 0xffffb
+addiw x1, x1, -1536
+sh x1, 2(x2)
+lh x14, 2(x2)
+lui x7, 0xffffb
+addiw x7, x7, -1536
+bne x14, x7, fail_0
+test_4:
+addi gp, x0, 4
+lui x2, 0x10010
+lui x1, 0xbf
+addiw x1, x1, -271
+slli x1, x1, 12
+addi x1, x1, -1376
+sh x1, 4(x2)
+lh x14, 4(x2)
+lui x7, 0x1
+addiw x7, x7, -1376
+bne x14, x7, fail_0
+test_5:
+addi gp, x0, 5
+lui x2, 0x10010
+lui x1, 0xffffa
+addiw x1, x1, 10
+sh x1, 6(x2)
+lh x14, 6(x2)
+lui x7, 0xffffa
+addiw x7, x7, 10
+bne x14, x7, fail_0
+// Negative offsets
+test_6:
+addi gp, x0, 6
+lui x2, 0x10010
+addi x2, x2, 16
+lui x1, 0x1
+addiw x1, x1, 670
+sh x1, -8(x2)
+lh x14, -8(x2)
+lui x7, 0x1
+addiw x7, x7, 670
+bne x14, x7, fail_0
+// Negative offsets
+test_7:
+addi gp, x0, 7
+lui x2, 0x10010
+addi x2, x2, 16
+lui x1, 0xffffc
+addiw x1, x1, -1996
+sh x1, -6(x2)
+lh x14, -6(x2)
+lui x7, 0xffffc
+addiw x7, x7, -1996
+bne x14, x7, fail_0
+// Negative offsets
+test_8:
+addi gp, x0, 8
+lui x2, 0x10010
+addi x2, x2, 16
+lui x1, 0x5f
+addiw x1, x1, 1913
+slli x1, x1, 13
+addi x1, x1, -1900
+sh x1, -4(x2)
+lh x14, -4(x2)
+lui x7, 0x2
+addiw x7, x7, -1900
+bne x14, x7, fail_0
+// Negative offsets
+test_9:
+addi gp, x0, 9
+lui x2, 0x10010
+addi x2, x2, 16
+lui x1, 0xffffb
+addiw x1, x1, 574
+sh x1, -2(x2)
+lh x14, -2(x2)
+lui x7, 0xffffb
+addiw x7, x7, 574
+bne x14, x7, fail_0
+// A negative base
+test_10:
+addi gp, x0, 10
+lui x1, 0x10010
+addi x1, x1, 18
+lui x2, 0x12345
+addiw x2, x2, 1656
+addi x4, x1, -32
+sh x2, 32(x4)
+lh x5, 0(x1)
+lui x7, 0x5
+addiw x7, x7, 1656
+bne x5, x7, fail_0
+// An unaligned base
+test_11:
+addi gp, x0, 11
+lui x1, 0x10010
+addi x1, x1, 18
+lui x2, 0x58213
+addiw x2, x2, 152
+addi x1, x1, -3
+sh x2, 3(x1)
+addi x4, x1, 3
+lh x5, 0(x4)
+lui x7, 0x3
+addiw x7, x7, 152
+bne x5, x7, fail_0
+// Bypassing
+test_12:
+addi gp, x0, 12
+addi x4, x0, 0
+l1:
+lui x13, 0xab
+addiw x13, x13, -1091
+slli x13, x13, 12
+addi x13, x13, -803
+lui x12, 0x10010
+sh x13, 0(x12)
+lh x14, 0(x12)
+lui x29, 0xffffd
+addiw x29, x29, -803
+bne x14, x29, fail_0
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l1
+// Bypassing
+test_13:
+addi gp, x0, 13
+addi x4, x0, 0
+l2:
+lui x13, 0x37
+addiw x13, x13, -1361
+slli x13, x13, 14
+addi x13, x13, -819
+lui x12, 0x10010
+addi x0, x0, 0
+sh x13, 2(x12)
+lh x14, 2(x12)
+lui x29, 0xffffc
+addiw x29, x29, -819
+bne x14, x29, fail_0
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l2
+// Bypassing
+test_14:
+addi gp, x0, 14
+addi x4, x0, 0
+l3:
+lui x13, 0x37
+addiw x13, x13, 1707
+slli x13, x13, 14
+addi x13, x13, -1076
+lui x12, 0x10010
+addi x0, x0, 0
+addi x0, x0, 0
+sh x13, 4(x12)
+lh x14, 4(x12)
+lui x29, 0xffffc
+addiw x29, x29, -1076
+bne x14, x29, fail_0
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l3
+// Bypassing
+test_15:
+addi gp, x0, 15
+addi x4, x0, 0
+l4:
+lui x13, 0xce
+addiw x13, x13, -597
+slli x13, x13, 12
+addi x13, x13, -1092
+addi x0, x0, 0
+lui x12, 0x10010
+sh x13, 2(x12)
+lh x14, 2(x12)
+lui x29, 0xffffb
+addiw x29, x29, -1092
+bne x14, x29, fail_0
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l4
+// Bypassing
+test_16:
+addi gp, x0, 16
+addi x4, x0, 0
+l5:
+lui x13, 0xcd
+addiw x13, x13, -549
+slli x13, x13, 12
+addi x13, x13, -1349
+addi x0, x0, 0
+lui x12, 0x10010
+addi x0, x0, 0
+sh x13, 4(x12)
+lh x14, 4(x12)
+lui x29, 0xffffb
+addiw x29, x29, -1349
+bne x14, x29, fail_0
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l5
+jal x0, continue_0
+fail_0:
+jal x0, fail_1
+continue_0:
+// Bypassing
+test_17:
+addi gp, x0, 17
+addi x4, x0, 0
+l6:
+lui x13, 0x5e
+addiw x13, x13, 1647
+slli x13, x13, 13
+addi x13, x13, -1365
+addi x0, x0, 0
+addi x0, x0, 0
+lui x12, 0x10010
+sh x13, 4(x12)
+lh x14, 4(x12)
+lui x29, 0xffffe
+addiw x29, x29, -1365
+bne x14, x29, fail_1
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l6
+// Bypassing
+test_18:
+addi gp, x0, 18
+addi x4, x0, 0
+l7:
+lui x12, 0x10010
+lui x13, 0xab
+addiw x13, x13, -1091
+slli x13, x13, 12
+addi x13, x13, -803
+sh x13, 0(x12)
+lh x14, 0(x12)
+lui x29, 0xffffd
+addiw x29, x29, -803
+bne x14, x29, fail_1
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l7
+// Bypassing
+test_19:
+addi gp, x0, 19
+addi x4, x0, 0
+l8:
+lui x12, 0x10010
+lui x13, 0x37
+addiw x13, x13, -1361
+slli x13, x13, 14
+addi x13, x13, -819
+addi x0, x0, 0
+sh x13, 2(x12)
+lh x14, 2(x12)
+lui x29, 0xffffc
+addiw x29, x29, -819
+bne x14, x29, fail_1
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l8
+// Bypassing
+test_20:
+addi gp, x0, 20
+addi x4, x0, 0
+l9:
+lui x12, 0x10010
+lui x13, 0x37
+addiw x13, x13, 1707
+slli x13, x13, 14
+addi x13, x13, -1076
+addi x0, x0, 0
+addi x0, x0, 0
+sh x13, 4(x12)
+lh x14, 4(x12)
+lui x29, 0xffffc
+addiw x29, x29, -1076
+bne x14, x29, fail_1
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l9
+// Bypassing
+test_21:
+addi gp, x0, 21
+addi x4, x0, 0
+l10:
+lui x12, 0x10010
+addi x0, x0, 0
+lui x13, 0xce
+addiw x13, x13, -597
+slli x13, x13, 12
+addi x13, x13, -1092
+sh x13, 2(x12)
+lh x14, 2(x12)
+lui x29, 0xffffb
+addiw x29, x29, -1092
+bne x14, x29, fail_1
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l10
+// Bypassing
+test_22:
+addi gp, x0, 22
+addi x4, x0, 0
+l11:
+lui x12, 0x10010
+addi x0, x0, 0
+lui x13, 0xcd
+addiw x13, x13, -549
+slli x13, x13, 12
+addi x13, x13, -1349
+addi x0, x0, 0
+sh x13, 4(x12)
+lh x14, 4(x12)
+lui x29, 0xffffb
+addiw x29, x29, -1349
+bne x14, x29, fail_1
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l11
+// Bypassing
+test_23:
+addi gp, x0, 23
+addi x4, x0, 0
+l12:
+lui x12, 0x10010
+addi x0, x0, 0
+addi x0, x0, 0
+lui x13, 0x5e
+addiw x13, x13, 1647
+slli x13, x13, 13
+addi x13, x13, -1365
+sh x13, 4(x12)
+lh x14, 4(x12)
+lui x29, 0xffffe
+addiw x29, x29, -1365
+bne x14, x29, fail_1
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l12
+bne x0, gp, pass
+fail_1:
+jal x0, end
+pass:
+addi gp, x0, 1
+end:
@@ -0,0 +1,10 @@
+// rv64ui simple: passes if the test framework works
+// In the style of riscv-tests isa/rv64ui/simple.S, with the TEST_*
+// macros expanded. gp holds the number of the test being run, and is
+// 1 at the end if they all passed.
+jal x0, pass
+fail_0:
+jal x0, end
+pass:
+addi gp, x0, 1
+end:
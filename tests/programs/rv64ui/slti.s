@@ -0,0 +1,246 @@
+// rv64ui slti: signed set less than an immediate
+// In the style of riscv-tests isa/rv64ui/slti.S, with the TEST_*
+// macros expanded. gp holds the number of the test being run, and is
+// 1 at the end if they all passed.
+test_2:
+addi gp, x0, 2
+addi x1, x0, 0
+slti x14, x1, 0
+addi x7, x0, 0
+bne x14, x7, fail_0
+test_3:
+addi gp, x0, 3
+addi x1, x0, 1
+slti x14, x1, 1
+addi x7, x0, 0
+bne x14, x7, fail_0
+test_4:
+addi gp, x0, 4
+addi x1, x0, 3
+slti x14, x1, 7
+addi x7, x0, 1
+bne x14, x7, fail_0
+test_5:
+addi gp, x0, 5
+addi x1, x0, 0
+slti x14, x1, -2048
+addi x7, x0, 0
+bne x14, x7, fail_0
+test_6:
+addi gp, x0, 6
+lui x1, 0x80000
+slti x14, x1, 0
+addi x7, x0, 1
+bne x14, x7, fail_0
+test_7:
+addi gp, x0, 7
+lui x1, 0x80000
+slti x14, x1, -2048
+addi x7, x0, 1
+bne x14, x7, fail_0
+test_8:
+addi gp, x0, 8
+addi x1, x0, 0
+slti x14, x1, 2047
+addi x7, x0, 1
+bne x14, x7, fail_0
+test_9:
+addi gp, x0, 9
+lui x1, 0x80000
+addiw x1, x1, -1
+slti x14, x1, 0
+addi x7, x0, 0
+bne x14, x7, fail_0
+test_10:
+addi gp, x0, 10
+lui x1, 0x80000
+addiw x1, x1, -1
+slti x14, x1, 2047
+addi x7, x0, 0
+bne x14, x7, fail_0
+test_11:
+addi gp, x0, 11
+lui x1, 0x80000
+slti x14, x1, 2047
+addi x7, x0, 1
+bne x14, x7, fail_0
+test_12:
+addi gp, x0, 12
+lui x1, 0x80000
+addiw x1, x1, -1
+slti x14, x1, -2048
+addi x7, x0, 0
+bne x14, x7, fail_0
+test_13:
+addi gp, x0, 13
+addi x1, x0, 0
+slti x14, x1, -1
+addi x7, x0, 0
+bne x14, x7, fail_0
+test_14:
+addi gp, x0, 14
+addi x1, x0, -1
+slti x14, x1, 1
+addi x7, x0, 1
+bne x14, x7, fail_0
+test_15:
+addi gp, x0, 15
+addi x1, x0, -1
+slti x14, x1, -1
+addi x7, x0, 0
+bne x14, x7, fail_0
+test_16:
+addi gp, x0, 16
+lui x1, 0x80000
+addiw x1, x1, -1
+slti x14, x1, 1
+addi x7, x0, 0
+bne x14, x7, fail_0
+test_17:
+addi gp, x0, 17
+addi x1, x0, 1
+slli x1, x1, 63
+addi x1, x1, -1
+slti x14, x1, 1
+addi x7, x0, 0
+bne x14, x7, fail_0
+test_18:
+addi gp, x0, 18
+addi x1, x0, 7
+slti x14, x1, 3
+addi x7, x0, 0
+bne x14, x7, fail_0
+test_19:
+addi gp, x0, 19
+addi x1, x0, 1
+slti x14, x1, 0
+addi x7, x0, 0
+bne x14, x7, fail_0
+test_20:
+addi gp, x0, 20
+addi x1, x0, -2048
+slti x14, x1, 0
+addi x7, x0, 1
+bne x14, x7, fail_0
+test_21:
+addi gp, x0, 21
+addi x1, x0, -1
+slti x14, x1, -2
+addi x7, x0, 0
+bne x14, x7, fail_0
+test_22:
+addi gp, x0, 22
+addi x1, x0, 2047
+slti x14, x1, -1
+addi x7, x0, 0
+bne x14, x7, fail_0
+test_23:
+addi gp, x0, 23
+addi x1, x0, -2048
+slti x14, x1, -2048
+addi x7, x0, 0
+bne x14, x7, fail_0
+// Source and destination the same
+test_24:
+addi gp, x0, 24
+addi x1, x0, 13
+slti x1, x1, 11
+addi x7, x0, 0
+bne x1, x7, fail_0
+// Bypassing
+test_25:
+addi gp, x0, 25
+addi x4, x0, 0
+l1:
+addi x1, x0, 13
+slti x14, x1, 11
+addi x6, x14, 0
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l1
+addi x7, x0, 0
+bne x6, x7, fail_0
+// Bypassing
+test_26:
+addi gp, x0, 26
+addi x4, x0, 0
+l2:
+addi x1, x0, 14
+slti x14, x1, 11
+addi x0, x0, 0
+addi x6, x14, 0
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l2
+addi x7, x0, 0
+bne x6, x7, fail_0
+// Bypassing
+test_27:
+addi gp, x0, 27
+addi x4, x0, 0
+l3:
+addi x1, x0, 15
+slti x14, x1, 11
+addi x0, x0, 0
+addi x0, x0, 0
+addi x6, x14, 0
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l3
+addi x7, x0, 0
+bne x6, x7, fail_0
+test_28:
+addi gp, x0, 28
+addi x4, x0, 0
+l4:
+addi x1, x0, 13
+slti x14, x1, 11
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l4
+addi x7, x0, 0
+bne x14, x7, fail_0
+test_29:
+addi gp, x0, 29
+addi x4, x0, 0
+l5:
+addi x1, x0, 14
+addi x0, x0, 0
+slti x14, x1, 11
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l5
+addi x7, x0, 0
+bne x14, x7, fail_0
+test_30:
+addi gp, x0, 30
+addi x4, x0, 0
+l6:
+addi x1, x0, 15
+addi x0, x0, 0
+addi x0, x0, 0
+slti x14, x1, 11
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l6
+addi x7, x0, 0
+bne x14, x7, fail_0
+// Zero source and destination
+test_31:
+addi gp, x0, 31
+slti x1, x0, -1
+addi x7, x0, 0
+bne x1, x7, fail_0
+test_32:
+addi gp, x0, 32
+lui x1, 0xff0
+addiw x1, x1, 255
+slti x0, x1, -1
+addi x7, x0, 0
+bne x0, x7, fail_0
+bne x0, gp, pass
+fail_0:
+jal x0, end
+pass:
+addi gp, x0, 1
+end:
@@ -0,0 +1,391 @@
+// rv64ui sraiw: 32 bit arithmetic right shift by an immediate
+// In the style of riscv-tests isa/rv64ui/sraiw.S, with the TEST_*
+// macros expanded. gp holds the number of the test being run, and is
+// 1 at the end if they all passed.
+test_2:
+addi gp, x0, 2
+addi x1, x0, 1
+sraiw x14, x1, 0
+addi x7, x0, 1
+bne x14, x7, fail_0
+test_3:
+addi gp, x0, 3
+addi x1, x0, 1
+sraiw x14, x1, 1
+addi x7, x0, 0
+bne x14, x7, fail_0
+test_4:
+addi gp, x0, 4
+addi x1, x0, 1
+sraiw x14, x1, 7
+addi x7, x0, 0
+bne x14, x7, fail_0
+test_5:
+addi gp, x0, 5
+addi x1, x0, 1
+sraiw x14, x1, 14
+addi x7, x0, 0
+bne x14, x7, fail_0
+test_6:
+addi gp, x0, 6
+addi x1, x0, 1
+sraiw x14, x1, 31
+addi x7, x0, 0
+bne x14, x7, fail_0
+test_7:
+addi gp, x0, 7
+addi x1, x0, -1
+sraiw x14, x1, 0
+addi x7, x0, -1
+bne x14, x7, fail_0
+test_8:
+addi gp, x0, 8
+addi x1, x0, -1
+sraiw x14, x1, 1
+addi x7, x0, -1
+bne x14, x7, fail_0
+test_9:
+addi gp, x0, 9
+addi x1, x0, -1
+sraiw x14, x1, 7
+addi x7, x0, -1
+bne x14, x7, fail_0
+test_10:
+addi gp, x0, 10
+addi x1, x0, -1
+sraiw x14, x1, 14
+addi x7, x0, -1
+bne x14, x7, fail_0
+test_11:
+addi gp, x0, 11
+addi x1, x0, -1
+sraiw x14, x1, 31
+addi x7, x0, -1
+bne x14, x7, fail_0
+test_12:
+addi gp, x0, 12
+lui x1, 0x21212
+addiw x1, x1, 289
+sraiw x14, x1, 0
+lui x7, 0x21212
+addiw x7, x7, 289
+bne x14, x7, fail_0
+test_13:
+addi gp, x0, 13
+lui x1, 0x21212
+addiw x1, x1, 289
+sraiw x14, x1, 1
+lui x7, 0x10909
+addiw x7, x7, 144
+bne x14, x7, fail_0
+test_14:
+addi gp, x0, 14
+lui x1, 0x21212
+addiw x1, x1, 289
+sraiw x14, x1, 7
+lui x7, 0x424
+addiw x7, x7, 578
+bne x14, x7, fail_0
+test_15:
+addi gp, x0, 15
+lui x1, 0x21212
+addiw x1, x1, 289
+sraiw x14, x1, 14
+lui x7, 0x8
+addiw x7, x7, 1156
+bne x14, x7, fail_0
+test_16:
+addi gp, x0, 16
+lui x1, 0x21212
+addiw x1, x1, 289
+sraiw x14, x1, 31
+addi x7, x0, 0
+bne x14, x7, fail_0
+test_17:
+addi gp, x0, 17
+lui x1, 0x80000
+sraiw x14, x1, 0
+lui x7, 0x80000
+bne x14, x7, fail_0
+test_18:
+addi gp, x0, 18
+lui x1, 0x80000
+sraiw x14, x1, 1
+lui x7, 0xc0000
+bne x14, x7, fail_0
+test_19:
+addi gp, x0, 19
+lui x1, 0x80000
+sraiw x14, x1, 7
+lui x7, 0xff000
+bne x14, x7, fail_0
+test_20:
+addi gp, x0, 20
+lui x1, 0x80000
+sraiw x14, x1, 14
+lui x7, 0xfffe0
+bne x14, x7, fail_0
+test_21:
+addi gp, x0, 21
+lui x1, 0x80000
+sraiw x14, x1, 31
+addi x7, x0, -1
+bne x14, x7, fail_0
+test_22:
+addi gp, x0, 22
+lui x1, 0x80000
+addiw x1, x1, -1
+sraiw x14, x1, 0
+lui x7, 0x80000
+addiw x7, x7, -1
+bne x14, x7, fail_0
+test_23:
+addi gp, x0, 23
+lui x1, 0x80000
+addiw x1, x1, -1
+sraiw x14, x1, 1
+lui x7, 0x40000
+addiw x7, x7, -1
+bne x14, x7, fail_0
+test_24:
+addi gp, x0, 24
+lui x1, 0x80000
+addiw x1, x1, -1
+sraiw x14, x1, 7
+lui x7, 0x1000
+addiw x7, x7, -1
+bne x14, x7, fail_0
+test_25:
+addi gp, x0, 25
+lui x1, 0x80000
+addiw x1, x1, -1
+sraiw x14, x1, 14
+lui x7, 0x20
+addiw x7, x7, -1
+bne x14, x7, fail_0
+test_26:
+addi gp, x0, 26
+lui x1, 0x80000
+addiw x1, x1, -1
+sraiw x14, x1, 31
+addi x7, x0, 0
+bne x14, x7, fail_0
+test_27:
+addi gp, x0, 27
+lui x1, 0x81818
+addiw x1, x1, 385
+sraiw x14, x1, 0
+lui x7, 0x81818
+addiw x7, x7, 385
+bne x14, x7, fail_0
+test_28:
+addi gp, x0, 28
+lui x1, 0x81818
+addiw x1, x1, 385
+sraiw x14, x1, 1
+lui x7, 0xc0c0c
+addiw x7, x7, 192
+bne x14, x7, fail_0
+test_29:
+addi gp, x0, 29
+lui x1, 0x81818
+addiw x1, x1, 385
+sraiw x14, x1, 7
+lui x7, 0xff030
+addiw x7, x7, 771
+bne x14, x7, fail_0
+test_30:
+addi gp, x0, 30
+lui x1, 0x81818
+addiw x1, x1, 385
+sraiw x14, x1, 14
+lui x7, 0xfffe0
+addiw x7, x7, 1542
+bne x14, x7, fail_0
+test_31:
+addi gp, x0, 31
+lui x1, 0x81818
+addiw x1, x1, 385
+sraiw x14, x1, 31
+addi x7, x0, -1
+bne x14, x7, fail_0
+test_32:
+addi gp, x0, 32
+lui x1, 0x247
+addiw x1, x1, -1875
+slli x1, x1, 14
+addi x1, x1, -947
+slli x1, x1, 12
+addi x1, x1, 1511
+slli x1, x1, 13
+addi x1, x1, -272
+sraiw x14, x1, 0
+lui x7, 0x9abce
+addiw x7, x7, -272
+bne x14, x7, fail_0
+test_33:
+addi gp, x0, 33
+lui x1, 0x247
+addiw x1, x1, -1875
+slli x1, x1, 14
+addi x1, x1, -947
+slli x1, x1, 12
+addi x1, x1, 1511
+slli x1, x1, 13
+addi x1, x1, -272
+sraiw x14, x1, 1
+lui x7, 0xcd5e7
+addiw x7, x7, -136
+bne x14, x7, fail_0
+jal x0, continue_0
+fail_0:
+jal x0, fail_1
+continue_0:
+test_34:
+addi gp, x0, 34
+lui x1, 0x247
+addiw x1, x1, -1875
+slli x1, x1, 14
+addi x1, x1, -947
+slli x1, x1, 12
+addi x1, x1, 1511
+slli x1, x1, 13
+addi x1, x1, -272
+sraiw x14, x1, 7
+lui x7, 0xff358
+addiw x7, x7, -1603
+bne x14, x7, fail_1
+test_35:
+addi gp, x0, 35
+lui x1, 0x247
+addiw x1, x1, -1875
+slli x1, x1, 14
+addi x1, x1, -947
+slli x1, x1, 12
+addi x1, x1, 1511
+slli x1, x1, 13
+addi x1, x1, -272
+sraiw x14, x1, 14
+lui x7, 0xfffe7
+addiw x7, x7, -1293
+bne x14, x7, fail_1
+test_36:
+addi gp, x0, 36
+lui x1, 0x247
+addiw x1, x1, -1875
+slli x1, x1, 14
+addi x1, x1, -947
+slli x1, x1, 12
+addi x1, x1, 1511
+slli x1, x1, 13
+addi x1, x1, -272
+sraiw x14, x1, 31
+addi x7, x0, -1
+bne x14, x7, fail_1
+// Source and destination the same
+test_37:
+addi gp, x0, 37
+lui x1, 0x80000
+sraiw x1, x1, 7
+lui x7, 0xff000
+bne x1, x7, fail_1
+// Bypassing
+test_38:
+addi gp, x0, 38
+addi x4, x0, 0
+l1:
+lui x1, 0x80000
+sraiw x14, x1, 7
+addi x6, x14, 0
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l1
+lui x7, 0xff000
+bne x6, x7, fail_1
+// Bypassing
+test_39:
+addi gp, x0, 39
+addi x4, x0, 0
+l2:
+lui x1, 0x80000
+addiw x1, x1, 1
+sraiw x14, x1, 7
+addi x0, x0, 0
+addi x6, x14, 0
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l2
+lui x7, 0xff000
+bne x6, x7, fail_1
+// Bypassing
+test_40:
+addi gp, x0, 40
+addi x4, x0, 0
+l3:
+lui x1, 0x80000
+addiw x1, x1, 2
+sraiw x14, x1, 7
+addi x0, x0, 0
+addi x0, x0, 0
+addi x6, x14, 0
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l3
+lui x7, 0xff000
+bne x6, x7, fail_1
+test_41:
+addi gp, x0, 41
+addi x4, x0, 0
+l4:
+lui x1, 0x80000
+sraiw x14, x1, 7
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l4
+lui x7, 0xff000
+bne x14, x7, fail_1
+test_42:
+addi gp, x0, 42
+addi x4, x0, 0
+l5:
+lui x1, 0x80000
+addiw x1, x1, 1
+addi x0, x0, 0
+sraiw x14, x1, 7
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l5
+lui x7, 0xff000
+bne x14, x7, fail_1
+test_43:
+addi gp, x0, 43
+addi x4, x0, 0
+l6:
+lui x1, 0x80000
+addiw x1, x1, 2
+addi x0, x0, 0
+addi x0, x0, 0
+sraiw x14, x1, 7
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l6
+lui x7, 0xff000
+bne x14, x7, fail_1
+// Zero source and destination
+test_44:
+addi gp, x0, 44
+sraiw x1, x0, 4
+addi x7, x0, 0
+bne x1, x7, fail_1
+test_45:
+addi gp, x0, 45
+addi x1, x0, 33
+sraiw x0, x1, 10
+addi x7, x0, 0
+bne x0, x7, fail_1
+bne x0, gp, pass
+fail_1:
+jal x0, end
+pass:
+addi gp, x0, 1
+end:
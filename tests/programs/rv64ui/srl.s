@@ -0,0 +1,680 @@
+// rv64ui srl: logical right shift, using the bottom 6 bits of rs2
+// In the style of riscv-tests isa/rv64ui/srl.S, with the TEST_*
+// macros expanded. gp holds the number of the test being run, and is
+// 1 at the end if they all passed.
+test_2:
+addi gp, x0, 2
+lui x1, 0x80000
+addi x2, x0, 0
+srl x14, x1, x2
+lui x7, 0x80000
+bne x14, x7, fail_0
+test_3:
+addi gp, x0, 3
+lui x1, 0x80000
+addi x2, x0, 1
+srl x14, x1, x2
+addi x7, x0, 1
+slli x7, x7, 33
+addi x7, x7, -1
+slli x7, x7, 30
+bne x14, x7, fail_0
+test_4:
+addi gp, x0, 4
+lui x1, 0x80000
+addi x2, x0, 7
+srl x14, x1, x2
+addi x7, x0, 1
+slli x7, x7, 33
+addi x7, x7, -1
+slli x7, x7, 24
+bne x14, x7, fail_0
+test_5:
+addi gp, x0, 5
+lui x1, 0x80000
+addi x2, x0, 14
+srl x14, x1, x2
+addi x7, x0, 1
+slli x7, x7, 33
+addi x7, x7, -1
+slli x7, x7, 17
+bne x14, x7, fail_0
+test_6:
+addi gp, x0, 6
+lui x1, 0x80000
+addi x2, x0, 31
+srl x14, x1, x2
+addi x7, x0, 1
+slli x7, x7, 33
+addi x7, x7, -1
+bne x14, x7, fail_0
+test_7:
+addi gp, x0, 7
+lui x1, 0x80000
+addi x2, x0, 32
+srl x14, x1, x2
+addi x7, x0, 1
+slli x7, x7, 32
+addi x7, x7, -1
+bne x14, x7, fail_0
+test_8:
+addi gp, x0, 8
+lui x1, 0x80000
+addi x2, x0, 33
+srl x14, x1, x2
+lui x7, 0x80000
+addiw x7, x7, -1
+bne x14, x7, fail_0
+test_9:
+addi gp, x0, 9
+lui x1, 0x80000
+addi x2, x0, 39
+srl x14, x1, x2
+lui x7, 0x2000
+addiw x7, x7, -1
+bne x14, x7, fail_0
+test_10:
+addi gp, x0, 10
+lui x1, 0x80000
+addi x2, x0, 43
+srl x14, x1, x2
+lui x7, 0x200
+addiw x7, x7, -1
+bne x14, x7, fail_0
+test_11:
+addi gp, x0, 11
+lui x1, 0x80000
+addi x2, x0, 63
+srl x14, x1, x2
+addi x7, x0, 1
+bne x14, x7, fail_0
+test_12:
+addi gp, x0, 12
+lui x1, 0x80000
+addiw x1, x1, -1
+addi x2, x0, 0
+srl x14, x1, x2
+lui x7, 0x80000
+addiw x7, x7, -1
+bne x14, x7, fail_0
+test_13:
+addi gp, x0, 13
+lui x1, 0x80000
+addiw x1, x1, -1
+addi x2, x0, 1
+srl x14, x1, x2
+lui x7, 0x40000
+addiw x7, x7, -1
+bne x14, x7, fail_0
+test_14:
+addi gp, x0, 14
+lui x1, 0x80000
+addiw x1, x1, -1
+addi x2, x0, 7
+srl x14, x1, x2
+lui x7, 0x1000
+addiw x7, x7, -1
+bne x14, x7, fail_0
+test_15:
+addi gp, x0, 15
+lui x1, 0x80000
+addiw x1, x1, -1
+addi x2, x0, 14
+srl x14, x1, x2
+lui x7, 0x20
+addiw x7, x7, -1
+bne x14, x7, fail_0
+test_16:
+addi gp, x0, 16
+lui x1, 0x80000
+addiw x1, x1, -1
+addi x2, x0, 31
+srl x14, x1, x2
+addi x7, x0, 0
+bne x14, x7, fail_0
+test_17:
+addi gp, x0, 17
+lui x1, 0x80000
+addiw x1, x1, -1
+addi x2, x0, 32
+srl x14, x1, x2
+addi x7, x0, 0
+bne x14, x7, fail_0
+test_18:
+addi gp, x0, 18
+lui x1, 0x80000
+addiw x1, x1, -1
+addi x2, x0, 33
+srl x14, x1, x2
+addi x7, x0, 0
+bne x14, x7, fail_0
+test_19:
+addi gp, x0, 19
+lui x1, 0x80000
+addiw x1, x1, -1
+addi x2, x0, 39
+srl x14, x1, x2
+addi x7, x0, 0
+bne x14, x7, fail_0
+test_20:
+addi gp, x0, 20
+lui x1, 0x80000
+addiw x1, x1, -1
+addi x2, x0, 43
+srl x14, x1, x2
+addi x7, x0, 0
+bne x14, x7, fail_0
+test_21:
+addi gp, x0, 21
+lui x1, 0x80000
+addiw x1, x1, -1
+addi x2, x0, 63
+srl x14, x1, x2
+addi x7, x0, 0
+bne x14, x7, fail_0
+test_22:
+addi gp, x0, 22
+lui x1, 0x81818
+addiw x1, x1, 385
+addi x2, x0, 0
+srl x14, x1, x2
+lui x7, 0x81818
+addiw x7, x7, 385
+bne x14, x7, fail_0
+test_23:
+addi gp, x0, 23
+lui x1, 0x81818
+addiw x1, x1, 385
+addi x2, x0, 1
+srl x14, x1, x2
+addi x7, x0, 1
+slli x7, x7, 33
+addi x7, x7, -1
+slli x7, x7, 16
+addi x7, x7, 771
+slli x7, x7, 14
+addi x7, x7, 192
+bne x14, x7, fail_0
+test_24:
+addi gp, x0, 24
+lui x1, 0x81818
+addiw x1, x1, 385
+addi x2, x0, 7
+srl x14, x1, x2
+addi x7, x0, 1
+slli x7, x7, 41
+addi x7, x7, -253
+slli x7, x7, 16
+addi x7, x7, 771
+bne x14, x7, fail_0
+test_25:
+addi gp, x0, 25
+lui x1, 0x81818
+addiw x1, x1, 385
+addi x2, x0, 14
+srl x14, x1, x2
+addi x7, x0, 1
+slli x7, x7, 33
+addi x7, x7, -1
+slli x7, x7, 17
+addi x7, x7, 1542
+bne x14, x7, fail_0
+test_26:
+addi gp, x0, 26
+lui x1, 0x81818
+addiw x1, x1, 385
+addi x2, x0, 31
+srl x14, x1, x2
+addi x7, x0, 1
+slli x7, x7, 33
+addi x7, x7, -1
+bne x14, x7, fail_0
+jal x0, continue_0
+fail_0:
+jal x0, fail_1
+continue_0:
+test_27:
+addi gp, x0, 27
+lui x1, 0x81818
+addiw x1, x1, 385
+addi x2, x0, 32
+srl x14, x1, x2
+addi x7, x0, 1
+slli x7, x7, 32
+addi x7, x7, -1
+bne x14, x7, fail_1
+test_28:
+addi gp, x0, 28
+lui x1, 0x81818
+addiw x1, x1, 385
+addi x2, x0, 33
+srl x14, x1, x2
+lui x7, 0x80000
+addiw x7, x7, -1
+bne x14, x7, fail_1
+test_29:
+addi gp, x0, 29
+lui x1, 0x81818
+addiw x1, x1, 385
+addi x2, x0, 39
+srl x14, x1, x2
+lui x7, 0x2000
+addiw x7, x7, -1
+bne x14, x7, fail_1
+test_30:
+addi gp, x0, 30
+lui x1, 0x81818
+addiw x1, x1, 385
+addi x2, x0, 43
+srl x14, x1, x2
+lui x7, 0x200
+addiw x7, x7, -1
+bne x14, x7, fail_1
+test_31:
+addi gp, x0, 31
+lui x1, 0x81818
+addiw x1, x1, 385
+addi x2, x0, 63
+srl x14, x1, x2
+addi x7, x0, 1
+bne x14, x7, fail_1
+test_32:
+addi gp, x0, 32
+lui x1, 0x81818
+addiw x1, x1, 385
+addi x2, x0, -64
+srl x14, x1, x2
+lui x7, 0x81818
+addiw x7, x7, 385
+bne x14, x7, fail_1
+test_33:
+addi gp, x0, 33
+lui x1, 0x81818
+addiw x1, x1, 385
+addi x2, x0, -63
+srl x14, x1, x2
+addi x7, x0, 1
+slli x7, x7, 33
+addi x7, x7, -1
+slli x7, x7, 16
+addi x7, x7, 771
+slli x7, x7, 14
+addi x7, x7, 192
+bne x14, x7, fail_1
+test_34:
+addi gp, x0, 34
+lui x1, 0x81818
+addiw x1, x1, 385
+addi x2, x0, -57
+srl x14, x1, x2
+addi x7, x0, 1
+slli x7, x7, 41
+addi x7, x7, -253
+slli x7, x7, 16
+addi x7, x7, 771
+bne x14, x7, fail_1
+test_35:
+addi gp, x0, 35
+lui x1, 0x81818
+addiw x1, x1, 385
+addi x2, x0, -50
+srl x14, x1, x2
+addi x7, x0, 1
+slli x7, x7, 33
+addi x7, x7, -1
+slli x7, x7, 17
+addi x7, x7, 1542
+bne x14, x7, fail_1
+test_36:
+addi gp, x0, 36
+lui x1, 0x81818
+addiw x1, x1, 385
+addi x2, x0, -39
+srl x14, x1, x2
+addi x7, x0, 1
+slli x7, x7, 39
+addi x7, x7, -64
+bne x14, x7, fail_1
+test_37:
+addi gp, x0, 37
+lui x1, 0x81818
+addiw x1, x1, 385
+addi x2, x0, 64
+srl x14, x1, x2
+lui x7, 0x81818
+addiw x7, x7, 385
+bne x14, x7, fail_1
+test_38:
+addi gp, x0, 38
+lui x1, 0x81818
+addiw x1, x1, 385
+addi x2, x0, 127
+srl x14, x1, x2
+addi x7, x0, 1
+bne x14, x7, fail_1
+// Source and destination the same
+test_39:
+addi gp, x0, 39
+lui x1, 0x80000
+addi x2, x0, 7
+srl x1, x1, x2
+addi x7, x0, 1
+slli x7, x7, 33
+addi x7, x7, -1
+slli x7, x7, 24
+bne x1, x7, fail_1
+test_40:
+addi gp, x0, 40
+lui x1, 0x80000
+addiw x1, x1, 1
+addi x2, x0, 7
+srl x2, x1, x2
+addi x7, x0, 1
+slli x7, x7, 33
+addi x7, x7, -1
+slli x7, x7, 24
+bne x2, x7, fail_1
+test_41:
+addi gp, x0, 41
+lui x1, 0x80000
+srl x1, x1, x1
+lui x7, 0x80000
+bne x1, x7, fail_1
+// Bypassing
+test_42:
+addi gp, x0, 42
+addi x4, x0, 0
+l1:
+lui x1, 0x80000
+addi x2, x0, 7
+srl x14, x1, x2
+addi x6, x14, 0
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l1
+addi x7, x0, 1
+slli x7, x7, 33
+addi x7, x7, -1
+slli x7, x7, 24
+bne x6, x7, fail_1
+// Bypassing
+test_43:
+addi gp, x0, 43
+addi x4, x0, 0
+l2:
+lui x1, 0x80000
+addiw x1, x1, 1
+addi x2, x0, 7
+srl x14, x1, x2
+addi x0, x0, 0
+addi x6, x14, 0
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l2
+addi x7, x0, 1
+slli x7, x7, 33
+addi x7, x7, -1
+slli x7, x7, 24
+bne x6, x7, fail_1
+// Bypassing
+test_44:
+addi gp, x0, 44
+addi x4, x0, 0
+l3:
+lui x1, 0x80000
+addiw x1, x1, 2
+addi x2, x0, 7
+srl x14, x1, x2
+addi x0, x0, 0
+addi x0, x0, 0
+addi x6, x14, 0
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l3
+addi x7, x0, 1
+slli x7, x7, 33
+addi x7, x7, -1
+slli x7, x7, 24
+bne x6, x7, fail_1
+test_45:
+addi gp, x0, 45
+addi x4, x0, 0
+l4:
+lui x1, 0x80000
+addi x2, x0, 7
+srl x14, x1, x2
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l4
+addi x7, x0, 1
+slli x7, x7, 33
+addi x7, x7, -1
+slli x7, x7, 24
+bne x14, x7, fail_1
+test_46:
+addi gp, x0, 46
+addi x4, x0, 0
+l5:
+lui x1, 0x80000
+addiw x1, x1, 1
+addi x2, x0, 7
+addi x0, x0, 0
+srl x14, x1, x2
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l5
+addi x7, x0, 1
+slli x7, x7, 33
+addi x7, x7, -1
+slli x7, x7, 24
+bne x14, x7, fail_1
+jal x0, continue_1
+fail_1:
+jal x0, fail_2
+continue_1:
+test_47:
+addi gp, x0, 47
+addi x4, x0, 0
+l6:
+lui x1, 0x80000
+addiw x1, x1, 2
+addi x2, x0, 7
+addi x0, x0, 0
+addi x0, x0, 0
+srl x14, x1, x2
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l6
+addi x7, x0, 1
+slli x7, x7, 33
+addi x7, x7, -1
+slli x7, x7, 24
+bne x14, x7, fail_2
+test_48:
+addi gp, x0, 48
+addi x4, x0, 0
+l7:
+lui x1, 0x80000
+addiw x1, x1, 1
+addi x0, x0, 0
+addi x2, x0, 7
+srl x14, x1, x2
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l7
+addi x7, x0, 1
+slli x7, x7, 33
+addi x7, x7, -1
+slli x7, x7, 24
+bne x14, x7, fail_2
+test_49:
+addi gp, x0, 49
+addi x4, x0, 0
+l8:
+lui x1, 0x80000
+addiw x1, x1, 2
+addi x0, x0, 0
+addi x2, x0, 7
+addi x0, x0, 0
+srl x14, x1, x2
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l8
+addi x7, x0, 1
+slli x7, x7, 33
+addi x7, x7, -1
+slli x7, x7, 24
+bne x14, x7, fail_2
+test_50:
+addi gp, x0, 50
+addi x4, x0, 0
+l9:
+lui x1, 0x80000
+addiw x1, x1, 2
+addi x0, x0, 0
+addi x0, x0, 0
+addi x2, x0, 7
+srl x14, x1, x2
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l9
+addi x7, x0, 1
+slli x7, x7, 33
+addi x7, x7, -1
+slli x7, x7, 24
+bne x14, x7, fail_2
+test_51:
+addi gp, x0, 51
+addi x4, x0, 0
+l10:
+addi x2, x0, 7
+lui x1, 0x80000
+srl x14, x1, x2
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l10
+addi x7, x0, 1
+slli x7, x7, 33
+addi x7, x7, -1
+slli x7, x7, 24
+bne x14, x7, fail_2
+test_52:
+addi gp, x0, 52
+addi x4, x0, 0
+l11:
+addi x2, x0, 7
+lui x1, 0x80000
+addiw x1, x1, 1
+addi x0, x0, 0
+srl x14, x1, x2
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l11
+addi x7, x0, 1
+slli x7, x7, 33
+addi x7, x7, -1
+slli x7, x7, 24
+bne x14, x7, fail_2
+test_53:
+addi gp, x0, 53
+addi x4, x0, 0
+l12:
+addi x2, x0, 7
+lui x1, 0x80000
+addiw x1, x1, 2
+addi x0, x0, 0
+addi x0, x0, 0
+srl x14, x1, x2
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l12
+addi x7, x0, 1
+slli x7, x7, 33
+addi x7, x7, -1
+slli x7, x7, 24
+bne x14, x7, fail_2
+test_54:
+addi gp, x0, 54
+addi x4, x0, 0
+l13:
+addi x2, x0, 7
+addi x0, x0, 0
+lui x1, 0x80000
+addiw x1, x1, 1
+srl x14, x1, x2
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l13
+addi x7, x0, 1
+slli x7, x7, 33
+addi x7, x7, -1
+slli x7, x7, 24
+bne x14, x7, fail_2
+test_55:
+addi gp, x0, 55
+addi x4, x0, 0
+l14:
+addi x2, x0, 7
+addi x0, x0, 0
+lui x1, 0x80000
+addiw x1, x1, 2
+addi x0, x0, 0
+srl x14, x1, x2
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l14
+addi x7, x0, 1
+slli x7, x7, 33
+addi x7, x7, -1
+slli x7, x7, 24
+bne x14, x7, fail_2
+test_56:
+addi gp, x0, 56
+addi x4, x0, 0
+l15:
+addi x2, x0, 7
+addi x0, x0, 0
+addi x0, x0, 0
+lui x1, 0x80000
+addiw x1, x1, 2
+srl x14, x1, x2
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l15
+addi x7, x0, 1
+slli x7, x7, 33
+addi x7, x7, -1
+slli x7, x7, 24
+bne x14, x7, fail_2
+// Zero sources and destination
+test_57:
+addi gp, x0, 57
+addi x1, x0, 15
+srl x2, x0, x1
+addi x7, x0, 0
+bne x2, x7, fail_2
+test_58:
+addi gp, x0, 58
+addi x1, x0, 32
+srl x2, x1, x0
+addi x7, x0, 32
+bne x2, x7, fail_2
+test_59:
+addi gp, x0, 59
+srl x1, x0, x0
+addi x7, x0, 0
+bne x1, x7, fail_2
+test_60:
+addi gp, x0, 60
+addi x1, x0, 1024
+lui x2, 0x1
+addiw x2, x2, -2048
+srl x0, x1, x2
+addi x7, x0, 0
+bne x0, x7, fail_2
+bne x0, gp, pass
+fail_2:
+jal x0, end
+pass:
+addi gp, x0, 1
+end:
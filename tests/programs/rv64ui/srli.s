@@ -0,0 +1,752 @@
+// rv64ui srli: logical right shift by an immediate
+// In the style of riscv-tests isa/rv64ui/srli.S, with the TEST_*
+// macros expanded. gp holds the number of the test being run, and is
+// 1 at the end if they all passed.
+test_2:
+addi gp, x0, 2
+addi x1, x0, 1
+srli x14, x1, 0
+addi x7, x0, 1
+bne x14, x7, fail_0
+test_3:
+addi gp, x0, 3
+addi x1, x0, 1
+srli x14, x1, 1
+addi x7, x0, 0
+bne x14, x7, fail_0
+test_4:
+addi gp, x0, 4
+addi x1, x0, 1
+srli x14, x1, 7
+addi x7, x0, 0
+bne x14, x7, fail_0
+test_5:
+addi gp, x0, 5
+addi x1, x0, 1
+srli x14, x1, 14
+addi x7, x0, 0
+bne x14, x7, fail_0
+test_6:
+addi gp, x0, 6
+addi x1, x0, 1
+srli x14, x1, 31
+addi x7, x0, 0
+bne x14, x7, fail_0
+test_7:
+addi gp, x0, 7
+addi x1, x0, 1
+srli x14, x1, 32
+addi x7, x0, 0
+bne x14, x7, fail_0
+test_8:
+addi gp, x0, 8
+addi x1, x0, 1
+srli x14, x1, 33
+addi x7, x0, 0
+bne x14, x7, fail_0
+test_9:
+addi gp, x0, 9
+addi x1, x0, 1
+srli x14, x1, 39
+addi x7, x0, 0
+bne x14, x7, fail_0
+test_10:
+addi gp, x0, 10
+addi x1, x0, 1
+srli x14, x1, 43
+addi x7, x0, 0
+bne x14, x7, fail_0
+test_11:
+addi gp, x0, 11
+addi x1, x0, 1
+srli x14, x1, 63
+addi x7, x0, 0
+bne x14, x7, fail_0
+test_12:
+addi gp, x0, 12
+addi x1, x0, -1
+srli x14, x1, 0
+addi x7, x0, -1
+bne x14, x7, fail_0
+test_13:
+addi gp, x0, 13
+addi x1, x0, -1
+srli x14, x1, 1
+addi x7, x0, 1
+slli x7, x7, 63
+addi x7, x7, -1
+bne x14, x7, fail_0
+test_14:
+addi gp, x0, 14
+addi x1, x0, -1
+srli x14, x1, 7
+addi x7, x0, 1
+slli x7, x7, 57
+addi x7, x7, -1
+bne x14, x7, fail_0
+test_15:
+addi gp, x0, 15
+addi x1, x0, -1
+srli x14, x1, 14
+addi x7, x0, 1
+slli x7, x7, 50
+addi x7, x7, -1
+bne x14, x7, fail_0
+test_16:
+addi gp, x0, 16
+addi x1, x0, -1
+srli x14, x1, 31
+addi x7, x0, 1
+slli x7, x7, 33
+addi x7, x7, -1
+bne x14, x7, fail_0
+test_17:
+addi gp, x0, 17
+addi x1, x0, -1
+srli x14, x1, 32
+addi x7, x0, 1
+slli x7, x7, 32
+addi x7, x7, -1
+bne x14, x7, fail_0
+test_18:
+addi gp, x0, 18
+addi x1, x0, -1
+srli x14, x1, 33
+lui x7, 0x80000
+addiw x7, x7, -1
+bne x14, x7, fail_0
+test_19:
+addi gp, x0, 19
+addi x1, x0, -1
+srli x14, x1, 39
+lui x7, 0x2000
+addiw x7, x7, -1
+bne x14, x7, fail_0
+test_20:
+addi gp, x0, 20
+addi x1, x0, -1
+srli x14, x1, 43
+lui x7, 0x200
+addiw x7, x7, -1
+bne x14, x7, fail_0
+test_21:
+addi gp, x0, 21
+addi x1, x0, -1
+srli x14, x1, 63
+addi x7, x0, 1
+bne x14, x7, fail_0
+test_22:
+addi gp, x0, 22
+lui x1, 0x21212
+addiw x1, x1, 289
+srli x14, x1, 0
+lui x7, 0x21212
+addiw x7, x7, 289
+bne x14, x7, fail_0
+test_23:
+addi gp, x0, 23
+lui x1, 0x21212
+addiw x1, x1, 289
+srli x14, x1, 1
+lui x7, 0x10909
+addiw x7, x7, 144
+bne x14, x7, fail_0
+test_24:
+addi gp, x0, 24
+lui x1, 0x21212
+addiw x1, x1, 289
+srli x14, x1, 7
+lui x7, 0x424
+addiw x7, x7, 578
+bne x14, x7, fail_0
+test_25:
+addi gp, x0, 25
+lui x1, 0x21212
+addiw x1, x1, 289
+srli x14, x1, 14
+lui x7, 0x8
+addiw x7, x7, 1156
+bne x14, x7, fail_0
+test_26:
+addi gp, x0, 26
+lui x1, 0x21212
+addiw x1, x1, 289
+srli x14, x1, 31
+addi x7, x0, 0
+bne x14, x7, fail_0
+test_27:
+addi gp, x0, 27
+lui x1, 0x21212
+addiw x1, x1, 289
+srli x14, x1, 32
+addi x7, x0, 0
+bne x14, x7, fail_0
+test_28:
+addi gp, x0, 28
+lui x1, 0x21212
+addiw x1, x1, 289
+srli x14, x1, 33
+addi x7, x0, 0
+bne x14, x7, fail_0
+test_29:
+addi gp, x0, 29
+lui x1, 0x21212
+addiw x1, x1, 289
+srli x14, x1, 39
+addi x7, x0, 0
+bne x14, x7, fail_0
+test_30:
+addi gp, x0, 30
+lui x1, 0x21212
+addiw x1, x1, 289
+srli x14, x1, 43
+addi x7, x0, 0
+bne x14, x7, fail_0
+test_31:
+addi gp, x0, 31
+lui x1, 0x21212
+addiw x1, x1, 289
+srli x14, x1, 63
+addi x7, x0, 0
+bne x14, x7, fail_0
+test_32:
+addi gp, x0, 32
+lui x1, 0x80000
+srli x14, x1, 0
+lui x7, 0x80000
+bne x14, x7, fail_0
+test_33:
+addi gp, x0, 33
+lui x1, 0x80000
+srli x14, x1, 1
+addi x7, x0, 1
+slli x7, x7, 33
+addi x7, x7, -1
+slli x7, x7, 30
+bne x14, x7, fail_0
+test_34:
+addi gp, x0, 34
+lui x1, 0x80000
+srli x14, x1, 7
+addi x7, x0, 1
+slli x7, x7, 33
+addi x7, x7, -1
+slli x7, x7, 24
+bne x14, x7, fail_0
+test_35:
+addi gp, x0, 35
+lui x1, 0x80000
+srli x14, x1, 14
+addi x7, x0, 1
+slli x7, x7, 33
+addi x7, x7, -1
+slli x7, x7, 17
+bne x14, x7, fail_0
+jal x0, continue_0
+fail_0:
+jal x0, fail_1
+continue_0:
+test_36:
+addi gp, x0, 36
+lui x1, 0x80000
+srli x14, x1, 31
+addi x7, x0, 1
+slli x7, x7, 33
+addi x7, x7, -1
+bne x14, x7, fail_1
+test_37:
+addi gp, x0, 37
+lui x1, 0x80000
+srli x14, x1, 32
+addi x7, x0, 1
+slli x7, x7, 32
+addi x7, x7, -1
+bne x14, x7, fail_1
+test_38:
+addi gp, x0, 38
+lui x1, 0x80000
+srli x14, x1, 33
+lui x7, 0x80000
+addiw x7, x7, -1
+bne x14, x7, fail_1
+test_39:
+addi gp, x0, 39
+lui x1, 0x80000
+srli x14, x1, 39
+lui x7, 0x2000
+addiw x7, x7, -1
+bne x14, x7, fail_1
+test_40:
+addi gp, x0, 40
+lui x1, 0x80000
+srli x14, x1, 43
+lui x7, 0x200
+addiw x7, x7, -1
+bne x14, x7, fail_1
+test_41:
+addi gp, x0, 41
+lui x1, 0x80000
+srli x14, x1, 63
+addi x7, x0, 1
+bne x14, x7, fail_1
+test_42:
+addi gp, x0, 42
+lui x1, 0x80000
+addiw x1, x1, -1
+srli x14, x1, 0
+lui x7, 0x80000
+addiw x7, x7, -1
+bne x14, x7, fail_1
+test_43:
+addi gp, x0, 43
+lui x1, 0x80000
+addiw x1, x1, -1
+srli x14, x1, 1
+lui x7, 0x40000
+addiw x7, x7, -1
+bne x14, x7, fail_1
+test_44:
+addi gp, x0, 44
+lui x1, 0x80000
+addiw x1, x1, -1
+srli x14, x1, 7
+lui x7, 0x1000
+addiw x7, x7, -1
+bne x14, x7, fail_1
+test_45:
+addi gp, x0, 45
+lui x1, 0x80000
+addiw x1, x1, -1
+srli x14, x1, 14
+lui x7, 0x20
+addiw x7, x7, -1
+bne x14, x7, fail_1
+test_46:
+addi gp, x0, 46
+lui x1, 0x80000
+addiw x1, x1, -1
+srli x14, x1, 31
+addi x7, x0, 0
+bne x14, x7, fail_1
+test_47:
+addi gp, x0, 47
+lui x1, 0x80000
+addiw x1, x1, -1
+srli x14, x1, 32
+addi x7, x0, 0
+bne x14, x7, fail_1
+test_48:
+addi gp, x0, 48
+lui x1, 0x80000
+addiw x1, x1, -1
+srli x14, x1, 33
+addi x7, x0, 0
+bne x14, x7, fail_1
+test_49:
+addi gp, x0, 49
+lui x1, 0x80000
+addiw x1, x1, -1
+srli x14, x1, 39
+addi x7, x0, 0
+bne x14, x7, fail_1
+test_50:
+addi gp, x0, 50
+lui x1, 0x80000
+addiw x1, x1, -1
+srli x14, x1, 43
+addi x7, x0, 0
+bne x14, x7, fail_1
+test_51:
+addi gp, x0, 51
+lui x1, 0x80000
+addiw x1, x1, -1
+srli x14, x1, 63
+addi x7, x0, 0
+bne x14, x7, fail_1
+test_52:
+addi gp, x0, 52
+lui x1, 0x81818
+addiw x1, x1, 385
+srli x14, x1, 0
+lui x7, 0x81818
+addiw x7, x7, 385
+bne x14, x7, fail_1
+test_53:
+addi gp, x0, 53
+lui x1, 0x81818
+addiw x1, x1, 385
+srli x14, x1, 1
+addi x7, x0, 1
+slli x7, x7, 33
+addi x7, x7, -1
+slli x7, x7, 16
+addi x7, x7, 771
+slli x7, x7, 14
+addi x7, x7, 192
+bne x14, x7, fail_1
+test_54:
+addi gp, x0, 54
+lui x1, 0x81818
+addiw x1, x1, 385
+srli x14, x1, 7
+addi x7, x0, 1
+slli x7, x7, 41
+addi x7, x7, -253
+slli x7, x7, 16
+addi x7, x7, 771
+bne x14, x7, fail_1
+test_55:
+addi gp, x0, 55
+lui x1, 0x81818
+addiw x1, x1, 385
+srli x14, x1, 14
+addi x7, x0, 1
+slli x7, x7, 33
+addi x7, x7, -1
+slli x7, x7, 17
+addi x7, x7, 1542
+bne x14, x7, fail_1
+test_56:
+addi gp, x0, 56
+lui x1, 0x81818
+addiw x1, x1, 385
+srli x14, x1, 31
+addi x7, x0, 1
+slli x7, x7, 33
+addi x7, x7, -1
+bne x14, x7, fail_1
+test_57:
+addi gp, x0, 57
+lui x1, 0x81818
+addiw x1, x1, 385
+srli x14, x1, 32
+addi x7, x0, 1
+slli x7, x7, 32
+addi x7, x7, -1
+bne x14, x7, fail_1
+test_58:
+addi gp, x0, 58
+lui x1, 0x81818
+addiw x1, x1, 385
+srli x14, x1, 33
+lui x7, 0x80000
+addiw x7, x7, -1
+bne x14, x7, fail_1
+test_59:
+addi gp, x0, 59
+lui x1, 0x81818
+addiw x1, x1, 385
+srli x14, x1, 39
+lui x7, 0x2000
+addiw x7, x7, -1
+bne x14, x7, fail_1
+test_60:
+addi gp, x0, 60
+lui x1, 0x81818
+addiw x1, x1, 385
+srli x14, x1, 43
+lui x7, 0x200
+addiw x7, x7, -1
+bne x14, x7, fail_1
+test_61:
+addi gp, x0, 61
+lui x1, 0x81818
+addiw x1, x1, 385
+srli x14, x1, 63
+addi x7, x0, 1
+bne x14, x7, fail_1
+test_62:
+addi gp, x0, 62
+lui x1, 0x247
+addiw x1, x1, -1875
+slli x1, x1, 14
+addi x1, x1, -947
+slli x1, x1, 12
+addi x1, x1, 1511
+slli x1, x1, 13
+addi x1, x1, -272
+srli x14, x1, 0
+lui x7, 0x247
+addiw x7, x7, -1875
+slli x7, x7, 14
+addi x7, x7, -947
+slli x7, x7, 12
+addi x7, x7, 1511
+slli x7, x7, 13
+addi x7, x7, -272
+bne x14, x7, fail_1
+jal x0, continue_1
+fail_1:
+jal x0, fail_2
+continue_1:
+test_63:
+addi gp, x0, 63
+lui x1, 0x247
+addiw x1, x1, -1875
+slli x1, x1, 14
+addi x1, x1, -947
+slli x1, x1, 12
+addi x1, x1, 1511
+slli x1, x1, 13
+addi x1, x1, -272
+srli x14, x1, 1
+lui x7, 0x247
+addiw x7, x7, -1875
+slli x7, x7, 14
+addi x7, x7, -947
+slli x7, x7, 12
+addi x7, x7, 1511
+slli x7, x7, 12
+addi x7, x7, -136
+bne x14, x7, fail_2
+test_64:
+addi gp, x0, 64
+lui x1, 0x247
+addiw x1, x1, -1875
+slli x1, x1, 14
+addi x1, x1, -947
+slli x1, x1, 12
+addi x1, x1, 1511
+slli x1, x1, 13
+addi x1, x1, -272
+srli x14, x1, 7
+lui x7, 0x2469
+addiw x7, x7, -1329
+slli x7, x7, 13
+addi x7, x7, 619
+slli x7, x7, 15
+addi x7, x7, -1603
+bne x14, x7, fail_2
+test_65:
+addi gp, x0, 65
+lui x1, 0x247
+addiw x1, x1, -1875
+slli x1, x1, 14
+addi x1, x1, -947
+slli x1, x1, 12
+addi x1, x1, 1511
+slli x1, x1, 13
+addi x1, x1, -272
+srli x14, x1, 14
+lui x7, 0x247
+addiw x7, x7, -1875
+slli x7, x7, 13
+addi x7, x7, -473
+slli x7, x7, 12
+addi x7, x7, -1293
+bne x14, x7, fail_2
+test_66:
+addi gp, x0, 66
+lui x1, 0x247
+addiw x1, x1, -1875
+slli x1, x1, 14
+addi x1, x1, -947
+slli x1, x1, 12
+addi x1, x1, 1511
+slli x1, x1, 13
+addi x1, x1, -272
+srli x14, x1, 31
+lui x7, 0x2468b
+addiw x7, x7, -783
+bne x14, x7, fail_2
+test_67:
+addi gp, x0, 67
+lui x1, 0x247
+addiw x1, x1, -1875
+slli x1, x1, 14
+addi x1, x1, -947
+slli x1, x1, 12
+addi x1, x1, 1511
+slli x1, x1, 13
+addi x1, x1, -272
+srli x14, x1, 32
+lui x7, 0x12345
+addiw x7, x7, 1656
+bne x14, x7, fail_2
+test_68:
+addi gp, x0, 68
+lui x1, 0x247
+addiw x1, x1, -1875
+slli x1, x1, 14
+addi x1, x1, -947
+slli x1, x1, 12
+addi x1, x1, 1511
+slli x1, x1, 13
+addi x1, x1, -272
+srli x14, x1, 33
+lui x7, 0x91a3
+addiw x7, x7, -1220
+bne x14, x7, fail_2
+test_69:
+addi gp, x0, 69
+lui x1, 0x247
+addiw x1, x1, -1875
+slli x1, x1, 14
+addi x1, x1, -947
+slli x1, x1, 12
+addi x1, x1, 1511
+slli x1, x1, 13
+addi x1, x1, -272
+srli x14, x1, 39
+lui x7, 0x247
+addiw x7, x7, -1876
+bne x14, x7, fail_2
+test_70:
+addi gp, x0, 70
+lui x1, 0x247
+addiw x1, x1, -1875
+slli x1, x1, 14
+addi x1, x1, -947
+slli x1, x1, 12
+addi x1, x1, 1511
+slli x1, x1, 13
+addi x1, x1, -272
+srli x14, x1, 43
+lui x7, 0x24
+addiw x7, x7, 1674
+bne x14, x7, fail_2
+test_71:
+addi gp, x0, 71
+lui x1, 0x247
+addiw x1, x1, -1875
+slli x1, x1, 14
+addi x1, x1, -947
+slli x1, x1, 12
+addi x1, x1, 1511
+slli x1, x1, 13
+addi x1, x1, -272
+srli x14, x1, 63
+addi x7, x0, 0
+bne x14, x7, fail_2
+// Source and destination the same
+test_72:
+addi gp, x0, 72
+lui x1, 0x80000
+srli x1, x1, 7
+addi x7, x0, 1
+slli x7, x7, 33
+addi x7, x7, -1
+slli x7, x7, 24
+bne x1, x7, fail_2
+// Bypassing
+test_73:
+addi gp, x0, 73
+addi x4, x0, 0
+l1:
+lui x1, 0x80000
+srli x14, x1, 7
+addi x6, x14, 0
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l1
+addi x7, x0, 1
+slli x7, x7, 33
+addi x7, x7, -1
+slli x7, x7, 24
+bne x6, x7, fail_2
+// Bypassing
+test_74:
+addi gp, x0, 74
+addi x4, x0, 0
+l2:
+lui x1, 0x80000
+addiw x1, x1, 1
+srli x14, x1, 7
+addi x0, x0, 0
+addi x6, x14, 0
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l2
+addi x7, x0, 1
+slli x7, x7, 33
+addi x7, x7, -1
+slli x7, x7, 24
+bne x6, x7, fail_2
+// Bypassing
+test_75:
+addi gp, x0, 75
+addi x4, x0, 0
+l3:
+lui x1, 0x80000
+addiw x1, x1, 2
+srli x14, x1, 7
+addi x0, x0, 0
+addi x0, x0, 0
+addi x6, x14, 0
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l3
+addi x7, x0, 1
+slli x7, x7, 33
+addi x7, x7, -1
+slli x7, x7, 24
+bne x6, x7, fail_2
+test_76:
+addi gp, x0, 76
+addi x4, x0, 0
+l4:
+lui x1, 0x80000
+srli x14, x1, 7
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l4
+addi x7, x0, 1
+slli x7, x7, 33
+addi x7, x7, -1
+slli x7, x7, 24
+bne x14, x7, fail_2
+test_77:
+addi gp, x0, 77
+addi x4, x0, 0
+l5:
+lui x1, 0x80000
+addiw x1, x1, 1
+addi x0, x0, 0
+srli x14, x1, 7
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l5
+addi x7, x0, 1
+slli x7, x7, 33
+addi x7, x7, -1
+slli x7, x7, 24
+bne x14, x7, fail_2
+jal x0, continue_2
+fail_2:
+jal x0, fail_3
+continue_2:
+test_78:
+addi gp, x0, 78
+addi x4, x0, 0
+l6:
+lui x1, 0x80000
+addiw x1, x1, 2
+addi x0, x0, 0
+addi x0, x0, 0
+srli x14, x1, 7
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l6
+addi x7, x0, 1
+slli x7, x7, 33
+addi x7, x7, -1
+slli x7, x7, 24
+bne x14, x7, fail_3
+// Zero source and destination
+test_79:
+addi gp, x0, 79
+srli x1, x0, 4
+addi x7, x0, 0
+bne x1, x7, fail_3
+test_80:
+addi gp, x0, 80
+addi x1, x0, 33
+srli x0, x1, 10
+addi x7, x0, 0
+bne x0, x7, fail_3
+bne x0, gp, pass
+fail_3:
+jal x0, end
+pass:
+addi gp, x0, 1
+end:
@@ -0,0 +1,408 @@
+// rv64ui sw: store word
+// In the style of riscv-tests isa/rv64ui/sw.S, with the TEST_*
+// macros expanded. gp holds the number of the test being run, and is
+// 1 at the end if they all passed.
+// The test data
+lui x2, 0x10010
+lui x1, 0xffff0
+addiw x1, x1, -257
+slli x1, x1, 16
+addi x1, x1, -257
+slli x1, x1, 16
+addi x1, x1, -257
+slli x1, x1, 12
+addi x1, x1, -17
+sd x1, 0(x2)
+lui x1, 0xffff0
+addiw x1, x1, -257
+slli x1, x1, 16
+addi x1, x1, -257
+slli x1, x1, 16
+addi x1, x1, -257
+slli x1, x1, 12
+addi x1, x1, -17
+sd x1, 8(x2)
+lui x1, 0xffff0
+addiw x1, x1, -257
+slli x1, x1, 16
+addi x1, x1, -257
+slli x1, x1, 16
+addi x1, x1, -257
+slli x1, x1, 12
+addi x1, x1, -17
+sd x1, 16(x2)
+lui x1, 0xffff0
+addiw x1, x1, -257
+slli x1, x1, 16
+addi x1, x1, -257
+slli x1, x1, 16
+addi x1, x1, -257
+slli x1, x1, 12
+addi x1, x1, -17
+sd x1, 24(x2)
+lui x1, 0xffff0
+addiw x1, x1, -257
+slli x1, x1, 16
+addi x1, x1, -257
+slli x1, x1, 16
+addi x1, x1, -257
+slli x1, x1, 12
+addi x1, x1, -17
+sd x1, 32(x2)
+test_2:
+addi gp, x0, 2
+lui x2, 0x10010
+lui x1, 0xaa0
+addiw x1, x1, 170
+sw x1, 0(x2)
+lw x14, 0(x2)
+lui x7, 0xaa0
+addiw x7, x7, 170
+bne x14, x7, fail_0
+test_3:
+addi gp, x0, 3
+lui x2, 0x10010
+lui x1, 0xaa00b
+addiw x1, x1, -1536
+sw x1, 4(x2)
+lw x14, 4(x2)
+lui x7, 0xaa00b
+addiw x7, x7, -1536
+bne x14, x7, fail_0
+test_4:
+addi gp, x0, 4
+lui x2, 0x10010
+lui x1, 0xaa01
+addiw x1, x1, -1376
+sw x1, 8(x2)
+lw x14, 8(x2)
+lui x7, 0xaa01
+addiw x7, x7, -1376
+bne x14, x7, fail_0
+test_5:
+addi gp, x0, 5
+lui x2, 0x10010
+lui x1, 0xa00aa
+addiw x1, x1, 10
+sw x1, 12(x2)
+lw x14, 12(x2)
+lui x7, 0xa00aa
+addiw x7, x7, 10
+bne x14, x7, fail_0
+// Negative offsets
+test_6:
+addi gp, x0, 6
+lui x2, 0x10010
+addi x2, x2, 32
+lui x1, 0xaa1
+addiw x1, x1, 670
+sw x1, -16(x2)
+lw x14, -16(x2)
+lui x7, 0xaa1
+addiw x7, x7, 670
+bne x14, x7, fail_0
+// Negative offsets
+test_7:
+addi gp, x0, 7
+lui x2, 0x10010
+addi x2, x2, 32
+lui x1, 0xaa00c
+addiw x1, x1, -1996
+sw x1, -12(x2)
+lw x14, -12(x2)
+lui x7, 0xaa00c
+addiw x7, x7, -1996
+bne x14, x7, fail_0
+// Negative offsets
+test_8:
+addi gp, x0, 8
+lui x2, 0x10010
+addi x2, x2, 32
+lui x1, 0xaa02
+addiw x1, x1, -1900
+sw x1, -8(x2)
+lw x14, -8(x2)
+lui x7, 0xaa02
+addiw x7, x7, -1900
+bne x14, x7, fail_0
+// Negative offsets
+test_9:
+addi gp, x0, 9
+lui x2, 0x10010
+addi x2, x2, 32
+lui x1, 0xa00ab
+addiw x1, x1, 574
+sw x1, -4(x2)
+lw x14, -4(x2)
+lui x7, 0xa00ab
+addiw x7, x7, 574
+bne x14, x7, fail_0
+// A negative base
+test_10:
+addi gp, x0, 10
+lui x1, 0x10010
+addi x1, x1, 36
+lui x2, 0x12345
+addiw x2, x2, 1656
+addi x4, x1, -32
+sw x2, 32(x4)
+lw x5, 0(x1)
+lui x7, 0x12345
+addiw x7, x7, 1656
+bne x5, x7, fail_0
+// An unaligned base
+test_11:
+addi gp, x0, 11
+lui x1, 0x10010
+addi x1, x1, 36
+lui x2, 0x58213
+addiw x2, x2, 152
+addi x1, x1, -3
+sw x2, 3(x1)
+addi x4, x1, 3
+lw x5, 0(x4)
+lui x7, 0x58213
+addiw x7, x7, 152
+bne x5, x7, fail_0
+// Bypassing
+test_12:
+addi gp, x0, 12
+addi x4, x0, 0
+l1:
+lui x13, 0xab
+addiw x13, x13, -1091
+slli x13, x13, 12
+addi x13, x13, -803
+lui x12, 0x10010
+sw x13, 0(x12)
+lw x14, 0(x12)
+lui x29, 0xaabbd
+addiw x29, x29, -803
+bne x14, x29, fail_0
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l1
+// Bypassing
+test_13:
+addi gp, x0, 13
+addi x4, x0, 0
+l2:
+lui x13, 0x37
+addiw x13, x13, -1361
+slli x13, x13, 14
+addi x13, x13, -819
+lui x12, 0x10010
+addi x0, x0, 0
+sw x13, 4(x12)
+lw x14, 4(x12)
+lui x29, 0xdaabc
+addiw x29, x29, -819
+bne x14, x29, fail_0
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l2
+// Bypassing
+test_14:
+addi gp, x0, 14
+addi x4, x0, 0
+l3:
+lui x13, 0x37
+addiw x13, x13, 1707
+slli x13, x13, 14
+addi x13, x13, -1076
+lui x12, 0x10010
+addi x0, x0, 0
+addi x0, x0, 0
+sw x13, 8(x12)
+lw x14, 8(x12)
+lui x29, 0xddaac
+addiw x29, x29, -1076
+bne x14, x29, fail_0
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l3
+// Bypassing
+test_15:
+addi gp, x0, 15
+addi x4, x0, 0
+l4:
+lui x13, 0xce
+addiw x13, x13, -597
+slli x13, x13, 12
+addi x13, x13, -1092
+addi x0, x0, 0
+lui x12, 0x10010
+sw x13, 4(x12)
+lw x14, 4(x12)
+lui x29, 0xcddab
+addiw x29, x29, -1092
+bne x14, x29, fail_0
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l4
+jal x0, continue_0
+fail_0:
+jal x0, fail_1
+continue_0:
+// Bypassing
+test_16:
+addi gp, x0, 16
+addi x4, x0, 0
+l5:
+lui x13, 0xcd
+addiw x13, x13, -549
+slli x13, x13, 12
+addi x13, x13, -1349
+addi x0, x0, 0
+lui x12, 0x10010
+addi x0, x0, 0
+sw x13, 8(x12)
+lw x14, 8(x12)
+lui x29, 0xccddb
+addiw x29, x29, -1349
+bne x14, x29, fail_1
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l5
+// Bypassing
+test_17:
+addi gp, x0, 17
+addi x4, x0, 0
+l6:
+lui x13, 0x5e
+addiw x13, x13, 1647
+slli x13, x13, 13
+addi x13, x13, -1365
+addi x0, x0, 0
+addi x0, x0, 0
+lui x12, 0x10010
+sw x13, 8(x12)
+lw x14, 8(x12)
+lui x29, 0xbccde
+addiw x29, x29, -1365
+bne x14, x29, fail_1
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l6
+// Bypassing
+test_18:
+addi gp, x0, 18
+addi x4, x0, 0
+l7:
+lui x12, 0x10010
+lui x13, 0xab
+addiw x13, x13, -1091
+slli x13, x13, 12
+addi x13, x13, -803
+sw x13, 0(x12)
+lw x14, 0(x12)
+lui x29, 0xaabbd
+addiw x29, x29, -803
+bne x14, x29, fail_1
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l7
+// Bypassing
+test_19:
+addi gp, x0, 19
+addi x4, x0, 0
+l8:
+lui x12, 0x10010
+lui x13, 0x37
+addiw x13, x13, -1361
+slli x13, x13, 14
+addi x13, x13, -819
+addi x0, x0, 0
+sw x13, 4(x12)
+lw x14, 4(x12)
+lui x29, 0xdaabc
+addiw x29, x29, -819
+bne x14, x29, fail_1
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l8
+// Bypassing
+test_20:
+addi gp, x0, 20
+addi x4, x0, 0
+l9:
+lui x12, 0x10010
+lui x13, 0x37
+addiw x13, x13, 1707
+slli x13, x13, 14
+addi x13, x13, -1076
+addi x0, x0, 0
+addi x0, x0, 0
+sw x13, 8(x12)
+lw x14, 8(x12)
+lui x29, 0xddaac
+addiw x29, x29, -1076
+bne x14, x29, fail_1
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l9
+// Bypassing
+test_21:
+addi gp, x0, 21
+addi x4, x0, 0
+l10:
+lui x12, 0x10010
+addi x0, x0, 0
+lui x13, 0xce
+addiw x13, x13, -597
+slli x13, x13, 12
+addi x13, x13, -1092
+sw x13, 4(x12)
+lw x14, 4(x12)
+lui x29, 0xcddab
+addiw x29, x29, -1092
+bne x14, x29, fail_1
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l10
+// Bypassing
+test_22:
+addi gp, x0, 22
+addi x4, x0, 0
+l11:
+lui x12, 0x10010
+addi x0, x0, 0
+lui x13, 0xcd
+addiw x13, x13, -549
+slli x13, x13, 12
+addi x13, x13, -1349
+addi x0, x0, 0
+sw x13, 8(x12)
+lw x14, 8(x12)
+lui x29, 0xccddb
+addiw x29, x29, -1349
+bne x14, x29, fail_1
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l11
+// Bypassing
+test_23:
+addi gp, x0, 23
+addi x4, x0, 0
+l12:
+lui x12, 0x10010
+addi x0, x0, 0
+addi x0, x0, 0
+lui x13, 0x5e
+addiw x13, x13, 1647
+slli x13, x13, 13
+addi x13, x13, -1365
+sw x13, 8(x12)
+lw x14, 8(x12)
+lui x29, 0xbccde
+addiw x29, x29, -1365
+bne x14, x29, fail_1
+addi x4, x4, 1
+addi x5, x0, 2
+bne x4, x5, l12
+bne x0, gp, pass
+fail_1:
+jal x0, end
+pass:
+addi gp, x0, 1
+end:
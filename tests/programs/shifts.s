@@ -0,0 +1,26 @@
+// Shift amounts only use the bottom 6 bits (5 for the W forms), and the W
+// forms work on the bottom 32 bits then sign extend
+lui t0, 0x80000
+addi t0, t0, 0x123
+addi t1, x0, 68
+sll t2, t0, t1
+srl t3, t0, t1
+sra t4, t0, t1
+addi t1, x0, 63
+sll t5, t1, t1
+addi a0, x0, -1
+srl a1, a0, t1
+sra a2, a0, t1
+addi t1, x0, 33
+sllw a3, t0, t1
+srlw a4, t0, t1
+sraw a5, t0, t1
+slli a6, t0, 32
+srli a7, a6, 40
+srai s1, a6, 40
+slliw s2, t0, 4
+srliw s3, t0, 4
+sraiw s4, t0, 4
+srliw s5, a6, 0
+sraiw s6, a0, 31
+srliw s7, a0, 31
@@ -7,6 +7,65 @@ const interpreter = WebInterface.new()
 var interval_id = null;
 
 document.getElementById('memory-go').onclick = () => interpreter.update_ui();
+document.getElementById('memory-model').onchange = (e) => report_errors(() => {
+  let [model, size] = e.target.value.split(":");
+  interpreter.set_memory_model_button(model == "sparse", size);
+});
+document.getElementById('virtual-memory-view').onchange = (e) => {
+  interpreter.set_virtual_memory_view_button(e.target.checked);
+}
+// Settings which can be invalid throw an error message to show the user
+function report_errors(setting) {
+  try {
+    setting();
+  } catch (error) {
+    alert(error);
+  }
+}
+
+for (let element of document.getElementsByClassName("cache-config")) {
+  element.onchange = () => report_errors(() => {
+    let value = (id) => document.getElementById(id).value;
+    interpreter.set_caches_button(
+      document.getElementById("cache-enabled").checked,
+      value("cache-size"),
+      value("cache-associativity"),
+      value("cache-block-size"),
+      value("cache-replacement"),
+      value("cache-write-policy"),
+      document.getElementById("cache-split").checked);
+  });
+}
+for (let element of document.getElementsByClassName("pipeline-config")) {
+  element.onchange = () => {
+    interpreter.set_pipeline_button(
+      document.getElementById("pipeline-enabled").checked,
+      document.getElementById("pipeline-forwarding").checked,
+      document.getElementById("pipeline-prediction").value);
+  }
+}
+for (let element of document.getElementsByClassName("predictor-config")) {
+  element.onchange = () => report_errors(() => {
+    let value = (id) => document.getElementById(id).value;
+    interpreter.set_branch_predictor_button(
+      document.getElementById("predictor-enabled").checked,
+      value("predictor-kind"),
+      Number(value("predictor-table-bits")),
+      Number(value("predictor-history-bits")),
+      Number(value("predictor-btb-entries")));
+  });
+}
+for (let element of document.getElementsByClassName("trace-config")) {
+  element.onchange = () => {
+    interpreter.set_trace_button(
+      document.getElementById("trace-enabled").checked,
+      document.getElementById("trace-format").value);
+  }
+}
+document.getElementById("trace-download").onclick = () => {
+  let trace = new Blob([interpreter.trace_contents()], {type: "text/plain"});
+  saveAs(trace, "trace.log");
+}
 document.getElementById("run").onclick = () => {
   interval_id = setInterval(() => {
     interpreter.update_ui()
@@ -18,7 +77,28 @@ document.getElementById("run").onclick = () => {
   interpreter.run_button();
 }
 document.getElementById("step").onclick = () => interpreter.step_button();
+document.getElementById("step-over").onclick = () => interpreter.step_over_button();
+document.getElementById("step-out").onclick = () => interpreter.step_out_button();
 document.getElementById("reset").onclick = () => interpreter.reset_button();
+document.getElementById("preserve-state").onchange = (e) => {
+  interpreter.set_preserve_state_button(e.target.checked);
+}
+document.getElementById("check-calling-convention").onchange = (e) => {
+  interpreter.set_check_calling_convention_button(e.target.checked);
+}
+document.getElementById("check-uninitialised").onchange = (e) => {
+  interpreter.set_check_uninitialised_button(e.target.checked);
+}
+document.getElementById("console-send").onclick = () => {
+  let input = document.getElementById("console-input");
+  interpreter.console_input_button(input.value + "\n");
+  input.value = "";
+}
+for (let element of document.getElementsByClassName("switch")) {
+  element.onchange = (e) => {
+    interpreter.set_switch_button(Number(e.target.dataset.switch), e.target.checked);
+  }
+}
 document.getElementById("stop").onclick = () => {
   interpreter.stop_button();
   if (interval_id !== null) {